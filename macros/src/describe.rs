@@ -19,6 +19,8 @@ pub fn derive(item: TokenStream) -> TokenStream {
     let types = struct_fields(&item).map(|field| &field.ty);
     let types_where = struct_fields(&item).map(|field| &field.ty);
 
+    let version = state_version(&item);
+
     let name = &item.ident;
     let mut generics = item.generics.clone();
     generics.params.iter_mut().for_each(|p| {
@@ -41,7 +43,9 @@ pub fn derive(item: TokenStream) -> TokenStream {
             #where_clause
         {
             fn describe() -> ::orga::describe::Descriptor {
-                ::orga::describe::Builder::new::<Self>().meta::<u8>()
+                ::orga::describe::Builder::new::<Self>()
+                .meta::<u8>()
+                .state_version(#version)
                 #(
                     .named_child_from_state::<Self, #types>(
                         stringify!(#names),
@@ -55,6 +59,29 @@ pub fn derive(item: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Reads the version from the `#[state(version = N)]` attribute emitted by
+/// `#[orga]`, defaulting to 0.
+fn state_version(item: &DeriveInput) -> u32 {
+    let mut version = 0;
+    for attr in item
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("state"))
+    {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("version") {
+                let lit: LitInt = meta.value()?.parse()?;
+                version = lit.base10_parse()?;
+            } else if meta.input.peek(Token![=]) {
+                let _: Expr = meta.value()?.parse()?;
+            }
+            Ok(())
+        });
+    }
+
+    version
+}
+
 fn struct_fields(item: &DeriveInput) -> impl Iterator<Item = &Field> {
     let data = match item.data {
         Data::Struct(ref data) => data,
@@ -0,0 +1,78 @@
+use darling::{ast, FromDeriveInput, FromField};
+use heck::SnakeCase;
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::*;
+
+#[derive(FromDeriveInput)]
+#[darling(attributes(event), supports(struct_named))]
+struct EventInputReceiver {
+    ident: Ident,
+    generics: Generics,
+    data: ast::Data<(), EventFieldReceiver>,
+
+    kind: Option<String>,
+}
+
+#[derive(FromField)]
+#[darling(attributes(event))]
+struct EventFieldReceiver {
+    ident: Option<Ident>,
+
+    #[darling(default)]
+    index: bool,
+}
+
+impl ToTokens for EventInputReceiver {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let EventInputReceiver {
+            ident,
+            generics,
+            data,
+            kind,
+        } = self;
+
+        let (imp, ty, wher) = generics.split_for_impl();
+        let kind = kind
+            .clone()
+            .unwrap_or_else(|| ident.to_string().to_snake_case());
+
+        let fields = data.as_ref().take_struct().unwrap().fields;
+        let field_idents: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+        let indexes = fields.iter().map(|f| f.index);
+
+        tokens.extend(quote! {
+            impl #imp ::orga::events::Event for #ident #ty #wher {
+                const KIND: &'static str = #kind;
+
+                fn attributes(&self) -> Vec<::orga::events::Attribute> {
+                    vec![
+                        #(::orga::events::Attribute {
+                            key: stringify!(#field_idents),
+                            value: ::orga::events::EventValue::to_event_value(&self.#field_idents),
+                            index: #indexes,
+                        },)*
+                    ]
+                }
+
+                fn from_attributes(attributes: &[(String, String)]) -> ::orga::Result<Self> {
+                    Ok(Self {
+                        #(#field_idents: ::orga::events::EventValue::from_event_value(
+                            ::orga::events::find_attribute(attributes, stringify!(#field_idents))?,
+                        )?,)*
+                    })
+                }
+            }
+        })
+    }
+}
+
+pub fn derive(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
+
+    EventInputReceiver::from_derive_input(&item)
+        .unwrap()
+        .into_token_stream()
+        .into()
+}
@@ -13,7 +13,7 @@ use syn::*;
 
 use crate::{
     child::const_field_id,
-    utils::{to_camel_case, to_snake_case, Types},
+    utils::{to_camel_case, to_snake_case, with_predicate, Types},
 };
 
 #[derive(Debug, Clone, FromDeriveInput)]
@@ -145,6 +145,48 @@ impl FieldCallInputReceiver {
         }
     }
 
+    fn call_fields_impl(&self) -> TokenStream2 {
+        let Types {
+            state_trait,
+            keyop_ty,
+            ..
+        } = Types::default();
+        let ident = &self.ident;
+        let (imp, ty, wher) = self.generics.split_for_impl();
+        // the store keys of the fields are part of the call encoding
+        let wher = with_predicate(wher, parse_quote! { #ident #ty: #state_trait });
+        let fields = self.call_fields();
+        let names = fields
+            .iter()
+            .map(|field| field.ident.as_ref().unwrap().to_string())
+            .collect_vec();
+        let tys = fields.iter().map(|field| &field.ty).collect_vec();
+
+        quote! {
+            impl #imp ::orga::describe::methods::CallFields for #ident #ty #wher {
+                fn call_fields() -> Vec<::orga::describe::methods::FieldDescriptor> {
+                    let fields: Vec<(&str, ::orga::describe::methods::MethodTree)> = vec![#((
+                        #names,
+                        <#tys as ::orga::describe::methods::DescribeCalls>::describe_calls(),
+                    ),)*];
+
+                    // fields with absolute keys can't be called
+                    fields
+                        .into_iter()
+                        .filter_map(|(name, tree)| match <Self as #state_trait>::field_keyop(name)? {
+                            #keyop_ty::Append(key) => Some(::orga::describe::methods::FieldDescriptor {
+                                name: name.to_string(),
+                                key,
+                                tree,
+                            }),
+                            #keyop_ty::Absolute(_) => None,
+                        })
+                        .collect()
+                }
+            }
+        }
+    }
+
     fn call_builder(&self) -> TokenStream2 {
         let Types {
             build_call_trait,
@@ -209,6 +251,7 @@ impl ToTokens for FieldCallInputReceiver {
         let fc_enum = self.field_call_enum();
         let fc_impl = self.field_call_impl(&fc_enum);
         let builders = self.call_builder();
+        let call_fields = self.call_fields_impl();
 
         tokens.extend(quote! {
             #fc_enum
@@ -216,6 +259,8 @@ impl ToTokens for FieldCallInputReceiver {
             #fc_impl
 
             #builders
+
+            #call_fields
         });
     }
 }
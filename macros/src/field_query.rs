@@ -11,7 +11,7 @@ use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
 use syn::*;
 
-use crate::utils::{to_camel_case, to_snake_case, Types};
+use crate::utils::{to_camel_case, to_snake_case, with_predicate, Types};
 
 #[derive(Debug, Clone, FromDeriveInput)]
 #[darling(supports(struct_named), forward_attrs)]
@@ -145,6 +145,60 @@ impl FieldQueryInputReceiver {
             }
         }
     }
+
+    fn read_fields_impl(&self) -> TokenStream2 {
+        let Types {
+            state_trait,
+            keyop_ty,
+            ..
+        } = Types::default();
+        let ident = &self.ident;
+        let (imp, ty, wher) = self.generics.split_for_impl();
+        // the store keys of the fields are part of the query encoding
+        let wher = with_predicate(wher, parse_quote! { #ident #ty: #state_trait });
+        let fields = self.query_fields();
+        let names = fields
+            .iter()
+            .map(|field| field.ident.as_ref().unwrap())
+            .collect_vec();
+        let name_strs = names.iter().map(|name| name.to_string()).collect_vec();
+        let tys = fields.iter().map(|field| &field.ty).collect_vec();
+
+        quote! {
+            impl #imp ::orga::describe::methods::ReadFields for #ident #ty #wher {
+                fn query_fields() -> Vec<::orga::describe::methods::FieldDescriptor> {
+                    let fields: Vec<(&str, ::orga::describe::methods::MethodTree)> = vec![#((
+                        #name_strs,
+                        <#tys as ::orga::describe::methods::DescribeQueries>::describe_queries(),
+                    ),)*];
+
+                    // fields with absolute keys can't be queried
+                    fields
+                        .into_iter()
+                        .filter_map(|(name, tree)| match <Self as #state_trait>::field_keyop(name)? {
+                            #keyop_ty::Append(key) => Some(::orga::describe::methods::FieldDescriptor {
+                                name: name.to_string(),
+                                key,
+                                tree,
+                            }),
+                            #keyop_ty::Absolute(_) => None,
+                        })
+                        .collect()
+                }
+
+                fn read_field(
+                    &self,
+                    name: &str,
+                    rest: &[String],
+                ) -> Option<::orga::Result<::orga::JsonValue>> {
+                    match name {
+                        #(#name_strs => Some(::orga::describe::methods::ReadPath::read_path(&self.#names, rest)),)*
+                        _ => None,
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, FromField)]
@@ -166,11 +220,14 @@ impl ToTokens for FieldQueryInputReceiver {
     fn to_tokens(&self, tokens: &mut TokenStream2) {
         let fq_enum = self.field_query_enum();
         let fq_impl = self.field_query_impl(&fq_enum);
+        let read_fields = self.read_fields_impl();
 
         tokens.extend(quote! {
             #fq_enum
 
             #fq_impl
+
+            #read_fields
         });
     }
 }
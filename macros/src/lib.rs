@@ -9,6 +9,7 @@ mod child;
 mod describe;
 mod encoding;
 mod entry;
+mod event;
 mod field_call;
 mod field_query;
 mod method_call;
@@ -44,6 +45,11 @@ pub fn derive_migrate(item: TokenStream) -> TokenStream {
     migrate::derive(item)
 }
 
+#[proc_macro_derive(Event, attributes(event))]
+pub fn derive_event(item: TokenStream) -> TokenStream {
+    event::derive(item)
+}
+
 #[proc_macro_attribute]
 pub fn orga(args: TokenStream, input: TokenStream) -> TokenStream {
     orga::orga(args, input)
@@ -3,7 +3,7 @@ use crate::{
     utils::{to_camel_case, to_snake_case},
 };
 
-use super::utils::{is_attr_with_ident, Types};
+use super::utils::{is_attr_with_ident, is_describable, with_predicate, Types};
 use darling::{usage::IdentSet, util::path_to_string, ToTokens};
 use itertools::Itertools;
 use proc_macro::TokenStream;
@@ -52,6 +52,23 @@ fn method_args(method: &ImplItemFn) -> Vec<Type> {
         .collect_vec()
 }
 
+fn method_arg_names(method: &ImplItemFn) -> Vec<String> {
+    method
+        .sig
+        .inputs
+        .iter()
+        .skip(1)
+        .enumerate()
+        .map(|(i, arg)| match arg {
+            FnArg::Typed(PatType { pat, .. }) => match &**pat {
+                Pat::Ident(PatIdent { ident, .. }) => ident.to_string(),
+                _ => format!("arg{}", i),
+            },
+            _ => panic!("Expected a typed argument"),
+        })
+        .collect_vec()
+}
+
 fn enum_ident(item: &ItemImpl) -> Ident {
     format_ident!("{}{}", self_ty_ident(&item), "MethodCall")
 }
@@ -173,11 +190,10 @@ fn method_call_impl(tokens: &mut TokenStream2, item: &ItemImpl) {
         Noop(_) => {}
     });
 
-    let enum_bound = quote! { #enum_ident #ty: #encode_trait + #decode_trait + ::std::fmt::Debug };
-    let wher = match wher {
-        Some(w) => quote! { #w #enum_bound },
-        None => quote! { where #enum_bound },
-    };
+    let wher = with_predicate(
+        wher,
+        parse_quote! { #enum_ident #ty: #encode_trait + #decode_trait + ::std::fmt::Debug },
+    );
 
     tokens.extend(quote! {
         impl #imp #method_call_trait for #ident #ty #wher {
@@ -324,6 +340,63 @@ fn call_builder(tokens: &mut TokenStream2, item: &ItemImpl) {
     }
 }
 
+fn call_methods_impl(tokens: &mut TokenStream2, item: &ItemImpl) {
+    let Types { encode_trait, .. } = Types::default();
+    let ident = self_ty_ident(&item);
+    let enum_ident = enum_ident(&item);
+    let (imp, ty, wher) = item.generics.split_for_impl();
+    let ty_turbofish = ty.as_turbofish();
+
+    // indexes are taken before filtering since they match the enum variants
+    let methods = call_methods(&item)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, method)| is_describable(method))
+        .map(|(index, method)| {
+            let index = index as u8;
+            let cc_ident = to_camel_case(&method.sig.ident);
+            let name = method.sig.ident.to_string();
+            let args = method_args(method);
+            let arg_names = method_arg_names(method);
+            let arg_type_names = args.iter().map(|ty| quote! { #ty }.to_string());
+            let n_args = args.len();
+            let indexes = 0..n_args;
+
+            quote! {
+                ::orga::describe::methods::MethodDescriptor {
+                    name: #name.to_string(),
+                    index: #index,
+                    args: vec![#(::orga::describe::methods::ArgDescriptor::new(#arg_names, #arg_type_names),)*],
+                    encode: Some(|args| {
+                        ::orga::describe::methods::check_arg_count(#name, args, #n_args)?;
+                        let call = #enum_ident #ty_turbofish::#cc_ident(
+                            #(::orga::describe::methods::parse_arg::<#args>(&args[#indexes])?,)*
+                        );
+                        Ok(#encode_trait::encode(&call)?)
+                    }),
+                }
+            }
+        })
+        .collect_vec();
+
+    // only generic impls need to require that the call enum can be encoded;
+    // otherwise the bound is trivially known
+    let wher = if item.generics.params.is_empty() {
+        quote! { #wher }
+    } else {
+        let clause = with_predicate(wher, parse_quote! { #enum_ident #ty: #encode_trait });
+        quote! { #clause }
+    };
+
+    tokens.extend(quote! {
+        impl #imp ::orga::describe::methods::CallMethods for #ident #ty #wher {
+            fn call_methods() -> Vec<::orga::describe::methods::MethodDescriptor> {
+                vec![#(#methods,)*]
+            }
+        }
+    })
+}
+
 pub fn call_block(_args: TokenStream, input: TokenStream) -> TokenStream {
     let mut item = syn::parse::<ItemImpl>(input.clone()).unwrap();
     // add_tracing(&mut item);
@@ -336,6 +409,7 @@ pub fn call_block(_args: TokenStream, input: TokenStream) -> TokenStream {
     method_call_enum(&mut tokens, &item);
     method_call_impl(&mut tokens, &item);
     call_builder(&mut tokens, &item);
+    call_methods_impl(&mut tokens, &item);
     strip_call_attr(&mut item);
     tokens.extend(item.into_token_stream());
 
@@ -1,6 +1,6 @@
 use crate::utils::{to_camel_case, to_snake_case};
 
-use super::utils::{is_attr_with_ident, Types};
+use super::utils::{is_attr_with_ident, is_describable, Types};
 use darling::{usage::IdentSet, util::path_to_string, ToTokens};
 use itertools::Itertools;
 use proc_macro::TokenStream;
@@ -49,6 +49,23 @@ fn method_args(method: &ImplItemFn) -> Vec<Type> {
         .collect_vec()
 }
 
+fn method_arg_names(method: &ImplItemFn) -> Vec<String> {
+    method
+        .sig
+        .inputs
+        .iter()
+        .skip(1)
+        .enumerate()
+        .map(|(i, arg)| match arg {
+            FnArg::Typed(PatType { pat, .. }) => match &**pat {
+                Pat::Ident(PatIdent { ident, .. }) => ident.to_string(),
+                _ => format!("arg{}", i),
+            },
+            _ => panic!("Expected a typed argument"),
+        })
+        .collect_vec()
+}
+
 fn enum_ident(item: &ItemImpl) -> Ident {
     format_ident!("{}{}", self_ty_ident(&item), "MethodQuery")
 }
@@ -299,6 +316,73 @@ fn add_tracing(item_impl: &mut ItemImpl) {
     }
 }
 
+fn query_methods_impl(tokens: &mut TokenStream2, item: &ItemImpl) {
+    let ident = self_ty_ident(&item);
+    let (imp, ty, wher) = item.generics.split_for_impl();
+    // indexes are taken before filtering since they match the enum variants
+    let methods = query_methods(&item)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, method)| is_describable(method))
+        .collect_vec();
+
+    let descriptors = methods.iter().map(|(index, method)| {
+        let index = *index as u8;
+        let name = method.sig.ident.to_string();
+        let arg_names = method_arg_names(method);
+        let arg_type_names = method_args(method)
+            .iter()
+            .map(|ty| quote! { #ty }.to_string())
+            .collect_vec();
+
+        quote! {
+            ::orga::describe::methods::MethodDescriptor {
+                name: #name.to_string(),
+                index: #index,
+                args: vec![#(::orga::describe::methods::ArgDescriptor::new(#arg_names, #arg_type_names),)*],
+                encode: None,
+            }
+        }
+    });
+
+    let arms = methods.iter().map(|(_, method)| {
+        let method_ident = &method.sig.ident;
+        let name = method_ident.to_string();
+        let args = method_args(method);
+        let n_args = args.len();
+        let indexes = 0..n_args;
+
+        quote! {
+            #name => Some((|| {
+                ::orga::describe::methods::check_arg_count(#name, args, #n_args)?;
+                let output = self.#method_ident(
+                    #(::orga::describe::methods::parse_arg::<#args>(&args[#indexes])?,)*
+                );
+                ::orga::describe::methods::QueryOutput::into_json(output)
+            })())
+        }
+    });
+
+    tokens.extend(quote! {
+        impl #imp ::orga::describe::methods::QueryMethods for #ident #ty #wher {
+            fn query_methods() -> Vec<::orga::describe::methods::MethodDescriptor> {
+                vec![#(#descriptors,)*]
+            }
+
+            fn query_method(
+                &self,
+                name: &str,
+                args: &[String],
+            ) -> Option<::orga::Result<::orga::JsonValue>> {
+                match name {
+                    #(#arms,)*
+                    _ => None,
+                }
+            }
+        }
+    })
+}
+
 pub fn query_block(_args: TokenStream, input: TokenStream) -> TokenStream {
     let mut item = syn::parse::<ItemImpl>(input.clone()).unwrap();
     add_tracing(&mut item);
@@ -310,6 +394,7 @@ pub fn query_block(_args: TokenStream, input: TokenStream) -> TokenStream {
     let mut tokens = quote! {}.into();
     method_query_enum(&mut tokens, &item);
     method_query_impl(&mut tokens, &item);
+    query_methods_impl(&mut tokens, &item);
     strip_query_attr(&mut item);
     tokens.extend(item.into_token_stream());
 
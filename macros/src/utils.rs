@@ -262,6 +262,31 @@ pub fn is_attr_with_ident(attr: &Attribute, ident: &str) -> bool {
         .map_or(false, |attr_ident| attr_ident.to_string() == ident)
 }
 
+/// Whether runtime method metadata can be generated for a method, i.e. its
+/// arguments can be named as concrete types outside of the method. Generic,
+/// `async` and `impl Trait`-taking methods, and methods with their own where
+/// clause, are left out of the metadata.
+pub fn is_describable(method: &ImplItemFn) -> bool {
+    let sig = &method.sig;
+    sig.generics.params.is_empty()
+        && sig.generics.where_clause.is_none()
+        && sig.asyncness.is_none()
+        && sig.inputs.iter().all(|arg| match arg {
+            FnArg::Typed(PatType { ty, .. }) => !matches!(**ty, Type::ImplTrait(_)),
+            FnArg::Receiver(_) => true,
+        })
+}
+
+/// Appends a predicate to an impl's where clause, if any.
+pub fn with_predicate(
+    where_clause: Option<&WhereClause>,
+    predicate: WherePredicate,
+) -> WhereClause {
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| parse_quote!(where));
+    where_clause.predicates.push(predicate);
+    where_clause
+}
+
 pub fn to_camel_case(ident: &Ident) -> Ident {
     Ident::new(&format!("{}", ident).as_str().to_camel_case(), ident.span())
 }
@@ -0,0 +1,355 @@
+//! A read-only HTTP/JSON view of app state.
+//!
+//! Requests such as `GET /state/staking/validators/<address>/delegators` are
+//! resolved by walking the app's [Descriptor](crate::describe::Descriptor),
+//! see [`Descriptor::browse`](crate::describe::Descriptor::browse). State is
+//! read through Tendermint RPC queries with merk proofs, so the browser can be
+//! pointed at any node and its responses can include the proofs they were
+//! built from.
+//!
+//! Query parameters:
+//! - `limit`: the number of entries to list for dynamic children, e.g. maps.
+//! - `after`: the hex-encoded key to list entries after, i.e. the `next` key
+//!   of a previous page.
+//! - `height`: the height to read state at. Defaults to the latest height.
+//! - `prove`: if set, the merk proofs backing the response are included.
+
+use crate::call::Call;
+use crate::client::exec::join_store;
+use crate::describe::browse::{Page, DEFAULT_PAGE_LIMIT};
+use crate::describe::Describe;
+use crate::encoding::Encode;
+use crate::plugins::query::QueryPlugin;
+use crate::plugins::ABCIPlugin;
+use crate::query::Query;
+use crate::state::State;
+use crate::store::{self, Read, Store};
+use crate::tendermint::client::{proof_store, HttpClient};
+use crate::{Error, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::any::TypeId;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+type QueryPluginQuery<T> = <QueryPlugin<T> as Query>::Query;
+
+/// The root hash and merk proof returned by a query.
+#[derive(Clone, Debug, Serialize)]
+pub struct Proof {
+    #[serde(serialize_with = "serialize_hex")]
+    pub root_hash: [u8; 32],
+    #[serde(serialize_with = "serialize_hex")]
+    pub proof: Vec<u8>,
+}
+
+/// The result of [StateBrowser::browse].
+#[derive(Clone, Debug)]
+pub struct Browsed {
+    pub height: u32,
+    pub value: Value,
+    pub proofs: Vec<Proof>,
+}
+
+enum Step {
+    Done(Value),
+    Fetch(Vec<u8>),
+}
+
+/// Reads the state of an app wrapped in a [QueryPlugin] as JSON, fetching
+/// the keys it needs from a Tendermint RPC node.
+pub struct StateBrowser<T> {
+    client: HttpClient,
+    root: Vec<String>,
+    _app: PhantomData<fn() -> T>,
+}
+
+impl<T> StateBrowser<T>
+where
+    T: State + Query + Call + Describe + 'static,
+{
+    /// Creates a browser with paths relative to the type wrapped by the
+    /// [QueryPlugin].
+    pub fn new(client: HttpClient) -> Self {
+        Self::with_root(client, TypeId::of::<T>())
+    }
+
+    /// Creates a browser with paths relative to the first value of the given
+    /// type in the app, e.g. the app type wrapped by the default plugins.
+    pub fn with_root(client: HttpClient, type_id: TypeId) -> Self {
+        let root = ABCIPlugin::<QueryPlugin<T>>::describe()
+            .path_to_type(type_id)
+            .unwrap_or_default();
+
+        Self {
+            client,
+            root,
+            _app: PhantomData,
+        }
+    }
+
+    /// Resolves the path at the given height, or the latest height if `None`.
+    pub async fn browse(
+        &self,
+        path: &[String],
+        page: &Page,
+        mut height: Option<u32>,
+    ) -> Result<Browsed> {
+        let path = [self.root.as_slice(), path].concat();
+        let mut proofs = vec![];
+        let mut queries = HashSet::new();
+
+        loop {
+            let query_bytes = match self.step(&proofs, &path, page)? {
+                Step::Done(value) => {
+                    let height = match height {
+                        Some(height) => height,
+                        None => self.client.latest_height().await?.0.try_into()?,
+                    };
+                    return Ok(Browsed {
+                        height,
+                        value,
+                        proofs,
+                    });
+                }
+                Step::Fetch(query_bytes) => query_bytes,
+            };
+
+            if !queries.insert(query_bytes.clone()) {
+                return Err(Error::Client("Execution did not advance".into()));
+            }
+
+            // the first response pins the height for the following queries,
+            // unless one was requested
+            let (root_hash, proof, res_height) =
+                self.client.query_proof(query_bytes, height).await?;
+            height.get_or_insert(res_height);
+            proofs.push(Proof { root_hash, proof });
+        }
+    }
+
+    fn step(&self, proofs: &[Proof], path: &[String], page: &Page) -> Result<Step> {
+        let mut store = Store::default();
+        for proof in proofs {
+            store = join_store(store, proof_store(proof.root_hash, &proof.proof)?)?;
+        }
+
+        let root_bytes = match store.get(&[]) {
+            Err(Error::StoreErr(store::Error::GetUnknown(_))) | Ok(None) => {
+                return Self::fetch(QueryPluginQuery::<T>::RawKey(vec![]))
+            }
+            Err(err) => return Err(err),
+            Ok(Some(bytes)) => bytes,
+        };
+
+        let desc = ABCIPlugin::<QueryPlugin<T>>::describe();
+        match desc.browse(store, &root_bytes, path, page) {
+            Ok(value) => Ok(Step::Done(value)),
+            Err(Error::StoreErr(store::Error::GetUnknown(key))) => {
+                Self::fetch(QueryPluginQuery::<T>::RawKey(key))
+            }
+            Err(Error::StoreErr(store::Error::GetNextUnknown(key))) => {
+                Self::fetch(QueryPluginQuery::<T>::RawNext(key))
+            }
+            Err(Error::StoreErr(store::Error::GetPrevUnknown(key))) => {
+                Self::fetch(QueryPluginQuery::<T>::RawPrev(key))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn fetch(query: QueryPluginQuery<T>) -> Result<Step> {
+        Ok(Step::Fetch(query.encode()?))
+    }
+
+    /// Serves `GET /state/<path>` requests on the given address until the
+    /// server fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let browser = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let browser = browser.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let browser = browser.clone();
+                    async move { Ok::<_, Infallible>(browser.handle(req).await) }
+                }))
+            }
+        });
+
+        Server::try_bind(&addr)
+            .map_err(|err| Error::App(format!("Failed to bind state browser: {}", err)))?
+            .serve(make_service)
+            .await
+            .map_err(|err| Error::App(format!("State browser failed: {}", err)))
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if req.method() != Method::GET {
+            return error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+        }
+
+        let path = match parse_path(req.uri().path()) {
+            Some(path) => path,
+            None => return error_response(StatusCode::NOT_FOUND, "Not found"),
+        };
+        let params = match Params::parse(req.uri().query().unwrap_or_default()) {
+            Ok(params) => params,
+            Err(err) => return error_response(StatusCode::BAD_REQUEST, &err.to_string()),
+        };
+
+        match self.browse(&path, &params.page, params.height).await {
+            Ok(browsed) => {
+                let mut body = json!({
+                    "height": browsed.height,
+                    "result": browsed.value,
+                });
+                if params.prove {
+                    body["proofs"] = json!(browsed.proofs);
+                }
+                json_response(StatusCode::OK, &body)
+            }
+            Err(err @ (Error::Tendermint(_) | Error::TendermintRPC(_))) => {
+                error_response(StatusCode::BAD_GATEWAY, &err.to_string())
+            }
+            Err(err) => error_response(StatusCode::BAD_REQUEST, &err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Params {
+    page: Page,
+    height: Option<u32>,
+    prove: bool,
+}
+
+impl Params {
+    fn parse(query: &str) -> Result<Self> {
+        let mut params = Params {
+            page: Page::default(),
+            height: None,
+            prove: false,
+        };
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match name {
+                "limit" => params.page.limit = value.parse()?,
+                "after" => {
+                    let after = hex::decode(value.trim_start_matches("0x"))
+                        .map_err(|err| Error::Query(format!("Invalid after key: {}", err)))?;
+                    params.page.after = Some(after);
+                }
+                "height" => params.height = Some(value.parse()?),
+                "prove" => params.prove = value != "false",
+                _ => return Err(Error::Query(format!("Unknown parameter '{}'", name))),
+            }
+        }
+
+        if params.page.limit == 0 {
+            params.page.limit = DEFAULT_PAGE_LIMIT;
+        }
+
+        Ok(params)
+    }
+}
+
+/// Splits a `/state/...` request path into decoded segments.
+fn parse_path(path: &str) -> Option<Vec<String>> {
+    let rest = path.strip_prefix("/state")?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+
+    rest.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode(segment).ok())
+        .collect()
+}
+
+fn percent_decode(s: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'%' => {
+                let hex_byte = [
+                    iter.next().unwrap_or_default(),
+                    iter.next().unwrap_or_default(),
+                ];
+                let decoded = hex::decode(hex_byte)
+                    .map_err(|_| Error::Query(format!("Invalid escape in '{}'", s)))?;
+                bytes.extend(decoded);
+            }
+            b'+' => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| Error::Query(format!("Invalid UTF-8 in '{}'", s)))
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error_response(status: StatusCode, msg: &str) -> Response<Body> {
+    json_response(status, &json!({ "error": msg }))
+}
+
+fn serialize_hex<S: serde::Serializer>(
+    bytes: impl AsRef<[u8]>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_paths() {
+        assert_eq!(parse_path("/state"), Some(vec![]));
+        assert_eq!(
+            parse_path("/state/accounts/%22abc%20d%22/"),
+            Some(vec!["accounts".to_string(), "\"abc d\"".to_string()])
+        );
+        assert_eq!(parse_path("/stateful"), None);
+        assert_eq!(parse_path("/other/state"), None);
+        assert_eq!(parse_path("/state/%zz"), None);
+    }
+
+    #[test]
+    fn parse_params() -> Result<()> {
+        let params = Params::parse("limit=5&after=0x0102&height=10&prove")?;
+        assert_eq!(
+            params,
+            Params {
+                page: Page {
+                    after: Some(vec![1, 2]),
+                    limit: 5,
+                },
+                height: Some(10),
+                prove: true,
+            }
+        );
+
+        assert_eq!(Params::parse("")?.page, Page::default());
+        assert!(!Params::parse("prove=false")?.prove);
+        assert!(Params::parse("limit=x").is_err());
+        assert!(Params::parse("foo=1").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,562 @@
+//! Node configuration, read from `orga.toml` in the node's home directory.
+//!
+//! ```toml
+//! version = 1
+//!
+//! [abci]
+//! addr = "127.0.0.1:26658"
+//!
+//! [grpc]
+//! enabled = true
+//! addr = "127.0.0.1:9001"
+//!
+//! [storage]
+//! query_retention = 20
+//! tx_index = true
+//!
+//! [[storage.history]]
+//! type = "interval"
+//! interval = 100
+//! limit = 50
+//!
+//! [halt]
+//! height = 1000000
+//!
+//! [logging]
+//! level = "info"
+//! tendermint = false
+//!
+//! [tendermint]
+//! source = "system"
+//!
+//! [upgrade]
+//! auto_download = true
+//! ```
+//!
+//! Every field is optional. Values can be overridden on the command line with
+//! `--config <key>=<value>` (see [NodeConfig::set]), and the legacy
+//! `ORGA_STOP_HEIGHT` and `ORGA_STATIC_VALSET` environment variables are
+//! still honored, with a warning.
+
+use crate::merk::snapshot::SnapshotFilter;
+use crate::tendermint::binary::BinaryConfig;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// The current version of the configuration format.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// The name of the configuration file in the node home.
+pub const CONFIG_FILE: &str = "orga.toml";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    pub version: u32,
+    pub abci: AbciConfig,
+    pub grpc: GrpcConfig,
+    pub storage: StorageConfig,
+    pub halt: HaltConfig,
+    pub consensus: ConsensusConfig,
+    pub logging: LoggingConfig,
+    pub metrics: MetricsConfig,
+    /// Where the Tendermint binary comes from, see
+    /// [`tendermint::binary`](crate::tendermint::binary). Tendermint is
+    /// initialized when the node is created, using the binary configured in
+    /// `orga.toml`, before any overrides passed to `Node::with_config`.
+    pub tendermint: BinaryConfig,
+    pub upgrade: UpgradeConfig,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            abci: Default::default(),
+            grpc: Default::default(),
+            storage: Default::default(),
+            halt: Default::default(),
+            consensus: Default::default(),
+            logging: Default::default(),
+            metrics: Default::default(),
+            tendermint: Default::default(),
+            upgrade: Default::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AbciConfig {
+    /// The address the ABCI server listens on. Defaults to the port of
+    /// Tendermint's `proxy_app` setting, or 26658.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addr: Option<SocketAddr>,
+}
+
+/// The Cosmos SDK-compatible gRPC server, started by the app with
+/// [`start_grpc`](crate::ibc::start_grpc). See
+/// [`GrpcOpts::from_config`](crate::ibc::GrpcOpts::from_config).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    pub addr: SocketAddr,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            addr: ([127, 0, 0, 1], 9001).into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// The number of recent heights kept in memory for queries.
+    pub query_retention: usize,
+    /// Filters selecting the on-disk checkpoints retained for historical
+    /// queries. See [`MerkStore::with_history`](crate::merk::MerkStore::with_history).
+    pub history: Vec<SnapshotFilter>,
+    /// Filters selecting the heights at which state sync snapshots are
+    /// created, if the `state-sync` feature is enabled. Defaults to the
+    /// built-in filters when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_sync: Option<Vec<SnapshotFilter>>,
+    /// Whether to keep a local transaction index, see
+    /// [`indexer`](super::indexer).
+    pub tx_index: bool,
+    /// A file to append the state changes of each block to, see
+    /// [`Node::diff_log`](super::Node::diff_log).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_log: Option<PathBuf>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            query_retention: 20,
+            history: vec![],
+            state_sync: None,
+            tx_index: false,
+            diff_log: None,
+        }
+    }
+}
+
+/// Stops the node at a given height or block time, e.g. for a coordinated
+/// upgrade.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HaltConfig {
+    /// The node halts after committing this height.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+    /// The node halts after committing the first block at or after this
+    /// time, in seconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConsensusConfig {
+    /// Ignores validator set changes made by the app, for testing.
+    pub static_valset: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// The maximum level of the app's logs, e.g. `info` or `debug`, set when
+    /// the node starts.
+    pub level: String,
+    /// Whether to print Tendermint's output.
+    pub tendermint: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            tendermint: false,
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn level_filter(&self) -> Result<log::LevelFilter> {
+        self.level
+            .parse()
+            .map_err(|_| Error::Config(format!("Invalid logging.level {}", self.level)))
+    }
+}
+
+/// The Prometheus metrics server, available with the `metrics` feature.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub addr: SocketAddr,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            addr: ([127, 0, 0, 1], 9090).into(),
+        }
+    }
+}
+
+/// How the upgrade [`supervisor`](super::supervisor) moves the node to new
+/// binaries.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpgradeConfig {
+    /// Whether to download a missing binary for a new network version from
+    /// the manifest announced with the upgrade signal.
+    pub auto_download: bool,
+    /// Whether to back up the store before starting the binary for a new
+    /// network version.
+    pub backup: bool,
+    /// The number of backups to keep.
+    pub keep_backups: usize,
+    /// Whether to restore the backup if the new binary fails before
+    /// committing a block.
+    pub rollback: bool,
+}
+
+impl Default for UpgradeConfig {
+    fn default() -> Self {
+        Self {
+            auto_download: false,
+            backup: true,
+            keep_backups: 2,
+            rollback: true,
+        }
+    }
+}
+
+impl NodeConfig {
+    /// The path of the configuration file of the node with the given home
+    /// directory.
+    pub fn path<P: AsRef<Path>>(home: P) -> PathBuf {
+        home.as_ref().join(CONFIG_FILE)
+    }
+
+    /// Loads the configuration of the node with the given home directory, or
+    /// the defaults if the file does not exist. Legacy environment variables
+    /// are applied on top.
+    pub fn load<P: AsRef<Path>>(home: P) -> Result<Self> {
+        let path = Self::path(home);
+        let mut config = if path.exists() {
+            Self::from_toml(&std::fs::read_to_string(&path)?)?
+        } else {
+            Self::default()
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let config: Self =
+            toml_edit::de::from_str(toml).map_err(|err| Error::Config(err.to_string()))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml_edit::ser::to_string_pretty(self).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    /// Sets the field at the given dotted path (e.g. `halt.height`), parsing
+    /// `value` as a TOML value, or as a string if it is not valid TOML.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        self.apply_overrides([format!("{}={}", key, value)])
+    }
+
+    /// Applies overrides of the form `<key>=<value>`, see [NodeConfig::set].
+    /// The resulting configuration is validated once all overrides are
+    /// applied, so related fields can be changed together.
+    pub fn apply_overrides<I, S>(&mut self, overrides: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut doc =
+            toml_edit::ser::to_document(self).map_err(|err| Error::Config(err.to_string()))?;
+        let mut keys = vec![];
+        for item in overrides {
+            let item = item.as_ref();
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| Error::Config(format!("Expected <key>=<value>, got {}", item)))?;
+            set_value(&mut doc, key.trim(), value.trim())?;
+            keys.push(key.trim().to_string());
+        }
+
+        *self = Self::from_toml(&doc.to_string()).map_err(|err| {
+            Error::Config(format!("Invalid value for {}: {}", keys.join(", "), err))
+        })?;
+
+        Ok(())
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        if let Ok(stop_height) = std::env::var("ORGA_STOP_HEIGHT") {
+            log::warn!("ORGA_STOP_HEIGHT is deprecated, set halt.height in orga.toml instead");
+            self.halt.height = Some(
+                stop_height
+                    .parse()
+                    .map_err(|_| Error::Config("Invalid ORGA_STOP_HEIGHT value".into()))?,
+            );
+        }
+
+        if let Ok(flag) = std::env::var("ORGA_STATIC_VALSET") {
+            log::warn!(
+                "ORGA_STATIC_VALSET is deprecated, set consensus.static_valset in orga.toml instead"
+            );
+            self.consensus.static_valset = flag != "0" && flag != "false";
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.version != CONFIG_VERSION {
+            return Err(Error::Config(format!(
+                "Unsupported config version {} (expected {})",
+                self.version, CONFIG_VERSION
+            )));
+        }
+
+        if self.storage.query_retention == 0 {
+            return Err(Error::Config(
+                "storage.query_retention must be at least 1".into(),
+            ));
+        }
+
+        let filters = self
+            .storage
+            .history
+            .iter()
+            .chain(self.storage.state_sync.iter().flatten());
+        for filter in filters {
+            if let SnapshotFilter::Interval { interval: 0, .. } = filter {
+                return Err(Error::Config("Snapshot intervals must be nonzero".into()));
+            }
+        }
+
+        if self.metrics.enabled && self.grpc.enabled && self.metrics.addr == self.grpc.addr {
+            return Err(Error::Config(
+                "metrics.addr and grpc.addr must be different".into(),
+            ));
+        }
+
+        self.logging.level_filter()?;
+        self.tendermint.validate()?;
+
+        if self.upgrade.backup && self.upgrade.keep_backups == 0 {
+            return Err(Error::Config(
+                "upgrade.keep_backups must be at least 1 when upgrade.backup is set".into(),
+            ));
+        }
+        if self.upgrade.rollback && !self.upgrade.backup {
+            return Err(Error::Config(
+                "upgrade.rollback requires upgrade.backup".into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn set_value(doc: &mut toml_edit::Document, key: &str, value: &str) -> Result<()> {
+    let value: toml_edit::Value = value
+        .parse()
+        .unwrap_or_else(|_| toml_edit::Value::from(value));
+
+    let (parents, field) = match key.rsplit_once('.') {
+        Some((parents, field)) => (parents.split('.').collect(), field),
+        None => (vec![], key),
+    };
+    let mut table: &mut dyn toml_edit::TableLike = doc.as_table_mut();
+    for parent in parents {
+        if table.get(parent).is_none() {
+            table.insert(parent, toml_edit::table());
+        }
+        table = table
+            .get_mut(parent)
+            .and_then(|item| item.as_table_like_mut())
+            .ok_or_else(|| Error::Config(format!("{} is not a table", parent)))?;
+    }
+    table.insert(field, toml_edit::value(value));
+
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+impl NodeConfig {
+    /// The `--home` and repeatable `--config <key>=<value>` arguments, for
+    /// node binaries built with clap. See [NodeConfig::from_arg_matches].
+    pub fn args() -> [clap::Arg; 2] {
+        [
+            clap::Arg::new("home")
+                .long("home")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("The node's home directory"),
+            clap::Arg::new("config")
+                .long("config")
+                .short('c')
+                .value_name("KEY=VALUE")
+                .action(clap::ArgAction::Append)
+                .help("Overrides a value of orga.toml, e.g. halt.height=1000"),
+        ]
+    }
+
+    /// Resolves the home directory from the arguments added by
+    /// [NodeConfig::args] (falling back to `default_home`), and loads its
+    /// configuration with the overrides applied.
+    pub fn from_arg_matches(
+        matches: &clap::ArgMatches,
+        default_home: PathBuf,
+    ) -> Result<(PathBuf, Self)> {
+        let home = matches
+            .get_one::<PathBuf>("home")
+            .cloned()
+            .unwrap_or(default_home);
+        let mut config = Self::load(&home)?;
+        if let Some(overrides) = matches.get_many::<String>("config") {
+            config.apply_overrides(overrides)?;
+        }
+
+        Ok((home, config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() -> Result<()> {
+        assert_eq!(NodeConfig::from_toml("")?, NodeConfig::default());
+
+        let config = NodeConfig::from_toml(
+            r#"
+            version = 1
+
+            [abci]
+            addr = "0.0.0.0:26000"
+
+            [storage]
+            query_retention = 5
+
+            [[storage.history]]
+            type = "interval"
+            interval = 100
+            limit = 50
+
+            [halt]
+            height = 1000
+            "#,
+        )?;
+        assert_eq!(config.abci.addr, Some(([0, 0, 0, 0], 26000).into()));
+        assert_eq!(config.storage.query_retention, 5);
+        assert_eq!(config.storage.history.len(), 1);
+        assert_eq!(config.halt.height, Some(1000));
+        assert_eq!(NodeConfig::from_toml(&config.to_toml()?)?, config);
+
+        assert!(NodeConfig::from_toml("version = 2").is_err());
+        assert!(NodeConfig::from_toml("[storage]\nquery_retention = 0").is_err());
+        assert!(NodeConfig::from_toml("[halt]\nheigth = 5").is_err());
+        assert!(NodeConfig::from_toml("[upgrade]\nbackup = false").is_err());
+        assert!(
+            !NodeConfig::from_toml("[upgrade]\nbackup = false\nrollback = false")?
+                .upgrade
+                .backup
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn overrides() -> Result<()> {
+        let mut config = NodeConfig::default();
+        config.apply_overrides([
+            "halt.height=500",
+            "grpc.enabled=true",
+            "logging.level=debug",
+            "logging.tendermint=true",
+            "abci.addr=127.0.0.1:1234",
+            "tendermint.source=path",
+            "tendermint.path=/usr/bin/tendermint",
+        ])?;
+        assert_eq!(config.halt.height, Some(500));
+        assert!(config.grpc.enabled);
+        assert_eq!(config.logging.level_filter()?, log::LevelFilter::Debug);
+        assert!(config.logging.tendermint);
+        assert_eq!(config.abci.addr, Some(([127, 0, 0, 1], 1234).into()));
+        assert_eq!(config.tendermint, BinaryConfig::path("/usr/bin/tendermint"));
+
+        assert!(config.set("halt.height", "soon").is_err());
+        assert!(config.set("tendermint.source", "archive").is_err());
+        assert!(config.set("nope", "1").is_err());
+        assert!(config.set("logging.level", "loud").is_err());
+        assert!(config.apply_overrides(["halt.height"]).is_err());
+        assert_eq!(config.halt.height, Some(500));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn load() -> Result<()> {
+        let home = tempdir::TempDir::new("orga-config")?;
+        let config = NodeConfig::load(home.path())?;
+        assert_eq!(config, NodeConfig::default());
+        assert!(!NodeConfig::path(home.path()).exists());
+
+        std::fs::write(NodeConfig::path(home.path()), "[halt]\ntime = 1700000000\n")?;
+        assert_eq!(NodeConfig::load(home.path())?.halt.time, Some(1700000000));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    #[serial_test::serial]
+    fn args() -> Result<()> {
+        let home = tempdir::TempDir::new("orga-config")?;
+        let matches = clap::Command::new("node")
+            .args(NodeConfig::args())
+            .try_get_matches_from([
+                "node",
+                "--home",
+                home.path().to_str().unwrap(),
+                "-c",
+                "halt.height=10",
+                "--config",
+                "storage.tx_index=true",
+            ])
+            .unwrap();
+
+        let (path, config) = NodeConfig::from_arg_matches(&matches, "unused".into())?;
+        assert_eq!(path, home.path());
+        assert_eq!(config.halt.height, Some(10));
+        assert!(config.storage.tx_index);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Framing for ABCI socket connections.
+//!
+//! Each message is a protobuf encoding prefixed by its length as an unsigned
+//! varint, the same across Tendermint 0.34 and CometBFT 0.37 and 0.38. Requests
+//! are decoded into the method set of whichever version sent them, see the
+//! [`consensus`](super::consensus) module.
+
+use super::consensus;
+use super::messages;
+use crate::{Error, Result};
+use prost::Message;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::TcpStream;
+
+/// The largest message we will read from a connection.
+const MAX_MESSAGE_LEN: u64 = 100 * 1024 * 1024;
+
+/// A request from the consensus engine.
+#[derive(Debug)]
+pub enum Request {
+    /// A method shared by all protocol versions, or specific to 0.34.
+    Base(messages::Request),
+    /// An ABCI++ method.
+    Consensus(consensus::request::Value),
+}
+
+/// A response to a [Request].
+#[derive(Debug)]
+pub enum Response {
+    Base(messages::Response),
+    Consensus(consensus::response::Value),
+}
+
+impl Request {
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let req = consensus::Request::decode(bytes)
+            .map_err(|err| Error::ABCI(format!("Invalid request: {}", err)))?;
+        if let Some(value) = req.value {
+            return Ok(Request::Consensus(value));
+        }
+
+        messages::Request::decode(bytes)
+            .map(Request::Base)
+            .map_err(|err| Error::ABCI(format!("Invalid request: {}", err)))
+    }
+
+    pub fn is_commit(&self) -> bool {
+        matches!(
+            self,
+            Request::Base(messages::Request {
+                value: Some(messages::request::Value::Commit(_)),
+            })
+        )
+    }
+}
+
+impl Response {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Response::Base(res) => res.encode_to_vec(),
+            Response::Consensus(value) => consensus::Response {
+                value: Some(value.clone()),
+            }
+            .encode_to_vec(),
+        }
+    }
+}
+
+/// A connection from the consensus engine to the ABCI server.
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Result<Self> {
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    /// Blocks until the next request is received.
+    pub fn read(&mut self) -> Result<Request> {
+        let len = read_uvarint(&mut self.reader)?;
+        if len > MAX_MESSAGE_LEN {
+            return Err(Error::ABCI(format!("Message too large ({} bytes)", len)));
+        }
+
+        let mut bytes = vec![0; len as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Request::decode(&bytes)
+    }
+
+    pub fn write(&mut self, res: Response) -> Result<()> {
+        let bytes = res.encode();
+        let mut len = vec![];
+        prost::encoding::encode_varint(bytes.len() as u64, &mut len);
+        self.writer.write_all(&len)?;
+        self.writer.write_all(&bytes)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> Result<()> {
+        self.writer
+            .get_ref()
+            .shutdown(std::net::Shutdown::Both)
+            .map_err(Into::into)
+    }
+}
+
+fn read_uvarint<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut value = 0;
+    for i in 0..10 {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << (7 * i);
+        if byte[0] < 0x80 {
+            return Ok(value);
+        }
+    }
+
+    Err(Error::ABCI("Invalid message length prefix".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uvarint() -> Result<()> {
+        for n in [0, 1, 127, 128, 300, 1 << 40] {
+            let mut bytes = vec![];
+            prost::encoding::encode_varint(n, &mut bytes);
+            assert_eq!(read_uvarint(&mut bytes.as_slice())?, n);
+        }
+        assert!(read_uvarint(&mut [0xff; 11].as_slice()).is_err());
+
+        Ok(())
+    }
+}
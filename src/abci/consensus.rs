@@ -0,0 +1,431 @@
+//! Messages for the ABCI++ methods added in CometBFT 0.37 and 0.38:
+//! `PrepareProposal`, `ProcessProposal`, `ExtendVote`, `VerifyVoteExtension`
+//! and `FinalizeBlock`.
+//!
+//! `tendermint-proto` does not include the 0.38 protocol, so these messages are
+//! defined here by hand. Their field numbers in the request and response
+//! envelopes never collide with those of the 0.34 methods, and the messages
+//! for the methods shared by all versions (`Info`, `InitChain`, `Query`,
+//! `CheckTx`, `Commit` and the snapshot methods) are wire-compatible. A frame
+//! read from the socket can therefore be decoded as a [Request] first, falling
+//! back to a 0.34 [`Request`](super::messages::Request) when none of these
+//! methods are set, which lets the same server speak to Tendermint 0.34 and
+//! CometBFT 0.37 or 0.38.
+
+use prost::bytes::Bytes;
+use tendermint_proto::google::protobuf::Timestamp;
+use tendermint_proto::v0_34::abci::{
+    Event, Evidence, LastCommitInfo, RequestBeginBlock, ResponseDeliverTx, Validator,
+    ValidatorUpdate,
+};
+use tendermint_proto::v0_34::types::Header;
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Request {
+    #[prost(oneof = "request::Value", tags = "16, 17, 18, 19, 20")]
+    pub value: Option<request::Value>,
+}
+
+pub mod request {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Value {
+        #[prost(message, tag = "16")]
+        PrepareProposal(super::RequestPrepareProposal),
+        #[prost(message, tag = "17")]
+        ProcessProposal(super::RequestProcessProposal),
+        #[prost(message, tag = "18")]
+        ExtendVote(super::RequestExtendVote),
+        #[prost(message, tag = "19")]
+        VerifyVoteExtension(super::RequestVerifyVoteExtension),
+        #[prost(message, tag = "20")]
+        FinalizeBlock(super::RequestFinalizeBlock),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Response {
+    #[prost(oneof = "response::Value", tags = "17, 18, 19, 20, 21")]
+    pub value: Option<response::Value>,
+}
+
+pub mod response {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Value {
+        #[prost(message, tag = "17")]
+        PrepareProposal(super::ResponsePrepareProposal),
+        #[prost(message, tag = "18")]
+        ProcessProposal(super::ResponseProcessProposal),
+        #[prost(message, tag = "19")]
+        ExtendVote(super::ResponseExtendVote),
+        #[prost(message, tag = "20")]
+        VerifyVoteExtension(super::ResponseVerifyVoteExtension),
+        #[prost(message, tag = "21")]
+        FinalizeBlock(super::ResponseFinalizeBlock),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestPrepareProposal {
+    #[prost(int64, tag = "1")]
+    pub max_tx_bytes: i64,
+    #[prost(bytes = "bytes", repeated, tag = "2")]
+    pub txs: Vec<Bytes>,
+    #[prost(message, optional, tag = "3")]
+    pub local_last_commit: Option<ExtendedCommitInfo>,
+    #[prost(message, repeated, tag = "4")]
+    pub misbehavior: Vec<Evidence>,
+    #[prost(int64, tag = "5")]
+    pub height: i64,
+    #[prost(message, optional, tag = "6")]
+    pub time: Option<Timestamp>,
+    #[prost(bytes = "bytes", tag = "7")]
+    pub next_validators_hash: Bytes,
+    #[prost(bytes = "bytes", tag = "8")]
+    pub proposer_address: Bytes,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResponsePrepareProposal {
+    #[prost(bytes = "bytes", repeated, tag = "1")]
+    pub txs: Vec<Bytes>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestProcessProposal {
+    #[prost(bytes = "bytes", repeated, tag = "1")]
+    pub txs: Vec<Bytes>,
+    #[prost(message, optional, tag = "2")]
+    pub proposed_last_commit: Option<CommitInfo>,
+    #[prost(message, repeated, tag = "3")]
+    pub misbehavior: Vec<Evidence>,
+    #[prost(bytes = "bytes", tag = "4")]
+    pub hash: Bytes,
+    #[prost(int64, tag = "5")]
+    pub height: i64,
+    #[prost(message, optional, tag = "6")]
+    pub time: Option<Timestamp>,
+    #[prost(bytes = "bytes", tag = "7")]
+    pub next_validators_hash: Bytes,
+    #[prost(bytes = "bytes", tag = "8")]
+    pub proposer_address: Bytes,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResponseProcessProposal {
+    #[prost(enumeration = "ProposalStatus", tag = "1")]
+    pub status: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ProposalStatus {
+    Unknown = 0,
+    Accept = 1,
+    Reject = 2,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestExtendVote {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub hash: Bytes,
+    #[prost(int64, tag = "2")]
+    pub height: i64,
+    #[prost(message, optional, tag = "3")]
+    pub time: Option<Timestamp>,
+    #[prost(bytes = "bytes", repeated, tag = "4")]
+    pub txs: Vec<Bytes>,
+    #[prost(message, optional, tag = "5")]
+    pub proposed_last_commit: Option<CommitInfo>,
+    #[prost(message, repeated, tag = "6")]
+    pub misbehavior: Vec<Evidence>,
+    #[prost(bytes = "bytes", tag = "7")]
+    pub next_validators_hash: Bytes,
+    #[prost(bytes = "bytes", tag = "8")]
+    pub proposer_address: Bytes,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResponseExtendVote {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub vote_extension: Bytes,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestVerifyVoteExtension {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub hash: Bytes,
+    #[prost(bytes = "bytes", tag = "2")]
+    pub validator_address: Bytes,
+    #[prost(int64, tag = "3")]
+    pub height: i64,
+    #[prost(bytes = "bytes", tag = "4")]
+    pub vote_extension: Bytes,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResponseVerifyVoteExtension {
+    #[prost(enumeration = "VerifyStatus", tag = "1")]
+    pub status: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum VerifyStatus {
+    Unknown = 0,
+    Accept = 1,
+    Reject = 2,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestFinalizeBlock {
+    #[prost(bytes = "bytes", repeated, tag = "1")]
+    pub txs: Vec<Bytes>,
+    #[prost(message, optional, tag = "2")]
+    pub decided_last_commit: Option<CommitInfo>,
+    #[prost(message, repeated, tag = "3")]
+    pub misbehavior: Vec<Evidence>,
+    #[prost(bytes = "bytes", tag = "4")]
+    pub hash: Bytes,
+    #[prost(int64, tag = "5")]
+    pub height: i64,
+    #[prost(message, optional, tag = "6")]
+    pub time: Option<Timestamp>,
+    #[prost(bytes = "bytes", tag = "7")]
+    pub next_validators_hash: Bytes,
+    #[prost(bytes = "bytes", tag = "8")]
+    pub proposer_address: Bytes,
+}
+
+impl RequestFinalizeBlock {
+    /// Builds the `BeginBlock` request which starts executing this block.
+    ///
+    /// CometBFT no longer sends block headers to the app, so the header only
+    /// carries the fields which are also part of this request.
+    pub fn begin_block(&self) -> RequestBeginBlock {
+        let header = Header {
+            height: self.height,
+            time: self.time.clone(),
+            next_validators_hash: self.next_validators_hash.to_vec().into(),
+            proposer_address: self.proposer_address.to_vec().into(),
+            ..Default::default()
+        };
+
+        RequestBeginBlock {
+            hash: self.hash.clone(),
+            header: Some(header),
+            last_commit_info: self.decided_last_commit.clone().map(Into::into),
+            byzantine_validators: self.misbehavior.clone(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResponseFinalizeBlock {
+    #[prost(message, repeated, tag = "1")]
+    pub events: Vec<Event>,
+    #[prost(message, repeated, tag = "2")]
+    pub tx_results: Vec<ExecTxResult>,
+    #[prost(message, repeated, tag = "3")]
+    pub validator_updates: Vec<ValidatorUpdate>,
+    #[prost(bytes = "bytes", tag = "5")]
+    pub app_hash: Bytes,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecTxResult {
+    #[prost(uint32, tag = "1")]
+    pub code: u32,
+    #[prost(bytes = "bytes", tag = "2")]
+    pub data: Bytes,
+    #[prost(string, tag = "3")]
+    pub log: String,
+    #[prost(string, tag = "4")]
+    pub info: String,
+    #[prost(int64, tag = "5")]
+    pub gas_wanted: i64,
+    #[prost(int64, tag = "6")]
+    pub gas_used: i64,
+    #[prost(message, repeated, tag = "7")]
+    pub events: Vec<Event>,
+    #[prost(string, tag = "8")]
+    pub codespace: String,
+}
+
+impl From<ResponseDeliverTx> for ExecTxResult {
+    fn from(res: ResponseDeliverTx) -> Self {
+        ExecTxResult {
+            code: res.code,
+            data: res.data,
+            log: res.log,
+            info: res.info,
+            gas_wanted: res.gas_wanted,
+            gas_used: res.gas_used,
+            events: res.events,
+            codespace: res.codespace,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CommitInfo {
+    #[prost(int32, tag = "1")]
+    pub round: i32,
+    #[prost(message, repeated, tag = "2")]
+    pub votes: Vec<VoteInfo>,
+}
+
+impl From<CommitInfo> for LastCommitInfo {
+    fn from(info: CommitInfo) -> Self {
+        LastCommitInfo {
+            round: info.round,
+            votes: info
+                .votes
+                .into_iter()
+                .map(|vote| tendermint_proto::v0_34::abci::VoteInfo {
+                    signed_last_block: vote.signed(),
+                    validator: vote.validator,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A validator's vote for the previous block. CometBFT 0.37 reports whether
+/// the validator signed as `signed_last_block`, while 0.38 replaces it with
+/// `block_id_flag`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VoteInfo {
+    #[prost(message, optional, tag = "1")]
+    pub validator: Option<Validator>,
+    #[prost(bool, tag = "2")]
+    pub signed_last_block: bool,
+    #[prost(enumeration = "BlockIdFlag", tag = "3")]
+    pub block_id_flag: i32,
+}
+
+impl VoteInfo {
+    /// Returns true if the validator's vote was included in the commit.
+    pub fn signed(&self) -> bool {
+        self.signed_last_block || self.block_id_flag == BlockIdFlag::Commit as i32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum BlockIdFlag {
+    Unknown = 0,
+    Absent = 1,
+    Commit = 2,
+    Nil = 3,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExtendedCommitInfo {
+    #[prost(int32, tag = "1")]
+    pub round: i32,
+    #[prost(message, repeated, tag = "2")]
+    pub votes: Vec<ExtendedVoteInfo>,
+}
+
+/// A validator's vote for the previous block, along with the vote extension
+/// it signed (only set on CometBFT 0.38).
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExtendedVoteInfo {
+    #[prost(message, optional, tag = "1")]
+    pub validator: Option<Validator>,
+    #[prost(bool, tag = "2")]
+    pub signed_last_block: bool,
+    #[prost(bytes = "bytes", tag = "3")]
+    pub vote_extension: Bytes,
+    #[prost(bytes = "bytes", tag = "4")]
+    pub extension_signature: Bytes,
+    #[prost(enumeration = "BlockIdFlag", tag = "5")]
+    pub block_id_flag: i32,
+}
+
+impl ExtendedVoteInfo {
+    /// Returns true if the validator's vote was included in the commit.
+    pub fn signed(&self) -> bool {
+        self.signed_last_block || self.block_id_flag == BlockIdFlag::Commit as i32
+    }
+}
+
+/// Takes transactions from the front of `txs` until their encoded size would
+/// exceed `max_tx_bytes`, the limit CometBFT places on the transactions
+/// returned from `PrepareProposal`.
+pub fn limit_txs<T: AsRef<[u8]>>(txs: Vec<T>, max_tx_bytes: i64) -> Vec<T> {
+    let mut total = 0;
+    txs.into_iter()
+        .take_while(|tx| {
+            let len = tx.as_ref().len();
+            total +=
+                prost::encoding::key_len(1) + prost::encoding::encoded_len_varint(len as u64) + len;
+            total as i64 <= max_tx_bytes
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abci::messages;
+    use prost::Message;
+
+    #[test]
+    fn envelope_fallback() {
+        let req = messages::Request {
+            value: Some(messages::request::Value::DeliverTx(
+                messages::RequestDeliverTx { tx: vec![1].into() },
+            )),
+        };
+        let bytes = req.encode_to_vec();
+        assert_eq!(Request::decode(bytes.as_slice()).unwrap().value, None);
+
+        let req = Request {
+            value: Some(request::Value::VerifyVoteExtension(
+                RequestVerifyVoteExtension {
+                    height: 5,
+                    vote_extension: vec![1, 2, 3].into(),
+                    ..Default::default()
+                },
+            )),
+        };
+        let bytes = req.encode_to_vec();
+        assert_eq!(Request::decode(bytes.as_slice()).unwrap(), req);
+        assert_eq!(
+            messages::Request::decode(bytes.as_slice()).unwrap().value,
+            None
+        );
+    }
+
+    #[test]
+    fn vote_signed() {
+        let vote = VoteInfo {
+            block_id_flag: BlockIdFlag::Commit as i32,
+            ..Default::default()
+        };
+        assert!(vote.signed());
+
+        let info: LastCommitInfo = CommitInfo {
+            round: 1,
+            votes: vec![
+                vote,
+                VoteInfo {
+                    block_id_flag: BlockIdFlag::Absent as i32,
+                    ..Default::default()
+                },
+            ],
+        }
+        .into();
+        assert!(info.votes[0].signed_last_block);
+        assert!(!info.votes[1].signed_last_block);
+    }
+
+    #[test]
+    fn limit_tx_bytes() {
+        let txs = vec![vec![0; 10], vec![0; 10], vec![0; 10]];
+        assert_eq!(limit_txs(txs.clone(), 24).len(), 2);
+        assert_eq!(limit_txs(txs.clone(), 23).len(), 1);
+        assert_eq!(limit_txs(txs, 0).len(), 0);
+    }
+}
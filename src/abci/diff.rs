@@ -0,0 +1,129 @@
+//! Capture of the state changes made by each ABCI phase.
+//!
+//! When a [DiffSink] is attached to an
+//! [`ABCIStateMachine`](super::ABCIStateMachine), a [StateDiff] is emitted for
+//! each `InitChain`, `BeginBlock`, `DeliverTx` and `EndBlock` request which
+//! wrote to the store, followed by a combined diff for the whole block on
+//! `Commit` if the block changed any state. Raw keys can be mapped back to
+//! state fields with
+//! [`Descriptor::resolve_key`](crate::describe::Descriptor::resolve_key).
+
+use crate::describe::{format_path, Descriptor};
+use crate::store::log::Change;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The part of block execution which produced a [StateDiff].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffScope {
+    InitChain,
+    BeginBlock,
+    /// The transaction at the given index within the block.
+    DeliverTx(u32),
+    EndBlock,
+    /// All changes committed by the block.
+    Block,
+}
+
+/// The key/value changes made during a single [DiffScope].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiff {
+    pub height: u64,
+    pub scope: DiffScope,
+    pub changes: Vec<Change>,
+}
+
+impl StateDiff {
+    /// Returns the `/`-separated field path of each changed key, resolved
+    /// against the descriptor of the root state type.
+    pub fn paths(&self, desc: &Descriptor) -> Vec<String> {
+        self.changes
+            .iter()
+            .map(|change| format_path(&desc.resolve_key(change.key.as_slice())))
+            .collect()
+    }
+}
+
+/// A destination for the [StateDiff]s emitted by the state machine.
+///
+/// An error returned by the sink halts the node, the same as an error returned
+/// by the application.
+pub trait DiffSink: Send {
+    fn write(&mut self, diff: &StateDiff) -> Result<()>;
+}
+
+impl<F> DiffSink for F
+where
+    F: FnMut(&StateDiff) -> Result<()> + Send,
+{
+    fn write(&mut self, diff: &StateDiff) -> Result<()> {
+        self(diff)
+    }
+}
+
+/// Writes each diff as a line of JSON, flushing after every block.
+pub struct JsonLinesSink<W: Write + Send> {
+    out: W,
+}
+
+impl JsonLinesSink<BufWriter<File>> {
+    /// Appends diffs to the file at the given path, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write + Send> JsonLinesSink<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Send> DiffSink for JsonLinesSink<W> {
+    fn write(&mut self, diff: &StateDiff) -> Result<()> {
+        serde_json::to_writer(&mut self.out, diff)?;
+        self.out.write_all(b"\n")?;
+        if diff.scope == DiffScope::Block {
+            self.out.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lines() -> Result<()> {
+        let diff = StateDiff {
+            height: 3,
+            scope: DiffScope::DeliverTx(1),
+            changes: vec![Change {
+                key: vec![1, 2],
+                old: None,
+                new: Some(vec![3]),
+            }],
+        };
+
+        let mut sink = JsonLinesSink::new(vec![]);
+        sink.write(&diff)?;
+        sink.write(&diff)?;
+
+        let out = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(serde_json::from_str::<StateDiff>(lines[0])?, diff);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,424 @@
+//! Exporting an app's state, and starting a new chain from it.
+//!
+//! A [StateExport] holds every raw key/value entry of the state at a height,
+//! along with a hash of the entries and the root hash of the Merk tree they
+//! form. It can be embedded in the `app_state` of a genesis file (see
+//! [StateExport::to_app_state]), or written to a binary file next to the
+//! genesis file and referenced from it (see [StateExport::to_file_app_state]),
+//! which is much smaller for large states.
+//!
+//! When a node is started with such a genesis, `InitChain` writes the entries
+//! into the empty store instead of running the app's `init_chain`, then
+//! checks that the resulting state hashes to the exported hash and forms the
+//! exported Merk root, and sets the chain ID committed to by the state to the
+//! new chain's (see [ImportChainId]). The validator set in the exported state
+//! is returned to Tendermint as the new chain's initial validators, replacing
+//! the ones in the genesis file (unless the state has none).
+//!
+//! The Merk root is that of a tree built from the entries in a single batch,
+//! so it only depends on the entries. It generally differs from the
+//! exported chain's root, whose tree shape depends on the order its entries
+//! were written in, so it can't be checked against that chain's app hash.
+//!
+//! The hash checks only detect an export which was corrupted or truncated
+//! after it was written: the hash travels with the export (or, for a binary
+//! file, in the genesis referencing it), so it does not show that the export
+//! came from the original chain. The genesis file should be obtained from a
+//! trusted source, and its `state_hash` compared out of band.
+//!
+//! See [`Node::export_state`](super::Node::export_state) and
+//! [`Node::export_genesis`](super::Node::export_genesis).
+
+use super::ABCIStore;
+use crate::client::offline::hex_bytes;
+use crate::coins::Symbol;
+use crate::merk::{calc_app_hash, MerkStore};
+use crate::plugins::DefaultPlugins;
+use crate::state::State;
+use crate::store::{Read, Write};
+use crate::{Error, Result};
+use merk::tree::Tree;
+use merk::{Merk, Op};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// The current version of the export format.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// The `app_state` key of an export embedded in a genesis file.
+const INLINE_KEY: &str = "orga_export";
+
+/// The `app_state` key of a reference to a binary export file.
+const FILE_KEY: &str = "orga_export_file";
+
+/// The prefix of binary export files.
+const MAGIC: &[u8] = b"ORGAEXP\0";
+
+/// The state of an app at a given height.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateExport {
+    pub version: u32,
+    pub height: u64,
+    /// The app hash of the exported chain at `height`, for reference. The new
+    /// chain's app hash will differ since the Merk tree is rebuilt.
+    #[serde(with = "hex_bytes")]
+    pub app_hash: Vec<u8>,
+    /// The hash of the entries, see [state_hash].
+    #[serde(with = "hex_bytes")]
+    pub state_hash: Vec<u8>,
+    /// The root hash of the Merk tree formed by the entries, see [root_hash].
+    #[serde(with = "hex_bytes")]
+    pub root_hash: Vec<u8>,
+    pub entries: Vec<ExportEntry>,
+}
+
+/// A raw key/value entry, encoded as a pair of hex strings.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportEntry(
+    #[serde(with = "hex_bytes")] pub Vec<u8>,
+    #[serde(with = "hex_bytes")] pub Vec<u8>,
+);
+
+/// A reference to a binary export file from the `app_state` of a genesis.
+#[derive(Serialize, Deserialize)]
+struct FileRef {
+    path: String,
+    #[serde(with = "hex_bytes")]
+    state_hash: Vec<u8>,
+}
+
+/// Hashes the given entries, which must be in ascending key order, with each
+/// key and value prefixed by its length.
+pub fn state_hash<'a, I>(entries: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = (&'a [u8], &'a [u8])>,
+{
+    let mut hasher = Sha256::new();
+    for (key, value) in entries {
+        hasher.update((key.len() as u32).to_be_bytes());
+        hasher.update(key);
+        hasher.update((value.len() as u32).to_be_bytes());
+        hasher.update(value);
+    }
+
+    hasher.finalize().to_vec()
+}
+
+/// Computes the root hash of a Merk tree built from the given entries, which
+/// must be in ascending key order, in a single batch. The tree is built in a
+/// temporary directory, which is removed afterwards.
+pub fn root_hash<'a, I>(entries: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = (&'a [u8], &'a [u8])>,
+{
+    let batch: Vec<_> = entries
+        .into_iter()
+        .map(|(key, value)| (key.to_vec(), Op::Put(value.to_vec())))
+        .collect();
+
+    let path = std::env::temp_dir().join(format!("orga-export-{}", rand::random::<u64>()));
+    let mut merk = Merk::open(&path)?;
+    let res = merk.apply(&batch, &[]);
+    let root_hash = merk.root_hash().to_vec();
+    merk.destroy()?;
+    res?;
+
+    Ok(root_hash)
+}
+
+impl StateExport {
+    /// Exports all entries of the given store.
+    pub fn from_store<S: Read>(store: S, height: u64, app_hash: Vec<u8>) -> Result<Self> {
+        let entries = store
+            .into_iter(..)
+            .map(|entry| entry.map(|(key, value)| ExportEntry(key, value)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::new(height, app_hash, entries)
+    }
+
+    /// Exports the Merk store in the given directory at `height`, or at its
+    /// latest height if `None`. Past heights can only be exported if a
+    /// checkpoint was retained for them (see
+    /// [`MerkStore::with_history`](crate::merk::MerkStore::with_history)).
+    pub fn from_merk_home(merk_home: &Path, height: Option<u64>) -> Result<Self> {
+        let store = MerkStore::open_readonly(merk_home);
+        let latest = store.height()?;
+        let height = height.unwrap_or(latest);
+
+        if height == latest {
+            return Self::from_merk(store.merk(), height);
+        }
+
+        let path = merk_home.join("history").join(height.to_string());
+        if !path.exists() {
+            return Err(Error::App(format!(
+                "No checkpoint was retained for height {}",
+                height
+            )));
+        }
+
+        Self::from_merk(&Merk::open_readonly(path)?, height)
+    }
+
+    fn from_merk(merk: &Merk, height: u64) -> Result<Self> {
+        let mut entries = vec![];
+        let mut iter = merk.raw_iter();
+        iter.seek_to_first();
+        while iter.valid() {
+            let tree = Tree::decode(vec![], iter.value().unwrap());
+            entries.push(ExportEntry(
+                iter.key().unwrap().to_vec(),
+                tree.value().to_vec(),
+            ));
+            iter.next();
+        }
+        iter.status()?;
+
+        let app_hash = calc_app_hash(merk.root_hash().as_slice());
+
+        Self::new(height, app_hash, entries)
+    }
+
+    fn new(height: u64, app_hash: Vec<u8>, entries: Vec<ExportEntry>) -> Result<Self> {
+        let kvs = || entries.iter().map(|e| (e.0.as_slice(), e.1.as_slice()));
+        let state_hash = state_hash(kvs());
+        let root_hash = root_hash(kvs())?;
+
+        Ok(Self {
+            version: EXPORT_VERSION,
+            height,
+            app_hash,
+            state_hash,
+            root_hash,
+            entries,
+        })
+    }
+
+    /// The `app_state` of a genesis file embedding this export.
+    pub fn to_app_state(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::json!({ INLINE_KEY: serde_json::to_value(self)? }))
+    }
+
+    /// The `app_state` of a genesis file referencing this export, written
+    /// with [StateExport::to_bytes] to `path`, relative to the directory of
+    /// the genesis file.
+    pub fn to_file_app_state(&self, path: &str) -> Result<serde_json::Value> {
+        let file_ref = FileRef {
+            path: path.to_string(),
+            state_hash: self.state_hash.clone(),
+        };
+
+        Ok(serde_json::json!({ FILE_KEY: serde_json::to_value(file_ref)? }))
+    }
+
+    /// Reads an export from the `app_state` of a genesis, or returns `None`
+    /// if it does not contain one. Referenced files are resolved relative to
+    /// `genesis_dir`.
+    pub fn from_app_state(app_state_bytes: &[u8], genesis_dir: &Path) -> Result<Option<Self>> {
+        let app_state: serde_json::Value = match serde_json::from_slice(app_state_bytes) {
+            Ok(app_state) => app_state,
+            Err(_) => return Ok(None),
+        };
+
+        if let Some(export) = app_state.get(INLINE_KEY) {
+            return Ok(Some(serde_json::from_value(export.clone())?));
+        }
+
+        if let Some(file_ref) = app_state.get(FILE_KEY) {
+            let file_ref: FileRef = serde_json::from_value(file_ref.clone())?;
+            let export = Self::from_bytes(&std::fs::read(genesis_dir.join(&file_ref.path))?)?;
+            if export.state_hash != file_ref.state_hash {
+                return Err(Error::App(format!(
+                    "State export {} does not match the hash in the genesis",
+                    file_ref.path
+                )));
+            }
+            return Ok(Some(export));
+        }
+
+        Ok(None)
+    }
+
+    /// Encodes the export in the binary format: a magic prefix, the
+    /// length-prefixed JSON of the export without its entries, then each
+    /// entry's key and value, prefixed by their lengths.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let header = serde_json::to_vec(&Self {
+            entries: vec![],
+            ..self.clone()
+        })?;
+
+        let mut bytes = MAGIC.to_vec();
+        write_bytes(&mut bytes, &header);
+        for ExportEntry(key, value) in self.entries.iter() {
+            write_bytes(&mut bytes, key);
+            write_bytes(&mut bytes, value);
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut bytes = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| Error::App("Not a state export".into()))?;
+
+        let mut export: Self = serde_json::from_slice(read_bytes(&mut bytes)?)?;
+        while !bytes.is_empty() {
+            let key = read_bytes(&mut bytes)?.to_vec();
+            let value = read_bytes(&mut bytes)?.to_vec();
+            export.entries.push(ExportEntry(key, value));
+        }
+
+        Ok(export)
+    }
+
+    /// Writes the entries into the given store, which must be empty, then
+    /// checks that the resulting state matches the exported hash and Merk
+    /// root. This only detects corruption of the export, not whether it is
+    /// authentic, see the [module docs](self).
+    pub fn import<S: Read + Write + Clone>(&self, mut store: S) -> Result<()> {
+        if self.version != EXPORT_VERSION {
+            return Err(Error::App(format!(
+                "Unsupported state export version {}",
+                self.version
+            )));
+        }
+
+        if store.get_next_inclusive(&[])?.is_some() {
+            return Err(Error::App(
+                "Cannot import state into a non-empty store".into(),
+            ));
+        }
+
+        for ExportEntry(key, value) in self.entries.iter() {
+            store.put(key.clone(), value.clone())?;
+        }
+
+        let entries = store.clone().into_iter(..).collect::<Result<Vec<_>>>()?;
+        let hash = state_hash(entries.iter().map(|(k, v)| (k.as_slice(), v.as_slice())));
+        if hash != self.state_hash {
+            return Err(Error::App(format!(
+                "Imported state hash {} does not match exported hash {}",
+                hex::encode(hash),
+                hex::encode(&self.state_hash)
+            )));
+        }
+
+        let root = root_hash(entries.iter().map(|(k, v)| (k.as_slice(), v.as_slice())))?;
+        if root != self.root_hash {
+            return Err(Error::App(format!(
+                "Imported state root {} does not match exported root {}",
+                hex::encode(root),
+                hex::encode(&self.root_hash)
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Sets the chain ID committed to by an imported state, so calls signed for
+/// the new chain are accepted.
+///
+/// By default states don't commit to a chain ID. For apps wrapped in the
+/// [default plugins](DefaultPlugins), the chain ID of the
+/// [ChainCommitmentPlugin](crate::plugins::ChainCommitmentPlugin) is replaced.
+pub trait ImportChainId {
+    fn set_chain_id(&mut self, chain_id: &str) -> Result<()>;
+}
+
+impl<T> ImportChainId for T {
+    default fn set_chain_id(&mut self, _chain_id: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<S, T> ImportChainId for DefaultPlugins<S, T>
+where
+    S: Symbol,
+    T: State,
+{
+    fn set_chain_id(&mut self, chain_id: &str) -> Result<()> {
+        self.inner.borrow_mut().inner.inner.chain_id = chain_id.as_bytes().to_vec().try_into()?;
+
+        Ok(())
+    }
+}
+
+fn write_bytes(dest: &mut Vec<u8>, bytes: &[u8]) {
+    dest.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    dest.extend_from_slice(bytes);
+}
+
+fn read_bytes<'a>(src: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let err = || Error::App("Truncated state export".into());
+
+    let len_bytes = src.get(..4).ok_or_else(err)?;
+    let len = u32::from_be_bytes(len_bytes.try_into().unwrap()) as usize;
+    let bytes = src.get(4..4 + len).ok_or_else(err)?;
+    *src = &src[4 + len..];
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{MapStore, Shared};
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let mut source = Shared::new(MapStore::new());
+        source.put(vec![], vec![0, 1])?;
+        source.put(vec![1, 2], vec![3])?;
+        source.put(vec![5], vec![])?;
+
+        let export = StateExport::from_store(source, 10, vec![9; 32])?;
+        assert_eq!(export.entries.len(), 3);
+
+        let app_state = serde_json::to_vec(&export.to_app_state()?)?;
+        let inline = StateExport::from_app_state(&app_state, Path::new("."))?.unwrap();
+        assert_eq!(inline, export);
+        assert_eq!(StateExport::from_bytes(&export.to_bytes()?)?, export);
+        assert!(StateExport::from_app_state(b"{\"accounts\":{}}", Path::new("."))?.is_none());
+
+        let dest = Shared::new(MapStore::new());
+        inline.import(dest.clone())?;
+        assert_eq!(dest.get(&[1, 2])?, Some(vec![3]));
+        assert!(inline.import(dest).is_err());
+
+        let mut tampered = export.clone();
+        tampered.entries[1].1 = vec![4];
+        assert!(tampered.import(Shared::new(MapStore::new())).is_err());
+
+        let mut tampered = export.clone();
+        tampered.root_hash = vec![0; 32];
+        assert!(tampered.import(Shared::new(MapStore::new())).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn file() -> Result<()> {
+        let dir = tempdir::TempDir::new("orga-export")?;
+        let mut source = Shared::new(MapStore::new());
+        source.put(vec![1], vec![2])?;
+        let export = StateExport::from_store(source, 1, vec![])?;
+
+        std::fs::write(dir.path().join("state.bin"), export.to_bytes()?)?;
+        let app_state = serde_json::to_vec(&export.to_file_app_state("state.bin")?)?;
+        assert_eq!(
+            StateExport::from_app_state(&app_state, dir.path())?,
+            Some(export.clone())
+        );
+
+        let other = StateExport::from_store(Shared::new(MapStore::new()), 1, vec![])?;
+        std::fs::write(dir.path().join("state.bin"), other.to_bytes()?)?;
+        assert!(StateExport::from_app_state(&app_state, dir.path()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,502 @@
+//! A local index of executed transactions.
+//!
+//! When enabled with [`Node::index_txs`](super::Node::index_txs), the node
+//! records each transaction it executes in a RocksDB database next to its
+//! state: its hash, height, signer, decoded call, result code, log and
+//! events. The index can then be searched by hash, by address and by event
+//! attribute, either in-process or from another process with
+//! [TxIndex::open_reader], and serves the `GetTx` and `GetTxsEvent` gRPC
+//! methods.
+//!
+//! A transaction is listed under an address if the address signed it, or if
+//! the address appears as the value of one of its event attributes (e.g. the
+//! recipient of a transfer). Event queries can match on this with the
+//! `tx.address` attribute, e.g. `tx.address='nomic1...'`.
+
+use crate::call::Call;
+use crate::client::receipt::{Event, Receipt};
+use crate::coins::{Address, Symbol};
+use crate::context::Context;
+use crate::encoding::Decode;
+use crate::plugins::sdk_compat;
+use crate::plugins::{ChainId, DefaultPlugins, NonceCall, PayableCall, SignerCall};
+use crate::state::State;
+use crate::{Error, Result};
+use merk::rocksdb::{Options, WriteBatch, DB};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+const TX_PREFIX: u8 = b't';
+const ADDRESS_PREFIX: u8 = b'a';
+const EVENT_PREFIX: u8 = b'e';
+
+/// A transaction recorded in the index.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedTx {
+    pub hash: Vec<u8>,
+    pub height: u64,
+    /// The position of the transaction within its block.
+    pub index: u32,
+    pub signer: Option<Address>,
+    /// A rendering of the decoded call, if the app's call type supports it.
+    /// See [DescribeTx].
+    pub call: Option<String>,
+    pub code: u32,
+    pub log: String,
+    pub events: Vec<Event>,
+    /// The raw transaction bytes.
+    pub tx: Vec<u8>,
+}
+
+impl From<IndexedTx> for Receipt {
+    fn from(tx: IndexedTx) -> Self {
+        Receipt {
+            hash: tx.hash,
+            height: Some(tx.height),
+            code: tx.code,
+            log: tx.log,
+            events: tx.events,
+        }
+    }
+}
+
+/// Which results of a query to return.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Page {
+    /// The number of matching transactions to skip.
+    pub offset: u64,
+    pub limit: u64,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: 100,
+        }
+    }
+}
+
+/// A page of matching transactions, in the order they were executed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxPage {
+    pub txs: Vec<IndexedTx>,
+    /// The number of matching transactions across all pages.
+    pub total: u64,
+}
+
+/// A condition on an event attribute, written as `kind.key=value` (the value
+/// may be quoted, as in Tendermint's query syntax). The `tx.address`
+/// attribute matches the transactions listed under an address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventQuery {
+    pub kind: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl EventQuery {
+    /// A query for the transactions signed by or involving the given address.
+    pub fn address(address: Address) -> Self {
+        Self {
+            kind: "tx".to_string(),
+            key: "address".to_string(),
+            value: address.to_string(),
+        }
+    }
+
+    fn as_address(&self) -> Option<Address> {
+        if self.kind != "tx" || self.key != "address" {
+            return None;
+        }
+
+        self.value.parse().ok()
+    }
+
+    fn prefix(&self) -> Vec<u8> {
+        match self.as_address() {
+            Some(address) => [[ADDRESS_PREFIX].as_slice(), address.bytes().as_slice()].concat(),
+            None => event_prefix(&self.kind, &self.key, &self.value),
+        }
+    }
+
+    fn matches(&self, tx: &IndexedTx) -> bool {
+        if let Some(address) = self.as_address() {
+            return tx.signer == Some(address)
+                || tx.events.iter().any(|event| {
+                    event
+                        .attributes
+                        .iter()
+                        .any(|(_, v)| v.parse::<Address>().ok() == Some(address))
+                });
+        }
+
+        tx.events.iter().any(|event| {
+            event.kind == self.kind
+                && event
+                    .attributes
+                    .iter()
+                    .any(|(k, v)| *k == self.key && *v == self.value)
+        })
+    }
+}
+
+impl FromStr for EventQuery {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::Query(format!("Invalid event query: {}", s));
+        let (path, value) = s.split_once('=').ok_or_else(invalid)?;
+        let (kind, key) = path.trim().split_once('.').ok_or_else(invalid)?;
+        let value = value.trim().trim_matches('\'').trim_matches('"');
+
+        Ok(Self {
+            kind: kind.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// The transactions of the block being executed.
+#[derive(Default)]
+struct PendingBlock {
+    height: u64,
+    txs: Vec<IndexedTx>,
+}
+
+/// A transaction index stored in RocksDB.
+pub struct TxIndex {
+    db: DB,
+    reader: bool,
+    pending: Mutex<PendingBlock>,
+}
+
+impl TxIndex {
+    /// Opens the index at the given path for writing, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+
+        Ok(Self {
+            db: DB::open(&opts, path)?,
+            reader: false,
+            pending: Default::default(),
+        })
+    }
+
+    /// Opens an index written by another process, e.g. a running node, for
+    /// queries. `secondary_path` is a directory for the reader's own files.
+    pub fn open_reader<P: AsRef<Path>, Q: AsRef<Path>>(path: P, secondary_path: Q) -> Result<Self> {
+        let mut opts = Options::default();
+        opts.set_max_open_files(-1);
+
+        Ok(Self {
+            db: DB::open_as_secondary(&opts, path.as_ref(), secondary_path.as_ref())?,
+            reader: true,
+            pending: Default::default(),
+        })
+    }
+
+    /// Starts collecting the transactions of the block at the given height.
+    pub fn begin_block(&self, height: u64) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.height = height;
+        pending.txs.clear();
+    }
+
+    /// Adds an executed transaction to the current block.
+    pub fn add_tx(
+        &self,
+        tx: Vec<u8>,
+        signer: Option<Address>,
+        call: Option<String>,
+        receipt: Receipt,
+    ) {
+        let mut pending = self.pending.lock().unwrap();
+        let tx = IndexedTx {
+            hash: receipt.hash,
+            height: pending.height,
+            index: pending.txs.len() as u32,
+            signer,
+            call,
+            code: receipt.code,
+            log: receipt.log,
+            events: receipt.events,
+            tx,
+        };
+        pending.txs.push(tx);
+    }
+
+    /// Writes the transactions of the current block to the index. Writing a
+    /// block again, e.g. when it is replayed after a crash, replaces its
+    /// entries.
+    pub fn end_block(&self) -> Result<()> {
+        let txs = std::mem::take(&mut self.pending.lock().unwrap().txs);
+
+        let mut batch = WriteBatch::default();
+        for tx in txs.iter() {
+            let position = position(tx.height, tx.index);
+
+            let mut addresses: Vec<Address> = tx.signer.into_iter().collect();
+            for event in tx.events.iter() {
+                for (key, value) in event.attributes.iter() {
+                    if let Ok(address) = value.parse() {
+                        addresses.push(address);
+                    }
+                    batch.put(
+                        [event_prefix(&event.kind, key, value), position.to_vec()].concat(),
+                        &tx.hash,
+                    );
+                }
+            }
+            addresses.sort_by_key(|address| address.bytes());
+            addresses.dedup();
+            for address in addresses {
+                batch.put(
+                    [
+                        [ADDRESS_PREFIX].as_slice(),
+                        address.bytes().as_slice(),
+                        position.as_slice(),
+                    ]
+                    .concat(),
+                    &tx.hash,
+                );
+            }
+
+            batch.put(
+                [[TX_PREFIX].as_slice(), tx.hash.as_slice()].concat(),
+                serde_json::to_vec(tx)?,
+            );
+        }
+
+        self.db.write(batch)?;
+
+        Ok(())
+    }
+
+    /// Looks up a transaction by its hash.
+    pub fn tx(&self, hash: &[u8]) -> Result<Option<IndexedTx>> {
+        self.catch_up()?;
+        self.get(hash)
+    }
+
+    /// Returns the transactions signed by or involving the given address.
+    pub fn txs_by_address(&self, address: Address, page: Page) -> Result<TxPage> {
+        self.catch_up()?;
+        self.scan(&EventQuery::address(address).prefix(), page, |_| true)
+    }
+
+    /// Returns the transactions which emitted events matching all of the
+    /// given queries.
+    pub fn txs_by_events(&self, queries: &[EventQuery], page: Page) -> Result<TxPage> {
+        let (first, rest) = queries
+            .split_first()
+            .ok_or_else(|| Error::Query("Expected at least one event query".into()))?;
+
+        self.catch_up()?;
+        self.scan(&first.prefix(), page, |tx| {
+            rest.iter().all(|query| query.matches(tx))
+        })
+    }
+
+    fn get(&self, hash: &[u8]) -> Result<Option<IndexedTx>> {
+        let key = [[TX_PREFIX].as_slice(), hash].concat();
+        match self.db.get(key)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Iterates over the position-ordered entries under `prefix`, loading the
+    /// transactions they point to.
+    fn scan(
+        &self,
+        prefix: &[u8],
+        page: Page,
+        filter: impl Fn(&IndexedTx) -> bool,
+    ) -> Result<TxPage> {
+        let mut res = TxPage::default();
+        let mut iter = self.db.raw_iterator();
+        iter.seek(prefix);
+
+        while iter.valid() {
+            let key = iter.key().unwrap();
+            if !key.starts_with(prefix) {
+                break;
+            }
+
+            let tx = self
+                .get(iter.value().unwrap())?
+                .ok_or_else(|| Error::Query("Index entry for missing transaction".into()))?;
+            if filter(&tx) {
+                if res.total >= page.offset && (res.txs.len() as u64) < page.limit {
+                    res.txs.push(tx);
+                }
+                res.total += 1;
+            }
+
+            iter.next();
+        }
+        iter.status()?;
+
+        Ok(res)
+    }
+
+    fn catch_up(&self) -> Result<()> {
+        if self.reader {
+            self.db.try_catch_up_with_primary()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn position(height: u64, index: u32) -> [u8; 12] {
+    let mut bytes = [0; 12];
+    bytes[..8].copy_from_slice(&height.to_be_bytes());
+    bytes[8..].copy_from_slice(&index.to_be_bytes());
+    bytes
+}
+
+fn event_prefix(kind: &str, key: &str, value: &str) -> Vec<u8> {
+    [
+        [EVENT_PREFIX].as_slice(),
+        format!("{}.{}={}", kind, key, value).as_bytes(),
+        &[0],
+    ]
+    .concat()
+}
+
+/// Renders the call in a transaction for the index.
+///
+/// By default calls are not decoded. For apps wrapped in the
+/// [default plugins](DefaultPlugins) whose call type implements [Debug], the
+/// call (with its fee payer call) is rendered with its `Debug` format.
+pub trait DescribeTx {
+    fn describe_tx(tx: &[u8]) -> Option<String>;
+}
+
+impl<T> DescribeTx for T {
+    default fn describe_tx(_tx: &[u8]) -> Option<String> {
+        None
+    }
+}
+
+impl<S, T> DescribeTx for DefaultPlugins<S, T>
+where
+    S: Symbol,
+    T: State + Call,
+    T::Call: Debug,
+{
+    fn describe_tx(tx: &[u8]) -> Option<String> {
+        match sdk_compat::Call::<SignerCall>::decode(tx).ok()? {
+            sdk_compat::Call::Sdk(tx) => Some(format!("{:?}", tx)),
+            sdk_compat::Call::Native(call) => {
+                let chain_id = Context::resolve::<ChainId>()?;
+                let bytes = call.call_bytes.strip_prefix(chain_id.0.as_bytes())?;
+                let call = NonceCall::<PayableCall<T::Call>>::decode(bytes).ok()?;
+                Some(format!("{:?}", call.inner_call))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::receipt::tx_hash;
+
+    fn receipt(tx: &[u8], events: Vec<Event>) -> Receipt {
+        Receipt {
+            hash: tx_hash(tx),
+            events,
+            ..Default::default()
+        }
+    }
+
+    fn transfer(from: Address, to: Address) -> Event {
+        Event {
+            kind: "transfer".into(),
+            attributes: vec![
+                ("sender".into(), from.to_string()),
+                ("recipient".into(), to.to_string()),
+            ],
+        }
+    }
+
+    #[test]
+    fn index() -> Result<()> {
+        let dir = tempdir::TempDir::new("tx-index")?;
+        let index = TxIndex::open(dir.path())?;
+        let alice = Address::from_pubkey([2; 33]);
+        let bob = Address::from_pubkey([3; 33]);
+        let carol = Address::from_pubkey([4; 33]);
+
+        index.begin_block(1);
+        index.add_tx(
+            b"tx1".to_vec(),
+            Some(alice),
+            Some("Transfer".into()),
+            receipt(b"tx1", vec![transfer(alice, bob)]),
+        );
+        index.add_tx(b"tx2".to_vec(), None, None, receipt(b"tx2", vec![]));
+        index.end_block()?;
+
+        index.begin_block(2);
+        index.add_tx(
+            b"tx3".to_vec(),
+            Some(bob),
+            None,
+            receipt(b"tx3", vec![transfer(bob, carol)]),
+        );
+        index.end_block()?;
+
+        let tx = index.tx(&tx_hash(b"tx3"))?.unwrap();
+        assert_eq!((tx.height, tx.index, tx.signer), (2, 0, Some(bob)));
+        assert_eq!(index.tx(&tx_hash(b"tx2"))?.unwrap().index, 1);
+        assert!(index.tx(&tx_hash(b"tx4"))?.is_none());
+
+        let page = index.txs_by_address(bob, Page::default())?;
+        assert_eq!(page.total, 2);
+        assert_eq!(page.txs[0].tx, b"tx1");
+        assert_eq!(page.txs[1].tx, b"tx3");
+
+        let page = index.txs_by_address(
+            bob,
+            Page {
+                offset: 1,
+                limit: 1,
+            },
+        )?;
+        assert_eq!(page.total, 2);
+        assert_eq!(page.txs.len(), 1);
+        assert_eq!(page.txs[0].tx, b"tx3");
+
+        let query: EventQuery = format!("transfer.recipient='{}'", carol).parse()?;
+        let page = index.txs_by_events(&[query.clone()], Page::default())?;
+        assert_eq!(page.total, 1);
+        assert_eq!(page.txs[0].signer, Some(bob));
+
+        let other: EventQuery = format!("transfer.sender='{}'", alice).parse()?;
+        let page = index.txs_by_events(&[query.clone(), other], Page::default())?;
+        assert_eq!(page.total, 0);
+
+        let page = index.txs_by_events(&[EventQuery::address(alice)], Page::default())?;
+        assert_eq!(page.total, 1);
+        assert_eq!(page.txs[0].tx, b"tx1");
+
+        let by_address: EventQuery = format!("tx.address='{}'", bob).parse()?;
+        assert_eq!(by_address, EventQuery::address(bob));
+        let page = index.txs_by_events(&[by_address, query], Page::default())?;
+        assert_eq!(page.total, 1);
+        assert_eq!(page.txs[0].tx, b"tx3");
+
+        assert!("transfer".parse::<EventQuery>().is_err());
+
+        Ok(())
+    }
+}
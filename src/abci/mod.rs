@@ -4,24 +4,43 @@ use crate::state::State;
 
 use crate::Result;
 #[cfg(feature = "abci")]
+pub mod browser;
+#[cfg(feature = "abci")]
+pub mod config;
+#[cfg(feature = "abci")]
+mod conn;
+#[cfg(feature = "abci")]
+pub mod export;
+#[cfg(feature = "abci")]
+pub mod indexer;
+#[cfg(feature = "abci")]
 mod node;
 #[cfg(feature = "abci")]
+pub mod supervisor;
+#[cfg(feature = "abci")]
 pub use node::*;
 
+pub mod consensus;
+pub mod diff;
 pub mod prost;
+pub mod test_chain;
 
 use messages::*;
 pub use tendermint_proto::v0_34::abci as messages;
 
 #[cfg(feature = "abci")]
 mod server {
+    use super::config::HaltConfig;
+    use super::conn::{self, Connection};
+    use super::consensus::{self, limit_txs, ProposalStatus, VerifyStatus};
+    use super::diff::{DiffScope, DiffSink, StateDiff};
     use super::*;
     use crate::merk::MerkStore;
+    use crate::store::log::{merge_changes, Change, WriteLog};
     use crate::store::{BufStore, BufStoreMap, MapStore, Read, Shared, Write, KV};
     use crate::Error;
     use log::info;
-    use std::env;
-    use std::net::ToSocketAddrs;
+    use std::net::{TcpListener, ToSocketAddrs};
     use std::sync::mpsc::{self, Receiver, SyncSender};
     use std::sync::{Arc, RwLock};
     use tendermint_proto::v0_34::abci::request::Value as Req;
@@ -33,8 +52,8 @@ mod server {
     pub struct ABCIStateMachine<A: Application> {
         app: Option<A>,
         store: Option<Shared<MerkStore>>,
-        receiver: Receiver<(Request, SyncSender<Response>)>,
-        sender: SyncSender<(Request, SyncSender<Response>)>,
+        receiver: Receiver<(conn::Request, SyncSender<conn::Response>)>,
+        sender: SyncSender<(conn::Request, SyncSender<conn::Response>)>,
         mempool_state: Option<BufStoreMap>,
         consensus_state: Option<BufStoreMap>,
         height: u64,
@@ -42,6 +61,11 @@ mod server {
         header: Option<Header>,
         shutdown: Arc<RwLock<Option<Error>>>,
         shutdown_notifier: Arc<RwLock<bool>>,
+        diff_sink: Option<Box<dyn DiffSink>>,
+        block_changes: Vec<Change>,
+        tx_index: u32,
+        finalized: bool,
+        halt: HaltConfig,
     }
 
     impl<A: Application> ABCIStateMachine<A> {
@@ -68,9 +92,104 @@ mod server {
                 header: None,
                 shutdown,
                 shutdown_notifier,
+                diff_sink: None,
+                block_changes: vec![],
+                tx_index: 0,
+                finalized: false,
+                halt: Default::default(),
             }
         }
 
+        /// Stops the state machine once the given height or block time has
+        /// been committed. See [HaltConfig].
+        #[must_use]
+        pub fn with_halt(mut self, halt: HaltConfig) -> Self {
+            self.halt = halt;
+
+            self
+        }
+
+        /// Returns an error describing the halt condition if the last committed
+        /// block reached it.
+        fn check_halt(&self) -> Result<()> {
+            if let Some(halt_height) = self.halt.height {
+                if self.height >= halt_height {
+                    return Err(Error::ABCI(format!(
+                        "Reached stop height ({})",
+                        halt_height
+                    )));
+                }
+            }
+
+            if let Some(halt_time) = self.halt.time {
+                let time = self
+                    .header
+                    .as_ref()
+                    .and_then(|header| header.time.as_ref())
+                    .map(|time| time.seconds);
+                if matches!(time, Some(time) if time >= halt_time) {
+                    return Err(Error::ABCI(format!("Reached halt time ({})", halt_time)));
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Emits the state changes made by each phase of each block to the
+        /// given sink. See the [`diff`](super::diff) module for details.
+        #[must_use]
+        pub fn with_diff_sink(mut self, sink: Box<dyn DiffSink>) -> Self {
+            self.diff_sink = Some(sink);
+
+            self
+        }
+
+        /// Flushes a phase's write buffer into the consensus state, emitting
+        /// the resulting diff if a sink is attached.
+        fn flush_phase<S>(&mut self, mut buf: BufStore<S>, scope: DiffScope) -> Result<()>
+        where
+            S: Read + Write + Clone,
+        {
+            if self.diff_sink.is_none() {
+                return buf.flush();
+            }
+
+            let mut log = WriteLog::new(buf.store().clone());
+            for (key, value) in buf.into_map() {
+                match value {
+                    Some(value) => log.put(key, value)?,
+                    None => log.delete(key.as_slice())?,
+                }
+            }
+
+            let changes = log.changes();
+            if changes.is_empty() {
+                return Ok(());
+            }
+
+            let block_changes = std::mem::take(&mut self.block_changes);
+            self.block_changes = merge_changes(block_changes, changes.clone());
+
+            self.emit_diff(StateDiff {
+                height: self.block_height(),
+                scope,
+                changes,
+            })
+        }
+
+        fn emit_diff(&mut self, diff: StateDiff) -> Result<()> {
+            match self.diff_sink.as_mut() {
+                Some(sink) => sink.write(&diff),
+                None => Ok(()),
+            }
+        }
+
+        fn block_height(&self) -> u64 {
+            self.header
+                .as_ref()
+                .map_or(0, |header| header.height as u64)
+        }
+
         /// Handles a single incoming ABCI request.
         ///
         /// Some messages, such as `info`, `flush`, and `echo` are automatically
@@ -84,6 +203,9 @@ mod server {
                 Some(value) => value,
             };
 
+            #[cfg(feature = "metrics")]
+            let _timer = crate::metrics::phase_timer(phase_name(&value));
+
             match value {
                 Req::Info(_) => {
                     let self_store = self.store.take().unwrap().into_inner();
@@ -115,6 +237,8 @@ mod server {
                     let store = self.store.take().unwrap();
                     let app = self.app.take().unwrap();
 
+                    #[cfg(feature = "metrics")]
+                    let _query_timer = crate::metrics::query_timer(&req.path);
                     let res = app
                         .query(store.clone(), req)
                         .unwrap_or_else(|err| ResponseQuery {
@@ -151,8 +275,7 @@ mod server {
                         let owned_store = store.take().unwrap();
                         let flush_store = Shared::new(BufStore::wrap(owned_store.clone()));
                         let res = app.init_chain(flush_store.clone(), req)?;
-                        let mut unwrapped_fs = flush_store.into_inner();
-                        unwrapped_fs.flush()?;
+                        self.flush_phase(flush_store.into_inner(), DiffScope::InitChain)?;
                         store.replace(owned_store);
                         res
                     };
@@ -166,16 +289,11 @@ mod server {
                     Ok(Res::InitChain(res_init_chain))
                 }
                 Req::BeginBlock(req) => {
-                    if let Some(stop_height_str) = env::var_os("ORGA_STOP_HEIGHT") {
-                        let stop_height: i64 = stop_height_str
-                            .into_string()
-                            .unwrap()
-                            .parse()
-                            .expect("Invalid ORGA_STOP_HEIGHT value");
-                        if req.header.as_ref().unwrap().height > stop_height {
+                    if let Some(halt_height) = self.halt.height {
+                        if req.header.as_ref().unwrap().height as u64 > halt_height {
                             return Err(Error::ABCI(format!(
                                 "Reached stop height ({})",
-                                stop_height
+                                halt_height
                             )));
                         }
                     }
@@ -184,6 +302,9 @@ mod server {
                     let self_store = self.store.take().unwrap().into_inner();
                     let self_store_shared = Shared::new(self_store);
                     self.header = req.header.clone();
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::begin_block();
+                    self.tx_index = 0;
 
                     let mut store = Some(Shared::new(BufStore::wrap_with_map(
                         self_store_shared.clone(),
@@ -194,8 +315,7 @@ mod server {
                         let owned_store = store.take().unwrap();
                         let flush_store = Shared::new(BufStore::wrap(owned_store.clone()));
                         let res = app.begin_block(flush_store.clone(), req)?;
-                        let mut unwrapped_fs = flush_store.into_inner();
-                        unwrapped_fs.flush()?;
+                        self.flush_phase(flush_store.into_inner(), DiffScope::BeginBlock)?;
                         store.replace(owned_store);
                         res
                     };
@@ -221,10 +341,13 @@ mod server {
                         let owned_store = store.take().unwrap();
                         let flush_store = Shared::new(BufStore::wrap(owned_store.clone()));
                         let res = app.deliver_tx(flush_store.clone(), req)?;
-                        {
-                            let mut unwrapped_fs = flush_store.into_inner();
-                            unwrapped_fs.flush()?;
-                        }
+                        #[cfg(feature = "metrics")]
+                        crate::metrics::record_tx("deliver_tx", res.code);
+                        self.flush_phase(
+                            flush_store.into_inner(),
+                            DiffScope::DeliverTx(self.tx_index),
+                        )?;
+                        self.tx_index += 1;
                         let mut owned_store_inner = owned_store.into_inner();
                         owned_store_inner.flush()?;
                         let owned_store = Shared::new(owned_store_inner);
@@ -254,8 +377,7 @@ mod server {
                         let owned_store = store.take().unwrap();
                         let flush_store = Shared::new(BufStore::wrap(owned_store.clone()));
                         let res = app.end_block(flush_store.clone(), req)?;
-                        let mut unwrapped_fs = flush_store.into_inner();
-                        unwrapped_fs.flush()?;
+                        self.flush_phase(flush_store.into_inner(), DiffScope::EndBlock)?;
                         store.replace(owned_store);
                         res
                     };
@@ -268,27 +390,35 @@ mod server {
                     Ok(Res::EndBlock(res_end_block))
                 }
                 Req::Commit(_) => {
-                    let self_store = self.store.take().unwrap().into_inner();
-                    let mut self_store_shared = Shared::new(self_store);
-                    {
-                        let mut store = BufStore::wrap_with_map(
-                            self_store_shared.clone(),
-                            self.consensus_state.take().unwrap(),
-                        );
-                        store.flush()?;
+                    // blocks finalized through ABCI++ were already written to
+                    // the store to compute their app hash
+                    if !std::mem::take(&mut self.finalized) {
+                        self.flush_consensus_state()?;
                     }
 
+                    let self_store = self.store.take().unwrap().into_inner();
+                    let mut self_store_shared = Shared::new(self_store);
                     self_store_shared
                         .borrow_mut()
                         .commit(self.header.clone().unwrap())?;
 
                     self.mempool_state.replace(Default::default());
-                    self.consensus_state.replace(Default::default());
+
+                    let changes = std::mem::take(&mut self.block_changes);
+                    if !changes.is_empty() {
+                        self.emit_diff(StateDiff {
+                            height: self.block_height(),
+                            scope: DiffScope::Block,
+                            changes,
+                        })?;
+                    }
 
                     let mut res_commit = ResponseCommit::default();
                     let self_store = self_store_shared.into_inner();
 
                     res_commit.data = self_store.root_hash()?.into();
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::record_commit(self.height, &res_commit.data);
                     self.store = Some(Shared::new(self_store));
                     Ok(Res::Commit(res_commit))
                 }
@@ -305,6 +435,8 @@ mod server {
                         let owned_store = store.take().unwrap();
                         let flush_store = Shared::new(BufStore::wrap(owned_store.clone()));
                         let res = app.check_tx(flush_store.clone(), req)?;
+                        #[cfg(feature = "metrics")]
+                        crate::metrics::record_tx("check_tx", res.code);
 
                         let mut unwrapped_fs = flush_store.into_inner();
                         unwrapped_fs.flush()?;
@@ -355,25 +487,170 @@ mod server {
             }
         }
 
+        /// Handles a single incoming ABCI++ request, as sent by CometBFT 0.37
+        /// and 0.38.
+        ///
+        /// Proposals and vote extensions are handled against a throwaway view
+        /// of the committed state, so the app can inspect state but its writes
+        /// are discarded. `FinalizeBlock` is executed as the `BeginBlock`,
+        /// `DeliverTx` and `EndBlock` phases of a 0.34 block, then written to
+        /// the store since the app hash must be part of the response. The
+        /// store is only committed by the following `Commit` request.
+        ///
+        /// Errors from the app while preparing a proposal or extending a vote
+        /// are logged rather than halting the node, falling back to the
+        /// mempool's transactions or an empty extension.
+        pub fn run_consensus(
+            &mut self,
+            req: consensus::request::Value,
+        ) -> Result<consensus::response::Value> {
+            use consensus::request::Value as ConsensusReq;
+            use consensus::response::Value as ConsensusRes;
+
+            #[cfg(feature = "metrics")]
+            let _timer = crate::metrics::phase_timer(consensus_phase_name(&req));
+
+            match req {
+                ConsensusReq::PrepareProposal(req) => {
+                    let fallback_txs = limit_txs(req.txs.clone(), req.max_tx_bytes);
+                    let res = self
+                        .run_readonly(|app, store| app.prepare_proposal(store, req))
+                        .unwrap_or_else(|err| {
+                            log::warn!("Proposing mempool transactions: {}", err);
+                            consensus::ResponsePrepareProposal { txs: fallback_txs }
+                        });
+                    Ok(ConsensusRes::PrepareProposal(res))
+                }
+                ConsensusReq::ProcessProposal(req) => {
+                    let res = self
+                        .run_readonly(|app, store| app.process_proposal(store, req))
+                        .unwrap_or_else(|err| {
+                            log::warn!("Rejecting proposal: {}", err);
+                            consensus::ResponseProcessProposal {
+                                status: ProposalStatus::Reject as i32,
+                            }
+                        });
+                    Ok(ConsensusRes::ProcessProposal(res))
+                }
+                ConsensusReq::ExtendVote(req) => {
+                    let res = self
+                        .run_readonly(|app, store| app.extend_vote(store, req))
+                        .unwrap_or_else(|err| {
+                            log::warn!("Sending empty vote extension: {}", err);
+                            Default::default()
+                        });
+                    Ok(ConsensusRes::ExtendVote(res))
+                }
+                ConsensusReq::VerifyVoteExtension(req) => {
+                    let res = self
+                        .run_readonly(|app, store| app.verify_vote_extension(store, req))
+                        .unwrap_or_else(|err| {
+                            log::warn!("Rejecting vote extension: {}", err);
+                            consensus::ResponseVerifyVoteExtension {
+                                status: VerifyStatus::Reject as i32,
+                            }
+                        });
+                    Ok(ConsensusRes::VerifyVoteExtension(res))
+                }
+                ConsensusReq::FinalizeBlock(req) => {
+                    let mut res = consensus::ResponseFinalizeBlock::default();
+
+                    let begin_block = Req::BeginBlock(req.begin_block());
+                    if let Res::BeginBlock(begin_res) = self.run(Request {
+                        value: Some(begin_block),
+                    })? {
+                        res.events.extend(begin_res.events);
+                    }
+
+                    for tx in req.txs {
+                        let deliver_tx = Req::DeliverTx(RequestDeliverTx { tx });
+                        if let Res::DeliverTx(deliver_res) = self.run(Request {
+                            value: Some(deliver_tx),
+                        })? {
+                            res.tx_results.push(deliver_res.into());
+                        }
+                    }
+
+                    let end_block = Req::EndBlock(RequestEndBlock { height: req.height });
+                    if let Res::EndBlock(end_res) = self.run(Request {
+                        value: Some(end_block),
+                    })? {
+                        res.events.extend(end_res.events);
+                        res.validator_updates = end_res.validator_updates;
+                    }
+
+                    self.flush_consensus_state()?;
+                    let height = self.block_height();
+                    let store = self.store.as_mut().unwrap();
+                    store.borrow_mut().apply_block(height)?;
+                    res.app_hash = store.borrow().root_hash()?.into();
+                    self.finalized = true;
+
+                    Ok(ConsensusRes::FinalizeBlock(res))
+                }
+            }
+        }
+
+        /// Writes the changes made by the current block to the store, without
+        /// committing it.
+        fn flush_consensus_state(&mut self) -> Result<()> {
+            let self_store = self.store.take().unwrap().into_inner();
+            let self_store_shared = Shared::new(self_store);
+            {
+                let mut store = BufStore::wrap_with_map(
+                    self_store_shared.clone(),
+                    self.consensus_state.take().unwrap(),
+                );
+                store.flush()?;
+            }
+
+            self.consensus_state.replace(Default::default());
+            self.store = Some(Shared::new(self_store_shared.into_inner()));
+            Ok(())
+        }
+
+        /// Runs an app handler against a buffer over the committed state which
+        /// is dropped afterwards.
+        fn run_readonly<T, F>(&mut self, op: F) -> Result<T>
+        where
+            F: FnOnce(&A, WrappedMerk) -> Result<T>,
+        {
+            let app = self.app.take().unwrap();
+            let self_store = self.store.take().unwrap();
+
+            let store = Shared::new(BufStore::wrap(Shared::new(BufStore::wrap(
+                self_store.clone(),
+            ))));
+            let res = op(&app, store);
+
+            self.app.replace(app);
+            self.store.replace(self_store);
+            res
+        }
+
+        /// Handles a request read from an ABCI connection.
+        fn run_any(&mut self, req: conn::Request) -> Result<conn::Response> {
+            Ok(match req {
+                conn::Request::Base(req) => conn::Response::Base(Response {
+                    value: Some(self.run(req)?),
+                }),
+                conn::Request::Consensus(req) => {
+                    conn::Response::Consensus(self.run_consensus(req)?)
+                }
+            })
+        }
+
         /// Creates a TCP server for the ABCI protocol and begins handling the
         /// incoming connections.
         pub fn listen<SA: ToSocketAddrs>(mut self, addr: SA) -> Result<Arc<RwLock<bool>>> {
-            if let Some(stop_height_str) = env::var_os("ORGA_STOP_HEIGHT") {
-                let _stop_height: u64 = stop_height_str
-                    .into_string()
-                    .unwrap()
-                    .parse()
-                    .expect("Invalid ORGA_STOP_HEIGHT value");
-            }
-
-            let server = abci2::Server::listen(addr)?;
+            let server = TcpListener::bind(addr)?;
 
             // TODO: keep workers in struct
             // TODO: more intelligently handle connections, e.g. handle tendermint dying/reconnecting?
-            self.create_worker(server.accept()?, self.shutdown.clone())?;
-            self.create_worker(server.accept()?, self.shutdown.clone())?;
-            self.create_worker(server.accept()?, self.shutdown.clone())?;
-            self.create_worker(server.accept()?, self.shutdown.clone())?;
+            self.create_worker(server.accept()?.0, self.shutdown.clone())?;
+            self.create_worker(server.accept()?.0, self.shutdown.clone())?;
+            self.create_worker(server.accept()?.0, self.shutdown.clone())?;
+            self.create_worker(server.accept()?.0, self.shutdown.clone())?;
 
             loop {
                 if let Some(e) = self.shutdown.read().unwrap().as_ref() {
@@ -391,8 +668,8 @@ mod server {
                         continue;
                     }
                 };
-                let is_commit = matches!(req.value, Some(Req::Commit(_)));
-                let value = match self.run(req) {
+                let is_commit = req.is_commit();
+                let res = match self.run_any(req) {
                     Ok(val) => val,
                     Err(e) => {
                         let mut shutdown = self.shutdown.write().unwrap();
@@ -402,24 +679,13 @@ mod server {
                         return Err(e);
                     }
                 };
-                let res = Response { value: Some(value) };
                 cb.send(res).unwrap();
 
                 if is_commit {
-                    if let Some(stop_height_str) = env::var_os("ORGA_STOP_HEIGHT") {
-                        let stop_height: u64 = stop_height_str
-                            .into_string()
-                            .unwrap()
-                            .parse()
-                            .expect("Invalid ORGA_STOP_HEIGHT value");
-                        if self.height >= stop_height {
-                            let mut shutdown = self.shutdown_notifier.write().unwrap();
-                            *shutdown = true;
-                            break Err(Error::ABCI(format!(
-                                "Reached stop height ({})",
-                                stop_height
-                            )));
-                        }
+                    if let Err(err) = self.check_halt() {
+                        let mut shutdown = self.shutdown_notifier.write().unwrap();
+                        *shutdown = true;
+                        break Err(err);
                     }
                 }
             }
@@ -429,13 +695,49 @@ mod server {
         /// within its own threads.
         fn create_worker(
             &self,
-            conn: abci2::Connection,
+            stream: std::net::TcpStream,
             shutdown: Arc<RwLock<Option<Error>>>,
         ) -> Result<Worker> {
+            let conn = Connection::new(stream)?;
             Ok(Worker::new(self.sender.clone(), conn, shutdown))
         }
     }
 
+    /// The label of an ABCI request in the phase timing metrics.
+    #[cfg(feature = "metrics")]
+    fn phase_name(req: &Req) -> &'static str {
+        match req {
+            Req::Echo(_) => "echo",
+            Req::Flush(_) => "flush",
+            Req::Info(_) => "info",
+            Req::SetOption(_) => "set_option",
+            Req::InitChain(_) => "init_chain",
+            Req::Query(_) => "query",
+            Req::BeginBlock(_) => "begin_block",
+            Req::CheckTx(_) => "check_tx",
+            Req::DeliverTx(_) => "deliver_tx",
+            Req::EndBlock(_) => "end_block",
+            Req::Commit(_) => "commit",
+            Req::ListSnapshots(_) => "list_snapshots",
+            Req::OfferSnapshot(_) => "offer_snapshot",
+            Req::LoadSnapshotChunk(_) => "load_snapshot_chunk",
+            Req::ApplySnapshotChunk(_) => "apply_snapshot_chunk",
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn consensus_phase_name(req: &consensus::request::Value) -> &'static str {
+        use consensus::request::Value as ConsensusReq;
+
+        match req {
+            ConsensusReq::PrepareProposal(_) => "prepare_proposal",
+            ConsensusReq::ProcessProposal(_) => "process_proposal",
+            ConsensusReq::ExtendVote(_) => "extend_vote",
+            ConsensusReq::VerifyVoteExtension(_) => "verify_vote_extension",
+            ConsensusReq::FinalizeBlock(_) => "finalize_block",
+        }
+    }
+
     struct Worker {
         #[allow(dead_code)]
         thread: std::thread::JoinHandle<()>, // TODO: keep handle to connection or socket so we can close it
@@ -443,8 +745,8 @@ mod server {
 
     impl Worker {
         fn new(
-            req_sender: SyncSender<(Request, SyncSender<Response>)>,
-            mut conn: abci2::Connection,
+            req_sender: SyncSender<(conn::Request, SyncSender<conn::Response>)>,
+            mut conn: Connection,
             shutdown: Arc<RwLock<Option<Error>>>,
         ) -> Self {
             let thread = std::thread::spawn(move || {
@@ -460,7 +762,7 @@ mod server {
                         Ok(req) => req,
                         Err(e) => {
                             let mut shutdown = shutdown.write().unwrap();
-                            *shutdown = Some(Error::ABCI2(e));
+                            *shutdown = Some(e);
                             return;
                         }
                     };
@@ -524,6 +826,50 @@ mod server {
         fn query(&self, _store: Shared<MerkStore>, _req: RequestQuery) -> Result<ResponseQuery> {
             Ok(Default::default())
         }
+
+        /// Returns the transactions to include in a block proposed by this
+        /// node. Defaults to the mempool's transactions, up to the size limit.
+        fn prepare_proposal(
+            &self,
+            _store: WrappedMerk,
+            req: consensus::RequestPrepareProposal,
+        ) -> Result<consensus::ResponsePrepareProposal> {
+            Ok(consensus::ResponsePrepareProposal {
+                txs: limit_txs(req.txs, req.max_tx_bytes),
+            })
+        }
+
+        /// Accepts or rejects a block proposed by another node. Returning an
+        /// error rejects the proposal.
+        fn process_proposal(
+            &self,
+            _store: WrappedMerk,
+            _req: consensus::RequestProcessProposal,
+        ) -> Result<consensus::ResponseProcessProposal> {
+            Ok(consensus::ResponseProcessProposal {
+                status: ProposalStatus::Accept as i32,
+            })
+        }
+
+        fn extend_vote(
+            &self,
+            _store: WrappedMerk,
+            _req: consensus::RequestExtendVote,
+        ) -> Result<consensus::ResponseExtendVote> {
+            Ok(Default::default())
+        }
+
+        /// Accepts or rejects another validator's vote extension. Returning an
+        /// error rejects the extension.
+        fn verify_vote_extension(
+            &self,
+            _store: WrappedMerk,
+            _req: consensus::RequestVerifyVoteExtension,
+        ) -> Result<consensus::ResponseVerifyVoteExtension> {
+            Ok(consensus::ResponseVerifyVoteExtension {
+                status: VerifyStatus::Accept as i32,
+            })
+        }
     }
 
     /// Interface for persisting ABCI app state, as a supertrait of [`store::Store`](../store/trait.Store.html).
@@ -620,12 +966,251 @@ mod server {
             Ok(Default::default())
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ::prost::Message;
+        use consensus::request::Value as ConsensusReq;
+        use consensus::response::Value as ConsensusRes;
+        use tendermint_proto::google::protobuf::Timestamp;
+
+        /// Drives a state machine in-process, passing each request and
+        /// response through the same encoding as a socket connection.
+        struct ScriptedClient<A: Application>(ABCIStateMachine<A>);
+
+        impl<A: Application> ScriptedClient<A> {
+            fn base(&mut self, req: Req) -> Result<Res> {
+                let bytes = Request { value: Some(req) }.encode_to_vec();
+                let res = self.0.run_any(conn::Request::decode(&bytes)?)?;
+                let res = Response::decode(res.encode().as_slice()).unwrap();
+                Ok(res.value.unwrap())
+            }
+
+            fn consensus(&mut self, req: ConsensusReq) -> Result<ConsensusRes> {
+                let bytes = consensus::Request { value: Some(req) }.encode_to_vec();
+                let res = self.0.run_any(conn::Request::decode(&bytes)?)?;
+                let res = consensus::Response::decode(res.encode().as_slice()).unwrap();
+                Ok(res.value.unwrap())
+            }
+        }
+
+        struct TestApp;
+
+        impl Application for TestApp {
+            fn deliver_tx(
+                &self,
+                mut store: WrappedMerk,
+                req: RequestDeliverTx,
+            ) -> Result<ResponseDeliverTx> {
+                store.put(req.tx.to_vec(), vec![1])?;
+                Ok(Default::default())
+            }
+
+            fn prepare_proposal(
+                &self,
+                mut store: WrappedMerk,
+                req: consensus::RequestPrepareProposal,
+            ) -> Result<consensus::ResponsePrepareProposal> {
+                store.put(b"prepare".to_vec(), vec![1])?;
+                if req.txs.iter().any(|tx| tx.is_empty()) {
+                    return Err(Error::App("Empty transaction".into()));
+                }
+                Ok(consensus::ResponsePrepareProposal {
+                    txs: limit_txs(req.txs, req.max_tx_bytes),
+                })
+            }
+
+            fn process_proposal(
+                &self,
+                _store: WrappedMerk,
+                req: consensus::RequestProcessProposal,
+            ) -> Result<consensus::ResponseProcessProposal> {
+                if req.txs.iter().any(|tx| tx.is_empty()) {
+                    return Err(Error::App("Empty transaction".into()));
+                }
+                Ok(consensus::ResponseProcessProposal {
+                    status: ProposalStatus::Accept as i32,
+                })
+            }
+
+            fn extend_vote(
+                &self,
+                _store: WrappedMerk,
+                req: consensus::RequestExtendVote,
+            ) -> Result<consensus::ResponseExtendVote> {
+                if req.height == 0 {
+                    return Err(Error::App("Missing height".into()));
+                }
+                Ok(consensus::ResponseExtendVote {
+                    vote_extension: req.height.to_be_bytes().to_vec().into(),
+                })
+            }
+
+            fn verify_vote_extension(
+                &self,
+                _store: WrappedMerk,
+                req: consensus::RequestVerifyVoteExtension,
+            ) -> Result<consensus::ResponseVerifyVoteExtension> {
+                if req.vote_extension.as_ref() != req.height.to_be_bytes() {
+                    return Err(Error::App("Invalid vote extension".into()));
+                }
+                Ok(consensus::ResponseVerifyVoteExtension {
+                    status: VerifyStatus::Accept as i32,
+                })
+            }
+        }
+
+        fn time() -> Option<Timestamp> {
+            Some(Timestamp {
+                seconds: 1,
+                nanos: 0,
+            })
+        }
+
+        #[test]
+        fn consensus_script() -> Result<()> {
+            let home = tempdir::TempDir::new("orga-abci").unwrap();
+            let mut client = ScriptedClient(ABCIStateMachine::new(
+                TestApp,
+                MerkStore::new(home.path().join("merk")),
+                false,
+                Arc::new(RwLock::new(None)),
+                Arc::new(RwLock::new(false)),
+            ));
+
+            client.base(Req::InitChain(Default::default()))?;
+
+            let res = client.consensus(ConsensusReq::PrepareProposal(
+                consensus::RequestPrepareProposal {
+                    max_tx_bytes: 24,
+                    txs: vec![vec![0; 10].into(); 3],
+                    height: 1,
+                    ..Default::default()
+                },
+            ))?;
+            assert!(matches!(res, ConsensusRes::PrepareProposal(res) if res.txs.len() == 2));
+
+            // app errors fall back to the mempool's transactions
+            let res = client.consensus(ConsensusReq::PrepareProposal(
+                consensus::RequestPrepareProposal {
+                    max_tx_bytes: 24,
+                    txs: vec![vec![].into(); 3],
+                    height: 1,
+                    ..Default::default()
+                },
+            ))?;
+            assert!(matches!(res, ConsensusRes::PrepareProposal(res) if res.txs.len() == 3));
+
+            let process = |txs: Vec<&[u8]>| {
+                ConsensusReq::ProcessProposal(consensus::RequestProcessProposal {
+                    txs: txs.into_iter().map(|tx| tx.to_vec().into()).collect(),
+                    height: 1,
+                    ..Default::default()
+                })
+            };
+            let res = client.consensus(process(vec![b"a"]))?;
+            assert!(matches!(res, ConsensusRes::ProcessProposal(res) if res.status == 1));
+            let res = client.consensus(process(vec![b"a", b""]))?;
+            assert!(matches!(res, ConsensusRes::ProcessProposal(res) if res.status == 2));
+
+            let res = client.consensus(ConsensusReq::ExtendVote(Default::default()))?;
+            assert!(matches!(res, ConsensusRes::ExtendVote(res) if res.vote_extension.is_empty()));
+
+            let res = client.consensus(ConsensusReq::ExtendVote(consensus::RequestExtendVote {
+                height: 1,
+                ..Default::default()
+            }))?;
+            let vote_extension = match res {
+                ConsensusRes::ExtendVote(res) => res.vote_extension,
+                _ => panic!("Unexpected response"),
+            };
+            let verify = |height, vote_extension| {
+                ConsensusReq::VerifyVoteExtension(consensus::RequestVerifyVoteExtension {
+                    height,
+                    vote_extension,
+                    ..Default::default()
+                })
+            };
+            let res = client.consensus(verify(1, vote_extension.clone()))?;
+            assert!(matches!(res, ConsensusRes::VerifyVoteExtension(res) if res.status == 1));
+            let res = client.consensus(verify(2, vote_extension))?;
+            assert!(matches!(res, ConsensusRes::VerifyVoteExtension(res) if res.status == 2));
+
+            let res = client.consensus(ConsensusReq::FinalizeBlock(
+                consensus::RequestFinalizeBlock {
+                    txs: vec![b"a".to_vec().into(), b"b".to_vec().into()],
+                    height: 1,
+                    time: time(),
+                    ..Default::default()
+                },
+            ))?;
+            let app_hash = match res {
+                ConsensusRes::FinalizeBlock(res) => {
+                    assert_eq!(res.tx_results.len(), 2);
+                    res.app_hash
+                }
+                _ => panic!("Unexpected response"),
+            };
+            assert!(!app_hash.is_empty());
+            assert!(client
+                .0
+                .store
+                .as_ref()
+                .unwrap()
+                .borrow()
+                .mem_snapshots()
+                .is_empty());
+            assert!(matches!(
+                client.base(Req::Commit(Default::default()))?,
+                Res::Commit(_)
+            ));
+
+            match client.base(Req::Info(Default::default()))? {
+                Res::Info(res) => {
+                    assert_eq!(res.last_block_height, 1);
+                    assert_eq!(res.last_block_app_hash, app_hash);
+                }
+                _ => panic!("Unexpected response"),
+            }
+            {
+                let store = client.0.store.as_ref().unwrap().borrow();
+                assert_eq!(store.mem_snapshots().len(), 1);
+                assert_eq!(store.get(b"a")?, Some(vec![1]));
+                assert_eq!(store.get(b"prepare")?, None);
+            }
+
+            // blocks from a 0.34 node are still handled
+            client.base(Req::BeginBlock(RequestBeginBlock {
+                header: Some(Header {
+                    height: 2,
+                    time: time(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))?;
+            client.base(Req::DeliverTx(RequestDeliverTx {
+                tx: b"c".to_vec().into(),
+            }))?;
+            client.base(Req::EndBlock(RequestEndBlock { height: 2 }))?;
+            match client.base(Req::Commit(Default::default()))? {
+                Res::Commit(res) => assert_ne!(res.data, app_hash),
+                _ => panic!("Unexpected response"),
+            }
+            assert_eq!(client.0.store.as_ref().unwrap().borrow().height()?, 2);
+
+            Ok(())
+        }
+    }
 }
 
 #[cfg(feature = "abci")]
 pub use server::*;
 
-use crate::plugins::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+use crate::plugins::{
+    BeginBlockCtx, EndBlockCtx, ExtendVoteCtx, InitChainCtx, PrepareProposalCtx,
+    ProcessProposalCtx, VerifyVoteExtensionCtx,
+};
 pub trait BeginBlock {
     fn begin_block(&mut self, ctx: &BeginBlockCtx) -> Result<()>;
 }
@@ -654,6 +1239,55 @@ impl<S> InitChain for S {
     }
 }
 
+/// Chooses the transactions in a block proposed by this node, by modifying
+/// the candidate transactions in the context. Any state changes are discarded.
+pub trait PrepareProposal {
+    fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()>;
+}
+
+impl<S> PrepareProposal for S {
+    default fn prepare_proposal(&mut self, _ctx: &mut PrepareProposalCtx) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Validates a block proposed by another node, rejecting it by returning an
+/// error. Any state changes are discarded.
+pub trait ProcessProposal {
+    fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()>;
+}
+
+impl<S> ProcessProposal for S {
+    default fn process_proposal(&mut self, _ctx: &ProcessProposalCtx) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns data to attach to this validator's precommit vote, which is passed
+/// to the next proposer in [PrepareProposalCtx::local_last_commit]. Any state
+/// changes are discarded.
+pub trait ExtendVote {
+    fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>>;
+}
+
+impl<S> ExtendVote for S {
+    default fn extend_vote(&mut self, _ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+        Ok(vec![])
+    }
+}
+
+/// Validates the vote extension of another validator, rejecting it by
+/// returning an error. Any state changes are discarded.
+pub trait VerifyVoteExtension {
+    fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()>;
+}
+
+impl<S> VerifyVoteExtension for S {
+    default fn verify_vote_extension(&mut self, _ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+        Ok(())
+    }
+}
+
 pub trait AbciQuery {
     fn abci_query(&self, request: &RequestQuery) -> Result<ResponseQuery>;
 }
@@ -670,7 +1304,32 @@ impl<S> AbciQuery for S {
 }
 
 pub trait App:
-    BeginBlock + EndBlock + InitChain + State + Call + Query + Default + AbciQuery
+    BeginBlock
+    + EndBlock
+    + InitChain
+    + PrepareProposal
+    + ProcessProposal
+    + ExtendVote
+    + VerifyVoteExtension
+    + State
+    + Call
+    + Query
+    + Default
+    + AbciQuery
+{
+}
+impl<T> App for T where
+    T: Default
+        + BeginBlock
+        + EndBlock
+        + InitChain
+        + PrepareProposal
+        + ProcessProposal
+        + ExtendVote
+        + VerifyVoteExtension
+        + State
+        + Call
+        + Query
+        + AbciQuery
 {
 }
-impl<T: Default + BeginBlock + EndBlock + InitChain + State + Call + Query + AbciQuery> App for T {}
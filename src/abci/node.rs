@@ -1,20 +1,39 @@
-use super::{ABCIStateMachine, ABCIStore, AbciQuery, App, Application, WrappedMerk};
+use super::browser::StateBrowser;
+use super::config::NodeConfig;
+use super::consensus::{self, limit_txs, ProposalStatus, VerifyStatus};
+use super::diff::{DiffSink, JsonLinesSink};
+use super::export::{ImportChainId, StateExport};
+use super::indexer::{DescribeTx, EventQuery, Page, TxIndex, TxPage};
+use super::supervisor;
+use super::{
+    ABCIStateMachine, ABCIStore, AbciQuery, App, Application, ExtendVote, PrepareProposal,
+    ProcessProposal, VerifyVoteExtension, WrappedMerk,
+};
 use crate::call::Call;
+use crate::client::receipt::{tx_hash, Receipt};
+use crate::coins::Address;
 use crate::context::Context;
+use crate::describe::Describe;
 use crate::encoding::Decode;
 use crate::merk::memsnapshot::MemSnapshot;
+use crate::merk::snapshot::SnapshotFilter;
 use crate::merk::{MerkStore, ProofBuilder};
 use crate::migrate::Migrate;
-use crate::plugins::{ABCICall, ABCIPlugin};
+use crate::plugins::{
+    ABCICall, ABCIPlugin, ExtendVoteCtx, PrepareProposalCtx, ProcessProposalCtx, QueryPlugin,
+    Signer, VerifyVoteExtensionCtx,
+};
 use crate::query::Query;
 use crate::state::State;
 use crate::store::{BackingStore, Read, Shared, Store, Write};
+use crate::tendermint::client::HttpClient;
 use crate::tendermint::Child as TendermintChild;
 use crate::tendermint::Tendermint;
 use crate::{Error, Result};
-use home::home_dir;
+use std::any::TypeId;
 use std::borrow::Borrow;
 use std::marker::PhantomData;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::{Arc, RwLock};
@@ -68,6 +87,22 @@ impl Child {
     }
 }
 
+/// The directory node and wallet data is stored under: `ORGA_HOME_DIR` if
+/// set, otherwise the user's home directory. The legacy `NOMIC_HOME_DIR`
+/// variable is still honored, with a warning.
+pub fn home_dir() -> Option<PathBuf> {
+    if let Ok(home) = std::env::var("ORGA_HOME_DIR") {
+        return Some(PathBuf::from(home));
+    }
+
+    if let Ok(home) = std::env::var("NOMIC_HOME_DIR") {
+        log::warn!("NOMIC_HOME_DIR is deprecated, set ORGA_HOME_DIR instead");
+        return Some(PathBuf::from(home));
+    }
+
+    home::home_dir()
+}
+
 pub struct Node<A> {
     _app: PhantomData<A>,
     tm_home: PathBuf,
@@ -78,19 +113,22 @@ pub struct Node<A> {
     p2p_persistent_peers: Option<Vec<String>>,
     stdout: Stdio,
     stderr: Stdio,
-    logs: bool,
     skip_init_chain: bool,
     flags: Vec<String>,
+    diff_sink: Option<Box<dyn DiffSink>>,
+    config: NodeConfig,
+    state_browser: Option<(SocketAddr, BrowserSpawner)>,
 }
 
+/// Starts a state browser given the Tendermint RPC URL and the address to
+/// serve on.
+type BrowserSpawner = fn(String, SocketAddr);
+
 impl Node<()> {
     pub fn home(name: &str) -> PathBuf {
-        match std::env::var("NOMIC_HOME_DIR") {
-            Ok(home) => Some(PathBuf::from(home)),
-            Err(_) => home_dir(),
-        }
-        .expect("Could not resolve user home directory")
-        .join(format!(".{}", name).as_str())
+        home_dir()
+            .expect("Could not resolve user home directory")
+            .join(format!(".{}", name).as_str())
     }
 
     pub fn height<P: AsRef<Path>>(home: P) -> Result<u64> {
@@ -103,6 +141,79 @@ impl Node<()> {
         let store = MerkStore::new(home.join("merk"));
         store.height()
     }
+
+    /// Exports the state of the node with the given home directory at the
+    /// given height, or at its latest height if `None`. The node should not
+    /// be running. See [`export`](super::export).
+    pub fn export_state<P: AsRef<Path>>(home: P, height: Option<u64>) -> Result<StateExport> {
+        StateExport::from_merk_home(&home.as_ref().join("merk"), height)
+    }
+
+    /// Builds a genesis for a new chain with the given ID, starting from the
+    /// state of the node with the given home directory at the given height.
+    /// The rest of the node's genesis is kept. Its validators are replaced on
+    /// `InitChain` by the validator set in the exported state.
+    ///
+    /// If `file` is given, the state is written there in the binary format
+    /// and referenced from the genesis, otherwise it is embedded in the
+    /// genesis. A relative `file` is placed in the node's `tendermint/config`
+    /// directory, and must be copied next to the genesis of each node of the
+    /// new chain.
+    pub fn export_genesis<P: AsRef<Path>>(
+        home: P,
+        height: Option<u64>,
+        chain_id: &str,
+        file: Option<&Path>,
+    ) -> Result<serde_json::Value> {
+        let home = home.as_ref();
+        let export = Node::<()>::export_state(home, height)?;
+
+        let genesis_path = home.join("tendermint/config/genesis.json");
+        let mut genesis: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(genesis_path)?)?;
+
+        genesis["app_state"] = match file {
+            Some(file) => {
+                std::fs::write(
+                    home.join("tendermint/config").join(file),
+                    export.to_bytes()?,
+                )?;
+                export.to_file_app_state(&file.to_string_lossy())?
+            }
+            None => export.to_app_state()?,
+        };
+        genesis["chain_id"] = chain_id.into();
+        genesis["initial_height"] = (export.height + 1).to_string().into();
+        genesis["app_hash"] = "".into();
+
+        Ok(genesis)
+    }
+
+    /// The path of the transaction index of the node with the given home
+    /// directory.
+    pub fn tx_index_path<P: AsRef<Path>>(home: P) -> PathBuf {
+        home.as_ref().join("tx-index")
+    }
+
+    /// Returns the transactions signed by or involving the given address from
+    /// the transaction index of the node with the given home directory. The
+    /// node may be running.
+    pub fn txs_by_address<P: AsRef<Path>>(home: P, address: Address, page: Page) -> Result<TxPage> {
+        Node::<()>::search_txs(home, &[EventQuery::address(address)], page)
+    }
+
+    /// Returns the transactions matching all of the given event queries from
+    /// the transaction index of the node with the given home directory. The
+    /// node may be running.
+    pub fn search_txs<P: AsRef<Path>>(
+        home: P,
+        queries: &[EventQuery],
+        page: Page,
+    ) -> Result<TxPage> {
+        let path = Node::<()>::tx_index_path(home);
+        let index = TxIndex::open_reader(&path, path.with_extension("reader"))?;
+        index.txs_by_events(queries, page)
+    }
 }
 
 #[derive(Default)]
@@ -112,23 +223,39 @@ pub struct DefaultConfig {
 }
 
 impl<A: App> Node<A> {
+    /// Creates a node with the given home directory, panicking if it can't be
+    /// initialized. See [Node::try_new].
     pub async fn new<P: AsRef<Path>>(
         home: P,
         chain_id: Option<&str>,
         cfg_defaults: DefaultConfig,
     ) -> Self {
+        Self::try_new(home, chain_id, cfg_defaults)
+            .await
+            .expect("Failed to initialize node")
+    }
+
+    /// Creates a node with the given home directory, initializing Tendermint
+    /// if needed. Returns an error if `orga.toml` is invalid.
+    pub async fn try_new<P: AsRef<Path>>(
+        home: P,
+        chain_id: Option<&str>,
+        cfg_defaults: DefaultConfig,
+    ) -> Result<Self> {
         let home = home.as_ref().to_path_buf();
         let merk_home = home.join("merk");
         let tm_home = home.join("tendermint");
 
+        let config = NodeConfig::load(&home)?;
+
         if !home.exists() {
-            std::fs::create_dir_all(&home)
-                .expect("Failed to initialize application home directory");
+            std::fs::create_dir_all(&home)?;
         }
 
         let cfg_path = tm_home.join("config/config.toml");
         let tm_previously_configured = cfg_path.exists();
         let _ = Tendermint::new(tm_home.clone())
+            .binary(config.tendermint.clone())
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
             .init()
@@ -178,7 +305,9 @@ impl<A: App> Node<A> {
             .expect("Failed to modify genesis chain ID");
         }
 
-        let abci_port: u16 = if cfg_path.exists() {
+        let abci_port: u16 = if let Some(addr) = config.abci.addr {
+            addr.port()
+        } else if cfg_path.exists() {
             let toml = read_toml();
             let abci_laddr = toml["proxy_app"]
                 .as_str()
@@ -194,7 +323,7 @@ impl<A: App> Node<A> {
             26658
         };
 
-        Node {
+        Ok(Node {
             _app: PhantomData,
             merk_home,
             tm_home,
@@ -205,12 +334,16 @@ impl<A: App> Node<A> {
             skip_init_chain: false,
             stdout: Stdio::null(),
             stderr: Stdio::null(),
-            logs: false,
             flags: vec![],
-        }
+            diff_sink: None,
+            config,
+            state_browser: None,
+        })
     }
 
-    pub async fn run(self) -> Result<Child> {
+    pub async fn run(mut self) -> Result<Child> {
+        log::set_max_level(self.config.logging.level_filter()?);
+
         let tm_home = self.tm_home.clone();
         let abci_port = self.abci_port;
         let stdout = self.stdout;
@@ -219,9 +352,10 @@ impl<A: App> Node<A> {
         let maybe_peers = self.p2p_persistent_peers;
 
         let mut tm_process = Tendermint::new(&tm_home)
+            .binary(self.config.tendermint.clone())
             .stdout(stdout)
             .stderr(stderr)
-            .logs(self.logs)
+            .logs(self.config.logging.tendermint)
             .flags(self.flags)
             .proxy_app(format!("tcp://0.0.0.0:{}", abci_port).as_str());
 
@@ -235,6 +369,34 @@ impl<A: App> Node<A> {
 
         let tm_child = tm_process.start().await;
 
+        if let Some((addr, spawn)) = self.state_browser {
+            let toml: toml_edit::Document =
+                std::fs::read_to_string(self.tm_home.join("config/config.toml"))?
+                    .parse()
+                    .map_err(|_| Error::Tendermint("Failed to parse config.toml".into()))?;
+            let rpc_laddr = toml["rpc"]["laddr"]
+                .as_str()
+                .unwrap_or("tcp://127.0.0.1:26657");
+            let rpc_url = rpc_laddr
+                .replace("tcp://", "http://")
+                .replace("0.0.0.0", "127.0.0.1");
+            spawn(rpc_url, addr);
+        }
+
+        if self.config.metrics.enabled {
+            #[cfg(feature = "metrics")]
+            {
+                let addr = self.config.metrics.addr;
+                tokio::spawn(async move {
+                    if let Err(err) = crate::metrics::serve(addr).await {
+                        log::error!("{}", err);
+                    }
+                });
+            }
+            #[cfg(not(feature = "metrics"))]
+            log::warn!("metrics.enabled is set, but orga was built without the metrics feature");
+        }
+
         let genesis: serde_json::Value =
             std::fs::read_to_string(self.tm_home.join("config/genesis.json"))?
                 .parse()
@@ -245,25 +407,67 @@ impl<A: App> Node<A> {
         let shutdown_notifier = Arc::new(RwLock::new(false));
         let shutdown = shutdown_handler.clone();
         let notifier = shutdown_notifier.clone();
+        let diff_sink = match (self.diff_sink.take(), self.config.storage.diff_log.as_ref()) {
+            (None, Some(path)) => Some(Box::new(JsonLinesSink::open(path)?) as Box<dyn DiffSink>),
+            (sink, _) => sink,
+        };
+        let storage = self.config.storage.clone();
+        let halt = self.config.halt.clone();
+        let static_valset = self.config.consensus.static_valset;
+        let abci_addr = self
+            .config
+            .abci
+            .addr
+            .unwrap_or_else(|| ([127, 0, 0, 1], self.abci_port).into());
+        let tx_index = if storage.tx_index {
+            Some(Arc::new(TxIndex::open(Node::<()>::tx_index_path(
+                &self.home,
+            ))?))
+        } else {
+            None
+        };
 
         std::thread::spawn(move || {
-            let app = InternalApp::<ABCIPlugin<A>>::new();
-            let store = MerkStore::new(self.merk_home.clone());
-            let res = ABCIStateMachine::new(
+            let mut app = InternalApp::<ABCIPlugin<A>>::new()
+                .static_valset(static_valset)
+                .genesis_dir(self.tm_home.join("config"));
+            if let Some(tx_index) = tx_index {
+                app = app.with_tx_index(tx_index);
+            }
+            let mut store =
+                MerkStore::new(self.merk_home.clone()).mem_snapshot_limit(storage.query_retention);
+            if let Some(filters) = storage.state_sync {
+                store = store.with_snapshot_filters(filters);
+            }
+            if !storage.history.is_empty() {
+                store = store
+                    .with_history(storage.history)
+                    .expect("Failed to load historical checkpoints");
+            }
+            let mut state_machine = ABCIStateMachine::new(
                 app,
                 store,
                 self.skip_init_chain,
                 shutdown.clone(),
                 shutdown_notifier,
             )
-            .listen(format!("127.0.0.1:{}", self.abci_port));
+            .with_halt(halt);
+            if let Some(sink) = diff_sink {
+                state_machine = state_machine.with_diff_sink(sink);
+            }
+            let res = state_machine.listen(abci_addr);
             let mut shutdown = shutdown.write().unwrap();
 
             match res {
-                Err(crate::Error::Upgrade(crate::upgrade::Error::Version { expected, actual })) => {
+                Err(crate::Error::Upgrade(crate::upgrade::Error::Version {
+                    expected,
+                    actual,
+                    manifest,
+                })) => {
                     *shutdown = Some(crate::Error::Upgrade(crate::upgrade::Error::Version {
                         expected: expected.clone(),
                         actual: actual.clone(),
+                        manifest: manifest.clone(),
                     }));
 
                     log::warn!(
@@ -272,18 +476,18 @@ impl<A: App> Node<A> {
                         hex::encode(expected.to_vec()),
                     );
 
-                    std::fs::write(
-                        self.home.join("network_version"),
-                        format!("{}\n", hex::encode(expected.to_vec())),
-                    )
-                    .unwrap();
+                    supervisor::write_network_version(&self.home, &expected, manifest.as_ref())
+                        .unwrap();
 
-                    std::process::exit(138);
+                    std::process::exit(supervisor::UPGRADE_EXIT_CODE);
                 }
-                Err(crate::Error::ABCI(msg)) if msg.starts_with("Reached stop height ") => {
+                Err(crate::Error::ABCI(msg))
+                    if msg.starts_with("Reached stop height ")
+                        || msg.starts_with("Reached halt time ") =>
+                {
                     *shutdown = Some(crate::Error::ABCI(msg));
 
-                    std::process::exit(138);
+                    std::process::exit(supervisor::UPGRADE_EXIT_CODE);
                 }
                 Err(e) => {
                     *shutdown = Some(e);
@@ -304,6 +508,7 @@ impl<A: App> Node<A> {
         }
 
         Tendermint::new(&self.tm_home)
+            .binary(self.config.tendermint.clone())
             .stdout(std::process::Stdio::null())
             .unsafe_reset_all()
             .await;
@@ -412,7 +617,7 @@ impl<A: App> Node<A> {
 
     #[must_use]
     pub fn print_tendermint_logs(mut self, logs: bool) -> Self {
-        self.logs = logs;
+        self.config.logging.tendermint = logs;
 
         self
     }
@@ -423,6 +628,106 @@ impl<A: App> Node<A> {
 
         self
     }
+
+    /// Retains on-disk checkpoints of past heights matching the given filters,
+    /// so that queries and proofs can be served at those heights. See
+    /// [`MerkStore::with_history`].
+    #[must_use]
+    pub fn retain_history(mut self, filters: Vec<SnapshotFilter>) -> Self {
+        self.config.storage.history = filters;
+
+        self
+    }
+
+    /// Emits the state changes made by each transaction and block to the given
+    /// sink.
+    #[must_use]
+    pub fn diff_sink<S: DiffSink + 'static>(mut self, sink: S) -> Self {
+        self.diff_sink = Some(Box::new(sink));
+
+        self
+    }
+
+    /// Appends the state changes made by each transaction and block to the
+    /// given file, as lines of JSON.
+    pub fn diff_log<P: AsRef<Path>>(self, path: P) -> Result<Self> {
+        Ok(self.diff_sink(JsonLinesSink::open(path)?))
+    }
+
+    /// Records each executed transaction in a local index under the node's
+    /// home directory, see [`indexer`](super::indexer).
+    #[must_use]
+    pub fn index_txs(mut self) -> Self {
+        self.config.storage.tx_index = true;
+
+        self
+    }
+
+    /// Serves Prometheus metrics at `/metrics` on the given address. Requires
+    /// the `metrics` feature.
+    #[must_use]
+    pub fn metrics(mut self, addr: SocketAddr) -> Self {
+        self.config.metrics.enabled = true;
+        self.config.metrics.addr = addr;
+
+        self
+    }
+
+    /// Replaces the configuration loaded from the node's `orga.toml`, e.g.
+    /// after applying command-line overrides. See [`config`](super::config).
+    #[must_use]
+    pub fn with_config(mut self, config: NodeConfig) -> Self {
+        if let Some(addr) = config.abci.addr {
+            self.abci_port = addr.port();
+        }
+        self.config = config;
+
+        self
+    }
+
+    /// The node's configuration, as loaded from its `orga.toml` and modified
+    /// by builder methods.
+    pub fn config(&self) -> &NodeConfig {
+        &self.config
+    }
+}
+
+impl<T> Node<QueryPlugin<T>>
+where
+    QueryPlugin<T>: App,
+    T: State + Query + Call + Describe + 'static,
+{
+    /// Serves the app's state as JSON over HTTP on the given address, read
+    /// through the node's Tendermint RPC. Paths are relative to the first
+    /// value of type `U` in the state, usually the app type wrapped by the
+    /// plugins. See [`browser`](super::browser).
+    #[must_use]
+    pub fn state_browser<U: 'static>(mut self, addr: SocketAddr) -> Self {
+        self.state_browser = Some((addr, spawn_state_browser::<T, U>));
+
+        self
+    }
+}
+
+fn spawn_state_browser<T, U>(rpc_url: String, addr: SocketAddr)
+where
+    T: State + Query + Call + Describe + 'static,
+    U: 'static,
+{
+    let client = match HttpClient::new(rpc_url.as_str()) {
+        Ok(client) => client,
+        Err(err) => {
+            log::error!("Failed to start state browser: {}", err);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let browser = StateBrowser::<T>::with_root(client, TypeId::of::<U>());
+        if let Err(err) = browser.serve(addr).await {
+            log::error!("{}", err);
+        }
+    });
 }
 
 impl<A: App> InternalApp<ABCIPlugin<A>> {
@@ -453,6 +758,26 @@ impl<A: App> InternalApp<ABCIPlugin<A>> {
 
 impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
     fn init_chain(&self, store: WrappedMerk, req: RequestInitChain) -> Result<ResponseInitChain> {
+        if let Some(export) = StateExport::from_app_state(&req.app_state_bytes, &self.genesis_dir)?
+        {
+            export.import(store.clone())?;
+            let validators = self.run(store, |state| -> Result<_> {
+                state.inner.set_chain_id(&req.chain_id)?;
+                state.validator_set()
+            })??;
+            log::info!(
+                "Imported state exported at height {} ({} entries, {} validators, root {})",
+                export.height,
+                export.entries.len(),
+                validators.len(),
+                hex::encode(&export.root_hash)
+            );
+            return Ok(ResponseInitChain {
+                validators,
+                ..Default::default()
+            });
+        }
+
         let mut updates = self.run(store, move |state| -> Result<_> {
             state.call(req.into())?;
             Ok(state
@@ -473,6 +798,11 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
         store: WrappedMerk,
         req: RequestBeginBlock,
     ) -> Result<ResponseBeginBlock> {
+        if let Some(tx_index) = self.tx_index.as_ref() {
+            let height = req.header.as_ref().map_or(0, |header| header.height as u64);
+            tx_index.begin_block(height);
+        }
+
         let (events, _logs) = self.run(store, move |state| -> Result<_> {
             state.call(req.into())?;
             Ok((
@@ -503,21 +833,27 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
             ..Default::default()
         };
         updates.drain().for_each(|(_key, update)| {
-            if let Ok(flag) = std::env::var("ORGA_STATIC_VALSET") {
-                if flag != "0" && flag != "false" {
-                    return;
-                }
+            if self.static_valset {
+                return;
             }
             res.validator_updates.push(update);
         });
 
+        if let Some(tx_index) = self.tx_index.as_ref() {
+            tx_index.end_block()?;
+        }
+
         Ok(res)
     }
 
     fn deliver_tx(&self, store: WrappedMerk, req: RequestDeliverTx) -> Result<ResponseDeliverTx> {
-        let run_res = self.run(store, move |state| -> Result<_> {
-            let inner_call = Decode::decode(req.tx.to_vec().as_slice())?;
+        let tx = req.tx.to_vec();
+        let mut signer = None;
+        let run_res = self.run(store, |state| -> Result<_> {
+            let inner_call = Decode::decode(tx.as_slice())?;
+            Context::remove::<Signer>();
             let res = state.call(ABCICall::DeliverTx(inner_call));
+            signer = Context::resolve::<Signer>().and_then(|ctx| ctx.signer);
 
             Ok((
                 res,
@@ -549,6 +885,23 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
             }
         }
 
+        if let Some(tx_index) = self.tx_index.as_ref() {
+            let receipt = Receipt {
+                hash: tx_hash(&tx),
+                code: deliver_tx_res.code,
+                log: deliver_tx_res.log.clone(),
+                events: deliver_tx_res
+                    .events
+                    .iter()
+                    .cloned()
+                    .map(Into::into)
+                    .collect(),
+                ..Default::default()
+            };
+            let call = <A as DescribeTx>::describe_tx(&tx);
+            tx_index.add_tx(tx, signer, call, receipt);
+        }
+
         Ok(deliver_tx_res)
     }
 
@@ -600,23 +953,9 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
             ABCIPlugin::<A>::load(store, &mut state_bytes.as_slice())
         };
 
-        let (height, snapshot) = {
-            let merk_store_ref = merk_store.borrow();
-            if req.height == 0 {
-                merk_store_ref.mem_snapshots().last_key_value()
-            } else {
-                merk_store_ref
-                    .mem_snapshots()
-                    .get_key_value(&req.height.try_into()?)
-            }
-            .map(|(k, v)| (*k, (*v).clone()))
-            .ok_or_else(|| crate::Error::Query(format!("Cannot query for height {}", req.height)))?
-        };
-
-        let mss = Shared::new(MemSnapshot::new(snapshot, merk_store));
+        let (height, store, proof_store) = Self::snapshot_at(merk_store, req.height)?;
 
         if !req.path.is_empty() {
-            let store = BackingStore::MemSnapshot(mss);
             let state = create_state(store)?;
             let mut res = state.abci_query(&req)?;
             res.height = height.try_into().unwrap();
@@ -626,14 +965,23 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
         }
 
         let query = Decode::decode(&*req.data)?;
-        let store = BackingStore::ProofBuilderMemSnapshot(ProofBuilder::new(mss));
-        let state = create_state(store.clone())?;
+        let state = create_state(proof_store.clone())?;
         state.query(query)?;
         drop(state);
 
-        let proof_builder = store.into_proof_builder_memsnapshot()?;
-        let (proof_bytes, ss) = proof_builder.build()?;
-        let root_hash = ss.borrow().use_snapshot(|ss| ss.root_hash());
+        let (proof_bytes, root_hash) = match proof_store {
+            BackingStore::ProofBuilderMemSnapshot(builder) => {
+                let (proof_bytes, ss) = builder.build()?;
+                let root_hash = ss.borrow().use_snapshot(|ss| ss.root_hash());
+                (proof_bytes, root_hash)
+            }
+            BackingStore::ProofBuilderSnapshot(builder) => {
+                let (proof_bytes, ss) = builder.build()?;
+                let root_hash = ss.borrow().root_hash();
+                (proof_bytes, root_hash)
+            }
+            _ => unreachable!(),
+        };
 
         // TODO: we shouldn't need to include the root hash in the response
         let mut value = vec![];
@@ -648,6 +996,120 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
         };
         Ok(res)
     }
+
+    fn prepare_proposal(
+        &self,
+        store: WrappedMerk,
+        req: consensus::RequestPrepareProposal,
+    ) -> Result<consensus::ResponsePrepareProposal> {
+        let max_tx_bytes = req.max_tx_bytes;
+        let mut ctx: PrepareProposalCtx = req.into();
+        self.run(store, |state| state.prepare_proposal(&mut ctx))??;
+
+        let txs = ctx.txs.into_iter().map(Into::into).collect();
+        Ok(consensus::ResponsePrepareProposal {
+            txs: limit_txs(txs, max_tx_bytes),
+        })
+    }
+
+    fn process_proposal(
+        &self,
+        store: WrappedMerk,
+        req: consensus::RequestProcessProposal,
+    ) -> Result<consensus::ResponseProcessProposal> {
+        let ctx: ProcessProposalCtx = req.into();
+        self.run(store, |state| state.process_proposal(&ctx))??;
+
+        Ok(consensus::ResponseProcessProposal {
+            status: ProposalStatus::Accept as i32,
+        })
+    }
+
+    fn extend_vote(
+        &self,
+        store: WrappedMerk,
+        req: consensus::RequestExtendVote,
+    ) -> Result<consensus::ResponseExtendVote> {
+        let ctx: ExtendVoteCtx = req.into();
+        let vote_extension = self.run(store, |state| state.extend_vote(&ctx))??;
+
+        Ok(consensus::ResponseExtendVote {
+            vote_extension: vote_extension.into(),
+        })
+    }
+
+    fn verify_vote_extension(
+        &self,
+        store: WrappedMerk,
+        req: consensus::RequestVerifyVoteExtension,
+    ) -> Result<consensus::ResponseVerifyVoteExtension> {
+        let ctx: VerifyVoteExtensionCtx = req.into();
+        self.run(store, |state| state.verify_vote_extension(&ctx))??;
+
+        Ok(consensus::ResponseVerifyVoteExtension {
+            status: VerifyStatus::Accept as i32,
+        })
+    }
+}
+
+impl<A: App> InternalApp<ABCIPlugin<A>> {
+    /// Resolves the state at the given height (or the latest height if 0),
+    /// returning a plain store and a proof-building store over it.
+    ///
+    /// Recent heights are served from the in-memory snapshots, and older
+    /// heights from the on-disk checkpoints retained by
+    /// [`MerkStore::with_history`].
+    fn snapshot_at(
+        merk_store: Shared<MerkStore>,
+        height: i64,
+    ) -> Result<(u64, BackingStore, BackingStore)> {
+        let mem_snapshot = {
+            let merk_store_ref = merk_store.borrow();
+            if height == 0 {
+                merk_store_ref.mem_snapshots().last_key_value()
+            } else {
+                merk_store_ref
+                    .mem_snapshots()
+                    .get_key_value(&height.try_into()?)
+            }
+            .map(|(k, v)| (*k, (*v).clone()))
+        };
+
+        if let Some((height, snapshot)) = mem_snapshot {
+            let mss = Shared::new(MemSnapshot::new(snapshot, merk_store));
+            return Ok((
+                height,
+                BackingStore::MemSnapshot(mss.clone()),
+                BackingStore::ProofBuilderMemSnapshot(ProofBuilder::new(mss)),
+            ));
+        }
+
+        let checkpoint = if height == 0 {
+            None
+        } else {
+            let height = height.try_into()?;
+            merk_store
+                .borrow()
+                .history()
+                .get(height)?
+                .map(|ss| (height, ss))
+        };
+
+        match checkpoint {
+            Some((height, snapshot)) => {
+                let ss = Shared::new(snapshot);
+                Ok((
+                    height,
+                    BackingStore::Snapshot(ss.clone()),
+                    BackingStore::ProofBuilderSnapshot(ProofBuilder::new(ss)),
+                ))
+            }
+            None => Err(crate::Error::Query(format!(
+                "Cannot query for height {}",
+                height
+            ))),
+        }
+    }
 }
 
 impl Drop for Child {
@@ -660,24 +1122,58 @@ impl Drop for Child {
 
 struct InternalApp<A> {
     _app: PhantomData<A>,
+    tx_index: Option<Arc<TxIndex>>,
+    static_valset: bool,
+    genesis_dir: PathBuf,
 }
 
 impl<A: App> InternalApp<ABCIPlugin<A>> {
     pub fn new() -> Self {
-        Self { _app: PhantomData }
+        Self {
+            _app: PhantomData,
+            tx_index: None,
+            static_valset: false,
+            genesis_dir: PathBuf::new(),
+        }
+    }
+
+    /// Sets the directory which files referenced by the genesis are resolved
+    /// against, see [`export`](super::export).
+    fn genesis_dir(mut self, genesis_dir: PathBuf) -> Self {
+        self.genesis_dir = genesis_dir;
+
+        self
+    }
+
+    fn static_valset(mut self, static_valset: bool) -> Self {
+        self.static_valset = static_valset;
+
+        self
+    }
+
+    fn with_tx_index(mut self, tx_index: Arc<TxIndex>) -> Self {
+        self.tx_index = Some(tx_index);
+
+        self
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        abci::{BeginBlock, Node},
+        abci::{BeginBlock, EndBlock, InitChain, Node},
         client::{wallet::Unsigned, AppClient},
         coins::Symbol,
         context::Context,
-        plugins::{ChainId, ConvertSdkTx, DefaultPlugins, PaidCall},
+        plugins::{
+            ChainId, ConvertSdkTx, DefaultPlugins, EndBlockCtx, InitChainCtx, PaidCall, Validators,
+        },
+        store::BufStore,
         tendermint::client::HttpClient,
     };
+    use tendermint_proto::google::protobuf::Timestamp;
+    use tendermint_proto::v0_34::crypto::{public_key::Sum, PublicKey};
+    use tendermint_proto::v0_34::types::Header;
 
     use super::*;
     use orga::orga;
@@ -761,4 +1257,87 @@ mod tests {
 
         Ok(())
     }
+
+    #[orga]
+    pub struct ValidatorApp();
+
+    impl InitChain for ValidatorApp {
+        fn init_chain(&mut self, _ctx: &InitChainCtx) -> Result<()> {
+            let validators = Context::resolve::<Validators>().unwrap();
+            validators.set_voting_power([1; 32], 10);
+
+            Ok(())
+        }
+    }
+
+    impl EndBlock for ValidatorApp {
+        fn end_block(&mut self, _ctx: &EndBlockCtx) -> Result<()> {
+            let validators = Context::resolve::<Validators>().unwrap();
+            validators.set_voting_power([1; 32], 0);
+            validators.set_voting_power([2; 32], 20);
+
+            Ok(())
+        }
+    }
+
+    fn validator(pubkey: [u8; 32], power: i64) -> ValidatorUpdate {
+        ValidatorUpdate {
+            pub_key: Some(PublicKey {
+                sum: Some(Sum::Ed25519(pubkey.to_vec())),
+            }),
+            power,
+        }
+    }
+
+    fn with_store<T>(
+        merk: &Shared<MerkStore>,
+        op: impl FnOnce(WrappedMerk) -> Result<T>,
+    ) -> Result<T> {
+        let inner = Shared::new(BufStore::wrap(merk.clone()));
+        let outer = Shared::new(BufStore::wrap(inner.clone()));
+        let res = op(outer.clone())?;
+        outer.borrow_mut().flush()?;
+        inner.borrow_mut().flush()?;
+
+        Ok(res)
+    }
+
+    #[test]
+    fn export_changed_validators() -> Result<()> {
+        let home = tempdir::TempDir::new("orga-export")?;
+        let home = home.path();
+        let app = InternalApp::<ABCIPlugin<ValidatorApp>>::new();
+
+        let merk = Shared::new(MerkStore::new(home.join("merk")));
+        let res = with_store(&merk, |store| app.init_chain(store, Default::default()))?;
+        assert_eq!(res.validators, vec![validator([1; 32], 10)]);
+
+        let req = RequestEndBlock { height: 1 };
+        let res = with_store(&merk, |store| app.end_block(store, req))?;
+        assert_eq!(res.validator_updates.len(), 2);
+        merk.borrow_mut().commit(Header {
+            height: 1,
+            time: Some(Timestamp::default()),
+            ..Default::default()
+        })?;
+        drop(merk);
+
+        let config_dir = home.join("tendermint/config");
+        std::fs::create_dir_all(&config_dir)?;
+        std::fs::write(config_dir.join("genesis.json"), r#"{"chain_id":"old"}"#)?;
+        let genesis = Node::<()>::export_genesis(home, None, "new", None)?;
+        assert_eq!(genesis["initial_height"], "2");
+
+        let new_home = tempdir::TempDir::new("orga-import")?;
+        let merk = Shared::new(MerkStore::new(new_home.path().join("merk")));
+        let req = RequestInitChain {
+            chain_id: "new".to_string(),
+            app_state_bytes: serde_json::to_vec(&genesis["app_state"])?.into(),
+            ..Default::default()
+        };
+        let res = with_store(&merk, |store| app.init_chain(store, req))?;
+        assert_eq!(res.validators, vec![validator([2; 32], 20)]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,660 @@
+//! Running a node across network upgrades.
+//!
+//! When the network upgrades to a version the running binary does not
+//! support, the node writes the new version to `network_version` in its home
+//! and exits with [UPGRADE_EXIT_CODE]. A [Supervisor] runs the node as a child
+//! process, and on that exit starts the binary installed for the new version
+//! against the same home. Binaries are kept in the home, keyed by the
+//! hex-encoded [Version]:
+//!
+//! ```text
+//! upgrades/genesis/node    # run until the first upgrade
+//! upgrades/02/node         # run once the network is at version 02
+//! backups/<height>-<from>  # store backups taken before each upgrade
+//! ```
+//!
+//! Binaries can be installed ahead of the upgrade with
+//! [Supervisor::install]. If validators with enough voting power announced
+//! the same manifest with
+//! [`Upgrade::signal_with_manifest`](crate::upgrade::Upgrade::signal_with_manifest),
+//! the installed binary is checked against it, and a missing binary is
+//! downloaded if `upgrade.auto_download` is set in `orga.toml` (see
+//! [UpgradeConfig]). If the manifest can't be fetched or doesn't match, the
+//! binary is not started. The manifest lists a binary for each platform:
+//!
+//! ```json
+//! {
+//!   "version": "02",
+//!   "binaries": {
+//!     "linux_amd64": {
+//!       "url": "https://example.com/v2/node-linux-amd64",
+//!       "sha256": "<hex-encoded SHA-256 hash of the binary>"
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! The store is backed up before the new binary starts. If the new binary
+//! fails before committing a block, the backup is restored, `network_version`
+//! is reset to the version the backup was taken at, and the supervisor stops,
+//! leaving the node's state as it was before the upgrade. Restarting the
+//! supervisor then runs the previous binary, which retries the upgrade.
+
+use super::config::{NodeConfig, UpgradeConfig};
+use super::Node;
+use crate::merk::MerkStore;
+use crate::tendermint::binary::platform;
+use crate::upgrade::{Error as UpgradeError, Manifest, Version};
+use crate::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// The exit code of a node which stopped for an upgrade or a halt.
+pub const UPGRADE_EXIT_CODE: i32 = 138;
+
+/// The file in the node home holding the hex-encoded network version.
+pub const NETWORK_VERSION_FILE: &str = "network_version";
+
+/// The file in the node home holding the [AnnouncedManifest] of the network
+/// version, if one was announced.
+pub const MANIFEST_FILE: &str = "upgrade_manifest.json";
+
+/// The file name of the binaries in the upgrades directory.
+pub const BINARY_NAME: &str = "node";
+
+/// Records the version the network has upgraded to, and the manifest
+/// announced for it, for the supervisor to pick up after the node exits.
+pub fn write_network_version(
+    home: &Path,
+    version: &Version,
+    manifest: Option<&Manifest>,
+) -> Result<()> {
+    let manifest_path = home.join(MANIFEST_FILE);
+    match manifest {
+        Some(manifest) => {
+            let announced = AnnouncedManifest::try_from(manifest)?;
+            fs::write(&manifest_path, serde_json::to_string_pretty(&announced)?)?;
+        }
+        None if manifest_path.exists() => fs::remove_file(&manifest_path)?,
+        None => {}
+    }
+
+    fs::write(
+        home.join(NETWORK_VERSION_FILE),
+        format!("{}\n", hex::encode(version.to_vec())),
+    )?;
+
+    Ok(())
+}
+
+/// Reads the version the network has upgraded to, if the node has recorded
+/// one.
+pub fn read_network_version(home: &Path) -> Result<Option<Version>> {
+    let path = home.join(NETWORK_VERSION_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = hex::decode(fs::read_to_string(&path)?.trim()).map_err(|_| {
+        UpgradeError::Binary(format!("{} must be hex-encoded", NETWORK_VERSION_FILE))
+    })?;
+    let version = bytes
+        .try_into()
+        .map_err(|_| UpgradeError::Binary("Network version is too long".into()))?;
+
+    Ok(Some(version))
+}
+
+/// A [Manifest] as written to [MANIFEST_FILE].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnnouncedManifest {
+    pub url: String,
+    /// The hex-encoded SHA-256 hash of the manifest.
+    pub sha256: String,
+}
+
+impl TryFrom<&Manifest> for AnnouncedManifest {
+    type Error = crate::Error;
+
+    fn try_from(manifest: &Manifest) -> Result<Self> {
+        Ok(Self {
+            url: manifest.url()?.to_string(),
+            sha256: hex::encode(manifest.sha256),
+        })
+    }
+}
+
+/// The binaries released for a network version, by platform (e.g.
+/// `linux_amd64`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BinaryManifest {
+    /// The hex-encoded network version.
+    pub version: String,
+    pub binaries: BTreeMap<String, ManifestBinary>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestBinary {
+    pub url: String,
+    /// The hex-encoded SHA-256 hash of the binary.
+    pub sha256: String,
+}
+
+impl BinaryManifest {
+    /// Parses a downloaded manifest, checking it against the announced hash
+    /// and the network version.
+    pub fn parse(bytes: &[u8], announced: &AnnouncedManifest, version: &Version) -> Result<Self> {
+        check_hash(bytes, &announced.sha256, "Manifest")?;
+
+        let manifest: Self = serde_json::from_slice(bytes)
+            .map_err(|err| UpgradeError::Manifest(format!("Invalid manifest: {}", err)))?;
+        if manifest.version != hex::encode(version.to_vec()) {
+            return Err(UpgradeError::Manifest(format!(
+                "Manifest is for version {}, expected {}",
+                manifest.version,
+                hex::encode(version.to_vec())
+            ))
+            .into());
+        }
+
+        Ok(manifest)
+    }
+
+    /// The binary for the given platform.
+    pub fn binary(&self, platform: &str) -> Result<&ManifestBinary> {
+        self.binaries.get(platform).ok_or_else(|| {
+            UpgradeError::Manifest(format!(
+                "Manifest for version {} has no binary for {}",
+                self.version, platform
+            ))
+            .into()
+        })
+    }
+}
+
+fn check_hash(bytes: &[u8], sha256: &str, what: &str) -> Result<()> {
+    let digest = hex::encode(Sha256::digest(bytes));
+    if !digest.eq_ignore_ascii_case(sha256) {
+        return Err(UpgradeError::Manifest(format!(
+            "{} has hash {}, expected {}",
+            what, digest, sha256
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    info!("Downloading {}", url);
+    let bytes = reqwest::get(url)
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|err| UpgradeError::Manifest(format!("Failed to download {}: {}", url, err)))?
+        .bytes()
+        .await
+        .map_err(|err| UpgradeError::Manifest(format!("Failed to download {}: {}", url, err)))?;
+
+    Ok(bytes.to_vec())
+}
+
+fn label(version: Option<&Version>) -> String {
+    version.map_or_else(|| "genesis".to_string(), |v| hex::encode(v.to_vec()))
+}
+
+/// A store backup taken before an upgrade.
+#[derive(Clone, Debug)]
+struct Backup {
+    path: PathBuf,
+    height: u64,
+    version: Option<Version>,
+}
+
+/// Runs a node binary as a child process, moving it to the binary of each
+/// new network version. See the [module documentation](self).
+pub struct Supervisor {
+    home: PathBuf,
+    args: Vec<OsString>,
+    config: UpgradeConfig,
+}
+
+impl Supervisor {
+    /// Creates a supervisor for the node with the given home directory,
+    /// reading its `[upgrade]` config from `orga.toml`.
+    pub fn new<P: AsRef<Path>>(home: P) -> Result<Self> {
+        let home = home.as_ref().to_path_buf();
+        let config = NodeConfig::load(&home)?.upgrade;
+
+        Ok(Self {
+            home,
+            args: vec![],
+            config,
+        })
+    }
+
+    /// The arguments the node binary is started with, e.g. the subcommand
+    /// and flags which start the node with this home.
+    #[must_use]
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+
+        self
+    }
+
+    #[must_use]
+    pub fn with_config(mut self, config: UpgradeConfig) -> Self {
+        self.config = config;
+
+        self
+    }
+
+    /// The path of the binary for the given network version, or for the
+    /// genesis version if `None`.
+    pub fn binary_path(&self, version: Option<&Version>) -> PathBuf {
+        self.home
+            .join("upgrades")
+            .join(label(version))
+            .join(BINARY_NAME)
+    }
+
+    /// Copies the binary at `bin` into the upgrades directory, to be run at
+    /// the given network version (or before any upgrade, if `None`).
+    pub fn install(&self, version: Option<&Version>, bin: &Path) -> Result<PathBuf> {
+        let path = self.binary_path(version);
+        write_binary(&path, &fs::read(bin)?)?;
+        info!(
+            "Installed {} for version {}",
+            path.display(),
+            label(version)
+        );
+
+        Ok(path)
+    }
+
+    /// Runs the node until it exits for a reason other than an upgrade.
+    /// Returns an error if the node fails, or if there is no binary for the
+    /// network version.
+    pub async fn run(&self) -> Result<()> {
+        let mut version = read_network_version(&self.home)?;
+        let mut bin = self.prepare(version.as_ref()).await?;
+        let mut backup = None;
+
+        loop {
+            info!(
+                "Starting {} for version {}",
+                bin.display(),
+                label(version.as_ref())
+            );
+            let status = tokio::process::Command::new(&bin)
+                .args(&self.args)
+                .status()
+                .await?;
+
+            if status.code() == Some(UPGRADE_EXIT_CODE) {
+                let next = read_network_version(&self.home)?;
+                if next == version {
+                    info!("Node halted");
+                    return Ok(());
+                }
+
+                info!(
+                    "Network upgraded from version {} to {}",
+                    label(version.as_ref()),
+                    label(next.as_ref())
+                );
+                bin = self.prepare(next.as_ref()).await?;
+                backup = self.backup(version.as_ref())?;
+                version = next;
+                continue;
+            }
+
+            if status.success() {
+                return Ok(());
+            }
+
+            if let Some(backup) = backup {
+                if self.config.rollback && Node::<()>::height(&self.home)? <= backup.height {
+                    self.restore(&backup)?;
+                    self.reset_network_version(backup.version.as_ref())?;
+                    return Err(UpgradeError::Binary(format!(
+                        "Binary for version {} exited with {} before committing a block, restored the backup from height {}",
+                        label(version.as_ref()),
+                        status,
+                        backup.height
+                    ))
+                    .into());
+                }
+            }
+
+            return Err(UpgradeError::Binary(format!("Node exited with {}", status)).into());
+        }
+    }
+
+    /// Returns the binary for the given version, downloading it from the
+    /// announced manifest if needed and enabled, and otherwise checking the
+    /// installed binary against the manifest. Fails if a manifest was
+    /// announced but the binary can't be verified against it.
+    async fn prepare(&self, version: Option<&Version>) -> Result<PathBuf> {
+        let path = self.binary_path(version);
+        let manifest_path = self.home.join(MANIFEST_FILE);
+        let announced: Option<AnnouncedManifest> = match version {
+            Some(_) if manifest_path.exists() => {
+                Some(serde_json::from_slice(&fs::read(&manifest_path)?)?)
+            }
+            _ => None,
+        };
+
+        match (announced, version) {
+            (Some(announced), Some(version)) if path.exists() => {
+                let entry = self
+                    .fetch_binary_entry(&announced, version)
+                    .await
+                    .map_err(|err| {
+                        UpgradeError::Binary(format!(
+                            "Could not verify {} against the announced manifest: {}",
+                            path.display(),
+                            err
+                        ))
+                    })?;
+                check_hash(&fs::read(&path)?, &entry.sha256, "Binary")?;
+            }
+            (Some(announced), Some(version)) if self.config.auto_download => {
+                let entry = self.fetch_binary_entry(&announced, version).await?;
+                let bytes = download(&entry.url).await?;
+                check_hash(&bytes, &entry.sha256, "Binary")?;
+                write_binary(&path, &bytes)?;
+                info!(
+                    "Installed {} for version {}",
+                    path.display(),
+                    label(Some(version))
+                );
+            }
+            _ if path.exists() => {}
+            _ => {
+                return Err(UpgradeError::Binary(format!(
+                    "No binary installed for version {} at {}",
+                    label(version),
+                    path.display()
+                ))
+                .into())
+            }
+        }
+
+        Ok(path)
+    }
+
+    async fn fetch_binary_entry(
+        &self,
+        announced: &AnnouncedManifest,
+        version: &Version,
+    ) -> Result<ManifestBinary> {
+        let bytes = download(&announced.url).await?;
+        let manifest = BinaryManifest::parse(&bytes, announced, version)?;
+
+        manifest.binary(&platform()?).cloned()
+    }
+
+    fn backups_dir(&self) -> PathBuf {
+        self.home.join("backups")
+    }
+
+    /// Checkpoints the store into the backups directory, if backups are
+    /// enabled and the store exists.
+    fn backup(&self, version: Option<&Version>) -> Result<Option<Backup>> {
+        let merk_home = self.home.join("merk");
+        if !self.config.backup || !merk_home.join("db").exists() {
+            return Ok(None);
+        }
+
+        let height = Node::<()>::height(&self.home)?;
+        let path = self
+            .backups_dir()
+            .join(format!("{:012}-{}", height, label(version)));
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        info!(
+            "Backing up store at height {} to {}",
+            height,
+            path.display()
+        );
+        MerkStore::init_from(&merk_home, &path, None)?;
+
+        // Backups are named by height, so the oldest sort first
+        let mut backups = fs::read_dir(self.backups_dir())?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        backups.sort();
+        let excess = backups.len().saturating_sub(self.config.keep_backups);
+        for old in backups.into_iter().take(excess) {
+            fs::remove_dir_all(old)?;
+        }
+
+        Ok(Some(Backup {
+            path,
+            height,
+            version: version.cloned(),
+        }))
+    }
+
+    fn restore(&self, backup: &Backup) -> Result<()> {
+        warn!(
+            "Restoring store from {} at height {}",
+            backup.path.display(),
+            backup.height
+        );
+        let merk_home = self.home.join("merk");
+        let db = merk_home.join("db");
+        if db.exists() {
+            fs::remove_dir_all(db)?;
+        }
+        MerkStore::init_from(&backup.path, &merk_home, None)?;
+
+        Ok(())
+    }
+
+    /// Resets the recorded network version to the given version, or removes
+    /// it if `None`, so that a restart runs that version's binary rather than
+    /// the one which failed.
+    fn reset_network_version(&self, version: Option<&Version>) -> Result<()> {
+        info!("Resetting network version to {}", label(version));
+        if let Some(version) = version {
+            return write_network_version(&self.home, version, None);
+        }
+
+        for file in [NETWORK_VERSION_FILE, MANIFEST_FILE] {
+            let path = self.home.join(file);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes an executable to `dest`, through a temporary file so an interrupted
+/// write is not mistaken for a complete binary.
+fn write_binary(dest: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp = dest.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)?;
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&tmp, dest)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn version(bytes: &[u8]) -> Version {
+        bytes.to_vec().try_into().unwrap()
+    }
+
+    fn write_script(path: &Path, body: &str) -> Result<()> {
+        write_binary(path, format!("#!/bin/sh\n{}\n", body).as_bytes())
+    }
+
+    fn supervisor(home: &Path, config: UpgradeConfig) -> Result<Supervisor> {
+        Ok(Supervisor::new(home)?
+            .args([home.as_os_str()])
+            .with_config(config))
+    }
+
+    #[tokio::test]
+    async fn upgrade() -> Result<()> {
+        let home = TempDir::new("orga-supervisor")?;
+        let sup = supervisor(
+            home.path(),
+            UpgradeConfig {
+                backup: false,
+                rollback: false,
+                ..Default::default()
+            },
+        )?;
+        assert!(sup.run().await.is_err());
+
+        // The genesis binary exits for an upgrade, and the upgraded binary
+        // halts without a new version
+        let genesis = home.path().join("genesis.sh");
+        write_script(&genesis, "echo 02 > \"$1/network_version\"\nexit 138")?;
+        sup.install(None, &genesis)?;
+        let upgraded = home.path().join("upgraded.sh");
+        write_script(&upgraded, "touch \"$1/ran\"\nexit 138")?;
+        sup.install(Some(&version(&[2])), &upgraded)?;
+
+        sup.run().await?;
+        assert!(home.path().join("ran").exists());
+        assert_eq!(read_network_version(home.path())?, Some(version(&[2])));
+
+        // Without an installed binary, the supervisor stops
+        write_script(
+            &sup.binary_path(Some(&version(&[2]))),
+            "echo 03 > \"$1/network_version\"\nexit 138",
+        )?;
+        assert!(sup.run().await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rollback() -> Result<()> {
+        let home = TempDir::new("orga-supervisor")?;
+        drop(MerkStore::new(home.path().join("merk")));
+        let sup = supervisor(
+            home.path(),
+            UpgradeConfig {
+                keep_backups: 1,
+                ..Default::default()
+            },
+        )?;
+
+        write_script(
+            &sup.binary_path(None),
+            "echo genesis >> \"$1/runs\"\necho 02 > \"$1/network_version\"\nexit 138",
+        )?;
+        write_script(
+            &sup.binary_path(Some(&version(&[2]))),
+            "touch \"$1/merk/db/corrupt\"\nexit 1",
+        )?;
+
+        assert!(sup.run().await.is_err());
+        assert!(home.path().join("merk/db").exists());
+        assert!(!home.path().join("merk/db/corrupt").exists());
+        assert_eq!(fs::read_dir(home.path().join("backups"))?.count(), 1);
+        assert_eq!(read_network_version(home.path())?, None);
+
+        // A restart runs the genesis binary again, which retries the upgrade
+        // and is rolled back again
+        assert!(sup.run().await.is_err());
+        assert!(!home.path().join("merk/db/corrupt").exists());
+        assert_eq!(
+            fs::read_to_string(home.path().join("runs"))?,
+            "genesis\ngenesis\n"
+        );
+
+        // Once a working binary is installed, the upgrade goes through
+        write_script(
+            &sup.binary_path(Some(&version(&[2]))),
+            "touch \"$1/ran\"\nexit 138",
+        )?;
+        sup.run().await?;
+        assert!(home.path().join("ran").exists());
+        assert_eq!(read_network_version(home.path())?, Some(version(&[2])));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unverified_binary() -> Result<()> {
+        let home = TempDir::new("orga-supervisor")?;
+        let sup = supervisor(home.path(), Default::default())?;
+        let v2 = version(&[2]);
+        write_script(&sup.binary_path(Some(&v2)), "exit 0")?;
+        sup.prepare(Some(&v2)).await?;
+
+        // The announced manifest can't be fetched, so the binary is not used
+        let manifest = Manifest::new("http://127.0.0.1:1/manifest.json", [0; 32])?;
+        write_network_version(home.path(), &v2, Some(&manifest))?;
+        assert!(sup.prepare(Some(&v2)).await.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn manifests() -> Result<()> {
+        let home = TempDir::new("orga-supervisor")?;
+        let v2 = version(&[2]);
+        let binary = ManifestBinary {
+            url: "https://example.com/node".into(),
+            sha256: hex::encode(Sha256::digest(b"binary")),
+        };
+        let bytes = serde_json::to_vec(&BinaryManifest {
+            version: "02".into(),
+            binaries: [("linux_amd64".to_string(), binary.clone())].into(),
+        })?;
+        let manifest = Manifest::new(
+            "https://example.com/manifest.json",
+            Sha256::digest(&bytes).into(),
+        )?;
+
+        write_network_version(home.path(), &v2, Some(&manifest))?;
+        assert_eq!(read_network_version(home.path())?, Some(v2.clone()));
+        let announced: AnnouncedManifest =
+            serde_json::from_slice(&fs::read(home.path().join(MANIFEST_FILE))?)?;
+        assert_eq!(announced, AnnouncedManifest::try_from(&manifest)?);
+
+        let parsed = BinaryManifest::parse(&bytes, &announced, &v2)?;
+        assert_eq!(parsed.binary("linux_amd64")?, &binary);
+        assert!(parsed.binary("darwin_arm64").is_err());
+        assert!(check_hash(b"binary", &binary.sha256, "Binary").is_ok());
+        assert!(check_hash(b"other", &binary.sha256, "Binary").is_err());
+        assert!(BinaryManifest::parse(&bytes, &announced, &version(&[3])).is_err());
+        assert!(BinaryManifest::parse(b"{}", &announced, &v2).is_err());
+
+        write_network_version(home.path(), &version(&[3]), None)?;
+        assert!(!home.path().join(MANIFEST_FILE).exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,772 @@
+//! An in-process chain for testing apps across many blocks.
+//!
+//! [TestChain] runs an app wrapped in the [default
+//! plugins](crate::plugins::DefaultPlugins) through the same `InitChain`,
+//! `BeginBlock`, `DeliverTx`, `EndBlock` and `Commit` sequence as a node, but
+//! without Tendermint. The test controls block time and height, the validator
+//! set is simulated (including missed votes and double-sign evidence), and
+//! transactions are signed by test wallets such as
+//! [DerivedKey](crate::client::wallet::DerivedKey).
+
+use super::App;
+use crate::call::Call;
+use crate::client::receipt::{tx_hash, Event, Receipt};
+use crate::client::wallet::Wallet;
+use crate::coins::{Address, Symbol};
+use crate::context::Context;
+use crate::describe::Describe;
+use crate::encoding::{Decode, Encode};
+use crate::plugins::sdk_compat::{self, ConvertSdkTx};
+use crate::plugins::{
+    ABCICall, ABCIPlugin, ChainId, DefaultPlugins, NonceCall, PaidCall, PayableCall, SignerCall,
+};
+use crate::state::State;
+use crate::store::{Read, Store, Write};
+use crate::{Error, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::Duration;
+use tendermint_proto::google::protobuf::Timestamp;
+use tendermint_proto::v0_34::abci::{
+    Evidence, EvidenceType, LastCommitInfo, RequestBeginBlock, RequestEndBlock, RequestInitChain,
+    Validator, ValidatorUpdate, VoteInfo,
+};
+use tendermint_proto::v0_34::crypto::{public_key::Sum, PublicKey};
+use tendermint_proto::v0_34::types::Header;
+
+/// A simulated validator.
+#[derive(Clone, Debug)]
+pub struct TestValidator {
+    pub pubkey: [u8; 32],
+    pub power: u64,
+    /// Whether the validator signs blocks, i.e. whether its vote is counted
+    /// in the `last_commit_info` of the following block.
+    pub online: bool,
+}
+
+impl TestValidator {
+    /// The Tendermint address of the validator, the first 20 bytes of the
+    /// SHA-256 hash of its consensus key.
+    pub fn address(&self) -> [u8; 20] {
+        Sha256::digest(self.pubkey)[..20].try_into().unwrap()
+    }
+
+    fn abci_validator(&self) -> Validator {
+        Validator {
+            address: self.address().to_vec().into(),
+            power: self.power as i64,
+        }
+    }
+}
+
+/// The results of executing a block.
+#[derive(Clone, Debug, Default)]
+pub struct BlockResults {
+    pub height: u64,
+    /// The block time, in seconds.
+    pub time: i64,
+    pub begin_block_events: Vec<Event>,
+    /// The result of each transaction, in order.
+    pub txs: Vec<Receipt>,
+    pub end_block_events: Vec<Event>,
+    /// The validator updates returned from `EndBlock`, as pairs of consensus
+    /// key and voting power.
+    pub validator_updates: Vec<([u8; 32], u64)>,
+}
+
+impl BlockResults {
+    /// Iterates over all events emitted in the block, in order.
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.begin_block_events
+            .iter()
+            .chain(self.txs.iter().flat_map(|tx| tx.events.iter()))
+            .chain(self.end_block_events.iter())
+    }
+
+    /// Returns the first event of the given kind whose attributes include all
+    /// of the given key/value pairs.
+    pub fn find_event(&self, kind: &str, attributes: &[(&str, &str)]) -> Option<&Event> {
+        self.events().find(|event| {
+            event.kind == kind
+                && attributes
+                    .iter()
+                    .all(|(key, value)| event.attribute(key) == Some(*value))
+        })
+    }
+
+    /// Panics unless the block emitted an event matching
+    /// [BlockResults::find_event].
+    pub fn assert_event(&self, kind: &str, attributes: &[(&str, &str)]) {
+        if self.find_event(kind, attributes).is_none() {
+            panic!(
+                "No {} event with attributes {:?} at height {}, events were: {:?}",
+                kind,
+                attributes,
+                self.height,
+                self.events().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    /// Returns the voting power the block set for the given validator, if it
+    /// was updated.
+    pub fn validator_update(&self, pubkey: [u8; 32]) -> Option<u64> {
+        self.validator_updates
+            .iter()
+            .find(|(key, _)| *key == pubkey)
+            .map(|(_, power)| *power)
+    }
+}
+
+/// Drives an app through blocks in-process, with a store held in memory or,
+/// with [TestChain::with_merk], on disk.
+///
+/// The chain is initialized with `InitChain` before its first block. Each call
+/// to [TestChain::next_block] then executes and commits one block, with a
+/// block time [TestChain::block_time] after the previous one.
+///
+/// Contexts such as [ChainId] are global, so tests using a `TestChain` should
+/// not run in parallel with each other.
+pub struct TestChain<S, T> {
+    store: Store,
+    #[cfg(all(feature = "abci", feature = "merk-full"))]
+    merk: Option<crate::store::Shared<crate::merk::MerkStore>>,
+    chain_id: String,
+    height: u64,
+    time: i64,
+    block_time: Duration,
+    next_time: Option<i64>,
+    app_state: Vec<u8>,
+    initialized: bool,
+    validators: Vec<TestValidator>,
+    evidence: Vec<Evidence>,
+    pending_nonces: HashMap<Address, u64>,
+    last_block: Option<BlockResults>,
+    _marker: PhantomData<fn(S, T)>,
+}
+
+impl<S, T> TestChain<S, T>
+where
+    S: Symbol,
+    T: App + Describe + ConvertSdkTx<Output = PaidCall<T::Call>>,
+    DefaultPlugins<S, T>: App,
+{
+    /// Creates a chain with the given ID, backed by an in-memory store.
+    pub fn new(chain_id: &str) -> Self {
+        Self::with_store(chain_id, Store::with_map_store())
+    }
+
+    /// Creates a chain with the given ID, backed by a [MerkStore] at the given
+    /// path (e.g. a temporary directory), which is committed after each block.
+    ///
+    /// [MerkStore]: crate::merk::MerkStore
+    #[cfg(all(feature = "abci", feature = "merk-full"))]
+    pub fn with_merk<P: AsRef<std::path::Path>>(chain_id: &str, path: P) -> Self {
+        use crate::store::{BackingStore, Shared};
+
+        let merk = Shared::new(crate::merk::MerkStore::new(path.as_ref()));
+        let mut chain = Self::with_store(chain_id, Store::new(BackingStore::Merk(merk.clone())));
+        chain.merk = Some(merk);
+        chain
+    }
+
+    fn with_store(chain_id: &str, store: Store) -> Self {
+        Context::add(ChainId(chain_id.to_string()));
+
+        Self {
+            store,
+            #[cfg(all(feature = "abci", feature = "merk-full"))]
+            merk: None,
+            chain_id: chain_id.to_string(),
+            height: 0,
+            time: 0,
+            block_time: Duration::from_secs(5),
+            next_time: None,
+            app_state: vec![],
+            initialized: false,
+            validators: vec![],
+            evidence: vec![],
+            pending_nonces: HashMap::new(),
+            last_block: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Adds a genesis validator.
+    #[must_use]
+    pub fn validator(mut self, pubkey: [u8; 32], power: u64) -> Self {
+        self.validators.push(TestValidator {
+            pubkey,
+            power,
+            online: true,
+        });
+
+        self
+    }
+
+    /// Sets the time between blocks. Defaults to 5 seconds.
+    #[must_use]
+    pub fn block_time(mut self, block_time: Duration) -> Self {
+        self.block_time = block_time;
+
+        self
+    }
+
+    /// Sets the genesis time, in seconds. Defaults to 0.
+    #[must_use]
+    pub fn genesis_time(mut self, seconds: i64) -> Self {
+        self.time = seconds;
+
+        self
+    }
+
+    /// Sets the `app_state` of the genesis, e.g. to initialize modules with
+    /// [Genesis](crate::genesis::Genesis) configs.
+    #[must_use]
+    pub fn app_state(mut self, app_state: serde_json::Value) -> Self {
+        self.app_state = app_state.to_string().into_bytes();
+
+        self
+    }
+
+    /// The height of the last committed block.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// The time of the last committed block (or the genesis time), in seconds.
+    pub fn time(&self) -> i64 {
+        self.time
+    }
+
+    pub fn validators(&self) -> &[TestValidator] {
+        &self.validators
+    }
+
+    /// The results of the last committed block.
+    pub fn last_block(&self) -> Option<&BlockResults> {
+        self.last_block.as_ref()
+    }
+
+    /// Sets whether a validator signs the following blocks, e.g. to test
+    /// downtime penalties.
+    pub fn set_online(&mut self, pubkey: [u8; 32], online: bool) -> Result<()> {
+        self.validator_mut(pubkey)?.online = online;
+        Ok(())
+    }
+
+    /// Reports a validator for double-signing at the current height. The
+    /// evidence is included in the next block.
+    pub fn inject_evidence(&mut self, pubkey: [u8; 32]) -> Result<()> {
+        let validator = self.validator_mut(pubkey)?.abci_validator();
+        let total_voting_power = self.validators.iter().map(|v| v.power as i64).sum();
+        self.evidence.push(Evidence {
+            r#type: EvidenceType::DuplicateVote as i32,
+            validator: Some(validator),
+            height: self.height as i64,
+            time: Some(timestamp(self.time)),
+            total_voting_power,
+        });
+
+        Ok(())
+    }
+
+    fn validator_mut(&mut self, pubkey: [u8; 32]) -> Result<&mut TestValidator> {
+        self.validators
+            .iter_mut()
+            .find(|v| v.pubkey == pubkey)
+            .ok_or_else(|| Error::App("Unknown validator".into()))
+    }
+
+    /// Runs `op` against the app's committed state.
+    pub fn query<R>(&self, op: impl FnOnce(&T) -> Result<R>) -> Result<R> {
+        let state = self.load()?;
+        let plugins = state.inner.inner.borrow();
+        op(&plugins.inner.inner.inner.inner.inner.inner)
+    }
+
+    /// Builds and signs a transaction from `wallet`, to be included in a block
+    /// with [TestChain::next_block]. `payer` pays the fee for `paid`, as in
+    /// [AppClient::call](crate::client::AppClient::call).
+    ///
+    /// Nonces account for transactions signed since the last block, so
+    /// several transactions from the same wallet can be included in one block.
+    pub fn sign_tx<W: Wallet>(
+        &mut self,
+        wallet: &W,
+        payer: impl FnOnce(&T) -> T::Call,
+        paid: impl FnOnce(&T) -> T::Call,
+    ) -> Result<Vec<u8>> {
+        self.init_chain()?;
+
+        let (payer, paid) = self.query(|app| Ok((payer(app), paid(app))))?;
+        let nonce = match wallet.address()? {
+            Some(address) => {
+                let state = self.load()?;
+                let nonce = state
+                    .inner
+                    .inner
+                    .borrow_mut()
+                    .inner
+                    .inner
+                    .inner
+                    .nonce(address)?;
+                let pending = self.pending_nonces.entry(address).or_default();
+                *pending += 1;
+                Some(nonce + *pending)
+            }
+            None => None,
+        };
+
+        let call = NonceCall {
+            nonce,
+            inner_call: PayableCall::Paid(PaidCall { payer, paid }),
+        };
+        let call_bytes = [self.chain_id.as_bytes(), call.encode()?.as_slice()].concat();
+        let call = sdk_compat::Call::<SignerCall>::Native(wallet.sign(&call_bytes)?);
+
+        Ok(call.encode()?)
+    }
+
+    /// Signs a transaction and executes it in its own block, returning its
+    /// receipt.
+    pub fn call<W: Wallet>(
+        &mut self,
+        wallet: &W,
+        payer: impl FnOnce(&T) -> T::Call,
+        paid: impl FnOnce(&T) -> T::Call,
+    ) -> Result<Receipt> {
+        let tx = self.sign_tx(wallet, payer, paid)?;
+        let mut block = self.next_block(vec![tx])?;
+
+        Ok(block.txs.remove(0))
+    }
+
+    /// Executes and commits a block containing the given transactions.
+    /// Transactions which fail are included with a non-zero code, as on a
+    /// node.
+    pub fn next_block(&mut self, txs: Vec<Vec<u8>>) -> Result<BlockResults> {
+        self.init_chain()?;
+
+        let height = self.height + 1;
+        let time = self
+            .next_time
+            .take()
+            .unwrap_or(self.time + self.block_time.as_secs() as i64);
+        let header = Header {
+            chain_id: self.chain_id.clone(),
+            height: height as i64,
+            time: Some(timestamp(time)),
+            proposer_address: self
+                .validators
+                .first()
+                .map(|v| v.address().to_vec())
+                .unwrap_or_default()
+                .into(),
+            ..Default::default()
+        };
+
+        let mut results = BlockResults {
+            height,
+            time,
+            ..Default::default()
+        };
+
+        let req = RequestBeginBlock {
+            hash: Sha256::digest(height.to_be_bytes()).to_vec().into(),
+            header: Some(header.clone()),
+            last_commit_info: Some(self.last_commit_info()),
+            byzantine_validators: std::mem::take(&mut self.evidence),
+        };
+        results.begin_block_events = self
+            .run(|state| -> Result<_> {
+                state.call(req.into())?;
+                Ok(state.events.take().unwrap_or_default())
+            })??
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        for tx in txs {
+            let receipt = self.deliver_tx(&tx, height)?;
+            results.txs.push(receipt);
+        }
+
+        let req = RequestEndBlock {
+            height: height as i64,
+        };
+        let (updates, events) = self.run(|state| -> Result<_> {
+            state.call(req.into())?;
+            Ok((
+                state.validator_updates.take().unwrap_or_default(),
+                state.events.take().unwrap_or_default(),
+            ))
+        })??;
+        results.end_block_events = events.into_iter().map(Into::into).collect();
+        for update in updates.into_values() {
+            let (pubkey, power) = validator_update(update)?;
+            self.apply_validator_update(pubkey, power);
+            results.validator_updates.push((pubkey, power));
+        }
+
+        self.commit(header)?;
+        self.height = height;
+        self.time = time;
+        self.pending_nonces.clear();
+        self.last_block = Some(results.clone());
+
+        Ok(results)
+    }
+
+    /// Executes and commits `n` empty blocks.
+    pub fn advance_blocks(&mut self, n: u64) -> Result<()> {
+        for _ in 0..n {
+            self.next_block(vec![])?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes and commits an empty block `duration` after the last block,
+    /// e.g. to pass an unbonding period.
+    pub fn advance_time(&mut self, duration: Duration) -> Result<BlockResults> {
+        self.next_time = Some(self.time + duration.as_secs() as i64);
+        self.next_block(vec![])
+    }
+
+    /// Runs `InitChain` with the genesis validators, if it has not run yet.
+    pub fn init_chain(&mut self) -> Result<()> {
+        if self.initialized {
+            return Ok(());
+        }
+        self.initialized = true;
+
+        let req = RequestInitChain {
+            time: Some(timestamp(self.time)),
+            chain_id: self.chain_id.clone(),
+            validators: self
+                .validators
+                .iter()
+                .map(|v| ValidatorUpdate {
+                    pub_key: Some(PublicKey {
+                        sum: Some(Sum::Ed25519(v.pubkey.to_vec())),
+                    }),
+                    power: v.power as i64,
+                })
+                .collect(),
+            app_state_bytes: self.app_state.clone().into(),
+            initial_height: 1,
+            ..Default::default()
+        };
+        self.run(|state| state.call(req.into()))??;
+
+        Ok(())
+    }
+
+    fn deliver_tx(&mut self, tx: &[u8], height: u64) -> Result<Receipt> {
+        let (res, events, logs) = self.run(|state| -> Result<_> {
+            let call = Decode::decode(tx)?;
+            let res = state.call(ABCICall::DeliverTx(call));
+
+            Ok((
+                res,
+                state.events.take().unwrap_or_default(),
+                state.logs.take().unwrap_or_default(),
+            ))
+        })??;
+
+        let mut receipt = Receipt {
+            hash: tx_hash(tx),
+            height: Some(height),
+            ..Default::default()
+        };
+        match res {
+            Ok(()) => {
+                receipt.log = logs.join("\n");
+                receipt.events = events.into_iter().map(Into::into).collect();
+            }
+            Err(err) => {
+                receipt.code = 1;
+                receipt.log = if logs.is_empty() {
+                    err.to_string()
+                } else {
+                    logs.join("\n")
+                };
+            }
+        }
+
+        Ok(receipt)
+    }
+
+    fn last_commit_info(&self) -> LastCommitInfo {
+        let votes = if self.height == 0 {
+            vec![]
+        } else {
+            self.validators
+                .iter()
+                .map(|v| VoteInfo {
+                    validator: Some(v.abci_validator()),
+                    signed_last_block: v.online,
+                })
+                .collect()
+        };
+
+        LastCommitInfo { round: 0, votes }
+    }
+
+    /// Applies a validator update to the simulated validator set, taking
+    /// effect from the next block. Tendermint delays updates by one more
+    /// block, which is not simulated here.
+    fn apply_validator_update(&mut self, pubkey: [u8; 32], power: u64) {
+        if power == 0 {
+            self.validators.retain(|v| v.pubkey != pubkey);
+        } else if let Ok(validator) = self.validator_mut(pubkey) {
+            validator.power = power;
+        } else {
+            self.validators.push(TestValidator {
+                pubkey,
+                power,
+                online: true,
+            });
+        }
+    }
+
+    /// Loads the app state, creating it if the store is empty.
+    fn load(&self) -> Result<ABCIPlugin<DefaultPlugins<S, T>>> {
+        let mut store = self.store.clone();
+        let state_bytes = match store.get(&[])? {
+            Some(bytes) => bytes,
+            None => {
+                let mut default: ABCIPlugin<DefaultPlugins<S, T>> = Default::default();
+                default.attach(store.clone())?;
+                let mut bytes = vec![];
+                default.flush(&mut bytes)?;
+                store.put(vec![], bytes.clone())?;
+                bytes
+            }
+        };
+
+        ABCIPlugin::load(store, &mut state_bytes.as_slice())
+    }
+
+    /// Runs `op` against the app state and writes it back, as the node does
+    /// for each ABCI request.
+    fn run<R>(&mut self, op: impl FnOnce(&mut ABCIPlugin<DefaultPlugins<S, T>>) -> R) -> Result<R> {
+        let mut state = self.load()?;
+        let res = op(&mut state);
+        let mut bytes = vec![];
+        state.flush(&mut bytes)?;
+        self.store.put(vec![], bytes)?;
+
+        Ok(res)
+    }
+
+    #[allow(unused_variables)]
+    fn commit(&mut self, header: Header) -> Result<()> {
+        #[cfg(all(feature = "abci", feature = "merk-full"))]
+        if let Some(merk) = self.merk.as_mut() {
+            use super::ABCIStore;
+            merk.borrow_mut().commit(header)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn timestamp(seconds: i64) -> Timestamp {
+    Timestamp { seconds, nanos: 0 }
+}
+
+fn validator_update(update: ValidatorUpdate) -> Result<([u8; 32], u64)> {
+    let pubkey = match update.pub_key.and_then(|key| key.sum) {
+        Some(Sum::Ed25519(bytes)) => bytes,
+        _ => return Err(Error::App("Unexpected validator key type".into())),
+    };
+    let pubkey = pubkey
+        .try_into()
+        .map_err(|_| Error::App("Invalid validator key length".into()))?;
+
+    Ok((pubkey, update.power as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abci::{BeginBlock, EndBlock};
+    use crate::call::build_call;
+    use crate::client::wallet::DerivedKey;
+    use crate::orga;
+    use crate::plugins::{
+        disable_fee, BeginBlockCtx, EndBlockCtx, Events, Signer, Time, Validators,
+    };
+    use tendermint_proto::v0_34::abci::EventAttribute;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    pub struct Simp {}
+
+    impl Symbol for Simp {
+        const INDEX: u8 = 12;
+        const NAME: &'static str = "SIMP";
+    }
+
+    #[orga]
+    pub struct Counter {
+        pub count: u64,
+        pub last_time: i64,
+        pub votes: u64,
+        pub evidence: u64,
+    }
+
+    #[orga]
+    impl Counter {
+        #[call]
+        pub fn noop(&mut self) -> Result<()> {
+            disable_fee();
+            Ok(())
+        }
+
+        #[call]
+        pub fn increment(&mut self) -> Result<()> {
+            if Context::resolve::<Signer>().unwrap().signer.is_none() {
+                return Err(Error::App("Call must be signed".into()));
+            }
+            self.count += 1;
+
+            Context::resolve::<Events>()
+                .unwrap()
+                .add(tendermint_proto::v0_34::abci::Event {
+                    r#type: "increment".into(),
+                    attributes: vec![EventAttribute {
+                        key: "count".into(),
+                        value: self.count.to_string().into(),
+                        index: true,
+                    }],
+                });
+
+            Ok(())
+        }
+    }
+
+    impl BeginBlock for Counter {
+        fn begin_block(&mut self, ctx: &BeginBlockCtx) -> Result<()> {
+            self.last_time = Context::resolve::<Time>().unwrap().seconds;
+            self.votes = ctx.last_commit_info.as_ref().map_or(0, |info| {
+                info.votes.iter().filter(|v| v.signed_last_block).count()
+            }) as u64;
+            self.evidence += ctx.byzantine_validators.len() as u64;
+
+            Ok(())
+        }
+    }
+
+    impl EndBlock for Counter {
+        fn end_block(&mut self, _ctx: &EndBlockCtx) -> Result<()> {
+            if self.count == 2 {
+                let validators = Context::resolve::<Validators>().unwrap();
+                validators.set_voting_power([3; 32], 7);
+            }
+
+            Ok(())
+        }
+    }
+
+    impl ConvertSdkTx for Counter {
+        type Output = PaidCall<<Self as Call>::Call>;
+
+        fn convert(&self, _msg: &sdk_compat::sdk::Tx) -> Result<Self::Output> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn blocks() -> Result<()> {
+        let mut chain = TestChain::<Simp, Counter>::new("test-chain")
+            .validator([1; 32], 10)
+            .validator([2; 32], 10)
+            .genesis_time(100);
+        let alice = DerivedKey::new(b"alice")?;
+
+        let receipt = chain.call(
+            &alice,
+            |app| build_call!(app.noop()),
+            |app| build_call!(app.increment()),
+        )?;
+        assert!(receipt.is_ok(), "{}", receipt.log);
+        assert_eq!(receipt.height, Some(1));
+        chain
+            .last_block()
+            .unwrap()
+            .assert_event("increment", &[("count", "1")]);
+        assert_eq!(chain.query(|app| Ok((app.count, app.last_time)))?, (1, 105));
+
+        // nonces of transactions in the same block are sequential
+        let txs = vec![
+            chain.sign_tx(
+                &alice,
+                |app| build_call!(app.noop()),
+                |app| build_call!(app.increment()),
+            )?,
+            chain.sign_tx(
+                &alice,
+                |app| build_call!(app.noop()),
+                |app| build_call!(app.increment()),
+            )?,
+        ];
+        let block = chain.next_block(txs)?;
+        assert!(block.txs.iter().all(Receipt::is_ok));
+        assert_eq!(block.validator_update([3; 32]), None);
+        assert_eq!(chain.query(|app| Ok(app.count))?, 3);
+
+        // a replayed transaction fails
+        let tx = chain.sign_tx(
+            &alice,
+            |app| build_call!(app.noop()),
+            |app| build_call!(app.noop()),
+        )?;
+        let block = chain.next_block(vec![tx.clone(), tx])?;
+        assert!(block.txs[0].is_ok());
+        assert!(!block.txs[1].is_ok());
+
+        chain.set_online([2; 32], false)?;
+        chain.inject_evidence([1; 32])?;
+        let block = chain.advance_time(Duration::from_secs(60))?;
+        assert_eq!(block.time, chain.time());
+        assert_eq!(
+            chain.query(|app| Ok((app.last_time, app.votes, app.evidence)))?,
+            (175, 1, 1)
+        );
+
+        chain.advance_blocks(3)?;
+        assert_eq!(chain.height(), 7);
+        assert_eq!(chain.query(|app| Ok(app.last_time))?, 190);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn validator_updates() -> Result<()> {
+        let mut chain = TestChain::<Simp, Counter>::new("test-chain").validator([1; 32], 10);
+        let alice = DerivedKey::new(b"alice")?;
+
+        for _ in 0..2 {
+            chain.call(
+                &alice,
+                |app| build_call!(app.noop()),
+                |app| build_call!(app.increment()),
+            )?;
+        }
+
+        let block = chain.last_block().unwrap();
+        assert_eq!(block.validator_update([3; 32]), Some(7));
+        assert_eq!(chain.validators().len(), 2);
+
+        chain.next_block(vec![])?;
+        assert_eq!(chain.query(|app| Ok(app.votes))?, 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,467 @@
+//! A command-line interface generated from an app's call and query methods
+//! (gated by the `cli` feature).
+//!
+//! [Cli] builds a [clap] command from the metadata implemented by `#[orga]`
+//! (see [describe::methods](crate::describe::methods)):
+//!
+//! - `call <field>... <method> <args>...` signs and broadcasts a call to any
+//!   `#[call]` method reachable through `#[call]` fields, printing the receipt.
+//! - `query <field>... [<method> <args>...]` reads a public field or the
+//!   result of a `#[query]` method, printing it as JSON.
+//! - `schema [--format json|typescript|json-schema]` prints the app's
+//!   [Schema], or bindings generated from it.
+//!
+//! Arguments are parsed as JSON, falling back to a JSON string, so numbers
+//! and addresses can be passed unquoted. The node is set with `--node`, and
+//! calls are signed with the key selected by `--key` (from the default
+//! [Keyring], unlocked with the passphrase in `ORGA_KEYRING_PASSPHRASE`) or
+//! `--wallet` (a [SimpleWallet] directory).
+//!
+//! ```ignore
+//! Cli::<MyApp, MySymbol>::new("my-app")
+//!     .payer(|app| build_call!(app.accounts.take_as_funding(MIN_FEE.into())))
+//!     .run()
+//!     .await?;
+//! ```
+
+use crate::abci::App;
+use crate::call::Call;
+use crate::client::keyring::Keyring;
+use crate::client::wallet::{DerivedKey, SimpleWallet, Wallet};
+use crate::client::{exec::Transport, AppClient};
+use crate::coins::{Address, Symbol};
+use crate::describe::methods::{DescribeCalls, DescribeQueries, MethodTree, ReadPath};
+use crate::describe::schema::Schema;
+use crate::describe::Describe;
+use crate::encoding::Decode;
+use crate::plugins::{ABCIPlugin, ConvertSdkTx, DefaultPlugins, PaidCall, SignerCall};
+use crate::query::Query;
+use crate::state::State;
+use crate::tendermint::client::HttpClient;
+use crate::{Error, Result};
+use clap::{Arg, ArgMatches, Command};
+use std::ffi::OsString;
+use std::marker::PhantomData;
+
+const PASSPHRASE_ENV: &str = "ORGA_KEYRING_PASSPHRASE";
+
+/// A command-line client for the app `T` using the coin `S` for fees.
+pub struct Cli<T: Call, S> {
+    name: String,
+    about: Option<String>,
+    node: String,
+    payer: Option<fn(&T) -> T::Call>,
+    _pd: PhantomData<S>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Call(Vec<u8>),
+    Query(Vec<String>),
+    Schema(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum WalletSource {
+    Default,
+    Key(String),
+    Dir(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Invocation {
+    node: String,
+    wallet: WalletSource,
+    action: Action,
+}
+
+/// The wallets which can be selected on the command line.
+#[derive(Clone)]
+enum CliWallet {
+    Simple(SimpleWallet),
+    Key(DerivedKey),
+}
+
+impl Wallet for CliWallet {
+    fn sign(&self, call_bytes: &[u8]) -> Result<SignerCall> {
+        match self {
+            CliWallet::Simple(wallet) => wallet.sign(call_bytes),
+            CliWallet::Key(wallet) => wallet.sign(call_bytes),
+        }
+    }
+
+    fn address(&self) -> Result<Option<Address>> {
+        match self {
+            CliWallet::Simple(wallet) => wallet.address(),
+            CliWallet::Key(wallet) => Wallet::address(wallet),
+        }
+    }
+}
+
+impl<T: Call, S> Cli<T, S> {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            about: None,
+            node: "http://localhost:26657".to_string(),
+            payer: None,
+            _pd: PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub fn about(mut self, about: impl Into<String>) -> Self {
+        self.about = Some(about.into());
+        self
+    }
+
+    /// Sets the default RPC URL, which can be overridden with `--node`.
+    #[must_use]
+    pub fn node(mut self, url: impl Into<String>) -> Self {
+        self.node = url.into();
+        self
+    }
+
+    /// Sets the call which pays the fee for every call made with the `call`
+    /// command. Calls fail if no payer is set.
+    #[must_use]
+    pub fn payer(mut self, payer: fn(&T) -> T::Call) -> Self {
+        self.payer = Some(payer);
+        self
+    }
+
+    /// Builds the clap command, with a subcommand for each field and method.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(self.name.clone())
+            .subcommand_required(true)
+            .arg(
+                Arg::new("node")
+                    .long("node")
+                    .global(true)
+                    .default_value(self.node.clone())
+                    .help("Tendermint RPC URL"),
+            )
+            .arg(
+                Arg::new("key")
+                    .long("key")
+                    .global(true)
+                    .conflicts_with("wallet")
+                    .help(format!(
+                        "Name of the keyring key to sign with, unlocked with ${}",
+                        PASSPHRASE_ENV
+                    )),
+            )
+            .arg(
+                Arg::new("wallet")
+                    .long("wallet")
+                    .global(true)
+                    .help("Directory of an unencrypted wallet to sign with"),
+            )
+            .subcommand(
+                tree_command("call", &T::describe_calls(), true)
+                    .about("Signs and broadcasts a call"),
+            )
+            .subcommand(
+                tree_command("query", &T::describe_queries(), false)
+                    .about("Reads a field or query method result as JSON"),
+            )
+            .subcommand(
+                Command::new("schema")
+                    .about("Prints the app's schema, or bindings generated from it")
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["json", "typescript", "json-schema"])
+                            .default_value("json"),
+                    ),
+            );
+
+        if let Some(about) = &self.about {
+            command = command.about(about.clone());
+        }
+
+        command
+    }
+
+    fn parse<I, A>(&self, args: I) -> std::result::Result<Invocation, clap::Error>
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString> + Clone,
+    {
+        let matches = self.command().try_get_matches_from(args)?;
+        let node = matches.get_one::<String>("node").unwrap().clone();
+        let wallet = match (
+            matches.get_one::<String>("key"),
+            matches.get_one::<String>("wallet"),
+        ) {
+            (Some(key), _) => WalletSource::Key(key.clone()),
+            (_, Some(dir)) => WalletSource::Dir(dir.clone()),
+            _ => WalletSource::Default,
+        };
+
+        let action = match matches.subcommand() {
+            Some(("call", sub)) => {
+                let calls = T::describe_calls();
+                let (path, args) = resolve(sub, &calls);
+                let bytes = calls.encode_call(&path, &args).map_err(|err| {
+                    self.command()
+                        .error(clap::error::ErrorKind::InvalidValue, err.to_string())
+                })?;
+                Action::Call(bytes)
+            }
+            Some(("query", sub)) => {
+                let (mut path, args) = resolve(sub, &T::describe_queries());
+                path.extend(args);
+                Action::Query(path)
+            }
+            Some(("schema", sub)) => {
+                Action::Schema(sub.get_one::<String>("format").unwrap().clone())
+            }
+            _ => unreachable!(),
+        };
+
+        Ok(Invocation {
+            node,
+            wallet,
+            action,
+        })
+    }
+}
+
+impl<T, S> Cli<T, S>
+where
+    T: App + Call + State + Query + Default + Describe + ConvertSdkTx<Output = PaidCall<T::Call>>,
+    S: Symbol,
+    HttpClient: Transport<ABCIPlugin<DefaultPlugins<S, T>>>,
+{
+    /// Parses the process arguments and runs the command, exiting with usage
+    /// information if they are invalid.
+    pub async fn run(&self) -> Result<()> {
+        self.run_from(std::env::args_os()).await
+    }
+
+    pub async fn run_from<I, A>(&self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString> + Clone,
+    {
+        let invocation = self.parse(args).unwrap_or_else(|err| err.exit());
+        let transport = HttpClient::new(invocation.node.as_str())?;
+
+        match invocation.action {
+            Action::Call(call_bytes) => {
+                let payer = self
+                    .payer
+                    .ok_or_else(|| Error::Client("No fee payer configured".into()))?;
+                let call = T::Call::decode(call_bytes.as_slice())?;
+                let wallet = load_wallet(&invocation.wallet)?;
+                let client = AppClient::<T, T, _, S, _>::new(transport, wallet);
+
+                let receipt = client.call(payer, move |_| call).await?;
+                println!("{}", serde_json::to_string_pretty(&receipt)?);
+                receipt.into_result()?;
+            }
+            Action::Query(path) => {
+                let wallet = crate::client::wallet::Unsigned;
+                let client = AppClient::<T, T, _, S, _>::new(transport, wallet);
+
+                let value = client.query(|app| app.read_path(&path)).await?;
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            }
+            Action::Schema(format) => {
+                let schema = Schema::of::<T>();
+                let output = match format.as_str() {
+                    "typescript" => schema.typescript(),
+                    "json-schema" => serde_json::to_string_pretty(&schema.json_schema())?,
+                    _ => serde_json::to_string_pretty(&schema)?,
+                };
+                println!("{}", output);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn load_wallet(source: &WalletSource) -> Result<CliWallet> {
+    Ok(match source {
+        WalletSource::Key(name) => {
+            let passphrase = std::env::var(PASSPHRASE_ENV).map_err(|_| {
+                Error::Keyring(format!("Set {} to unlock key '{}'", PASSPHRASE_ENV, name))
+            })?;
+            CliWallet::Key(Keyring::open_default()?.unlock(name, &passphrase)?)
+        }
+        WalletSource::Dir(dir) => CliWallet::Simple(SimpleWallet::open(dir)?),
+        WalletSource::Default => {
+            let home =
+                home::home_dir().ok_or_else(|| Error::Client("No home directory set".into()))?;
+            CliWallet::Simple(SimpleWallet::open(home.join(".orga-wallet"))?)
+        }
+    })
+}
+
+/// Builds a subcommand for each field and method in the tree. For calls,
+/// fields without any call methods are omitted and a method must be given.
+fn tree_command(name: &str, tree: &MethodTree, calls: bool) -> Command {
+    let mut command = Command::new(name.to_string()).subcommand_required(calls);
+
+    for field in &tree.fields {
+        if calls && field.tree.is_empty() {
+            continue;
+        }
+        command = command.subcommand(tree_command(&field.name, &field.tree, calls));
+    }
+
+    for method in &tree.methods {
+        let mut sub = Command::new(method.name.clone());
+        for arg in &method.args {
+            sub = sub.arg(
+                Arg::new(arg.name.clone())
+                    .required(true)
+                    .allow_hyphen_values(true)
+                    .value_name(arg.type_name.clone()),
+            );
+        }
+        command = command.subcommand(sub);
+    }
+
+    command
+}
+
+/// Follows the matched subcommands, returning the path of field and method
+/// names along with the method's arguments.
+fn resolve(matches: &ArgMatches, tree: &MethodTree) -> (Vec<String>, Vec<String>) {
+    let mut path = vec![];
+    let mut matches = matches;
+    let mut tree = tree;
+
+    while let Some((name, sub)) = matches.subcommand() {
+        path.push(name.to_string());
+        if let Some(field) = tree.field(name) {
+            tree = &field.tree;
+            matches = sub;
+            continue;
+        }
+
+        let args = tree
+            .method(name)
+            .map(|method| {
+                method
+                    .args
+                    .iter()
+                    .map(|arg| sub.get_one::<String>(&arg.name).unwrap().clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        return (path, args);
+    }
+
+    (path, vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coins::Amount;
+    use crate::encoding::Encode;
+    use crate::orga;
+
+    #[orga]
+    pub struct Bank {
+        #[call]
+        pub accounts: Ledger,
+        pub fee: Amount,
+    }
+
+    #[orga]
+    pub struct Ledger {
+        pub count: u64,
+    }
+
+    #[orga]
+    impl Ledger {
+        #[call]
+        pub fn transfer(&mut self, to: Address, amount: Amount) -> Result<()> {
+            let _ = (to, amount);
+            self.count += 1;
+            Ok(())
+        }
+
+        #[query]
+        pub fn balance(&self, _address: Address) -> Result<Amount> {
+            Ok(self.count.into())
+        }
+    }
+
+    fn cli() -> Cli<Bank, ()> {
+        Cli::new("bank").node("http://node:26657")
+    }
+
+    #[test]
+    fn command() {
+        cli().command().debug_assert();
+    }
+
+    #[test]
+    fn parse_call() {
+        let to = Address::from_pubkey([2; 33]);
+        let invocation = cli()
+            .parse([
+                "bank",
+                "--key",
+                "alice",
+                "call",
+                "accounts",
+                "transfer",
+                &to.to_string(),
+                "100",
+            ])
+            .unwrap();
+
+        let expected = <Bank as Call>::Call::Field(BankFieldCall::Accounts(
+            <Ledger as Call>::Call::Method(LedgerMethodCall::Transfer(to, 100.into())),
+        ));
+        assert_eq!(invocation.node, "http://node:26657");
+        assert_eq!(invocation.wallet, WalletSource::Key("alice".to_string()));
+        assert_eq!(invocation.action, Action::Call(expected.encode().unwrap()));
+
+        assert!(cli().parse(["bank", "call", "accounts"]).is_err());
+        assert!(cli()
+            .parse(["bank", "call", "accounts", "transfer", "foo", "100"])
+            .is_err());
+    }
+
+    #[test]
+    fn parse_query() {
+        let invocation = cli()
+            .parse(["bank", "--node", "http://other:26657", "query", "fee"])
+            .unwrap();
+        assert_eq!(invocation.node, "http://other:26657");
+        assert_eq!(invocation.wallet, WalletSource::Default);
+        assert_eq!(invocation.action, Action::Query(vec!["fee".to_string()]));
+
+        let invocation = cli()
+            .parse(["bank", "query", "accounts", "balance", "nomic1foo"])
+            .unwrap();
+        assert_eq!(
+            invocation.action,
+            Action::Query(vec![
+                "accounts".to_string(),
+                "balance".to_string(),
+                "nomic1foo".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_schema() {
+        let invocation = cli().parse(["bank", "schema"]).unwrap();
+        assert_eq!(invocation.action, Action::Schema("json".to_string()));
+
+        let invocation = cli()
+            .parse(["bank", "schema", "--format", "typescript"])
+            .unwrap();
+        assert_eq!(invocation.action, Action::Schema("typescript".to_string()));
+        assert!(cli().parse(["bank", "schema", "--format", "rust"]).is_err());
+    }
+}
@@ -1,5 +1,6 @@
-use std::{any::TypeId, collections::HashSet};
+use std::{any::TypeId, collections::HashSet, time::Duration};
 
+use super::receipt::Receipt;
 use super::trace::{take_trace, tracing_guard};
 use crate::{
     abci::App,
@@ -30,7 +31,15 @@ pub enum StepResult<T: Query, U> {
 pub trait Transport<T: Query + Call>: Send + Sync {
     async fn query(&self, query: T::Query) -> Result<Store>;
 
-    async fn call(&self, call: T::Call) -> Result<()>;
+    async fn call(&self, call: T::Call) -> Result<Receipt>;
+
+    /// Waits until the transaction with the given hash is included in a
+    /// block, returning its receipt, or errors after `timeout`.
+    async fn wait_for_inclusion(&self, _hash: &[u8], _timeout: Duration) -> Result<Receipt> {
+        Err(Error::Client(
+            "Transport does not support waiting for inclusion".into(),
+        ))
+    }
 }
 
 impl<T: Transport<U>, U: Query + Call> Transport<U> for &mut T {
@@ -38,9 +47,13 @@ impl<T: Transport<U>, U: Query + Call> Transport<U> for &mut T {
         (**self).query(query).await
     }
 
-    async fn call(&self, call: <U as Call>::Call) -> Result<()> {
+    async fn call(&self, call: <U as Call>::Call) -> Result<Receipt> {
         (**self).call(call).await
     }
+
+    async fn wait_for_inclusion(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        (**self).wait_for_inclusion(hash, timeout).await
+    }
 }
 
 // TODO: remove need for ABCIPlugin wrapping at this level, and App bound
@@ -87,7 +100,15 @@ pub mod sync {
     pub trait Transport<T: Query + Call>: Send + Sync {
         fn query_sync(&self, query: T::Query) -> Result<Store>;
 
-        fn call_sync(&self, call: T::Call) -> Result<()>;
+        fn call_sync(&self, call: T::Call) -> Result<Receipt>;
+
+        /// Waits until the transaction with the given hash is included in a
+        /// block, returning its receipt, or errors after `timeout`.
+        fn wait_for_inclusion_sync(&self, _hash: &[u8], _timeout: Duration) -> Result<Receipt> {
+            Err(Error::Client(
+                "Transport does not support waiting for inclusion".into(),
+            ))
+        }
     }
 
     impl<T: Transport<U>, U: Query + Call> Transport<U> for &mut T {
@@ -95,9 +116,13 @@ pub mod sync {
             (**self).query_sync(query)
         }
 
-        fn call_sync(&self, call: <U as Call>::Call) -> Result<()> {
+        fn call_sync(&self, call: <U as Call>::Call) -> Result<Receipt> {
             (**self).call_sync(call)
         }
+
+        fn wait_for_inclusion_sync(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+            (**self).wait_for_inclusion_sync(hash, timeout)
+        }
     }
 
     // TODO: remove need for ABCIPlugin wrapping at this level, and App bound
@@ -0,0 +1,660 @@
+//! An encrypted, multi-key store for client private keys.
+//!
+//! Each key is kept in its own JSON file in the keyring directory. The secret
+//! (a BIP39 mnemonic, or a raw private key for imported keys) is encrypted with
+//! ChaCha20-Poly1305 under a key derived from a passphrase with scrypt, so no
+//! plaintext key material is written to disk.
+//!
+//! Keys created from mnemonics are derived along a BIP32/BIP44 path. With the
+//! Cosmos (`m/44'/118'/0'/0/n`) and Ethereum (`m/44'/60'/0'/0/n`) paths, the
+//! resulting addresses match those shown by Keplr and MetaMask for the same
+//! mnemonic. Only keys with native addresses can be unlocked for signing, since
+//! the signer plugin attributes native signatures to the native address of the
+//! signing key.
+
+use super::wallet::DerivedKey;
+use crate::coins::Address;
+use crate::{Error, Result};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const HARDENED: u32 = 1 << 31;
+const KEYFILE_VERSION: u32 = 1;
+
+/// The BIP44 coin type used by Cosmos chains.
+pub const COSMOS_COIN_TYPE: u32 = 118;
+/// The BIP44 coin type used by Ethereum.
+pub const ETHEREUM_COIN_TYPE: u32 = 60;
+
+/// A BIP32 derivation path, e.g. `m/44'/118'/0'/0/0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// The BIP44 path `m/44'/coin_type'/account'/0/index`.
+    pub fn bip44(coin_type: u32, account: u32, index: u32) -> Self {
+        Self(vec![
+            44 | HARDENED,
+            coin_type | HARDENED,
+            account | HARDENED,
+            0,
+            index,
+        ])
+    }
+
+    /// The path used by Keplr and other Cosmos wallets.
+    pub fn cosmos(index: u32) -> Self {
+        Self::bip44(COSMOS_COIN_TYPE, 0, index)
+    }
+
+    /// The path used by MetaMask and other Ethereum wallets.
+    pub fn ethereum(index: u32) -> Self {
+        Self::bip44(ETHEREUM_COIN_TYPE, 0, index)
+    }
+
+    /// Returns the BIP44 coin type, if this is a BIP44 path.
+    pub fn coin_type(&self) -> Option<u32> {
+        match self.0.as_slice() {
+            [purpose, coin_type, ..] if *purpose == 44 | HARDENED => Some(coin_type & !HARDENED),
+            _ => None,
+        }
+    }
+
+    /// Derives the private key for this path from a BIP39 seed.
+    pub fn derive(&self, seed: &[u8]) -> Result<SecretKey> {
+        let secp = Secp256k1::new();
+        let (key, mut chain_code) = hmac_sha512(b"Bitcoin seed", seed);
+        let mut secret = SecretKey::from_slice(&key)?;
+
+        for &index in self.0.iter() {
+            let mut data = Vec::with_capacity(37);
+            if index & HARDENED != 0 {
+                data.push(0);
+                data.extend_from_slice(&secret.secret_bytes());
+            } else {
+                data.extend_from_slice(&PublicKey::from_secret_key(&secp, &secret).serialize());
+            }
+            data.extend_from_slice(&index.to_be_bytes());
+
+            let (tweak, child_chain_code) = hmac_sha512(&chain_code, &data);
+            let tweak = Scalar::from_be_bytes(tweak)
+                .map_err(|_| Error::Keyring("Invalid derived key".to_string()))?;
+            secret = secret.add_tweak(&tweak)?;
+            chain_code = child_chain_code;
+        }
+
+        Ok(secret)
+    }
+}
+
+impl Display for DerivationPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "m")?;
+        for index in self.0.iter() {
+            if index & HARDENED != 0 {
+                write!(f, "/{}'", index & !HARDENED)?;
+            } else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(Error::Keyring(format!("Invalid derivation path: {}", s)));
+        }
+
+        parts
+            .map(|part| {
+                let (index, hardened) = match part.strip_suffix(['\'', 'h']) {
+                    Some(index) => (index, true),
+                    None => (part, false),
+                };
+                let index: u32 = index.parse()?;
+                if index & HARDENED != 0 {
+                    return Err(Error::Keyring(format!("Invalid derivation path: {}", s)));
+                }
+                Ok(if hardened { index | HARDENED } else { index })
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    let out = mac.finalize().into_bytes();
+
+    let mut left = [0; 32];
+    let mut right = [0; 32];
+    left.copy_from_slice(&out[..32]);
+    right.copy_from_slice(&out[32..]);
+    (left, right)
+}
+
+/// The kind of address a key is shown with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressKind {
+    /// Derived from the compressed public key, which native calls are signed
+    /// for.
+    #[default]
+    Native,
+    /// Derived from the uncompressed public key as on Ethereum.
+    Ethereum,
+}
+
+impl AddressKind {
+    pub fn address(self, pubkey: &PublicKey) -> Address {
+        match self {
+            AddressKind::Native => Address::from_pubkey(pubkey.serialize()),
+            AddressKind::Ethereum => {
+                let mut eth_pubkey = [0; 64];
+                eth_pubkey.copy_from_slice(&pubkey.serialize_uncompressed()[1..]);
+                Address::from_pubkey_eth(eth_pubkey)
+            }
+        }
+    }
+}
+
+/// Returns the kind of address shown by wallets for the given coin type:
+/// Ethereum-style for coin type 60, Cosmos-style otherwise.
+pub fn address_kind_for_coin_type(coin_type: Option<u32>) -> AddressKind {
+    match coin_type {
+        Some(ETHEREUM_COIN_TYPE) => AddressKind::Ethereum,
+        _ => AddressKind::Native,
+    }
+}
+
+/// Returns the address for a public key as shown by wallets for the given
+/// coin type.
+pub fn address_for_coin_type(pubkey: &PublicKey, coin_type: Option<u32>) -> Address {
+    address_kind_for_coin_type(coin_type).address(pubkey)
+}
+
+/// Public information about a key in the keyring, readable without the
+/// passphrase.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub name: String,
+    pub address: Address,
+    pub address_kind: AddressKind,
+    pub path: Option<DerivationPath>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Secret {
+    Mnemonic(String),
+    PrivateKey(String),
+}
+
+/// Parameters for deriving the encryption key from a passphrase with scrypt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScryptParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for ScryptParams {
+    fn default() -> Self {
+        Self {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    name: String,
+    address: String,
+    /// Absent in key files written before address kinds were stored, in
+    /// which case it is inferred from the path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address_kind: Option<AddressKind>,
+    path: Option<String>,
+    scrypt: ScryptParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// A directory of passphrase-encrypted keys, addressed by name.
+pub struct Keyring {
+    dir: PathBuf,
+    scrypt: ScryptParams,
+}
+
+impl Keyring {
+    /// Opens the keyring in the given directory, creating it if needed.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            scrypt: ScryptParams::default(),
+        })
+    }
+
+    /// Opens the keyring at `~/.orga-wallet/keyring`, or under
+    /// `ORGA_HOME_DIR` if set (see [home_dir](crate::abci::home_dir)).
+    #[cfg(feature = "abci")]
+    pub fn open_default() -> Result<Self> {
+        let home = crate::abci::home_dir()
+            .ok_or_else(|| Error::Keyring("No home directory set".to_string()))?;
+
+        Self::open(home.join(".orga-wallet").join("keyring"))
+    }
+
+    /// Sets the scrypt parameters used when adding keys. Existing keys keep
+    /// the parameters they were encrypted with.
+    #[must_use]
+    pub fn with_scrypt_params(mut self, params: ScryptParams) -> Self {
+        self.scrypt = params;
+        self
+    }
+
+    /// Generates a new 24-word mnemonic and adds the key derived from it at
+    /// `path`, returning the key info and the mnemonic phrase to back up.
+    pub fn create(
+        &self,
+        name: &str,
+        passphrase: &str,
+        path: &DerivationPath,
+    ) -> Result<(KeyInfo, String)> {
+        use rand::Rng;
+        let entropy: [u8; 32] = rand::thread_rng().gen();
+        let mnemonic =
+            bip39::Mnemonic::from_entropy(&entropy).map_err(|e| Error::Keyring(e.to_string()))?;
+        let phrase = mnemonic.to_string();
+
+        let info = self.import_mnemonic(name, &phrase, passphrase, path)?;
+        Ok((info, phrase))
+    }
+
+    /// Adds the key derived at `path` from an existing BIP39 mnemonic.
+    pub fn import_mnemonic(
+        &self,
+        name: &str,
+        phrase: &str,
+        passphrase: &str,
+        path: &DerivationPath,
+    ) -> Result<KeyInfo> {
+        let mnemonic = parse_mnemonic(phrase)?;
+        let privkey = path.derive(&mnemonic.to_seed_normalized(""))?;
+
+        self.add(
+            name,
+            passphrase,
+            &privkey,
+            Some(path),
+            Secret::Mnemonic(mnemonic.to_string()),
+        )
+    }
+
+    /// Adds a raw private key, e.g. one previously stored unencrypted.
+    pub fn import_private_key(
+        &self,
+        name: &str,
+        privkey: &SecretKey,
+        passphrase: &str,
+    ) -> Result<KeyInfo> {
+        let secret = Secret::PrivateKey(hex::encode(privkey.secret_bytes()));
+        self.add(name, passphrase, privkey, None, secret)
+    }
+
+    /// Imports the plaintext key file written by `SimpleWallet::open` or
+    /// `load_privkey`. The original file is left in place and should be
+    /// deleted once the import has been checked.
+    pub fn import_legacy<P: AsRef<Path>>(
+        &self,
+        name: &str,
+        privkey_path: P,
+        passphrase: &str,
+    ) -> Result<KeyInfo> {
+        let bytes = std::fs::read(privkey_path)?;
+        let privkey = SecretKey::from_slice(bytes.as_slice())?;
+        self.import_private_key(name, &privkey, passphrase)
+    }
+
+    /// Returns the mnemonic phrase for a key created from a mnemonic.
+    pub fn export_mnemonic(&self, name: &str, passphrase: &str) -> Result<String> {
+        match self.decrypt(name, passphrase)? {
+            Secret::Mnemonic(phrase) => Ok(phrase),
+            Secret::PrivateKey(_) => Err(Error::Keyring(format!(
+                "Key {} was imported as a private key and has no mnemonic",
+                name
+            ))),
+        }
+    }
+
+    /// Decrypts a key, returning a wallet which signs for the key's address.
+    /// Keys with Ethereum addresses can't be unlocked, since native calls are
+    /// signed for the native address of the key.
+    pub fn unlock(&self, name: &str, passphrase: &str) -> Result<DerivedKey> {
+        let info = self.info(name)?;
+        if info.address_kind != AddressKind::Native {
+            return Err(Error::Keyring(format!(
+                "Key {} has an Ethereum address, which can't sign native calls",
+                name
+            )));
+        }
+
+        let privkey = match self.decrypt(name, passphrase)? {
+            Secret::Mnemonic(phrase) => {
+                let path = info
+                    .path
+                    .ok_or_else(|| Error::Keyring(format!("Key {} has no path", name)))?;
+                path.derive(&parse_mnemonic(&phrase)?.to_seed_normalized(""))?
+            }
+            Secret::PrivateKey(privkey) => {
+                let bytes = hex::decode(privkey).map_err(|e| Error::Keyring(e.to_string()))?;
+                SecretKey::from_slice(bytes.as_slice())?
+            }
+        };
+
+        let wallet = DerivedKey::from_secret_key(privkey);
+        if wallet.address() != info.address {
+            return Err(Error::Keyring(format!(
+                "Key {} does not match its stored address",
+                name
+            )));
+        }
+
+        Ok(wallet)
+    }
+
+    /// Returns the public information for a key.
+    pub fn info(&self, name: &str) -> Result<KeyInfo> {
+        let file = self.read(name)?;
+        let path: Option<DerivationPath> = file.path.map(|path| path.parse()).transpose()?;
+        let address_kind = file.address_kind.unwrap_or_else(|| {
+            address_kind_for_coin_type(path.as_ref().and_then(DerivationPath::coin_type))
+        });
+
+        Ok(KeyInfo {
+            name: file.name,
+            address: file
+                .address
+                .parse()
+                .map_err(|_| Error::Keyring(format!("Invalid address for key {}", name)))?,
+            address_kind,
+            path,
+        })
+    }
+
+    /// Lists the keys in the keyring, ordered by name.
+    pub fn list(&self) -> Result<Vec<KeyInfo>> {
+        let mut names = vec![];
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |ext| ext == "json") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+
+        names.iter().map(|name| self.info(name)).collect()
+    }
+
+    /// Deletes a key from the keyring.
+    pub fn remove(&self, name: &str) -> Result<()> {
+        Ok(std::fs::remove_file(self.key_path(name)?)?)
+    }
+
+    fn add(
+        &self,
+        name: &str,
+        passphrase: &str,
+        privkey: &SecretKey,
+        path: Option<&DerivationPath>,
+        secret: Secret,
+    ) -> Result<KeyInfo> {
+        let key_path = self.key_path(name)?;
+        if key_path.exists() {
+            return Err(Error::Keyring(format!("Key {} already exists", name)));
+        }
+
+        let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), privkey);
+        let address_kind = address_kind_for_coin_type(path.and_then(DerivationPath::coin_type));
+        let address = address_kind.address(&pubkey);
+
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let salt: [u8; 32] = rng.gen();
+        let nonce: [u8; 12] = rng.gen();
+
+        let cipher = cipher(passphrase, &salt, self.scrypt)?;
+        let plaintext = serde_json::to_vec(&secret)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| Error::Keyring("Failed to encrypt key".to_string()))?;
+
+        let file = KeyFile {
+            version: KEYFILE_VERSION,
+            name: name.to_string(),
+            address: address.to_string(),
+            address_kind: Some(address_kind),
+            path: path.map(ToString::to_string),
+            scrypt: self.scrypt,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        write_private(&key_path, &serde_json::to_vec_pretty(&file)?)?;
+
+        Ok(KeyInfo {
+            name: name.to_string(),
+            address,
+            address_kind,
+            path: path.cloned(),
+        })
+    }
+
+    fn decrypt(&self, name: &str, passphrase: &str) -> Result<Secret> {
+        let file = self.read(name)?;
+        let decode = |s: &str| hex::decode(s).map_err(|e| Error::Keyring(e.to_string()));
+
+        let cipher = cipher(passphrase, &decode(&file.salt)?, file.scrypt)?;
+        let nonce = decode(&file.nonce)?;
+        if nonce.len() != 12 {
+            return Err(Error::Keyring(format!("Invalid nonce for key {}", name)));
+        }
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                decode(&file.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| Error::Keyring("Incorrect passphrase".to_string()))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn read(&self, name: &str) -> Result<KeyFile> {
+        let key_path = self.key_path(name)?;
+        if !key_path.exists() {
+            return Err(Error::Keyring(format!("Key {} not found", name)));
+        }
+
+        let file: KeyFile = serde_json::from_slice(&std::fs::read(key_path)?)?;
+        if file.version != KEYFILE_VERSION {
+            return Err(Error::Keyring(format!(
+                "Unsupported key file version {}",
+                file.version
+            )));
+        }
+
+        Ok(file)
+    }
+
+    fn key_path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::Keyring(format!("Invalid key name: {}", name)));
+        }
+
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+}
+
+fn parse_mnemonic(phrase: &str) -> Result<bip39::Mnemonic> {
+    let phrase = phrase
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    bip39::Mnemonic::parse_normalized(&phrase).map_err(|e| Error::Keyring(e.to_string()))
+}
+
+fn cipher(passphrase: &str, salt: &[u8], params: ScryptParams) -> Result<ChaCha20Poly1305> {
+    let params = scrypt::Params::new(params.log_n, params.r, params.p, 32)
+        .map_err(|e| Error::Keyring(e.to_string()))?;
+    let mut key = [0; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| Error::Keyring(e.to_string()))?;
+
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn keyring() -> (tempdir::TempDir, Keyring) {
+        let dir = tempdir::TempDir::new("orga-keyring").unwrap();
+        let keyring = Keyring::open(dir.path())
+            .unwrap()
+            .with_scrypt_params(ScryptParams {
+                log_n: 10,
+                r: 8,
+                p: 1,
+            });
+        (dir, keyring)
+    }
+
+    #[test]
+    fn derivation_path() -> Result<()> {
+        let path: DerivationPath = "m/44'/118'/0'/0/3".parse()?;
+        assert_eq!(path, DerivationPath::cosmos(3));
+        assert_eq!(path.to_string(), "m/44'/118'/0'/0/3");
+        assert_eq!(path.coin_type(), Some(COSMOS_COIN_TYPE));
+        assert!("44'/0".parse::<DerivationPath>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cosmos_address() -> Result<()> {
+        use bech32::FromBase32;
+
+        let (_dir, keyring) = keyring();
+        let info =
+            keyring.import_mnemonic("cosmos", MNEMONIC, "pass", &DerivationPath::cosmos(0))?;
+
+        let (_, data, _) = bech32::decode("cosmos19rl4cm2hmr8afy4kldpxz3fka4jguq0auqdal4").unwrap();
+        let expected = Vec::<u8>::from_base32(&data).unwrap();
+        assert_eq!(info.address.bytes().to_vec(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ethereum_address() -> Result<()> {
+        let (_dir, keyring) = keyring();
+        let info =
+            keyring.import_mnemonic("eth", MNEMONIC, "pass", &DerivationPath::ethereum(0))?;
+
+        assert_eq!(
+            hex::encode(info.address.bytes()),
+            "9858effd232b4033e47d90003d41ec34ecaeda94"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ethereum_keys() -> Result<()> {
+        let (_dir, keyring) = keyring();
+        let info =
+            keyring.import_mnemonic("eth", MNEMONIC, "pass", &DerivationPath::ethereum(0))?;
+        assert_eq!(info.address_kind, AddressKind::Ethereum);
+        assert_eq!(keyring.info("eth")?.address_kind, AddressKind::Ethereum);
+        assert!(keyring.unlock("eth", "pass").is_err());
+
+        let info =
+            keyring.import_mnemonic("cosmos", MNEMONIC, "pass", &DerivationPath::cosmos(0))?;
+        assert_eq!(keyring.unlock("cosmos", "pass")?.address(), info.address);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypted_roundtrip() -> Result<()> {
+        let (dir, keyring) = keyring();
+        let (info, phrase) = keyring.create("alice", "hunter2", &DerivationPath::cosmos(0))?;
+        let privkey = SecretKey::from_slice(&[7; 32])?;
+        keyring.import_private_key("bob", &privkey, "swordfish")?;
+
+        let names: Vec<_> = keyring.list()?.into_iter().map(|key| key.name).collect();
+        assert_eq!(names, vec!["alice", "bob"]);
+
+        let file = std::fs::read_to_string(dir.path().join("alice.json"))?;
+        assert!(!file.contains(&phrase));
+
+        assert_eq!(keyring.export_mnemonic("alice", "hunter2")?, phrase);
+        assert!(keyring.export_mnemonic("alice", "wrong").is_err());
+        assert!(keyring.export_mnemonic("bob", "swordfish").is_err());
+
+        let wallet = keyring.unlock("alice", "hunter2")?;
+        assert_eq!(wallet.address(), info.address);
+        assert_eq!(keyring.unlock("bob", "swordfish")?.privkey(), &privkey);
+
+        assert!(keyring
+            .create("alice", "x", &DerivationPath::cosmos(0))
+            .is_err());
+        keyring.remove("alice")?;
+        assert_eq!(keyring.list()?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
-use std::{any::Any, collections::BTreeMap, marker::PhantomData, sync::Mutex};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
     abci::App,
@@ -12,6 +16,8 @@ use crate::{
 };
 
 use super::exec::{sync::Transport as SyncTransport, Transport};
+use super::receipt::{tx_hash, Event, Receipt};
+use super::subscribe::{BlockEvent, BlockEvents, EventSource};
 
 #[derive(Default)]
 pub struct MockClient<T> {
@@ -39,21 +45,19 @@ impl<T: App + State + Query + Call> SyncTransport<ABCIPlugin<QueryPlugin<T>>>
         let query_bytes = query.encode()?;
         self.queries.lock().unwrap().push(query_bytes);
 
-        let store = Store::new(BackingStore::Other(Shared::new(Box::new(ReadLog::new(
-            self.store.clone(),
-        )))));
+        let store = Store::with_backend(ReadLog::new(self.store.clone()));
 
         let root_bytes = store.get(&[])?.unwrap_or_default();
         let app = ABCIPlugin::<QueryPlugin<T>>::load(store.clone(), &mut root_bytes.as_slice())?;
         app.query(query)?;
         drop(app);
 
-        let mut log = if let BackingStore::Other(b) = store.into_backing_store().into_inner() {
-            let b = b.into_inner() as Box<dyn Any>;
-            b.downcast::<ReadLog<Store>>().unwrap().reads().clone()
-        } else {
-            unreachable!()
-        };
+        let mut log = store
+            .into_backing_store()
+            .into_inner()
+            .downcast_other::<ReadLog<Store>>()?
+            .reads()
+            .clone();
 
         // TODO: move to PartialMapStore associated function
         let mut out = BTreeMap::new();
@@ -97,8 +101,10 @@ impl<T: App + State + Query + Call> SyncTransport<ABCIPlugin<QueryPlugin<T>>>
         ))))
     }
 
-    fn call_sync(&self, call: <ABCIPlugin<QueryPlugin<T>> as Call>::Call) -> Result<()> {
-        self.calls.lock().unwrap().push(call.encode()?);
+    fn call_sync(&self, call: <ABCIPlugin<QueryPlugin<T>> as Call>::Call) -> Result<Receipt> {
+        let call_bytes = call.encode()?;
+        let hash = tx_hash(call_bytes.as_slice());
+        self.calls.lock().unwrap().push(call_bytes);
 
         let root_bytes = self.store.get(&[])?.unwrap_or_default();
         let mut app =
@@ -110,7 +116,9 @@ impl<T: App + State + Query + Call> SyncTransport<ABCIPlugin<QueryPlugin<T>>>
         app.flush(&mut out)?;
         self.store.clone().put(vec![], out)?;
 
-        Ok(())
+        // calls are applied immediately, but there are no blocks to be
+        // included in
+        Ok(Receipt::pending(hash))
     }
 }
 
@@ -121,7 +129,87 @@ impl<T: App + State + Query + Call> Transport<ABCIPlugin<QueryPlugin<T>>>
         self.query_sync(query)
     }
 
-    async fn call(&self, call: <ABCIPlugin<QueryPlugin<T>> as Call>::Call) -> Result<()> {
+    async fn call(&self, call: <ABCIPlugin<QueryPlugin<T>> as Call>::Call) -> Result<Receipt> {
         self.call_sync(call)
     }
 }
+
+/// An in-memory [EventSource] for testing subscriptions. Blocks are produced
+/// with [MockEventSource::push_block], and [MockEventSource::disconnect]
+/// simulates a dropped connection. Clones share the same blocks.
+#[derive(Clone, Default)]
+pub struct MockEventSource {
+    inner: Arc<Mutex<MockEvents>>,
+}
+
+#[derive(Default)]
+struct MockEvents {
+    blocks: BTreeMap<u64, BlockEvents>,
+    pending: VecDeque<u64>,
+    disconnected: bool,
+}
+
+impl MockEventSource {
+    /// Adds a block with the given events, all emitted by the block's first
+    /// transaction, and returns its height. Connected subscribers are notified
+    /// of the new block.
+    pub fn push_block(&self, events: Vec<Event>) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        let height = inner.blocks.len() as u64 + 1;
+        let events = events
+            .into_iter()
+            .map(|event| BlockEvent {
+                tx_index: Some(0),
+                event,
+            })
+            .collect();
+        inner.blocks.insert(height, BlockEvents { height, events });
+        if !inner.disconnected {
+            inner.pending.push_back(height);
+        }
+
+        height
+    }
+
+    /// Drops the connection. New blocks are not notified until the next call
+    /// to [EventSource::connect].
+    pub fn disconnect(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.disconnected = true;
+        inner.pending.clear();
+    }
+}
+
+impl EventSource for MockEventSource {
+    async fn connect(&mut self) -> Result<()> {
+        self.inner.lock().unwrap().disconnected = false;
+        Ok(())
+    }
+
+    async fn next_height(&mut self) -> Result<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.disconnected {
+            return Err(Error::Client("Disconnected".into()));
+        }
+
+        // with no block to return, behave like a dropped connection rather
+        // than blocking forever
+        inner
+            .pending
+            .pop_front()
+            .ok_or_else(|| Error::Client("No new blocks".into()))
+    }
+
+    async fn block_events(&mut self, height: u64) -> Result<BlockEvents> {
+        let inner = self.inner.lock().unwrap();
+        if inner.disconnected {
+            return Err(Error::Client("Disconnected".into()));
+        }
+
+        inner
+            .blocks
+            .get(&height)
+            .cloned()
+            .ok_or_else(|| Error::Client(format!("No block at height {}", height)))
+    }
+}
@@ -3,22 +3,43 @@ use crate::describe::Describe;
 use crate::encoding::{Decode, Encode};
 
 use crate::abci::App;
+use crate::plugins::sdk_compat::sdk;
 use crate::plugins::{sdk_compat, ABCICall, ABCIPlugin, ConvertSdkTx};
-use crate::plugins::{PaidCall, PayableCall};
+use crate::plugins::{PaidCall, PayableCall, SignerCall};
 use crate::query::Query;
 use crate::state::State;
 use crate::store::Store;
 
-use crate::Result;
+use crate::coins::Address;
+use crate::{Error, Result};
 
 use std::marker::PhantomData;
+use std::time::Duration;
+
+use receipt::{is_nonce_conflict, tx_hash};
+
+/// How many times a call is re-signed with a fresh nonce after a nonce
+/// conflict before giving up.
+const MAX_NONCE_RETRIES: u32 = 3;
 
 pub mod exec;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod keyring;
 pub mod mock;
+pub mod offline;
+pub mod receipt;
+#[cfg(feature = "merk-verify")]
+pub mod rpc;
+pub mod subscribe;
 pub mod trace;
 pub mod wallet;
+#[cfg(all(target_arch = "wasm32", feature = "merk-verify"))]
+pub mod web;
 
 pub use exec::Transport;
+pub use offline::{SignedTx, UnsignedTx};
+pub use receipt::{BroadcastMode, Receipt};
+pub use subscribe::{EventFilter, Subscription};
 pub use wallet::Wallet;
 
 pub trait Client<T: Query + Call>: Send + Sync {
@@ -28,7 +49,7 @@ pub trait Client<T: Query + Call>: Send + Sync {
         &self,
         payer: impl FnOnce(&T) -> T::Call,
         payee: impl FnOnce(&T) -> T::Call,
-    ) -> Result<()>;
+    ) -> Result<Receipt>;
 }
 
 pub struct AppClient<T, U, Transport, Symbol, Wallet> {
@@ -65,7 +86,7 @@ pub mod sync {
             &self,
             _payer: impl FnOnce(&U) -> U::Call,
             _payee: impl FnOnce(&U) -> U::Call,
-        ) -> Result<()> {
+        ) -> Result<Receipt> {
             todo!()
             // self.call(payer, payee)
         }
@@ -105,6 +126,62 @@ impl<T, U, Transport, Symbol, Wallet> AppClient<T, U, Transport, Symbol, Wallet>
             sub,
         }
     }
+
+    /// Wraps an encoded `PayableCall` with the nonce and chain ID, and signs
+    /// it.
+    fn sign(
+        &self,
+        chain_id: &[u8],
+        nonce: Option<u64>,
+        payable_call_bytes: &[u8],
+    ) -> Result<<ABCIPlugin<DefaultPlugins<Symbol, T>> as Call>::Call>
+    where
+        T: App
+            + Call
+            + State
+            + Query
+            + Default
+            + Describe
+            + ConvertSdkTx<Output = PaidCall<T::Call>>,
+        Wallet: wallet::Wallet,
+        Symbol: crate::coins::Symbol,
+    {
+        let call = crate::plugins::NonceCall {
+            nonce,
+            inner_call: PayableCall::<T::Call>::decode(payable_call_bytes)?,
+        };
+        let call = [chain_id, call.encode()?.as_slice()].concat();
+        let call = self.wallet.sign(&call)?;
+        Ok(ABCICall::DeliverTx(sdk_compat::Call::Native(call)))
+    }
+}
+
+fn chain_id_string(chain_id: Vec<u8>) -> Result<String> {
+    String::from_utf8(chain_id).map_err(|_| Error::Client("Chain ID is not valid UTF-8".into()))
+}
+
+/// The nonce to retry with after a conflict: the next on-chain nonce, or one
+/// past the conflicting nonce if that is higher (e.g. when an earlier
+/// transaction is still in the mempool).
+/// Whether a submission should be re-signed with a fresh nonce: when it was
+/// rejected from the mempool for a nonce conflict, or, in commit mode, included
+/// in a block but failed for one.
+///
+/// Before retrying, the caller checks that the transaction itself was not
+/// already included, e.g. when a transport resubmitted it and the resubmission
+/// was rejected for reusing its own nonce.
+fn should_retry(res: &Result<Receipt>) -> bool {
+    match res {
+        Ok(receipt) => receipt.is_nonce_conflict(),
+        Err(err) => is_nonce_conflict(err),
+    }
+}
+
+fn retry_nonce(conflicting: Option<u64>, fresh: Option<u64>) -> Option<u64> {
+    match (conflicting, fresh) {
+        (Some(conflicting), Some(fresh)) => Some(fresh.max(conflicting + 1)),
+        (_, fresh) => fresh,
+    }
 }
 
 impl<T, U, Transport, Symbol, Wallet> AppClient<T, U, Transport, Symbol, Wallet>
@@ -115,27 +192,79 @@ where
     Symbol: crate::coins::Symbol,
 {
     // TODO: support subclients
-    // TODO: return object with result data (e.g. txid)
+    /// Signs and submits a call, returning its receipt.
+    ///
+    /// If the call is rejected or, in commit mode, fails in a block because of
+    /// a nonce conflict (e.g. another transaction from the same account was
+    /// submitted concurrently), it is re-signed with a fresh nonce and
+    /// resubmitted.
     pub async fn call(
         &self,
         payer: impl FnOnce(&U) -> T::Call,
         payee: impl FnOnce(&U) -> T::Call,
-    ) -> Result<()> {
+    ) -> Result<Receipt> {
         let (chain_id, store) = exec::execute(Store::default(), &self.transport, |app| {
             Ok(app.inner.inner.borrow().inner.inner.chain_id.to_vec())
         })
         .await?;
-        let (nonce, store) = match self.wallet.address()? {
-            None => (None, store),
-            Some(addr) => {
-                exec::execute(store, &self.transport, |app| {
-                    Ok(Some(
-                        app.inner.inner.borrow_mut().inner.inner.inner.nonce(addr)? + 1,
-                    ))
-                })
-                .await?
+        let (mut nonce, store) = self.next_nonce(store, self.wallet.address()?).await?;
+
+        let app = self.query_with_store(store, Ok).await?;
+
+        let payer_call = payer(&app);
+        let payer_call_bytes = payer_call.encode()?;
+        let payer = <T as Call>::Call::decode(payer_call_bytes.as_slice())?;
+
+        let paid = payee(&app);
+        let call_bytes = PayableCall::Paid(PaidCall { payer, paid }).encode()?;
+
+        let mut retries = 0;
+        loop {
+            let call = self.sign(chain_id.as_slice(), nonce, call_bytes.as_slice())?;
+            let hash = tx_hash(call.encode()?.as_slice());
+            let res = self.transport.call(call).await;
+            if !should_retry(&res) || retries >= MAX_NONCE_RETRIES {
+                return res;
             }
-        };
+
+            if let Ok(receipt) = self
+                .transport
+                .wait_for_inclusion(hash.as_slice(), Duration::ZERO)
+                .await
+            {
+                if receipt.is_ok() {
+                    return Ok(receipt);
+                }
+            }
+
+            retries += 1;
+            let (fresh, _) = self
+                .next_nonce(Store::default(), self.wallet.address()?)
+                .await?;
+            nonce = retry_nonce(nonce, fresh);
+        }
+    }
+
+    /// Waits until the transaction with the given hash is included in a
+    /// block, returning its receipt, or errors after `timeout`.
+    pub async fn wait_for_inclusion(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        self.transport.wait_for_inclusion(hash, timeout).await
+    }
+
+    /// Builds a transaction to be signed separately, e.g. on an offline
+    /// machine. The nonce is fetched for `signer`, rather than for the
+    /// client's wallet, which is not used.
+    pub async fn build_tx(
+        &self,
+        signer: Option<Address>,
+        payer: impl FnOnce(&U) -> T::Call,
+        payee: impl FnOnce(&U) -> T::Call,
+    ) -> Result<UnsignedTx> {
+        let (chain_id, store) = exec::execute(Store::default(), &self.transport, |app| {
+            Ok(app.inner.inner.borrow().inner.inner.chain_id.to_vec())
+        })
+        .await?;
+        let (nonce, store) = self.next_nonce(store, signer).await?;
 
         let app = self.query_with_store(store, Ok).await?;
 
@@ -144,17 +273,90 @@ where
         let payer = <T as Call>::Call::decode(payer_call_bytes.as_slice())?;
 
         let paid = payee(&app);
-        let call = PayableCall::Paid(PaidCall { payer, paid });
-        let call = crate::plugins::NonceCall {
+        let call_bytes = PayableCall::Paid(PaidCall { payer, paid }).encode()?;
+
+        Ok(UnsignedTx::new(
+            chain_id_string(chain_id)?,
             nonce,
-            inner_call: call,
-        };
-        let call = [chain_id, call.encode()?].concat();
-        let call = self.wallet.sign(&call)?;
+            signer,
+            call_bytes,
+        ))
+    }
+
+    /// Submits a transaction which was built with [AppClient::build_tx] and
+    /// signed separately.
+    pub async fn broadcast(&self, tx: SignedTx) -> Result<Receipt> {
+        self.broadcast_call(tx.into_call()?).await
+    }
+
+    /// Submits a call signed over the sign bytes of an [UnsignedTx] by a
+    /// signer other than a [Wallet], e.g. a browser extension signing with
+    /// [SigType::Adr36](crate::plugins::SigType::Adr36).
+    pub async fn broadcast_call(&self, call: SignerCall) -> Result<Receipt> {
         let call = ABCICall::DeliverTx(sdk_compat::Call::Native(call));
-        self.transport.call(call).await?;
+        self.transport.call(call).await
+    }
 
-        Ok(())
+    /// Builds the amino sign doc of an sdk transaction from `signer`, using
+    /// the chain ID and the signer's next nonce. Once signed, it is submitted
+    /// with [AppClient::broadcast_sdk] and converted to a call by the app's
+    /// [ConvertSdkTx] implementation.
+    pub async fn build_sign_doc(
+        &self,
+        signer: Address,
+        msgs: Vec<sdk::Msg>,
+        fee: sdk::Fee,
+        memo: String,
+    ) -> Result<sdk::SignDoc> {
+        let (chain_id, store) = exec::execute(Store::default(), &self.transport, |app| {
+            Ok(app.inner.inner.borrow().inner.inner.chain_id.to_vec())
+        })
+        .await?;
+        let (nonce, _) = self.next_nonce(store, Some(signer)).await?;
+
+        Ok(sdk::SignDoc {
+            account_number: "0".to_string(),
+            chain_id: chain_id_string(chain_id)?,
+            fee,
+            memo,
+            msgs,
+            sequence: nonce.unwrap_or_default().to_string(),
+        })
+    }
+
+    /// Submits an sdk transaction built with [AppClient::build_sign_doc] along
+    /// with a signature over its sign doc.
+    pub async fn broadcast_sdk(
+        &self,
+        sign_doc: sdk::SignDoc,
+        signature: sdk::Signature,
+    ) -> Result<Receipt> {
+        let tx = sdk::Tx::Amino(sdk::AminoTx {
+            msg: sign_doc.msgs,
+            fee: sign_doc.fee,
+            memo: sign_doc.memo,
+            signatures: vec![signature],
+        });
+        let call = ABCICall::DeliverTx(sdk_compat::Call::Sdk(tx));
+        self.transport.call(call).await
+    }
+
+    async fn next_nonce(
+        &self,
+        store: Store,
+        signer: Option<Address>,
+    ) -> Result<(Option<u64>, Store)> {
+        match signer {
+            None => Ok((None, store)),
+            Some(addr) => {
+                exec::execute(store, &self.transport, |app| {
+                    Ok(Some(
+                        app.inner.inner.borrow_mut().inner.inner.inner.nonce(addr)? + 1,
+                    ))
+                })
+                .await
+            }
+        }
     }
 
     pub async fn query_root<U2, F2: FnMut(ABCIPlugin<DefaultPlugins<Symbol, T>>) -> Result<U2>>(
@@ -200,23 +402,17 @@ where
     Symbol: crate::coins::Symbol,
 {
     // TODO: support subclients
-    // TODO: return object with result data (e.g. txid)
+    /// Signs and submits a call, returning its receipt. See
+    /// [AppClient::call].
     pub fn call_sync(
         &self,
         payer: impl FnOnce(&U) -> T::Call,
         payee: impl FnOnce(&U) -> T::Call,
-    ) -> Result<()> {
+    ) -> Result<Receipt> {
         let (chain_id, store) = exec::sync::execute(Store::default(), &self.transport, |app| {
             Ok(app.inner.inner.borrow().inner.inner.chain_id.to_vec())
         })?;
-        let (nonce, store) = match self.wallet.address()? {
-            None => (None, store),
-            Some(addr) => exec::sync::execute(store, &self.transport, |app| {
-                Ok(Some(
-                    app.inner.inner.borrow_mut().inner.inner.inner.nonce(addr)? + 1,
-                ))
-            })?,
-        };
+        let (mut nonce, store) = self.next_nonce_sync(store, self.wallet.address()?)?;
 
         let app = self.query_with_store_sync(store, Ok)?;
 
@@ -225,17 +421,88 @@ where
         let payer = <T as Call>::Call::decode(payer_call_bytes.as_slice())?;
 
         let paid = payee(&app);
-        let call = PayableCall::Paid(PaidCall { payer, paid });
-        let call = crate::plugins::NonceCall {
+        let call_bytes = PayableCall::Paid(PaidCall { payer, paid }).encode()?;
+
+        let mut retries = 0;
+        loop {
+            let call = self.sign(chain_id.as_slice(), nonce, call_bytes.as_slice())?;
+            let hash = tx_hash(call.encode()?.as_slice());
+            let res = self.transport.call_sync(call);
+            if !should_retry(&res) || retries >= MAX_NONCE_RETRIES {
+                return res;
+            }
+
+            if let Ok(receipt) = self
+                .transport
+                .wait_for_inclusion_sync(hash.as_slice(), Duration::ZERO)
+            {
+                if receipt.is_ok() {
+                    return Ok(receipt);
+                }
+            }
+
+            retries += 1;
+            let (fresh, _) = self.next_nonce_sync(Store::default(), self.wallet.address()?)?;
+            nonce = retry_nonce(nonce, fresh);
+        }
+    }
+
+    /// Waits until the transaction with the given hash is included in a
+    /// block, returning its receipt, or errors after `timeout`.
+    pub fn wait_for_inclusion_sync(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        self.transport.wait_for_inclusion_sync(hash, timeout)
+    }
+
+    /// Builds a transaction to be signed separately. See
+    /// [AppClient::build_tx].
+    pub fn build_tx_sync(
+        &self,
+        signer: Option<Address>,
+        payer: impl FnOnce(&U) -> T::Call,
+        payee: impl FnOnce(&U) -> T::Call,
+    ) -> Result<UnsignedTx> {
+        let (chain_id, store) = exec::sync::execute(Store::default(), &self.transport, |app| {
+            Ok(app.inner.inner.borrow().inner.inner.chain_id.to_vec())
+        })?;
+        let (nonce, store) = self.next_nonce_sync(store, signer)?;
+
+        let app = self.query_with_store_sync(store, Ok)?;
+
+        let payer_call = payer(&app);
+        let payer_call_bytes = payer_call.encode()?;
+        let payer = <T as Call>::Call::decode(payer_call_bytes.as_slice())?;
+
+        let paid = payee(&app);
+        let call_bytes = PayableCall::Paid(PaidCall { payer, paid }).encode()?;
+
+        Ok(UnsignedTx::new(
+            chain_id_string(chain_id)?,
             nonce,
-            inner_call: call,
-        };
-        let call = [chain_id, call.encode()?].concat();
-        let call = self.wallet.sign(&call)?;
-        let call = ABCICall::DeliverTx(sdk_compat::Call::Native(call));
-        self.transport.call_sync(call)?;
+            signer,
+            call_bytes,
+        ))
+    }
 
-        Ok(())
+    /// Submits a transaction which was built and signed separately. See
+    /// [AppClient::broadcast].
+    pub fn broadcast_sync(&self, tx: SignedTx) -> Result<Receipt> {
+        let call = ABCICall::DeliverTx(sdk_compat::Call::Native(tx.into_call()?));
+        self.transport.call_sync(call)
+    }
+
+    fn next_nonce_sync(
+        &self,
+        store: Store,
+        signer: Option<Address>,
+    ) -> Result<(Option<u64>, Store)> {
+        match signer {
+            None => Ok((None, store)),
+            Some(addr) => exec::sync::execute(store, &self.transport, |app| {
+                Ok(Some(
+                    app.inner.inner.borrow_mut().inner.inner.inner.nonce(addr)? + 1,
+                ))
+            }),
+        }
     }
 
     pub fn query_root_sync<U2, F2: FnMut(ABCIPlugin<DefaultPlugins<Symbol, T>>) -> Result<U2>>(
@@ -576,6 +843,30 @@ mod tests {
         Ok(())
     }
 
+    #[serial_test::serial]
+    #[test]
+    fn offline_sync() -> Result<()> {
+        let mut mock_client = setup()?;
+        let client = AppClient::<Foo, Foo, _, _, _>::new(&mut mock_client, Unsigned);
+
+        let alice = DerivedKey::new(b"alice").unwrap();
+        let unsigned = client.build_tx_sync(
+            Some(alice.address()),
+            |app| build_call!(app.bar.inc_b(4)),
+            |app| build_call!(app.signed_method(alice.address())),
+        )?;
+        assert_eq!(unsigned.nonce, Some(1));
+
+        let json = serde_json::to_string(&unsigned)?;
+        let signed = serde_json::from_str::<UnsignedTx>(&json)?.sign(&alice)?;
+        client.broadcast_sync(signed)?;
+
+        let bar_b = client.query_sync(|app| Ok(app.bar.b))?;
+        assert_eq!(bar_b, 12);
+
+        Ok(())
+    }
+
     #[serial_test::serial]
     #[test]
     fn sub_sync() -> Result<()> {
@@ -617,4 +908,36 @@ mod tests {
 
         do_query(client);
     }
+    #[test]
+    fn retry_on_conflict() {
+        let conflict = "Nonce Error: Nonce is not valid. Expected 4-1003, got 3";
+        assert!(should_retry(&Err(Error::Call(format!(
+            "code 1: {}",
+            conflict
+        )))));
+        assert!(!should_retry(&Err(Error::Call(
+            "code 1: Nonce Error: Nonce increase is too large: 2000".into()
+        ))));
+
+        let delivered = Receipt {
+            height: Some(10),
+            code: 1,
+            log: conflict.into(),
+            ..Receipt::pending(vec![0; 32])
+        };
+        assert!(should_retry(&Ok(delivered.clone())));
+        assert!(!should_retry(&Ok(Receipt {
+            code: 0,
+            log: String::new(),
+            ..delivered
+        })));
+        assert!(!should_retry(&Ok(Receipt::pending(vec![0; 32]))));
+    }
+
+    #[test]
+    fn nonce_retry() {
+        assert_eq!(retry_nonce(Some(5), Some(5)), Some(6));
+        assert_eq!(retry_nonce(Some(5), Some(9)), Some(9));
+        assert_eq!(retry_nonce(None, None), None);
+    }
 }
@@ -0,0 +1,258 @@
+//! Building, signing and broadcasting transactions as separate steps.
+//!
+//! An online process builds an [UnsignedTx] with
+//! [AppClient::build_tx](super::AppClient::build_tx), which fetches the chain ID
+//! and the signer's next nonce. The envelope serializes to JSON, so it can be
+//! carried to an air-gapped machine and signed there with any
+//! [Wallet](super::Wallet), without network access. The resulting [SignedTx]
+//! is carried back and submitted with
+//! [AppClient::broadcast](super::AppClient::broadcast).
+//!
+//! Multisig transactions are not supported: the signer plugin checks a single
+//! signature per transaction, so a [SignedTx] carries exactly one.
+
+use super::wallet::Wallet;
+use crate::coins::Address;
+use crate::encoding::Encode;
+use crate::plugins::{SigType, SignerCall};
+use crate::{Error, Result};
+use secp256k1::{ecdsa, Message, PublicKey, Secp256k1};
+use serde::{Deserialize, Serialize};
+
+const ENVELOPE_VERSION: u32 = 1;
+
+/// A transaction which has been built but not yet signed.
+///
+/// `call_bytes` is the encoded `PayableCall`. The bytes which are signed are
+/// the chain ID followed by the encoded `NonceCall` wrapping it, as produced by
+/// [UnsignedTx::sign_bytes].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsignedTx {
+    pub version: u32,
+    pub chain_id: String,
+    pub nonce: Option<u64>,
+    pub signer: Option<Address>,
+    #[serde(with = "hex_bytes")]
+    pub call_bytes: Vec<u8>,
+}
+
+/// A signature over the sign bytes of an [UnsignedTx].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxSignature {
+    #[serde(with = "hex_bytes")]
+    pub pubkey: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+/// An [UnsignedTx] along with its signature. `signatures` is a list to keep
+/// the envelope format open to multisig, but only a single signature is
+/// produced or accepted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTx {
+    pub tx: UnsignedTx,
+    pub signatures: Vec<TxSignature>,
+}
+
+impl UnsignedTx {
+    pub fn new(
+        chain_id: String,
+        nonce: Option<u64>,
+        signer: Option<Address>,
+        call_bytes: Vec<u8>,
+    ) -> Self {
+        Self {
+            version: ENVELOPE_VERSION,
+            chain_id,
+            nonce,
+            signer,
+            call_bytes,
+        }
+    }
+
+    /// The bytes passed to [Wallet::sign].
+    pub fn sign_bytes(&self) -> Result<Vec<u8>> {
+        if self.version != ENVELOPE_VERSION {
+            return Err(Error::Client(format!(
+                "Unsupported transaction envelope version {}",
+                self.version
+            )));
+        }
+
+        // `NonceCall` encodes as its nonce followed by the inner call
+        Ok([
+            self.chain_id.as_bytes(),
+            self.nonce.encode()?.as_slice(),
+            self.call_bytes.as_slice(),
+        ]
+        .concat())
+    }
+
+    /// Signs the transaction with the given wallet. This does not require
+    /// network access.
+    pub fn sign<W: Wallet>(self, wallet: &W) -> Result<SignedTx> {
+        let sign_bytes = self.sign_bytes()?;
+        let call = wallet.sign(sign_bytes.as_slice())?;
+        if !matches!(call.sigtype, SigType::Native) {
+            return Err(Error::Client(
+                "Offline signing requires a native signature".into(),
+            ));
+        }
+
+        let (pubkey, signature) = match (call.pubkey, call.signature) {
+            (Some(pubkey), Some(signature)) => (pubkey, signature),
+            _ => return Err(Error::Client("Wallet did not produce a signature".into())),
+        };
+
+        Ok(SignedTx {
+            tx: self,
+            signatures: vec![TxSignature {
+                pubkey: pubkey.to_vec(),
+                signature: signature.to_vec(),
+            }],
+        })
+    }
+}
+
+impl SignedTx {
+    /// Checks every signature against the transaction's sign bytes,
+    /// returning the addresses of the signers.
+    pub fn verify(&self) -> Result<Vec<Address>> {
+        use secp256k1::hashes::sha256;
+        let secp = Secp256k1::verification_only();
+        let msg = Message::from_hashed_data::<sha256::Hash>(self.tx.sign_bytes()?.as_slice());
+
+        self.signatures
+            .iter()
+            .map(|sig| {
+                let (pubkey, signature) = sig.to_arrays()?;
+                let pk = PublicKey::from_slice(&pubkey)?;
+                secp.verify_ecdsa(&msg, &ecdsa::Signature::from_compact(&signature)?, &pk)?;
+                Ok(Address::from_pubkey(pubkey))
+            })
+            .collect()
+    }
+
+    /// Converts to the call submitted to the chain, after verifying the
+    /// signature and checking that it was made by the expected signer.
+    ///
+    /// The signer plugin checks a single signature per transaction, so this
+    /// errors unless the envelope has exactly one signature.
+    pub fn into_call(self) -> Result<SignerCall> {
+        let signers = self.verify()?;
+        let signer = match signers.as_slice() {
+            [signer] => *signer,
+            [] => return Err(Error::Client("Transaction has no signatures".into())),
+            _ => {
+                return Err(Error::Client(
+                    "Signer plugin only accepts a single signature".into(),
+                ))
+            }
+        };
+
+        if let Some(expected) = self.tx.signer {
+            if expected != signer {
+                return Err(Error::Client(format!(
+                    "Transaction was built for {} but signed by {}",
+                    expected, signer
+                )));
+            }
+        }
+
+        let (pubkey, signature) = self.signatures[0].to_arrays()?;
+        Ok(SignerCall {
+            signature: Some(signature),
+            pubkey: Some(pubkey),
+            sigtype: SigType::Native,
+            call_bytes: self.tx.sign_bytes()?,
+        })
+    }
+}
+
+impl TxSignature {
+    fn to_arrays(&self) -> Result<([u8; 33], [u8; 64])> {
+        let pubkey = self
+            .pubkey
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::Client("Invalid public key length".into()))?;
+        let signature = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::Client("Invalid signature length".into()))?;
+
+        Ok((pubkey, signature))
+    }
+}
+
+pub(crate) mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::wallet::DerivedKey;
+
+    fn tx(signer: Option<Address>) -> UnsignedTx {
+        UnsignedTx::new("foo".to_string(), Some(3), signer, vec![1, 2, 3])
+    }
+
+    #[test]
+    fn sign_roundtrip() -> Result<()> {
+        let alice = DerivedKey::new(b"alice")?;
+        let unsigned = tx(Some(alice.address()));
+
+        let json = serde_json::to_string(&unsigned)?;
+        let unsigned: UnsignedTx = serde_json::from_str(&json)?;
+        let signed = unsigned.sign(&alice)?;
+
+        let json = serde_json::to_string(&signed)?;
+        let signed: SignedTx = serde_json::from_str(&json)?;
+        assert_eq!(signed.verify()?, vec![alice.address()]);
+
+        let call = signed.into_call()?;
+        assert_eq!(call.address()?, alice.address());
+        assert_eq!(call.call_bytes, b"foo\x01\0\0\0\0\0\0\0\x03\x01\x02\x03");
+
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_signer() -> Result<()> {
+        let alice = DerivedKey::new(b"alice")?;
+        let bob = DerivedKey::new(b"bob")?;
+
+        let signed = tx(Some(alice.address())).sign(&bob)?;
+        assert!(signed.into_call().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn single_signature() -> Result<()> {
+        let alice = DerivedKey::new(b"alice")?;
+        let bob = DerivedKey::new(b"bob")?;
+
+        let mut multi = tx(None).sign(&alice)?;
+        multi.signatures.extend(tx(None).sign(&bob)?.signatures);
+        assert_eq!(multi.verify()?, vec![alice.address(), bob.address()]);
+        assert!(multi.into_call().is_err());
+
+        let mut tampered = tx(None).sign(&alice)?;
+        tampered.tx.nonce = Some(4);
+        assert!(tampered.verify().is_err());
+
+        Ok(())
+    }
+}
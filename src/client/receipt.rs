@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// How a transport submits transactions to the network.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BroadcastMode {
+    /// Returns as soon as the transaction has been sent, without waiting for
+    /// `CheckTx`.
+    Async,
+    /// Waits for `CheckTx`, returning an error if the transaction was rejected
+    /// from the mempool.
+    Sync,
+    /// Waits for the transaction to be included in a block and returns its
+    /// `DeliverTx` result.
+    #[default]
+    Commit,
+}
+
+/// An event emitted while executing a transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub kind: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Event {
+    /// Returns the value of the first attribute with the given key.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Decodes the event as the typed event `E`, returning `None` if it is of
+    /// a different kind.
+    pub fn decode<E: crate::events::Event>(&self) -> crate::Result<Option<E>> {
+        if self.kind != E::KIND {
+            return Ok(None);
+        }
+
+        E::from_attributes(&self.attributes).map(Some)
+    }
+}
+
+impl From<tendermint_proto::v0_34::abci::Event> for Event {
+    fn from(event: tendermint_proto::v0_34::abci::Event) -> Self {
+        let attributes = event
+            .attributes
+            .into_iter()
+            .map(|attr| {
+                let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                let value = String::from_utf8_lossy(attr.value.as_ref()).to_string();
+                (key, value)
+            })
+            .collect();
+
+        Event {
+            kind: event.r#type,
+            attributes,
+        }
+    }
+}
+
+/// The result of submitting a transaction.
+///
+/// `height` is only set once the transaction is known to be included in a
+/// block, in which case `code`, `log` and `events` are its `DeliverTx` result.
+/// Otherwise they reflect `CheckTx`, if it was waited for.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub hash: Vec<u8>,
+    pub height: Option<u64>,
+    pub code: u32,
+    pub log: String,
+    pub events: Vec<Event>,
+}
+
+impl Receipt {
+    /// A receipt for a transaction which has been sent but whose result is not
+    /// yet known.
+    pub fn pending(hash: Vec<u8>) -> Self {
+        Self {
+            hash,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_included(&self) -> bool {
+        self.height.is_some()
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.code == 0
+    }
+
+    /// Whether the transaction was included in a block but failed because
+    /// another transaction from the same account had already used its nonce.
+    pub fn is_nonce_conflict(&self) -> bool {
+        self.is_included() && !self.is_ok() && self.log.contains(NONCE_CONFLICT)
+    }
+
+    /// Decodes all events of type `E` emitted by the transaction.
+    pub fn events_of<E: crate::events::Event>(&self) -> crate::Result<Vec<E>> {
+        self.events
+            .iter()
+            .filter_map(|event| event.decode::<E>().transpose())
+            .collect()
+    }
+
+    /// The transaction hash as uppercase hex, as displayed by Tendermint.
+    pub fn hash_hex(&self) -> String {
+        hex::encode_upper(&self.hash)
+    }
+
+    /// Returns an error if the transaction failed.
+    pub fn into_result(self) -> crate::Result<Self> {
+        if self.is_ok() {
+            Ok(self)
+        } else {
+            Err(Error::Call(format!("code {}: {}", self.code, self.log)))
+        }
+    }
+}
+
+/// The Tendermint transaction hash (the SHA-256 of the transaction bytes).
+pub fn tx_hash(tx: &[u8]) -> Vec<u8> {
+    use sha2::Digest;
+    sha2::Sha256::digest(tx).to_vec()
+}
+
+/// The start of the nonce plugin's error for a nonce which has already been
+/// used.
+const NONCE_CONFLICT: &str = "Nonce is not valid";
+
+/// Whether an error returned when submitting a transaction was caused by its
+/// nonce having already been used, in which case it may succeed if re-signed
+/// with a fresh nonce. Other nonce errors, e.g. a nonce which skips too far
+/// ahead, are not conflicts.
+pub fn is_nonce_conflict(err: &Error) -> bool {
+    match err {
+        Error::Nonce(msg) | Error::Call(msg) => msg.contains(NONCE_CONFLICT),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receipt_result() {
+        let mut receipt = Receipt::pending(tx_hash(b"tx"));
+        assert!(!receipt.is_included());
+        assert_eq!(receipt.hash_hex().len(), 64);
+        assert!(receipt.clone().into_result().is_ok());
+
+        receipt.code = 1;
+        receipt.log = "insufficient funds".into();
+        assert_eq!(
+            receipt.into_result().unwrap_err().to_string(),
+            "Call Error: code 1: insufficient funds"
+        );
+    }
+
+    #[test]
+    fn nonce_conflict() {
+        assert!(is_nonce_conflict(&Error::Nonce(
+            "Nonce is not valid. Expected 4-1003, got 3".into()
+        )));
+        assert!(is_nonce_conflict(&Error::Call(
+            "code 1: Nonce Error: Nonce is not valid. Expected 4-1003, got 3".into()
+        )));
+        assert!(!is_nonce_conflict(&Error::Nonce(
+            "Nonce increase is too large: 2000".into()
+        )));
+        assert!(!is_nonce_conflict(&Error::Call(
+            "code 1: Nonce Error: Signed calls must include a nonce".into()
+        )));
+        assert!(!is_nonce_conflict(&Error::Call("code 1: other".into())));
+    }
+
+    #[test]
+    fn deliver_tx_nonce_conflict() {
+        let mut receipt = Receipt {
+            height: Some(10),
+            code: 1,
+            log: "Nonce Error: Nonce is not valid. Expected 4-1003, got 3".into(),
+            ..Receipt::pending(tx_hash(b"tx"))
+        };
+        assert!(receipt.is_nonce_conflict());
+
+        receipt.log = "Nonce Error: Nonce increase is too large: 2000".into();
+        assert!(!receipt.is_nonce_conflict());
+
+        let pending = Receipt {
+            height: None,
+            log: "Nonce Error: Nonce is not valid".into(),
+            ..receipt.clone()
+        };
+        assert!(!pending.is_nonce_conflict());
+
+        let ok = Receipt {
+            code: 0,
+            log: String::new(),
+            ..receipt
+        };
+        assert!(!ok.is_nonce_conflict());
+    }
+}
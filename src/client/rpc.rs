@@ -0,0 +1,359 @@
+//! A transport which speaks Tendermint's JSON-RPC protocol over any HTTP
+//! implementation.
+//!
+//! [RpcClient] only needs a way to POST a request body and read the response,
+//! given by a [Fetch] implementation, so the same client works natively, in the
+//! browser (see [`JsFetch`](super::web::JsFetch)), and against a mock RPC in
+//! tests.
+//!
+//! Query results are merk proofs, which are checked against the root hash
+//! returned alongside them before any value is read. That root hash comes from
+//! the same response and is not checked against a verified header, so this
+//! only guards against malformed proofs, not against a dishonest node. Use
+//! `tendermint::client::HttpClient::trust` where results must be verified by
+//! a light client.
+
+use super::exec::Transport;
+use super::receipt::{tx_hash, Receipt};
+use super::subscribe::{json_events, AttributeEncoding};
+use super::BroadcastMode;
+use crate::abci::App;
+use crate::call::Call;
+use crate::encoding::Encode;
+use crate::merk::ProofStore;
+use crate::plugins::{ABCICall, ABCIPlugin};
+use crate::query::Query;
+use crate::state::State;
+use crate::store::Store;
+use crate::{Error, Result};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Sends HTTP requests on behalf of an [RpcClient].
+pub trait Fetch: Send + Sync {
+    /// Sends a POST request with the given JSON body, returning the body of
+    /// the response.
+    async fn post(&self, url: &str, body: String) -> Result<String>;
+}
+
+/// A client for a Tendermint RPC node, e.g. `http://localhost:26657`. Query
+/// results are trusted to come from the node's actual state, see the
+/// [module docs](self).
+pub struct RpcClient<F> {
+    url: String,
+    fetch: F,
+    height: Mutex<Option<u32>>,
+    broadcast_mode: BroadcastMode,
+    next_id: AtomicU64,
+    encoding: Mutex<Option<AttributeEncoding>>,
+}
+
+impl<F: Fetch> RpcClient<F> {
+    pub fn new(url: impl Into<String>, fetch: F) -> Self {
+        Self {
+            url: url.into(),
+            fetch,
+            height: Mutex::new(None),
+            broadcast_mode: BroadcastMode::default(),
+            next_id: AtomicU64::new(0),
+            encoding: Mutex::new(None),
+        }
+    }
+
+    /// Queries state at the given height rather than the latest height.
+    #[must_use]
+    pub fn height(self, height: u32) -> Self {
+        *self.height.lock().unwrap() = Some(height);
+        self
+    }
+
+    /// Sets how calls are broadcast. Defaults to [BroadcastMode::Commit].
+    #[must_use]
+    pub fn broadcast_mode(mut self, mode: BroadcastMode) -> Self {
+        self.broadcast_mode = mode;
+        self
+    }
+
+    /// Sets the encoding of event attributes in the node's responses, rather
+    /// than determining it from the node's version.
+    #[must_use]
+    pub fn attribute_encoding(self, encoding: AttributeEncoding) -> Self {
+        *self.encoding.lock().unwrap() = Some(encoding);
+        self
+    }
+
+    /// Sends a JSON-RPC request, returning its result.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let req = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let res = self.fetch.post(&self.url, req.to_string()).await?;
+        let mut res: Value = serde_json::from_str(&res)
+            .map_err(|err| Error::Tendermint(format!("Invalid RPC response: {}", err)))?;
+        if !res["error"].is_null() {
+            return Err(Error::Tendermint(format!(
+                "RPC error for {}: {}",
+                method, res["error"]
+            )));
+        }
+
+        Ok(res["result"].take())
+    }
+
+    /// Runs an encoded query at the given height, or the latest height if
+    /// `None`, returning a store backed by the proof and the height it was
+    /// evaluated at. The proof is only checked against the root hash reported
+    /// by the node.
+    pub async fn query_at(&self, query_bytes: &[u8], height: Option<u32>) -> Result<(Store, u32)> {
+        let res = self
+            .request(
+                "abci_query",
+                json!({
+                    "path": "",
+                    "data": hex::encode(query_bytes),
+                    "height": height.unwrap_or_default().to_string(),
+                    "prove": true,
+                }),
+            )
+            .await?;
+        let (root_hash, proof_bytes, height) = split_query_response(&res["response"])?;
+        let store = ProofStore::verify(root_hash, &proof_bytes)?;
+
+        Ok((store, height))
+    }
+
+    /// The encoding of event attributes in the node's responses, fetched
+    /// from its version on first use unless set with
+    /// [RpcClient::attribute_encoding].
+    async fn encoding(&self) -> Result<AttributeEncoding> {
+        if let Some(encoding) = *self.encoding.lock().unwrap() {
+            return Ok(encoding);
+        }
+
+        let status = self.request("status", json!({})).await?;
+        let encoding = AttributeEncoding::from_status(&status)?;
+        *self.encoding.lock().unwrap() = Some(encoding);
+
+        Ok(encoding)
+    }
+
+    /// Broadcasts an encoded transaction according to the broadcast mode.
+    pub async fn broadcast(&self, tx: &[u8]) -> Result<Receipt> {
+        use base64::Engine;
+        let method = match self.broadcast_mode {
+            BroadcastMode::Async => "broadcast_tx_async",
+            BroadcastMode::Sync => "broadcast_tx_sync",
+            BroadcastMode::Commit => "broadcast_tx_commit",
+        };
+        let params = json!({ "tx": base64::prelude::BASE64_STANDARD.encode(tx) });
+        let res = self.request(method, params).await?;
+
+        let hash = tx_hash(tx);
+        match self.broadcast_mode {
+            BroadcastMode::Async => Ok(Receipt::pending(hash)),
+            BroadcastMode::Sync => {
+                check_code(&res)?;
+                Ok(Receipt {
+                    log: json_str(&res["log"]),
+                    ..Receipt::pending(hash)
+                })
+            }
+            BroadcastMode::Commit => {
+                check_code(&res["check_tx"])?;
+                // renamed to `tx_result` in Tendermint v0.38
+                let result = match &res["deliver_tx"] {
+                    Value::Null => &res["tx_result"],
+                    result => result,
+                };
+
+                Ok(Receipt {
+                    hash,
+                    height: json_str(&res["height"]).parse().ok(),
+                    code: result["code"].as_u64().unwrap_or_default() as u32,
+                    log: json_str(&result["log"]),
+                    events: json_events(&result["events"], self.encoding().await?)?,
+                })
+            }
+        }
+    }
+}
+
+/// Splits the value of an `abci_query` response into the unverified root hash
+/// and merk proof returned by the node, along with the height of the result.
+fn split_query_response(res: &Value) -> Result<([u8; 32], Vec<u8>, u32)> {
+    use base64::Engine;
+    if let Err(Error::Call(msg)) = check_code(res) {
+        return Err(Error::Query(msg));
+    }
+
+    let value = base64::prelude::BASE64_STANDARD
+        .decode(json_str(&res["value"]))
+        .map_err(|err| Error::Tendermint(format!("Invalid query result: {}", err)))?;
+    if value.len() < 32 {
+        return Err(Error::Tendermint(
+            "Query result is missing root hash".into(),
+        ));
+    }
+
+    let mut root_hash = [0; 32];
+    root_hash.copy_from_slice(&value[..32]);
+    let height = json_str(&res["height"])
+        .parse()
+        .map_err(|_| Error::Tendermint("Missing query height".into()))?;
+
+    Ok((root_hash, value[32..].to_vec(), height))
+}
+
+fn check_code(res: &Value) -> Result<()> {
+    match res["code"].as_u64().unwrap_or_default() {
+        0 => Ok(()),
+        code => Err(Error::Call(format!(
+            "code {}: {}",
+            code,
+            json_str(&res["log"])
+        ))),
+    }
+}
+
+fn json_str(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+impl<T, F> Transport<ABCIPlugin<T>> for RpcClient<F>
+where
+    T: App + Call + Query + State + Default,
+    F: Fetch,
+{
+    async fn call(&self, call: <ABCIPlugin<T> as Call>::Call) -> Result<Receipt> {
+        let call = match call {
+            ABCICall::DeliverTx(call) => call,
+            _ => return Err(Error::Client("Unexpected call type".into())),
+        };
+        self.broadcast(&call.encode()?).await
+    }
+
+    async fn query(&self, query: T::Query) -> Result<Store> {
+        let maybe_height = *self.height.lock().unwrap();
+        let (store, height) = self.query_at(&query.encode()?, maybe_height).await?;
+        self.height.lock().unwrap().replace(height);
+
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::receipt::Event;
+    use futures_lite::future::block_on;
+
+    struct MockFetch {
+        requests: Mutex<Vec<Value>>,
+        result: Value,
+    }
+
+    impl MockFetch {
+        fn new(result: Value) -> Self {
+            Self {
+                requests: Mutex::new(vec![]),
+                result,
+            }
+        }
+    }
+
+    impl Fetch for MockFetch {
+        async fn post(&self, _url: &str, body: String) -> Result<String> {
+            let req: Value = serde_json::from_str(&body)?;
+            let res = json!({
+                "jsonrpc": "2.0",
+                "id": req["id"],
+                "result": self.result,
+            });
+            self.requests.lock().unwrap().push(req);
+            Ok(res.to_string())
+        }
+    }
+
+    #[test]
+    fn broadcast_commit() -> Result<()> {
+        let fetch = MockFetch::new(json!({
+            "check_tx": { "code": 0, "log": "" },
+            "deliver_tx": {
+                "code": 0,
+                "log": "ok",
+                "events": [{
+                    "type": "transfer",
+                    "attributes": [{ "key": "YW1vdW50", "value": "MTA=" }],
+                }],
+            },
+            "hash": "ABCD",
+            "height": "12",
+        }));
+        let client = RpcClient::new("http://localhost:26657", fetch)
+            .attribute_encoding(AttributeEncoding::Base64);
+
+        let receipt = block_on(client.broadcast(b"tx"))?;
+        assert_eq!(receipt.hash, tx_hash(b"tx"));
+        assert_eq!(receipt.height, Some(12));
+        assert_eq!(receipt.log, "ok");
+        assert_eq!(
+            receipt.events,
+            vec![Event {
+                kind: "transfer".into(),
+                attributes: vec![("amount".into(), "10".into())],
+            }]
+        );
+
+        let requests = client.fetch.requests.lock().unwrap();
+        assert_eq!(requests[0]["method"], "broadcast_tx_commit");
+        assert_eq!(requests[0]["params"]["tx"], "dHg=");
+
+        Ok(())
+    }
+
+    #[test]
+    fn broadcast_rejected() {
+        let fetch = MockFetch::new(json!({ "code": 1, "log": "Nonce Error: too low" }));
+        let client =
+            RpcClient::new("http://localhost:26657", fetch).broadcast_mode(BroadcastMode::Sync);
+
+        let err = block_on(client.broadcast(b"tx")).unwrap_err();
+        assert_eq!(err.to_string(), "Call Error: code 1: Nonce Error: too low");
+    }
+
+    #[test]
+    fn query_response() -> Result<()> {
+        let mut value = [7; 32].to_vec();
+        value.extend([1, 2, 3]);
+        let res = json!({
+            "code": 0,
+            "value": base64::Engine::encode(&base64::prelude::BASE64_STANDARD, &value),
+            "height": "5",
+        });
+        assert_eq!(split_query_response(&res)?, ([7; 32], vec![1, 2, 3], 5));
+
+        let res = json!({ "code": 1, "log": "invalid query" });
+        assert!(matches!(split_query_response(&res), Err(Error::Query(_))));
+
+        let fetch =
+            MockFetch::new(json!({ "response": { "code": 0, "value": "", "height": "5" } }));
+        let client = RpcClient::new("http://localhost:26657", fetch).height(4);
+        assert!(block_on(client.query_at(&[1], Some(4))).is_err());
+        assert_eq!(
+            client.fetch.requests.lock().unwrap()[0]["params"],
+            json!({ "path": "", "data": "01", "height": "4", "prove": true })
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use super::{
+    parse_block_results, parse_new_block_height, AttributeEncoding, BlockEvents, EventSource,
+};
+use crate::{Error, Result};
+use async_channel::{Receiver, Sender};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CloseEvent, MessageEvent, WebSocket};
+
+const SUBSCRIBE_ID: u64 = 0;
+
+enum Message {
+    Open,
+    Text(String),
+    Closed,
+}
+
+/// An [EventSource] for the browser, which speaks Tendermint's JSON-RPC
+/// protocol over a `WebSocket`, e.g. `ws://localhost:26657/websocket`.
+pub struct BrowserWebSocketSource {
+    url: String,
+    conn: Option<Connection>,
+    next_id: u64,
+    heights: VecDeque<u64>,
+    encoding: AttributeEncoding,
+}
+
+struct Connection {
+    socket: WebSocket,
+    messages: Receiver<Message>,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+impl BrowserWebSocketSource {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            conn: None,
+            next_id: SUBSCRIBE_ID + 1,
+            heights: VecDeque::new(),
+            encoding: AttributeEncoding::default(),
+        }
+    }
+
+    fn send(&mut self, id: u64, method: &str, params: Value) -> Result<()> {
+        let conn = self.conn.as_ref().ok_or_else(disconnected)?;
+        let req = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        conn.socket
+            .send_with_str(&req.to_string())
+            .map_err(|e| Error::Client(format!("{:?}", e)))
+    }
+
+    /// Receives the next message, queueing any new block notifications and
+    /// returning responses to requests.
+    async fn recv(&mut self) -> Result<Option<Value>> {
+        let conn = self.conn.as_ref().ok_or_else(disconnected)?;
+        let received = conn.messages.recv().await;
+        let text = match received {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Open) => return Ok(None),
+            Ok(Message::Closed) | Err(_) => {
+                self.conn = None;
+                return Err(disconnected());
+            }
+        };
+
+        let msg: Value = serde_json::from_str(&text)?;
+        let data = &msg["result"]["data"];
+        if data["type"] == "tendermint/event/NewBlock" {
+            self.heights.push_back(parse_new_block_height(data)?);
+            return Ok(None);
+        }
+
+        Ok(Some(msg))
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(id, method, params)?;
+
+        loop {
+            let msg = match self.recv().await? {
+                Some(msg) => msg,
+                None => continue,
+            };
+            if msg["id"] != id {
+                continue;
+            }
+            if !msg["error"].is_null() {
+                return Err(Error::Client(format!(
+                    "RPC error for {}: {}",
+                    method, msg["error"]
+                )));
+            }
+
+            return Ok(msg["result"].clone());
+        }
+    }
+}
+
+impl EventSource for BrowserWebSocketSource {
+    async fn connect(&mut self) -> Result<()> {
+        self.conn = None;
+        self.heights.clear();
+
+        let socket = WebSocket::new(&self.url).map_err(|e| Error::Client(format!("{:?}", e)))?;
+        let (tx, rx) = async_channel::unbounded();
+
+        let on_open = callback(&tx, |_: JsValue| Some(Message::Open));
+        let on_message = callback(&tx, |e: MessageEvent| {
+            e.data().as_string().map(Message::Text)
+        });
+        let on_close = callback(&tx, |_: CloseEvent| Some(Message::Closed));
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        self.conn = Some(Connection {
+            socket,
+            messages: rx,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        });
+
+        // wait for the socket to open
+        let opened = self.conn.as_ref().unwrap().messages.recv().await;
+        match opened {
+            Ok(Message::Open) => {}
+            _ => {
+                self.conn = None;
+                return Err(disconnected());
+            }
+        }
+
+        self.send(
+            SUBSCRIBE_ID,
+            "subscribe",
+            json!({ "query": "tm.event='NewBlock'" }),
+        )?;
+
+        let status = self.request("status", json!({})).await?;
+        self.encoding = AttributeEncoding::from_status(&status)?;
+
+        Ok(())
+    }
+
+    async fn next_height(&mut self) -> Result<u64> {
+        loop {
+            if let Some(height) = self.heights.pop_front() {
+                return Ok(height);
+            }
+            self.recv().await?;
+        }
+    }
+
+    async fn block_events(&mut self, height: u64) -> Result<BlockEvents> {
+        let result = self
+            .request("block_results", json!({ "height": height.to_string() }))
+            .await?;
+        parse_block_results(&result, self.encoding)
+    }
+}
+
+fn callback<E: 'static>(
+    tx: &Sender<Message>,
+    f: impl Fn(E) -> Option<Message> + 'static,
+) -> Closure<dyn FnMut(E)>
+where
+    E: wasm_bindgen::convert::FromWasmAbi,
+{
+    let tx = tx.clone();
+    Closure::wrap(Box::new(move |e: E| {
+        if let Some(msg) = f(e) {
+            let _ = tx.try_send(msg);
+        }
+    }) as Box<dyn FnMut(E)>)
+}
+
+fn disconnected() -> Error {
+    Error::Client("WebSocket disconnected".into())
+}
@@ -0,0 +1,491 @@
+//! Subscriptions to new blocks and the events they emit.
+//!
+//! An [EventSource] is notified of new block heights (e.g. by a Tendermint
+//! websocket `NewBlock` subscription) and can fetch the events emitted at any
+//! height. A [Subscription] drives a source, filtering events and reconnecting
+//! when the connection drops. Blocks produced while disconnected are fetched
+//! after reconnecting, so no block is skipped, and a subscription can be
+//! resumed from a stored height with [Subscription::from_height].
+//!
+//! Sources are provided for native code
+//! ([`WebSocketSource`](crate::tendermint::subscribe::WebSocketSource)), the
+//! browser ([BrowserWebSocketSource]), and tests
+//! ([`MockEventSource`](super::mock::MockEventSource)).
+
+use super::receipt::Event;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[cfg(target_arch = "wasm32")]
+mod browser;
+#[cfg(target_arch = "wasm32")]
+pub use browser::BrowserWebSocketSource;
+
+/// An event along with the position in the block which emitted it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEvent {
+    /// The index of the transaction which emitted the event, or `None` for
+    /// events emitted by `BeginBlock` or `EndBlock`.
+    pub tx_index: Option<u32>,
+    pub event: Event,
+}
+
+/// The events emitted by a block.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEvents {
+    pub height: u64,
+    pub events: Vec<BlockEvent>,
+}
+
+/// Matches events by type and attribute values.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    kind: Option<String>,
+    attributes: Vec<(String, String)>,
+}
+
+impl EventFilter {
+    /// Matches events of the given type.
+    pub fn kind(kind: impl Into<String>) -> Self {
+        Self {
+            kind: Some(kind.into()),
+            attributes: vec![],
+        }
+    }
+
+    /// Only matches events with the given attribute value.
+    #[must_use]
+    pub fn attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        if let Some(kind) = &self.kind {
+            if kind != &event.kind {
+                return false;
+            }
+        }
+
+        self.attributes
+            .iter()
+            .all(|(key, value)| event.attribute(key) == Some(value.as_str()))
+    }
+}
+
+/// A connection which is notified of new blocks.
+pub trait EventSource {
+    /// Connects, or reconnects after an error, and starts listening for new
+    /// blocks.
+    async fn connect(&mut self) -> Result<()>;
+
+    /// Waits for a new block, returning its height. Errors if the connection
+    /// is lost.
+    async fn next_height(&mut self) -> Result<u64>;
+
+    /// Fetches the events emitted by the block at the given height.
+    async fn block_events(&mut self, height: u64) -> Result<BlockEvents>;
+}
+
+/// Yields each new block from an [EventSource] with the events matching its
+/// filters.
+pub struct Subscription<S> {
+    source: S,
+    filters: Vec<EventFilter>,
+    next_height: Option<u64>,
+    latest_height: u64,
+    connected: bool,
+    max_reconnects: u32,
+    reconnect_interval: Duration,
+}
+
+impl<S: EventSource> Subscription<S> {
+    /// Subscribes to blocks produced after the first notification received
+    /// from the source.
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            filters: vec![],
+            next_height: None,
+            latest_height: 0,
+            connected: false,
+            max_reconnects: 10,
+            reconnect_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Starts from the given height, fetching any blocks between it and the
+    /// latest block before yielding new ones.
+    #[must_use]
+    pub fn from_height(mut self, height: u64) -> Self {
+        self.next_height = Some(height);
+        self
+    }
+
+    /// Only yields events matching the given filter. If several filters are
+    /// added, events matching any of them are yielded.
+    #[must_use]
+    pub fn filter(mut self, filter: EventFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Sets how many consecutive times to try reconnecting before returning an
+    /// error, and how long to wait between attempts.
+    #[must_use]
+    pub fn reconnect(mut self, max_attempts: u32, interval: Duration) -> Self {
+        self.max_reconnects = max_attempts;
+        self.reconnect_interval = interval;
+        self
+    }
+
+    /// The height of the next block to be yielded, which can be stored to
+    /// resume the subscription later.
+    pub fn next_height(&self) -> Option<u64> {
+        self.next_height
+    }
+
+    /// Returns the next block, with its events filtered.
+    pub async fn next(&mut self) -> Result<BlockEvents> {
+        let mut failures = 0;
+        loop {
+            match self.try_next().await {
+                Ok(block) => return Ok(block),
+                Err(err) => {
+                    self.connected = false;
+                    failures += 1;
+                    if failures > self.max_reconnects {
+                        return Err(err);
+                    }
+                    log::debug!("Subscription error, reconnecting: {}", err);
+                    if !self.reconnect_interval.is_zero() {
+                        sleep(self.reconnect_interval).await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the next block which emitted at least one matching event.
+    pub async fn next_matching(&mut self) -> Result<BlockEvents> {
+        loop {
+            let block = self.next().await?;
+            if !block.events.is_empty() {
+                return Ok(block);
+            }
+        }
+    }
+
+    async fn try_next(&mut self) -> Result<BlockEvents> {
+        if !self.connected {
+            self.source.connect().await?;
+            self.connected = true;
+        }
+
+        loop {
+            if let Some(height) = self.next_height {
+                if height <= self.latest_height {
+                    let mut block = self.source.block_events(height).await?;
+                    if block.height != height {
+                        return Err(Error::Client(format!(
+                            "Expected events for height {}, got {}",
+                            height, block.height
+                        )));
+                    }
+                    block.events.retain(|e| self.is_match(&e.event));
+                    self.next_height = Some(height + 1);
+                    return Ok(block);
+                }
+            }
+
+            let height = self.source.next_height().await?;
+            self.latest_height = self.latest_height.max(height);
+            if self.next_height.is_none() {
+                self.next_height = Some(height);
+            }
+        }
+    }
+
+    fn is_match(&self, event: &Event) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|f| f.matches(event))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                &resolve,
+                duration.as_millis() as i32,
+            )
+            .unwrap();
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "tokio"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "tokio")))]
+async fn sleep(duration: Duration) {
+    // without a runtime timer, wait for a thread to close the channel rather
+    // than blocking the executor
+    let (tx, rx) = async_channel::bounded::<()>(1);
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        drop(tx);
+    });
+    let _ = rx.recv().await;
+}
+
+/// How event attribute keys and values are encoded in RPC responses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttributeEncoding {
+    /// Base64-encoded, as by Tendermint v0.34 and earlier.
+    Base64,
+    /// Plain strings, as by CometBFT v0.37 and later.
+    #[default]
+    Plain,
+}
+
+impl AttributeEncoding {
+    /// The encoding used by the node with the given version, as reported in
+    /// `node_info.version` by the `status` RPC method.
+    pub fn from_version(version: &str) -> Result<Self> {
+        let mut parts = version.trim_start_matches('v').split('.');
+        let mut next = || -> Result<u64> {
+            parts
+                .next()
+                .and_then(|part| part.parse().ok())
+                .ok_or_else(|| Error::Client(format!("Invalid node version: {}", version)))
+        };
+
+        Ok(match (next()?, next()?) {
+            (0, minor) if minor <= 34 => AttributeEncoding::Base64,
+            _ => AttributeEncoding::Plain,
+        })
+    }
+
+    /// The encoding used by the node which returned the given JSON result of
+    /// a `status` RPC request.
+    pub fn from_status(status: &serde_json::Value) -> Result<Self> {
+        let version = status["node_info"]["version"]
+            .as_str()
+            .ok_or_else(|| Error::Client("Missing node version".into()))?;
+        Self::from_version(version)
+    }
+}
+
+/// Parses the JSON result of a Tendermint `block_results` RPC request, whose
+/// attributes have the given encoding.
+pub fn parse_block_results(
+    result: &serde_json::Value,
+    encoding: AttributeEncoding,
+) -> Result<BlockEvents> {
+    let height = parse_height(&result["height"])?;
+    let mut events = vec![];
+
+    for event in json_events(&result["begin_block_events"], encoding)? {
+        events.push(BlockEvent {
+            tx_index: None,
+            event,
+        });
+    }
+    if let Some(txs) = result["txs_results"].as_array() {
+        for (i, tx) in txs.iter().enumerate() {
+            for event in json_events(&tx["events"], encoding)? {
+                events.push(BlockEvent {
+                    tx_index: Some(i as u32),
+                    event,
+                });
+            }
+        }
+    }
+    for event in json_events(&result["end_block_events"], encoding)? {
+        events.push(BlockEvent {
+            tx_index: None,
+            event,
+        });
+    }
+
+    Ok(BlockEvents { height, events })
+}
+
+/// Parses the height of a block from the JSON data of a Tendermint `NewBlock`
+/// event.
+pub fn parse_new_block_height(data: &serde_json::Value) -> Result<u64> {
+    parse_height(&data["value"]["block"]["header"]["height"])
+}
+
+fn parse_height(value: &serde_json::Value) -> Result<u64> {
+    value
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| Error::Client("Missing block height".into()))
+}
+
+pub(crate) fn json_events(
+    value: &serde_json::Value,
+    encoding: AttributeEncoding,
+) -> Result<Vec<Event>> {
+    let events = match value.as_array() {
+        Some(events) => events,
+        None => return Ok(vec![]),
+    };
+
+    events
+        .iter()
+        .map(|event| {
+            let kind = event["type"]
+                .as_str()
+                .ok_or_else(|| Error::Client("Missing event type".into()))?
+                .to_string();
+            let attributes = event["attributes"]
+                .as_array()
+                .map(|attrs| {
+                    attrs
+                        .iter()
+                        .map(|attr| {
+                            Ok((
+                                json_attr(&attr["key"], encoding)?,
+                                json_attr(&attr["value"], encoding)?,
+                            ))
+                        })
+                        .collect::<Result<_>>()
+                })
+                .transpose()?
+                .unwrap_or_default();
+
+            Ok(Event { kind, attributes })
+        })
+        .collect()
+}
+
+fn json_attr(value: &serde_json::Value, encoding: AttributeEncoding) -> Result<String> {
+    use base64::Engine;
+    let s = value.as_str().unwrap_or_default();
+    match encoding {
+        AttributeEncoding::Plain => Ok(s.to_string()),
+        AttributeEncoding::Base64 => {
+            let bytes = base64::prelude::BASE64_STANDARD
+                .decode(s)
+                .map_err(|e| Error::Client(format!("Invalid event attribute: {}", e)))?;
+            Ok(String::from_utf8_lossy(&bytes).to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockEventSource;
+    use futures_lite::future::block_on;
+
+    fn transfer(to: &str) -> Event {
+        Event {
+            kind: "transfer".to_string(),
+            attributes: vec![("recipient".to_string(), to.to_string())],
+        }
+    }
+
+    #[test]
+    fn filter() {
+        let filter = EventFilter::kind("transfer").attribute("recipient", "alice");
+        assert!(filter.matches(&transfer("alice")));
+        assert!(!filter.matches(&transfer("bob")));
+        assert!(EventFilter::default().matches(&transfer("bob")));
+    }
+
+    #[test]
+    fn resume_after_disconnect() -> Result<()> {
+        let source = MockEventSource::default();
+        let mut sub = Subscription::new(source.clone())
+            .filter(EventFilter::kind("transfer").attribute("recipient", "alice"))
+            .reconnect(3, Duration::ZERO);
+
+        source.push_block(vec![transfer("alice"), transfer("bob")]);
+        let block = block_on(sub.next())?;
+        assert_eq!(block.height, 1);
+        assert_eq!(block.events.len(), 1);
+
+        // blocks produced while disconnected are not notified
+        source.disconnect();
+        source.push_block(vec![transfer("alice")]);
+        source.push_block(vec![]);
+        assert!(block_on(sub.next()).is_err());
+
+        source.push_block(vec![transfer("bob")]);
+        let heights: Vec<_> = (0..3)
+            .map(|_| block_on(sub.next()).map(|b| (b.height, b.events.len())))
+            .collect::<Result<_>>()?;
+        assert_eq!(heights, vec![(2, 1), (3, 0), (4, 0)]);
+        assert_eq!(sub.next_height(), Some(5));
+
+        // resuming from a stored height replays from that block
+        let mut sub = Subscription::new(source.clone()).from_height(3);
+        source.push_block(vec![]);
+        assert_eq!(block_on(sub.next())?.height, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_results_json() -> Result<()> {
+        let json = serde_json::json!({
+            "height": "7",
+            "begin_block_events": [{
+                "type": "begin",
+                "attributes": [{ "key": "a2V5", "value": "dmFsdWU=", "index": true }]
+            }],
+            "txs_results": [{ "code": 0, "events": [{
+                "type": "transfer",
+                "attributes": [{ "key": "cmVjaXBpZW50", "value": "YWxpY2U=" }]
+            }] }],
+            "end_block_events": null
+        });
+
+        let block = parse_block_results(&json, AttributeEncoding::Base64)?;
+        assert_eq!(block.height, 7);
+        assert_eq!(block.events[0].tx_index, None);
+        assert_eq!(block.events[0].event.attribute("key"), Some("value"));
+        assert_eq!(block.events[1].tx_index, Some(0));
+        assert_eq!(block.events[1].event, transfer("alice"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn plain_attributes() -> Result<()> {
+        // a plain value which happens to be valid base64 is kept as-is
+        let json = serde_json::json!([{
+            "type": "transfer",
+            "attributes": [{ "key": "recipient", "value": "abcd" }]
+        }]);
+
+        let events = json_events(&json, AttributeEncoding::Plain)?;
+        assert_eq!(events, vec![transfer("abcd")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn attribute_encoding() -> Result<()> {
+        use AttributeEncoding::*;
+        assert_eq!(AttributeEncoding::from_version("0.34.26")?, Base64);
+        assert_eq!(AttributeEncoding::from_version("v0.33.9")?, Base64);
+        assert_eq!(AttributeEncoding::from_version("0.37.2")?, Plain);
+        assert_eq!(AttributeEncoding::from_version("0.38.0-rc1")?, Plain);
+        assert_eq!(AttributeEncoding::from_version("1.0.0")?, Plain);
+        assert!(AttributeEncoding::from_version("unknown").is_err());
+
+        let status = serde_json::json!({ "node_info": { "version": "0.34.26" } });
+        assert_eq!(AttributeEncoding::from_status(&status)?, Base64);
+
+        Ok(())
+    }
+}
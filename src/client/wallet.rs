@@ -98,6 +98,9 @@ impl Wallet for DerivedKey {
     }
 }
 
+/// A wallet which stores a single private key unencrypted on disk. Prefer
+/// [Keyring](super::keyring::Keyring), which can import keys written by this
+/// wallet with [Keyring::import_legacy](super::keyring::Keyring::import_legacy).
 #[derive(Clone, Debug)]
 pub struct SimpleWallet {
     privkey: secp256k1::SecretKey,
@@ -0,0 +1,643 @@
+//! A client for use from JavaScript, in the browser or in Node.
+//!
+//! [WebClient] reads state from merk proofs and submits calls over a
+//! fetch-based [RpcClient], trusting the node's root hash. Calls are given as a path of fields ending in a
+//! call method, along with its arguments as strings, in the same format as the
+//! [CLI](crate::cli), e.g. `{ path: ["accounts", "transfer"], args: [...] }`.
+//!
+//! Transactions are signed by a [WebSigner]:
+//! - Keplr signs native calls with [SigType::Adr36] (`signArbitrary`), and sdk
+//!   transactions with `signAmino`.
+//! - MetaMask signs sdk transactions with `personal_sign`, which are verified
+//!   with [SigType::EthPersonalSign](crate::plugins::SigType::EthPersonalSign).
+//! - An in-browser secret key signs either kind of transaction.
+//!
+//! Apps export a JavaScript class wrapping a [WebClient] for their own type
+//! with [export_web_client](crate::export_web_client). Requests go through the
+//! global `fetch`, or any function with the same signature, so the client can
+//! be run in Node against a mock RPC.
+
+use super::offline::UnsignedTx;
+use super::receipt::Receipt;
+use super::rpc::{Fetch, RpcClient};
+use super::wallet::{DerivedKey, Unsigned, Wallet};
+use super::AppClient;
+use crate::abci::App;
+use crate::call::Call;
+use crate::coins::{Address, Symbol};
+use crate::describe::methods::{DescribeCalls, ReadPath};
+use crate::describe::{err_to_js, Describe};
+use crate::encoding::Decode;
+use crate::plugins::sdk_compat::sdk;
+use crate::plugins::{ConvertSdkTx, PaidCall, SigType, SignerCall};
+use crate::query::Query;
+use crate::state::State;
+use crate::{Error, Result};
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::future::Future;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+const SECP256K1_PUBKEY_TYPE: &str = "tendermint/PubKeySecp256k1";
+
+/// Sends requests with the global `fetch` function, or a function with the
+/// same signature.
+pub struct JsFetch {
+    fetch: Function,
+}
+
+// wasm32 is single-threaded, so the JS function is never shared across threads
+unsafe impl Send for JsFetch {}
+unsafe impl Sync for JsFetch {}
+
+impl JsFetch {
+    /// Uses the global `fetch`, available in browsers and in Node 18+.
+    pub fn global() -> Result<Self> {
+        let fetch = get(&js_sys::global(), "fetch")?
+            .dyn_into()
+            .map_err(|_| Error::Client("No global `fetch` function".into()))?;
+
+        Ok(Self { fetch })
+    }
+
+    /// Uses the given function in place of `fetch`, e.g. a mock RPC in tests.
+    /// It is called with the URL and request options, and must return a
+    /// promise of an object with a `text()` method.
+    pub fn new(fetch: Function) -> Self {
+        Self { fetch }
+    }
+}
+
+impl Fetch for JsFetch {
+    async fn post(&self, url: &str, body: String) -> Result<String> {
+        let headers = Object::new();
+        set(&headers, "content-type", &"application/json".into())?;
+        let init = Object::new();
+        set(&init, "method", &"POST".into())?;
+        set(&init, "headers", &headers)?;
+        set(&init, "body", &body.into())?;
+
+        let res = self
+            .fetch
+            .call2(&JsValue::NULL, &url.into(), &init)
+            .map_err(js_err)?;
+        let res = resolve(res).await?;
+        let text = call_method(&res, "text", &[]).await?;
+
+        text.as_string()
+            .ok_or_else(|| Error::Client("RPC response body is not a string".into()))
+    }
+}
+
+#[derive(Clone)]
+enum SignerKind {
+    Keplr { chain_id: String },
+    MetaMask,
+    Key(DerivedKey),
+}
+
+/// Signs transactions built by a [WebClient].
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct WebSigner(SignerKind);
+
+#[wasm_bindgen]
+impl WebSigner {
+    /// Signs with the Keplr extension, using its key for the given chain.
+    pub fn keplr(chain_id: String) -> WebSigner {
+        WebSigner(SignerKind::Keplr { chain_id })
+    }
+
+    /// Signs with MetaMask, or another wallet injected as `ethereum`. Only sdk
+    /// transactions can be signed.
+    pub fn metamask() -> WebSigner {
+        WebSigner(SignerKind::MetaMask)
+    }
+
+    /// Signs with the given hex-encoded secp256k1 secret key, held in memory.
+    #[wasm_bindgen(js_name = fromSecretKey)]
+    pub fn from_secret_key(secret_key: &str) -> std::result::Result<WebSigner, JsValue> {
+        let bytes = hex::decode(secret_key.trim_start_matches("0x")).map_err(err_to_js)?;
+        let key = secp256k1::SecretKey::from_slice(&bytes).map_err(err_to_js)?;
+        Ok(WebSigner(SignerKind::Key(DerivedKey::from_secret_key(key))))
+    }
+
+    /// Resolves to the signer's address.
+    #[wasm_bindgen(js_name = address)]
+    pub fn address_js(&self) -> Promise {
+        let signer = self.clone();
+        promise(async move { Ok(signer.address().await?.to_string()) })
+    }
+}
+
+impl WebSigner {
+    /// The address calls signed by this signer are made from. For MetaMask,
+    /// this is the Ethereum address of the account.
+    pub async fn address(&self) -> Result<Address> {
+        match &self.0 {
+            SignerKind::Keplr { chain_id } => {
+                Ok(Address::from_pubkey(keplr_key(chain_id).await?.0))
+            }
+            SignerKind::MetaMask => Ok(Address::from(eth_account().await?.1)),
+            SignerKind::Key(key) => Ok(key.address()),
+        }
+    }
+
+    /// Signs the sign bytes of a transaction built by
+    /// [AppClient::build_tx](super::AppClient::build_tx).
+    pub async fn sign_tx(&self, tx: &UnsignedTx) -> Result<SignerCall> {
+        let sign_bytes = tx.sign_bytes()?;
+        match &self.0 {
+            SignerKind::Keplr { chain_id } => {
+                let (pubkey, signer) = keplr_key(chain_id).await?;
+                let data = Uint8Array::from(sign_bytes.as_slice());
+                let res = call_method(
+                    &keplr()?,
+                    "signArbitrary",
+                    &[chain_id.into(), signer.into(), data.into()],
+                )
+                .await?;
+                let signature: sdk::Signature = from_js(res)?;
+
+                Ok(SignerCall {
+                    signature: Some(decode_signature(&signature.signature)?),
+                    pubkey: Some(pubkey),
+                    sigtype: SigType::Adr36,
+                    call_bytes: sign_bytes,
+                })
+            }
+            SignerKind::MetaMask => Err(Error::Signer(
+                "MetaMask can only sign sdk transactions".into(),
+            )),
+            SignerKind::Key(key) => key.sign(&sign_bytes),
+        }
+    }
+
+    /// Signs the sign doc of an sdk transaction built by
+    /// [AppClient::build_sign_doc](super::AppClient::build_sign_doc).
+    pub async fn sign_doc(&self, sign_doc: &sdk::SignDoc) -> Result<sdk::Signature> {
+        match &self.0 {
+            SignerKind::Keplr { chain_id } => {
+                let (_, signer) = keplr_key(chain_id).await?;
+                let res = call_method(
+                    &keplr()?,
+                    "signAmino",
+                    &[chain_id.into(), signer.into(), to_js(sign_doc)?],
+                )
+                .await?;
+
+                from_js(get(&res, "signature")?)
+            }
+            SignerKind::MetaMask => {
+                let (account, _) = eth_account().await?;
+                let sign_bytes = serde_json::to_vec(sign_doc)?;
+                let params = Array::of2(
+                    &format!("0x{}", hex::encode(&sign_bytes)).into(),
+                    &account.into(),
+                );
+                let res = ethereum_request("personal_sign", params.into()).await?;
+                let res = res
+                    .as_string()
+                    .ok_or_else(|| Error::Signer("Invalid personal_sign result".into()))?;
+                let signature = hex::decode(res.trim_start_matches("0x"))
+                    .map_err(|err| Error::Signer(err.to_string()))?;
+                let pubkey = recover_eth_pubkey(&sign_bytes, &signature)?;
+
+                Ok(sdk_signature(pubkey, &signature[..64], "eth"))
+            }
+            SignerKind::Key(key) => {
+                let sign_bytes = serde_json::to_vec(sign_doc)?;
+                let call = key.sign(&sign_bytes)?;
+                let signature = call.signature.unwrap_or([0; 64]);
+
+                Ok(sdk_signature(key.pubkey().serialize(), &signature, "sdk"))
+            }
+        }
+    }
+}
+
+/// A call given as a path of fields ending in a call method, along with the
+/// method's arguments.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CallSpec {
+    pub path: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+type Inner<T, S> = AppClient<T, T, RpcClient<JsFetch>, S, Unsigned>;
+
+/// Queries and calls an app of type `T` from JavaScript.
+pub struct WebClient<T, S> {
+    app: Rc<Inner<T, S>>,
+}
+
+impl<T, S> Clone for WebClient<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            app: self.app.clone(),
+        }
+    }
+}
+
+impl<T, S> WebClient<T, S>
+where
+    T: App
+        + Call
+        + State
+        + Query
+        + Default
+        + Describe
+        + ConvertSdkTx<Output = PaidCall<T::Call>>
+        + 'static,
+    S: Symbol,
+{
+    /// Creates a client for the Tendermint RPC node at `url`, sending requests
+    /// with the given `fetch`-like function or the global `fetch`.
+    pub fn new(url: &str, fetch: Option<Function>) -> Result<Self> {
+        let fetch = match fetch {
+            Some(fetch) => JsFetch::new(fetch),
+            None => JsFetch::global()?,
+        };
+        let app = AppClient::new(RpcClient::new(url, fetch), Unsigned);
+
+        Ok(Self { app: Rc::new(app) })
+    }
+
+    /// Reads a value by path, e.g. `["accounts", "balance", "<address>"]`,
+    /// as JSON. See [ReadPath].
+    pub async fn query(&self, path: &[String]) -> Result<Value> {
+        self.app.query(|app| app.read_path(path)).await
+    }
+
+    /// Builds a transaction to be signed by `signer`.
+    pub async fn build(
+        &self,
+        signer: Option<Address>,
+        payer: &CallSpec,
+        payee: &CallSpec,
+    ) -> Result<UnsignedTx> {
+        let payer = encode_call::<T>(payer)?;
+        let payee = encode_call::<T>(payee)?;
+        self.app.build_tx(signer, |_| payer, |_| payee).await
+    }
+
+    /// Builds, signs and submits a call. Unlike
+    /// [AppClient::call](super::AppClient::call), calls are not re-signed
+    /// after nonce conflicts, since signing may prompt the user.
+    pub async fn call(
+        &self,
+        signer: &WebSigner,
+        payer: &CallSpec,
+        payee: &CallSpec,
+    ) -> Result<Receipt> {
+        let address = signer.address().await?;
+        let tx = self.build(Some(address), payer, payee).await?;
+        let call = signer.sign_tx(&tx).await?;
+        self.app.broadcast_call(call).await
+    }
+
+    /// Builds, signs and submits an sdk transaction made of the given amino
+    /// messages, which the app converts to a call.
+    pub async fn call_sdk(
+        &self,
+        signer: &WebSigner,
+        msgs: Vec<sdk::Msg>,
+        fee: sdk::Fee,
+        memo: String,
+    ) -> Result<Receipt> {
+        let address = signer.address().await?;
+        let sign_doc = self.app.build_sign_doc(address, msgs, fee, memo).await?;
+        let signature = signer.sign_doc(&sign_doc).await?;
+        self.app.broadcast_sdk(sign_doc, signature).await
+    }
+
+    /// Submits a transaction which was signed separately, e.g. offline.
+    pub async fn broadcast(&self, tx: super::SignedTx) -> Result<Receipt> {
+        self.app.broadcast(tx).await
+    }
+}
+
+fn encode_call<T: Call + DescribeCalls>(spec: &CallSpec) -> Result<T::Call> {
+    let bytes = T::describe_calls().encode_call(&spec.path, &spec.args)?;
+    Ok(T::Call::decode(bytes.as_slice())?)
+}
+
+/// Exports a JavaScript class named `$name` wrapping a [WebClient] for the
+/// given app and symbol types. The calling crate must depend on
+/// `wasm-bindgen`.
+///
+/// ```ignore
+/// orga::export_web_client!(NomicClient, InnerApp, Nom);
+/// ```
+///
+/// ```js
+/// const client = new NomicClient("http://localhost:26657");
+/// const signer = WebSigner.keplr("nomic-testnet");
+/// await client.query(["accounts", "balance", address]);
+/// await client.call(signer, payer, { path: ["accounts", "transfer"], args: [to, "100"] });
+/// ```
+#[macro_export]
+macro_rules! export_web_client {
+    ($name:ident, $app:ty, $symbol:ty) => {
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        pub struct $name($crate::client::web::WebClient<$app, $symbol>);
+
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        impl $name {
+            #[wasm_bindgen(constructor)]
+            pub fn new(
+                url: &str,
+                fetch: Option<$crate::client::web::Function>,
+            ) -> ::std::result::Result<$name, ::wasm_bindgen::JsValue> {
+                $crate::client::web::WebClient::new(url, fetch)
+                    .map($name)
+                    .map_err($crate::describe::err_to_js)
+            }
+
+            pub fn query(&self, path: ::wasm_bindgen::JsValue) -> $crate::client::web::Promise {
+                let client = self.0.clone();
+                $crate::client::web::promise(async move {
+                    let path: Vec<String> = $crate::client::web::from_js(path)?;
+                    client.query(&path).await
+                })
+            }
+
+            pub fn build(
+                &self,
+                signer: Option<String>,
+                payer: ::wasm_bindgen::JsValue,
+                payee: ::wasm_bindgen::JsValue,
+            ) -> $crate::client::web::Promise {
+                let client = self.0.clone();
+                $crate::client::web::promise(async move {
+                    let signer = signer
+                        .map(|signer| signer.parse())
+                        .transpose()
+                        .map_err(|_| $crate::Error::Client("Invalid signer address".into()))?;
+                    let payer = $crate::client::web::from_js(payer)?;
+                    let payee = $crate::client::web::from_js(payee)?;
+                    client.build(signer, &payer, &payee).await
+                })
+            }
+
+            pub fn call(
+                &self,
+                signer: &$crate::client::web::WebSigner,
+                payer: ::wasm_bindgen::JsValue,
+                payee: ::wasm_bindgen::JsValue,
+            ) -> $crate::client::web::Promise {
+                let client = self.0.clone();
+                let signer = signer.clone();
+                $crate::client::web::promise(async move {
+                    let payer = $crate::client::web::from_js(payer)?;
+                    let payee = $crate::client::web::from_js(payee)?;
+                    client.call(&signer, &payer, &payee).await
+                })
+            }
+
+            #[wasm_bindgen(js_name = callSdk)]
+            pub fn call_sdk(
+                &self,
+                signer: &$crate::client::web::WebSigner,
+                msgs: ::wasm_bindgen::JsValue,
+                fee: ::wasm_bindgen::JsValue,
+                memo: String,
+            ) -> $crate::client::web::Promise {
+                let client = self.0.clone();
+                let signer = signer.clone();
+                $crate::client::web::promise(async move {
+                    let msgs = $crate::client::web::from_js(msgs)?;
+                    let fee = $crate::client::web::from_js(fee)?;
+                    client.call_sdk(&signer, msgs, fee, memo).await
+                })
+            }
+
+            pub fn broadcast(&self, tx: ::wasm_bindgen::JsValue) -> $crate::client::web::Promise {
+                let client = self.0.clone();
+                $crate::client::web::promise(async move {
+                    let tx = $crate::client::web::from_js(tx)?;
+                    client.broadcast(tx).await
+                })
+            }
+        }
+    };
+}
+
+pub use js_sys::{Function, Promise};
+
+/// Runs a future, resolving the returned promise with its output converted to
+/// a plain JavaScript value, or rejecting it with an `Error`.
+pub fn promise<T, F>(fut: F) -> Promise
+where
+    T: Serialize,
+    F: Future<Output = Result<T>> + 'static,
+{
+    future_to_promise(async move {
+        let value = fut.await.map_err(err_to_js)?;
+        to_js(&value).map_err(err_to_js)
+    })
+}
+
+/// Converts a JavaScript value to `T` through its JSON representation.
+pub fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T> {
+    serde_wasm_bindgen::from_value(value).map_err(|err| Error::Client(err.to_string()))
+}
+
+/// Converts `T` to a plain JavaScript value, with maps as objects.
+pub fn to_js<T: Serialize + ?Sized>(value: &T) -> Result<JsValue> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|err| Error::Client(err.to_string()))
+}
+
+fn js_err(err: JsValue) -> Error {
+    let msg = match err.dyn_ref::<js_sys::Error>() {
+        Some(err) => String::from(err.message()),
+        None => format!("{:?}", err),
+    };
+    Error::Client(msg)
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue> {
+    Reflect::get(target, &key.into()).map_err(js_err)
+}
+
+fn set(target: &JsValue, key: &str, value: &JsValue) -> Result<()> {
+    Reflect::set(target, &key.into(), value).map_err(js_err)?;
+    Ok(())
+}
+
+/// Awaits the value if it is a promise.
+async fn resolve(value: JsValue) -> Result<JsValue> {
+    JsFuture::from(Promise::resolve(&value))
+        .await
+        .map_err(js_err)
+}
+
+/// Calls the named method of `target`, awaiting the result if it is a promise.
+async fn call_method(target: &JsValue, name: &str, args: &[JsValue]) -> Result<JsValue> {
+    let method: Function = get(target, name)?
+        .dyn_into()
+        .map_err(|_| Error::Client(format!("`{}` is not a function", name)))?;
+    let args: Array = args.iter().collect();
+    let res = Reflect::apply(&method, target, &args).map_err(js_err)?;
+    resolve(res).await
+}
+
+fn keplr() -> Result<JsValue> {
+    let keplr = get(&js_sys::global(), "keplr")?;
+    if keplr.is_undefined() {
+        return Err(Error::Signer("Keplr is not installed".into()));
+    }
+    Ok(keplr)
+}
+
+/// Returns the public key and bech32 address of the Keplr account for the
+/// given chain.
+async fn keplr_key(chain_id: &str) -> Result<([u8; 33], String)> {
+    let keplr = keplr()?;
+    call_method(&keplr, "enable", &[chain_id.into()]).await?;
+    let key = call_method(&keplr, "getKey", &[chain_id.into()]).await?;
+
+    let pubkey = Uint8Array::new(&get(&key, "pubKey")?).to_vec();
+    let pubkey = pubkey
+        .try_into()
+        .map_err(|_| Error::Signer("Invalid Keplr public key".into()))?;
+    let address = get(&key, "bech32Address")?
+        .as_string()
+        .ok_or_else(|| Error::Signer("Missing Keplr address".into()))?;
+
+    Ok((pubkey, address))
+}
+
+async fn ethereum_request(method: &str, params: JsValue) -> Result<JsValue> {
+    let ethereum = get(&js_sys::global(), "ethereum")?;
+    if ethereum.is_undefined() {
+        return Err(Error::Signer("No Ethereum wallet is installed".into()));
+    }
+
+    let args = Object::new();
+    set(&args, "method", &method.into())?;
+    set(&args, "params", &params)?;
+    call_method(&ethereum, "request", &[args.into()]).await
+}
+
+/// Returns the hex address of the first Ethereum account, along with its
+/// bytes.
+async fn eth_account() -> Result<(String, [u8; Address::LENGTH])> {
+    let accounts = ethereum_request("eth_requestAccounts", Array::new().into()).await?;
+    let account = get(&accounts, "0")?
+        .as_string()
+        .ok_or_else(|| Error::Signer("No Ethereum account is available".into()))?;
+    let bytes = hex::decode(account.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::Signer(format!("Invalid Ethereum address {}", account)))?;
+
+    Ok((account, bytes))
+}
+
+/// Recovers the compressed public key from a 65-byte `personal_sign`
+/// signature over the given message.
+fn recover_eth_pubkey(msg: &[u8], signature: &[u8]) -> Result<[u8; 33]> {
+    use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+    use sha3::{Digest, Keccak256};
+
+    if signature.len() != 65 {
+        return Err(Error::Signer("Invalid personal_sign signature".into()));
+    }
+
+    let mut hasher = Keccak256::new();
+    hasher.update(b"\x19Ethereum Signed Message:\n");
+    hasher.update(msg.len().to_string().as_bytes());
+    hasher.update(msg);
+    let msg = secp256k1::Message::from_slice(&hasher.finalize())?;
+
+    // the recovery ID is offset by 27 in Ethereum signatures
+    let recovery_id = RecoveryId::from_i32(signature[64] as i32 % 27)?;
+    let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)?;
+    let pubkey = secp256k1::Secp256k1::verification_only().recover_ecdsa(&msg, &signature)?;
+
+    Ok(pubkey.serialize())
+}
+
+fn sdk_signature(pubkey: [u8; 33], signature: &[u8], sig_type: &str) -> sdk::Signature {
+    use base64::Engine;
+    let b64 = base64::prelude::BASE64_STANDARD;
+    sdk::Signature {
+        pub_key: sdk::PubKey {
+            type_: SECP256K1_PUBKEY_TYPE.to_string(),
+            value: b64.encode(pubkey),
+        },
+        signature: b64.encode(signature),
+        r#type: Some(sig_type.to_string()),
+    }
+}
+
+fn decode_signature(signature: &str) -> Result<[u8; 64]> {
+    use base64::Engine;
+    base64::prelude::BASE64_STANDARD
+        .decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::Signer("Invalid signature".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::BroadcastMode;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn mock_rpc(result: &str) -> Function {
+        let body = format!(r#"{{"jsonrpc":"2.0","id":0,"result":{}}}"#, result);
+        Function::new_with_args(
+            "url, init",
+            &format!(
+                "globalThis.lastRequest = JSON.parse(init.body); \
+                 return Promise.resolve({{ text: async () => {:?} }});",
+                body
+            ),
+        )
+    }
+
+    #[wasm_bindgen_test]
+    async fn broadcast_through_mock_rpc() {
+        let fetch = JsFetch::new(mock_rpc(r#"{"code":0,"log":"","hash":"AB"}"#));
+        let client = RpcClient::new("http://rpc", fetch).broadcast_mode(BroadcastMode::Sync);
+
+        let receipt = client.broadcast(b"tx").await.unwrap();
+        assert_eq!(receipt.hash, super::super::receipt::tx_hash(b"tx"));
+
+        let req = get(&js_sys::global(), "lastRequest").unwrap();
+        assert_eq!(get(&req, "method").unwrap(), "broadcast_tx_sync");
+    }
+
+    #[wasm_bindgen_test]
+    async fn sign_with_key() {
+        let signer = WebSigner::from_secret_key(&"01".repeat(32)).unwrap();
+        let tx = UnsignedTx::new("foo".to_string(), Some(1), None, vec![1, 2, 3]);
+
+        let call = signer.sign_tx(&tx).await.unwrap();
+        assert_eq!(call.address().unwrap(), signer.address().await.unwrap());
+
+        let sign_doc = sdk::SignDoc {
+            account_number: "0".to_string(),
+            chain_id: "foo".to_string(),
+            fee: sdk::Fee {
+                amount: vec![],
+                gas: "0".to_string(),
+            },
+            memo: String::new(),
+            msgs: vec![],
+            sequence: "1".to_string(),
+        };
+        let signature = signer.sign_doc(&sign_doc).await.unwrap();
+        assert_eq!(signature.r#type.as_deref(), Some("sdk"));
+    }
+}
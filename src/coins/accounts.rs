@@ -2,10 +2,14 @@ use crate::coins::{Address, Amount, Coin, Give, Symbol, Take};
 use crate::collections::map::Iter as MapIter;
 use crate::collections::Map;
 use crate::context::GetContext;
+use crate::events::{emit, Event};
+use crate::genesis::Genesis;
 use crate::orga;
 use crate::plugins::Paid;
 use crate::plugins::Signer;
 use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 #[orga]
 pub struct Accounts<S: Symbol> {
@@ -14,6 +18,18 @@ pub struct Accounts<S: Symbol> {
     accounts: Map<Address, Coin<S>>,
 }
 
+/// Emitted by [Accounts::transfer].
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+#[event(kind = "transfer")]
+pub struct TransferEvent {
+    #[event(index)]
+    pub sender: Address,
+    #[event(index)]
+    pub recipient: Address,
+    pub amount: Amount,
+    pub denom: String,
+}
+
 #[orga]
 impl<S: Symbol> Accounts<S> {
     pub fn iter(&self) -> Result<MapIter<Address, Coin<S>>> {
@@ -30,6 +46,13 @@ impl<S: Symbol> Accounts<S> {
         let mut receiver = self.accounts.entry(to)?.or_insert_default()?;
         receiver.give(taken_coins)?;
 
+        emit(&TransferEvent {
+            sender: signer,
+            recipient: to,
+            amount,
+            denom: S::NAME.to_string(),
+        });
+
         Ok(())
     }
 
@@ -128,3 +151,122 @@ impl<S: Symbol> Accounts<S> {
         account.take(amount)
     }
 }
+
+/// The genesis config of [Accounts]. Balances are minted.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AccountsGenesis {
+    #[serde(default)]
+    pub balances: Vec<GenesisBalance>,
+    #[serde(default)]
+    pub transfers_allowed: bool,
+    #[serde(default)]
+    pub transfer_exceptions: Vec<Address>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenesisBalance {
+    pub address: Address,
+    pub amount: Amount,
+}
+
+impl<S: Symbol> Genesis for Accounts<S> {
+    type Config = AccountsGenesis;
+
+    fn validate_genesis(config: &AccountsGenesis, _chain_id: &str) -> Result<()> {
+        let mut addresses = HashSet::new();
+        let mut total = Amount::new(0);
+        for balance in config.balances.iter() {
+            if !addresses.insert(balance.address) {
+                return Err(Error::Genesis(format!(
+                    "Duplicate genesis balance for {}",
+                    balance.address
+                )));
+            }
+            if balance.amount == 0 {
+                return Err(Error::Genesis(format!(
+                    "Genesis balance for {} is zero",
+                    balance.address
+                )));
+            }
+            total = (total + balance.amount).result()?;
+        }
+
+        let mut exceptions = HashSet::new();
+        for address in config.transfer_exceptions.iter() {
+            if !exceptions.insert(*address) {
+                return Err(Error::Genesis(format!(
+                    "Duplicate transfer exception for {}",
+                    address
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_genesis(&mut self, config: AccountsGenesis) -> Result<()> {
+        for GenesisBalance { address, amount } in config.balances {
+            self.deposit(address, Coin::mint(amount))?;
+        }
+
+        self.allow_transfers(config.transfers_allowed);
+        for address in config.transfer_exceptions {
+            self.add_transfer_exception(address)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis::section;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+        const NAME: &'static str = "SIMP";
+    }
+
+    #[test]
+    fn genesis() -> Result<()> {
+        let alice = Address::from_pubkey([2; 33]);
+        let bob = Address::from_pubkey([3; 33]);
+        let app_state = serde_json::json!({
+            "accounts": {
+                "balances": [
+                    { "address": alice.to_string(), "amount": 100 },
+                    { "address": bob.to_string(), "amount": 50 },
+                ],
+                "transfer_exceptions": [alice.to_string()],
+            }
+        });
+
+        let config: AccountsGenesis =
+            section(&serde_json::to_vec(&app_state)?, "accounts")?.unwrap();
+        Accounts::<Simp>::validate_genesis(&config, "test")?;
+
+        let mut accounts = Accounts::<Simp>::default();
+        accounts.apply_genesis(config.clone())?;
+        assert_eq!(accounts.balance(alice)?, 100);
+        assert_eq!(accounts.balance(bob)?, 50);
+        assert!(!accounts.transfers_allowed);
+        assert!(accounts.transfer_exceptions.contains_key(alice)?);
+
+        let mut duplicate = config.clone();
+        duplicate.balances.push(GenesisBalance {
+            address: bob,
+            amount: 1.into(),
+        });
+        assert!(Accounts::<Simp>::validate_genesis(&duplicate, "test").is_err());
+
+        let mut zero = config;
+        zero.balances[0].amount = 0.into();
+        assert!(Accounts::<Simp>::validate_genesis(&zero, "test").is_err());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 use super::{Amount, Coin, Decimal, Symbol};
 use crate::context::GetContext;
+use crate::genesis::Genesis;
 use crate::orga;
 use crate::plugins::Time;
 use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 use std::time::Duration;
 
@@ -103,6 +105,56 @@ pub struct FaucetOptions {
     pub start_seconds: i64,
 }
 
+/// The genesis config of [Faucet], see [FaucetOptions].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FaucetGenesis {
+    pub num_periods: u32,
+    pub period_length_seconds: u64,
+    pub total_coins: Amount,
+    pub period_decay: Decimal,
+    pub start_seconds: i64,
+}
+
+impl From<FaucetGenesis> for FaucetOptions {
+    fn from(config: FaucetGenesis) -> Self {
+        Self {
+            num_periods: config.num_periods,
+            period_length: Duration::from_secs(config.period_length_seconds),
+            total_coins: config.total_coins,
+            period_decay: config.period_decay,
+            start_seconds: config.start_seconds,
+        }
+    }
+}
+
+impl<S: Symbol> Genesis for Faucet<S> {
+    type Config = FaucetGenesis;
+
+    fn validate_genesis(config: &FaucetGenesis, _chain_id: &str) -> Result<()> {
+        if config.num_periods == 0 {
+            return Err(Error::Genesis(
+                "Faucet must have at least one period".into(),
+            ));
+        }
+        if config.period_length_seconds == 0 {
+            return Err(Error::Genesis(
+                "Faucet period length must be positive".into(),
+            ));
+        }
+        if config.period_decay <= Decimal::zero() {
+            return Err(Error::Genesis(
+                "Faucet period decay must be positive".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn apply_genesis(&mut self, config: FaucetGenesis) -> Result<()> {
+        self.configure(config.into())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -202,4 +254,35 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    #[serial]
+    fn genesis() -> Result<()> {
+        let config: FaucetGenesis = serde_json::from_value(serde_json::json!({
+            "num_periods": 2,
+            "period_length_seconds": 10,
+            "total_coins": 300,
+            "period_decay": "0.5",
+            "start_seconds": 0,
+        }))?;
+        Faucet::<Simp>::validate_genesis(&config, "test")?;
+
+        let mut faucet: Faucet<Simp> = Faucet::default();
+        faucet.apply_genesis(config.clone())?;
+        Context::add(Time::from_seconds(25));
+        assert_eq!(faucet.mint()?.amount, 300);
+
+        let invalid = FaucetGenesis {
+            num_periods: 0,
+            ..config.clone()
+        };
+        assert!(Faucet::<Simp>::validate_genesis(&invalid, "test").is_err());
+        let invalid = FaucetGenesis {
+            period_length_seconds: 0,
+            ..config
+        };
+        assert!(Faucet::<Simp>::validate_genesis(&invalid, "test").is_err());
+
+        Ok(())
+    }
 }
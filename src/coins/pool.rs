@@ -131,6 +131,7 @@ where
             parent_num_tokens: &mut self.contributions,
             parent_shares_issued: &mut self.shares_issued,
             drop_errored: &mut self.drop_errored,
+            settled: false,
             entry: child,
             initial_balance,
             _symbol: PhantomData,
@@ -274,75 +275,64 @@ where
     parent_num_tokens: &'a mut Decimal,
     parent_shares_issued: &'a mut Decimal,
     drop_errored: &'a mut bool,
+    settled: bool,
     entry: MapChildMut<'a, K, RefCell<Entry<V>>>,
     initial_balance: Decimal,
     _symbol: PhantomData<S>,
 }
 
-impl<'a, K, V, S> Drop for ChildMut<'a, K, V, S>
+impl<'a, K, V, S> ChildMut<'a, K, V, S>
 where
     S: Symbol,
     K: Encode + Clone + 'static,
     V: State + Balance<S, Decimal>,
 {
-    fn drop(&mut self) {
-        let start_balance = self.initial_balance;
-        let end_balance = match self.entry.get_mut().balance() {
-            Ok(bal) => bal,
-            Err(_err) => {
-                *self.drop_errored = true;
-                return;
-            }
-        };
-        let balance_change: Decimal = match (end_balance - start_balance).result() {
-            Ok(bal) => bal,
-            Err(_err) => {
-                *self.drop_errored = true;
-                return;
-            }
-        };
+    /// Applies the change in the entry's balance to its shares and the pool's
+    /// totals, returning any error instead of leaving the pool in an errored
+    /// state as dropping the child does. Run inside
+    /// [`Store::transact`](crate::store::Store::transact), a failing call can
+    /// then be rolled back without affecting later calls.
+    pub fn finish(mut self) -> Result<()> {
+        self.settled = true;
+        self.settle()
+    }
 
-        if !balance_change.value.is_zero() {
-            let new_shares = if self.parent_num_tokens.value.is_zero() {
-                balance_change
-            } else {
-                match (*self.parent_shares_issued * balance_change / *self.parent_num_tokens)
-                    .result()
-                {
-                    Ok(value) => value,
-                    Err(_err) => {
-                        *self.drop_errored = true;
-                        return;
-                    }
-                }
-            };
-
-            let entry = self.entry.get_mut();
-
-            entry.shares = match (entry.shares + new_shares).result() {
-                Ok(value) => value,
-                Err(_err) => {
-                    *self.drop_errored = true;
-                    return;
-                }
-            };
-
-            *self.parent_num_tokens = match (*self.parent_num_tokens + balance_change).result() {
-                Ok(value) => value,
-                Err(_err) => {
-                    *self.drop_errored = true;
-                    return;
-                }
-            };
-
-            *self.parent_shares_issued = match (*self.parent_shares_issued + new_shares).result() {
-                Ok(value) => value,
-                Err(_err) => {
-                    *self.drop_errored = true;
-                    return;
-                }
-            };
+    fn settle(&mut self) -> Result<()> {
+        let end_balance = self.entry.get_mut().balance()?;
+        let balance_change: Decimal = (end_balance - self.initial_balance).result()?;
+        if balance_change.value.is_zero() {
+            return Ok(());
+        }
+
+        let new_shares = if self.parent_num_tokens.value.is_zero() {
+            balance_change
+        } else {
+            (*self.parent_shares_issued * balance_change / *self.parent_num_tokens).result()?
         };
+
+        let entry = self.entry.get_mut();
+        let shares = (entry.shares + new_shares).result()?;
+        let num_tokens = (*self.parent_num_tokens + balance_change).result()?;
+        let shares_issued = (*self.parent_shares_issued + new_shares).result()?;
+
+        entry.shares = shares;
+        *self.parent_num_tokens = num_tokens;
+        *self.parent_shares_issued = shares_issued;
+
+        Ok(())
+    }
+}
+
+impl<'a, K, V, S> Drop for ChildMut<'a, K, V, S>
+where
+    S: Symbol,
+    K: Encode + Clone + 'static,
+    V: State + Balance<S, Decimal>,
+{
+    fn drop(&mut self) {
+        if !self.settled && self.settle().is_err() {
+            *self.drop_errored = true;
+        }
     }
 }
 
@@ -520,6 +510,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn finish_in_transaction() -> Result<()> {
+        use crate::store::Store;
+
+        let store = Store::with_map_store();
+        let mut pool: Pool<Address, SimpAccount, Simp> = Default::default();
+        pool.attach(store.clone())?;
+        let alice = Address::from_pubkey([0; 33]);
+
+        let mut child = pool.get_mut(alice)?;
+        child.deposit_locked(50)?;
+        child.finish()?;
+        assert_eq!(pool.contributions, 50);
+
+        let res: Result<()> = store.transact(&mut pool, |pool| {
+            let mut child = pool.get_mut(alice)?;
+            child.deposit_locked(25)?;
+            child.finish()?;
+            Err(Error::App("failure".into()))
+        });
+        assert!(res.is_err());
+        assert_eq!(pool.contributions, 50);
+        assert_eq!(pool.get_mut(alice)?.balance()?, 50);
+
+        Ok(())
+    }
+
     #[test]
     fn emptied_pool() -> Result<()> {
         use crate::coins::Take;
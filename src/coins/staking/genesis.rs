@@ -0,0 +1,208 @@
+//! Initializing [Staking] from a genesis file, and the gentx flow for genesis
+//! validators.
+//!
+//! Each genesis validator signs a [Declaration] offline with [gentx], and
+//! sends the resulting JSON file to the coordinator of the launch. The
+//! coordinator collects them with [StakingGenesis::collect_gentxs] and writes
+//! the config into the `app_state` of the genesis file. At `InitChain`, each
+//! gentx is verified and declares its signer as a validator, with its
+//! self-delegation minted, and the resulting validator set is returned to
+//! Tendermint in place of the genesis file's validators.
+
+use super::{Declaration, Staking};
+use crate::client::{SignedTx, UnsignedTx, Wallet};
+use crate::coins::{Address, Coin, Decimal, Symbol};
+use crate::encoding::{Decode, Encode};
+use crate::genesis::Genesis;
+use crate::plugins::EndBlockCtx;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The genesis config of [Staking]. Omitted parameters keep their defaults.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StakingGenesis {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_validators: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_self_delegation_min: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unbonding_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_offline_blocks: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slash_fraction_double_sign: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slash_fraction_downtime: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downtime_jail_seconds: Option<u64>,
+    /// Signed validator declarations, see [gentx].
+    #[serde(default)]
+    pub gentxs: Vec<SignedTx>,
+}
+
+/// Signs a declaration of the wallet's address as a genesis validator of the
+/// chain with the given ID. This does not require network access.
+pub fn gentx<W: Wallet>(chain_id: &str, declaration: &Declaration, wallet: &W) -> Result<SignedTx> {
+    let signer = wallet
+        .address()?
+        .ok_or_else(|| Error::Genesis("Wallet has no address".into()))?;
+
+    UnsignedTx::new(
+        chain_id.to_string(),
+        None,
+        Some(signer),
+        declaration.encode()?,
+    )
+    .sign(wallet)
+}
+
+/// Verifies a gentx for the chain with the given ID, returning the validator
+/// address and its declaration.
+pub fn verify_gentx(gentx: &SignedTx, chain_id: &str) -> Result<(Address, Declaration)> {
+    if gentx.tx.chain_id != chain_id {
+        return Err(Error::Genesis(format!(
+            "Gentx was signed for chain {}, expected {}",
+            gentx.tx.chain_id, chain_id
+        )));
+    }
+    if gentx.tx.nonce.is_some() {
+        return Err(Error::Genesis("Gentx must not have a nonce".into()));
+    }
+
+    let signer = match gentx.verify()?.as_slice() {
+        [signer] => *signer,
+        _ => {
+            return Err(Error::Genesis(
+                "Gentx must have exactly one signature".into(),
+            ))
+        }
+    };
+    if gentx.tx.signer != Some(signer) {
+        return Err(Error::Genesis(format!(
+            "Gentx was not signed by its validator {}",
+            signer
+        )));
+    }
+
+    let declaration = Declaration::decode(gentx.tx.call_bytes.as_slice())?;
+    if declaration.amount == 0 {
+        return Err(Error::Genesis(format!(
+            "Gentx for {} has no self-delegation",
+            signer
+        )));
+    }
+
+    Ok((signer, declaration))
+}
+
+impl StakingGenesis {
+    /// Verifies and adds the gentxs in the JSON files of the given directory,
+    /// in order of their file names.
+    pub fn collect_gentxs(&mut self, dir: &Path, chain_id: &str) -> Result<()> {
+        let mut paths = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().map_or(false, |ext| ext == "json"));
+        paths.sort();
+
+        for path in paths {
+            let gentx: SignedTx = serde_json::from_slice(&std::fs::read(&path)?)?;
+            verify_gentx(&gentx, chain_id).map_err(|err| {
+                Error::Genesis(format!("Invalid gentx {}: {}", path.display(), err))
+            })?;
+            self.gentxs.push(gentx);
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Symbol> Genesis for Staking<S> {
+    type Config = StakingGenesis;
+
+    fn validate_genesis(config: &StakingGenesis, chain_id: &str) -> Result<()> {
+        let fractions = [
+            config.slash_fraction_double_sign,
+            config.slash_fraction_downtime,
+        ];
+        for fraction in fractions.into_iter().flatten() {
+            if fraction < Decimal::zero() || fraction > Decimal::one() {
+                return Err(Error::Genesis(
+                    "Slash fractions must be between 0 and 1".into(),
+                ));
+            }
+        }
+        if config.max_validators == Some(0) {
+            return Err(Error::Genesis("Max validators must be positive".into()));
+        }
+
+        let min_self_delegation_min = config.min_self_delegation_min.unwrap_or_default();
+        let mut addresses = HashSet::new();
+        let mut consensus_keys = HashSet::new();
+        for gentx in config.gentxs.iter() {
+            let (address, declaration) = verify_gentx(gentx, chain_id)?;
+            if !addresses.insert(address) {
+                return Err(Error::Genesis(format!("Duplicate gentx for {}", address)));
+            }
+            if !consensus_keys.insert(declaration.consensus_key) {
+                return Err(Error::Genesis(format!(
+                    "Gentx for {} reuses a consensus key",
+                    address
+                )));
+            }
+            if declaration.min_self_delegation < min_self_delegation_min
+                || declaration.amount < declaration.min_self_delegation
+            {
+                return Err(Error::Genesis(format!(
+                    "Gentx for {} has insufficient self-delegation",
+                    address
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_genesis(&mut self, config: StakingGenesis) -> Result<()> {
+        if let Some(max_validators) = config.max_validators {
+            self.max_validators = max_validators;
+        }
+        if let Some(min_self_delegation_min) = config.min_self_delegation_min {
+            self.min_self_delegation_min = min_self_delegation_min;
+        }
+        if let Some(unbonding_seconds) = config.unbonding_seconds {
+            self.unbonding_seconds = unbonding_seconds;
+        }
+        if let Some(max_offline_blocks) = config.max_offline_blocks {
+            self.max_offline_blocks = max_offline_blocks;
+        }
+        if let Some(fraction) = config.slash_fraction_double_sign {
+            self.slash_fraction_double_sign = fraction;
+        }
+        if let Some(fraction) = config.slash_fraction_downtime {
+            self.slash_fraction_downtime = fraction;
+        }
+        if let Some(downtime_jail_seconds) = config.downtime_jail_seconds {
+            self.downtime_jail_seconds = downtime_jail_seconds;
+        }
+
+        if config.gentxs.is_empty() {
+            return Ok(());
+        }
+
+        for gentx in config.gentxs {
+            let address = gentx
+                .tx
+                .signer
+                .ok_or_else(|| Error::Genesis("Gentx has no signer".into()))?;
+            let declaration = Declaration::decode(gentx.tx.call_bytes.as_slice())?;
+            let amount = declaration.amount;
+            self.declare(address, declaration, Coin::mint(amount))?;
+        }
+
+        // Report the initial validator set to Tendermint
+        self.end_block_step(&EndBlockCtx::default())
+    }
+}
@@ -4,6 +4,7 @@ use crate::abci::{BeginBlock, EndBlock};
 use crate::collections::{Deque, Entry, EntryMap, Map};
 use crate::context::GetContext;
 use crate::encoding::{Decode, Encode};
+use crate::events::{emit, Event};
 use crate::migrate::{Migrate, MigrateFrom};
 use crate::orga;
 use crate::plugins::{BeginBlockCtx, EndBlockCtx, Validators};
@@ -18,6 +19,9 @@ use tendermint_proto::v0_34::abci::EvidenceType;
 mod delegator;
 pub use delegator::*;
 
+mod genesis;
+pub use genesis::*;
+
 mod validator;
 pub use validator::*;
 
@@ -108,6 +112,29 @@ impl ValidatorPowerEntry {
     }
 }
 
+/// Emitted when stake is delegated to a validator.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(kind = "delegate")]
+pub struct DelegateEvent {
+    #[event(index)]
+    pub validator: Address,
+    #[event(index)]
+    pub delegator: Address,
+    pub amount: Amount,
+}
+
+/// Emitted when a validator is slashed and jailed, for `"downtime"` or
+/// `"double_sign"`.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(kind = "slash")]
+pub struct SlashEvent {
+    #[event(index)]
+    pub validator: Address,
+    #[event(index)]
+    pub reason: String,
+    pub fraction: Decimal,
+}
+
 impl<S: Symbol> EndBlock for Staking<S> {
     fn end_block(&mut self, ctx: &EndBlockCtx) -> Result<()> {
         self.end_block_step(ctx)
@@ -212,6 +239,11 @@ impl<S: Symbol> Staking<S> {
                 ));
             }
             let mut delegator = validator.get_mut(delegator_address)?;
+            emit(&DelegateEvent {
+                validator: val_address,
+                delegator: delegator_address,
+                amount: coins.amount,
+            });
             delegator.add_stake(coins)?;
         }
         self.index_delegation(val_address, delegator_address)?;
@@ -378,6 +410,11 @@ impl<S: Symbol> Staking<S> {
             validator.jail_for_seconds(self.downtime_jail_seconds)?;
             validator.slash(self.slash_fraction_downtime, true)?;
         }
+        emit(&SlashEvent {
+            validator: val_address,
+            reason: "downtime".into(),
+            fraction: self.slash_fraction_downtime,
+        });
         self.update_vp(val_address)
     }
 
@@ -387,6 +424,11 @@ impl<S: Symbol> Staking<S> {
             validator.jail_forever();
             validator.slash(self.slash_fraction_double_sign, false)?
         };
+        emit(&SlashEvent {
+            validator: val_address,
+            reason: "double_sign".into(),
+            fraction: self.slash_fraction_double_sign,
+        });
         let multiplier = (Decimal::one() - self.slash_fraction_double_sign)?;
         for entry in redelegations.iter() {
             let del_address = entry.delegator_address;
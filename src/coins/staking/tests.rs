@@ -1572,3 +1572,128 @@ fn alt_coin_rewards() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "abci")]
+#[test]
+#[serial]
+fn events() -> Result<()> {
+    use crate::client::receipt::Event as ReceiptEvent;
+    use crate::plugins::Events;
+
+    let mut staking = setup_state()?;
+    let val_0 = Address::from_pubkey([0; 33]);
+    let staker = Address::from_pubkey([1; 33]);
+
+    staking.declare(
+        val_0,
+        Declaration {
+            consensus_key: [0; 32],
+            commission: Commission {
+                rate: dec!(0.0).into(),
+                max: dec!(1.0).into(),
+                max_change: dec!(0.1).into(),
+            },
+            amount: Amount::new(100),
+            min_self_delegation: 1.into(),
+            validator_info: vec![].try_into()?,
+        },
+        Amount::new(100).into(),
+    )?;
+
+    Context::add(Events::default());
+    staking.delegate(val_0, staker, 100.into())?;
+    staking.punish_downtime(val_0)?;
+
+    let events: Vec<ReceiptEvent> = Context::resolve::<Events>()
+        .unwrap()
+        .events()
+        .iter()
+        .cloned()
+        .map(Into::into)
+        .collect();
+    Context::remove::<Events>();
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        events[0].decode::<DelegateEvent>()?,
+        Some(DelegateEvent {
+            validator: val_0,
+            delegator: staker,
+            amount: 100.into(),
+        })
+    );
+    let slash = events[1].decode::<SlashEvent>()?.unwrap();
+    assert_eq!(slash.validator, val_0);
+    assert_eq!(slash.reason, "downtime");
+
+    Ok(())
+}
+
+#[cfg(feature = "abci")]
+#[test]
+#[serial]
+fn genesis_gentxs() -> Result<()> {
+    use crate::client::wallet::DerivedKey;
+    use crate::genesis::Genesis;
+
+    let mut staking = setup_state()?;
+    let declaration = |consensus_key, amount: u64| Declaration {
+        consensus_key,
+        commission: Commission {
+            rate: dec!(0.1).into(),
+            max: dec!(0.5).into(),
+            max_change: dec!(0.1).into(),
+        },
+        min_self_delegation: 1.into(),
+        amount: amount.into(),
+        validator_info: vec![].try_into().unwrap(),
+    };
+
+    let alice = DerivedKey::new(b"alice")?;
+    let bob = DerivedKey::new(b"bob")?;
+    let dir = tempdir::TempDir::new("orga-gentxs")?;
+    for (name, wallet, consensus_key, amount) in
+        [("alice", &alice, [4; 32], 100), ("bob", &bob, [5; 32], 300)]
+    {
+        let gentx = gentx("test-chain", &declaration(consensus_key, amount), wallet)?;
+        std::fs::write(
+            dir.path().join(format!("{}.json", name)),
+            serde_json::to_vec(&gentx)?,
+        )?;
+    }
+
+    let mut config = StakingGenesis {
+        max_validators: Some(10),
+        unbonding_seconds: Some(20),
+        ..Default::default()
+    };
+    config.collect_gentxs(dir.path(), "test-chain")?;
+    assert_eq!(config.gentxs.len(), 2);
+    assert!(StakingGenesis::default()
+        .collect_gentxs(dir.path(), "other-chain")
+        .is_err());
+
+    let config: StakingGenesis = serde_json::from_value(serde_json::to_value(&config)?)?;
+    Staking::<Simp>::validate_genesis(&config, "test-chain")?;
+    assert!(Staking::<Simp>::validate_genesis(&config, "other-chain").is_err());
+
+    let mut duplicate = config.clone();
+    duplicate.gentxs.push(config.gentxs[0].clone());
+    assert!(Staking::<Simp>::validate_genesis(&duplicate, "test-chain").is_err());
+
+    let mut forged = config.clone();
+    forged.gentxs[1].tx.signer = Some(alice.address());
+    assert!(Staking::<Simp>::validate_genesis(&forged, "test-chain").is_err());
+
+    staking.apply_genesis(config)?;
+    assert_eq!(staking.max_validators, 10);
+    assert_eq!(staking.unbonding_seconds, 20);
+    assert_eq!(staking.staked()?, 400);
+    assert_eq!(staking.consensus_key(bob.address())?, [5; 32]);
+
+    let ctx = Context::resolve::<Validators>().unwrap();
+    assert_eq!(ctx.updates.get(&[4; 32]).unwrap().power, 100);
+    assert_eq!(ctx.updates.get(&[5; 32]).unwrap().power, 300);
+
+    Ok(())
+}
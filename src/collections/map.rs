@@ -672,7 +672,7 @@ struct StoreNextIter<'a, S: Default + Read, K: Decode> {
 }
 
 // TODO: dedupe this with the same code in Store
-fn increment_bytes(mut bytes: Vec<u8>) -> Vec<u8> {
+pub(crate) fn increment_bytes(mut bytes: Vec<u8>) -> Vec<u8> {
     for byte in bytes.iter_mut().rev() {
         if *byte == 255 {
             *byte = 0;
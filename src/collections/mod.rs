@@ -6,10 +6,12 @@ pub use crate::macros::{Entry, Next};
 pub mod deque;
 pub mod entry_map;
 pub mod map;
+pub mod priority_queue;
 
 pub use deque::Deque;
 pub use entry_map::EntryMap;
 pub use map::Map;
+pub use priority_queue::PriorityQueue;
 
 pub use map::{ChildMut, Ref};
 
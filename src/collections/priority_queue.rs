@@ -0,0 +1,283 @@
+use super::map::{Map, ReadOnly, Ref};
+use crate::encoding::{Decode, Encode, Terminated};
+use crate::orga;
+use crate::state::State;
+use crate::store::Store;
+use crate::{Error, Result};
+
+/// An opaque identifier for an item in a [PriorityQueue], returned when the
+/// item is pushed and used to remove it before it reaches the front of the
+/// queue.
+pub type Handle = u64;
+
+/// A queue which yields its items in order of their keys, lowest first.
+///
+/// Items are stored in a [Map] keyed by `(key, handle)`, so ordering follows
+/// the binary encoding of the key. The key type's encoding must therefore
+/// preserve its `Ord` ordering (as is the case for unsigned integers and
+/// fixed-size byte arrays). Items with equal keys are yielded in the order they
+/// were pushed.
+///
+/// A second map from handle to key allows items to be removed by handle
+/// without scanning the queue.
+#[orga(skip(Default))]
+pub struct PriorityQueue<K, V>
+where
+    K: Ord + Encode + Decode + Terminated + State + Clone + Send + Sync + 'static,
+    V: State,
+{
+    next_handle: Handle,
+    len: u64,
+    entries: Map<(K, Handle), V>,
+    handles: Map<Handle, K>,
+}
+
+impl<K, V> Default for PriorityQueue<K, V>
+where
+    K: Ord + Encode + Decode + Terminated + State + Clone + Send + Sync + 'static,
+    V: State,
+{
+    fn default() -> Self {
+        Self {
+            next_handle: 0,
+            len: 0,
+            entries: Map::default(),
+            handles: Map::default(),
+        }
+    }
+}
+
+impl<K, V> std::fmt::Debug for PriorityQueue<K, V>
+where
+    K: Ord + Encode + Decode + Terminated + State + Clone + Send + Sync + 'static,
+    V: State,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PriorityQueue")
+            .field("len", &self.len)
+            .field("next_handle", &self.next_handle)
+            .finish()
+    }
+}
+
+impl<K, V> PriorityQueue<K, V>
+where
+    K: Ord + Encode + Decode + Terminated + State + Clone + Send + Sync + 'static,
+    V: State,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_store(store: Store) -> Result<Self> {
+        let mut queue = Self::default();
+        queue.attach(store)?;
+        Ok(queue)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts a value with the given key, returning a handle which can later
+    /// be passed to [PriorityQueue::remove].
+    pub fn push(&mut self, key: K, value: V) -> Result<Handle> {
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.checked_add(1).ok_or(Error::Overflow)?;
+
+        self.entries.insert((key.clone(), handle), value)?;
+        self.handles.insert(handle, key)?;
+        self.len += 1;
+
+        Ok(handle)
+    }
+
+    /// Returns the key, handle and value of the item with the lowest key, or
+    /// `None` if the queue is empty.
+    pub fn peek(&self) -> Result<Option<(K, Handle, Ref<V>)>> {
+        self.entries
+            .iter()?
+            .next()
+            .transpose()?
+            .map(|(entry_key, value)| {
+                let (key, handle) = (*entry_key).clone();
+                Ok((key, handle, value))
+            })
+            .transpose()
+    }
+
+    /// Gets the value for the given handle, or `None` if it is not in the
+    /// queue.
+    pub fn get(&self, handle: Handle) -> Result<Option<(K, Ref<V>)>> {
+        let key = match self.handles.get(handle)? {
+            Some(key) => (*key).clone(),
+            None => return Ok(None),
+        };
+
+        Ok(self
+            .entries
+            .get((key.clone(), handle))?
+            .map(|value| (key, value)))
+    }
+
+    /// Removes and returns the item with the lowest key, or `None` if the
+    /// queue is empty.
+    pub fn pop_min(&mut self) -> Result<Option<(K, ReadOnly<V>)>> {
+        let handle = match self.peek()? {
+            Some((_, handle, _)) => handle,
+            None => return Ok(None),
+        };
+
+        self.remove(handle)
+    }
+
+    /// Removes and returns all items with a key less than or equal to `bound`,
+    /// in queue order.
+    pub fn pop_until(&mut self, bound: &K) -> Result<Vec<(K, ReadOnly<V>)>> {
+        let mut popped = vec![];
+
+        loop {
+            let handle = match self.peek()? {
+                Some((key, handle, _)) if key <= *bound => handle,
+                _ => break,
+            };
+
+            if let Some(item) = self.remove(handle)? {
+                popped.push(item);
+            }
+        }
+
+        Ok(popped)
+    }
+
+    /// Removes the item with the given handle, returning its key and value, or
+    /// `None` if the handle is not in the queue.
+    pub fn remove(&mut self, handle: Handle) -> Result<Option<(K, ReadOnly<V>)>> {
+        let key = match self.handles.remove(handle)? {
+            Some(key) => key.into_inner(),
+            None => return Ok(None),
+        };
+
+        let value = self
+            .entries
+            .remove((key.clone(), handle))?
+            .ok_or_else(|| Error::App("Priority queue entry missing for handle".into()))?;
+        self.len -= 1;
+
+        Ok(Some((key, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{MapStore, Shared};
+
+    fn setup() -> (Store, PriorityQueue<u64, u32>) {
+        let store = Store::new(Shared::new(MapStore::new()).into());
+        let queue = PriorityQueue::with_store(store.clone()).unwrap();
+        (store, queue)
+    }
+
+    #[test]
+    fn pop_min_order() {
+        let (_, mut queue) = setup();
+
+        queue.push(3, 30).unwrap();
+        queue.push(1, 10).unwrap();
+        queue.push(2, 20).unwrap();
+        queue.push(1, 11).unwrap();
+        assert_eq!(queue.len(), 4);
+
+        let (key, value) = queue.pop_min().unwrap().unwrap();
+        assert_eq!((key, *value), (1, 10));
+        let (key, value) = queue.pop_min().unwrap().unwrap();
+        assert_eq!((key, *value), (1, 11));
+        let (key, value) = queue.pop_min().unwrap().unwrap();
+        assert_eq!((key, *value), (2, 20));
+        let (key, value) = queue.pop_min().unwrap().unwrap();
+        assert_eq!((key, *value), (3, 30));
+        assert!(queue.pop_min().unwrap().is_none());
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn peek() {
+        let (_, mut queue) = setup();
+        assert!(queue.peek().unwrap().is_none());
+
+        queue.push(5, 50).unwrap();
+        let handle = queue.push(2, 20).unwrap();
+
+        let (key, peeked_handle, value) = queue.peek().unwrap().unwrap();
+        assert_eq!(key, 2);
+        assert_eq!(peeked_handle, handle);
+        assert_eq!(*value, 20);
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn pop_until() {
+        let (_, mut queue) = setup();
+
+        for i in [4, 1, 3, 2, 5] {
+            queue.push(i, i as u32 * 10).unwrap();
+        }
+
+        let popped: Vec<_> = queue
+            .pop_until(&3)
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| (key, *value))
+            .collect();
+        assert_eq!(popped, vec![(1, 10), (2, 20), (3, 30)]);
+        assert_eq!(queue.len(), 2);
+
+        assert!(queue.pop_until(&3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn remove_by_handle() {
+        let (_, mut queue) = setup();
+
+        queue.push(1, 10).unwrap();
+        let handle = queue.push(2, 20).unwrap();
+        queue.push(3, 30).unwrap();
+
+        let (key, value) = queue.remove(handle).unwrap().unwrap();
+        assert_eq!((key, *value), (2, 20));
+        assert!(queue.remove(handle).unwrap().is_none());
+        assert!(queue.get(handle).unwrap().is_none());
+
+        let keys: Vec<_> = queue
+            .pop_until(&u64::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec![1, 3]);
+    }
+
+    #[test]
+    fn persists_through_flush() {
+        let (store, mut queue) = setup();
+
+        queue.push(2, 20).unwrap();
+        let handle = queue.push(1, 10).unwrap();
+
+        let mut bytes = vec![];
+        queue.flush(&mut bytes).unwrap();
+
+        let mut queue: PriorityQueue<u64, u32> =
+            PriorityQueue::load(store, &mut bytes.as_slice()).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(*queue.get(handle).unwrap().unwrap().1, 10);
+
+        let (key, value) = queue.pop_min().unwrap().unwrap();
+        assert_eq!((key, *value), (1, 10));
+    }
+}
@@ -12,8 +12,11 @@ use std::{
 };
 use wasm_bindgen::prelude::*;
 
+pub mod browse;
 mod builder;
 pub mod child;
+pub mod methods;
+pub mod schema;
 
 pub use crate::macros::Describe;
 pub use builder::Builder;
@@ -30,6 +33,8 @@ pub struct Descriptor {
     pub state_version: u32,
     children: Children,
     pub load: Option<LoadFn>,
+    pub to_json: Option<ToJsonFn>,
+    pub from_json: Option<FromJsonFn>,
     pub meta: Option<Box<Self>>,
 }
 
@@ -48,6 +53,70 @@ impl Descriptor {
         &self.children
     }
 
+    /// Maps a raw store key back to the path of fields it is stored under,
+    /// e.g. to show which part of the state a write touched.
+    ///
+    /// Named children are matched by their store prefix. The key of a dynamic
+    /// child (such as a `Map` entry) is decoded with the key type's
+    /// descriptor, and resolution continues into the entry's value. If the key
+    /// can't be decoded, the remaining bytes are returned as a single raw
+    /// [KeySegment::Entry].
+    pub fn resolve_key(&self, key: &[u8]) -> Vec<KeySegment> {
+        let mut path = vec![];
+        let mut desc = self;
+        let mut offset = 0;
+
+        while offset < key.len() {
+            match &desc.children {
+                Children::None => break,
+                Children::Named(children) => {
+                    let child = children.iter().find_map(|child| {
+                        let (prefix, start) = match &child.store_key {
+                            KeyOp::Append(prefix) => (prefix, offset),
+                            KeyOp::Absolute(prefix) => (prefix, 0),
+                        };
+                        key[start..]
+                            .starts_with(prefix)
+                            .then(|| (child, start + prefix.len()))
+                    });
+                    let Some((child, end)) = child else {
+                        break;
+                    };
+
+                    path.push(KeySegment::Field(child.name.clone()));
+                    desc = &child.desc;
+                    offset = end;
+                }
+                Children::Dynamic(child) => {
+                    let mut rest = &key[offset..];
+                    let decoded = child
+                        .key_desc
+                        .to_json
+                        .map(|to_json| to_json(Store::default(), &mut rest));
+                    let Some(Ok(value)) = decoded else {
+                        path.push(KeySegment::Entry {
+                            key_type: child.key_desc.type_name.clone(),
+                            bytes: key[offset..].to_vec(),
+                            key: None,
+                        });
+                        break;
+                    };
+
+                    let end = key.len() - rest.len();
+                    path.push(KeySegment::Entry {
+                        key_type: child.key_desc.type_name.clone(),
+                        bytes: key[offset..end].to_vec(),
+                        key: value,
+                    });
+                    desc = &child.value_desc;
+                    offset = end;
+                }
+            }
+        }
+
+        path
+    }
+
     // pub fn kv_descs(self) -> impl Iterator<Item = DynamicChild> {
     //     let (own, named) = match self.children {
     //         Children::None => (vec![], vec![]),
@@ -61,6 +130,8 @@ impl Descriptor {
 
 pub type LoadFn = fn(Store, &mut &[u8]) -> Result<()>;
 pub type ApplyQueryBytesFn = fn(Vec<u8>) -> Vec<u8>;
+pub type ToJsonFn = fn(Store, &mut &[u8]) -> Result<Option<serde_json::Value>>;
+pub type FromJsonFn = fn(serde_json::Value) -> Result<Vec<u8>>;
 
 #[derive(Clone, Debug, Default)]
 pub enum Children {
@@ -100,6 +171,41 @@ impl DynamicChild {
     }
 }
 
+/// A step in the path returned by [Descriptor::resolve_key].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeySegment {
+    Field(String),
+    /// An entry of a dynamic child, with its raw key bytes and the key decoded
+    /// as JSON, if the key type supports it.
+    Entry {
+        key_type: String,
+        bytes: Vec<u8>,
+        key: Option<serde_json::Value>,
+    },
+}
+
+impl Display for KeySegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySegment::Field(name) => write!(f, "{}", name),
+            KeySegment::Entry {
+                key: Some(serde_json::Value::String(key)),
+                ..
+            } => write!(f, "{}", key),
+            KeySegment::Entry { key: Some(key), .. } => write!(f, "{}", key),
+            KeySegment::Entry { bytes, .. } => write!(f, "{}", hex::encode(bytes)),
+        }
+    }
+}
+
+/// Joins a resolved key path into a `/`-separated string.
+pub fn format_path(path: &[KeySegment]) -> String {
+    path.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum KeyOp {
     Append(Vec<u8>),
@@ -239,6 +345,25 @@ trait MaybeToJson {
 
 type WasmResult<T> = std::result::Result<T, JsValue>;
 
+trait MaybeFromJson {
+    fn maybe_from_json(value: serde_json::Value) -> Result<Vec<u8>>;
+}
+
+impl<T> MaybeFromJson for T {
+    default fn maybe_from_json(_value: serde_json::Value) -> Result<Vec<u8>> {
+        Err(Error::Downcast(format!(
+            "Cannot read {} from JSON",
+            std::any::type_name::<T>()
+        )))
+    }
+}
+
+impl<T: serde::de::DeserializeOwned + Encode> MaybeFromJson for T {
+    fn maybe_from_json(value: serde_json::Value) -> Result<Vec<u8>> {
+        Ok(serde_json::from_value::<T>(value)?.encode()?)
+    }
+}
+
 struct ToJsonWrapper<T>(T);
 
 impl<T> MaybeToJson for ToJsonWrapper<T> {
@@ -413,6 +538,62 @@ tuple_impl!(A, B, C, D, E, F, G, H, I; J; 0, 1, 2, 3, 4, 5, 6, 7, 8; 9);
 tuple_impl!(A, B, C, D, E, F, G, H, I, J; K; 0, 1, 2, 3, 4, 5, 6, 7, 8, 9; 10);
 tuple_impl!(A, B, C, D, E, F, G, H, I, J, K; L; 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10; 11);
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::Map;
+    use crate::orga;
+
+    #[orga]
+    pub struct Inner {
+        a: u32,
+        b: Map<u32, u32>,
+        c: Map<u32, Map<u8, u32>>,
+    }
+
+    #[orga]
+    pub struct Outer {
+        x: u64,
+        inner: Inner,
+    }
+
+    #[test]
+    fn resolve_key() {
+        let desc = Outer::describe();
+
+        let path = desc.resolve_key(&[1, 1, 0, 0, 0, 7]);
+        assert_eq!(format_path(&path), "inner/b/7");
+        assert_eq!(
+            path[2],
+            KeySegment::Entry {
+                key_type: "u32".to_string(),
+                bytes: vec![0, 0, 0, 7],
+                key: Some(7.into()),
+            }
+        );
+
+        // Nested entries are resolved through the entry's value
+        let path = desc.resolve_key(&[1, 2, 0, 0, 0, 7, 3]);
+        assert_eq!(format_path(&path), "inner/c/7/3");
+
+        // Keys too short to decode are kept as raw bytes
+        let path = desc.resolve_key(&[1, 1, 0, 7]);
+        assert_eq!(format_path(&path), "inner/b/0007");
+        assert_eq!(
+            path[2],
+            KeySegment::Entry {
+                key_type: "u32".to_string(),
+                bytes: vec![0, 7],
+                key: None,
+            }
+        );
+
+        assert_eq!(format_path(&desc.resolve_key(&[1, 0])), "inner/a");
+        assert!(desc.resolve_key(&[]).is_empty());
+        assert!(desc.resolve_key(&[9]).is_empty());
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use serde::{Deserialize, Serialize};
@@ -0,0 +1,388 @@
+//! Reading state as JSON by walking descriptors.
+//!
+//! A path such as `staking/validators/<address>/delegators` is resolved one
+//! segment at a time: named children are matched by name, and the entries of
+//! dynamic children (e.g. a `Map`) are matched by key, given as JSON (with
+//! unquoted strings accepted) or as `0x`-prefixed hex of the encoded key.
+//!
+//! This only requires the app's [Descriptor] and a [Store], so it works the
+//! same over a node's own store and over a partial store built from proofs, in
+//! which case reads of unknown keys error with the key to fetch.
+
+use super::{Children, Descriptor, DynamicChild};
+use crate::collections::map::increment_bytes;
+use crate::compat_mode;
+use crate::store::{Read, Store};
+use crate::{Error, Result};
+use serde_json::{json, Value};
+use std::any::TypeId;
+
+/// The default number of entries returned when listing a dynamic child.
+pub const DEFAULT_PAGE_LIMIT: u32 = 100;
+
+/// The maximum number of entries returned when listing a dynamic child.
+pub const MAX_PAGE_LIMIT: u32 = 1000;
+
+/// Selects a range of entries when listing a dynamic child.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page {
+    /// Only entries with encoded keys after this one are returned, e.g. the
+    /// `next` key of a previous page.
+    pub after: Option<Vec<u8>>,
+    pub limit: u32,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            after: None,
+            limit: DEFAULT_PAGE_LIMIT,
+        }
+    }
+}
+
+impl Descriptor {
+    /// Resolves the path starting from a value of this type, given the store
+    /// it is attached to and its encoding, returning it as JSON.
+    ///
+    /// The result is an object with the value's `type` and either its `value`
+    /// (for types with named children, also the `children` names), or for
+    /// dynamic children a page of `entries` and the `next` key to continue
+    /// from.
+    pub fn browse(
+        &self,
+        store: Store,
+        bytes: &[u8],
+        path: &[String],
+        page: &Page,
+    ) -> Result<Value> {
+        let (segment, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return self.node_json(&store, bytes, page),
+        };
+
+        match self.children() {
+            Children::Named(children) => {
+                let index = children
+                    .iter()
+                    .position(|child| &child.name == segment)
+                    .ok_or_else(|| {
+                        Error::Query(format!("{} has no field '{}'", self.type_name, segment))
+                    })?;
+
+                let mut bytes = bytes;
+                self.skip_header(&mut bytes)?;
+                for child in &children[..index] {
+                    child
+                        .desc
+                        .load_bytes(child.store_key.apply(&store), &mut bytes)?;
+                }
+
+                let child = &children[index];
+                child
+                    .desc
+                    .browse(child.store_key.apply(&store), bytes, rest, page)
+            }
+            Children::Dynamic(child) => {
+                let key = child.key_desc().parse_key(segment)?;
+                let value = store
+                    .get(&key)?
+                    .ok_or_else(|| Error::Query(format!("No entry for key '{}'", segment)))?;
+                child
+                    .value_desc()
+                    .browse(store.sub(&key), value.as_slice(), rest, page)
+            }
+            Children::None => Err(Error::Query(format!("{} has no children", self.type_name))),
+        }
+    }
+
+    /// Parses a key from a path segment, either JSON or `0x`-prefixed hex of
+    /// the encoded key.
+    pub fn parse_key(&self, segment: &str) -> Result<Vec<u8>> {
+        if let Some(hex_key) = segment.strip_prefix("0x") {
+            return hex::decode(hex_key)
+                .map_err(|err| Error::Query(format!("Invalid hex key '{}': {}", segment, err)));
+        }
+
+        let from_json = self
+            .from_json
+            .ok_or_else(|| Error::Query(format!("Cannot parse keys of {}", self.type_name)))?;
+        let value = serde_json::from_str(segment).unwrap_or(Value::String(segment.to_string()));
+        from_json(value).map_err(|err| Error::Query(format!("Invalid key '{}': {}", segment, err)))
+    }
+
+    /// Returns true if this type or any of its named descendants has dynamic
+    /// children, in which case it is not converted to JSON as a whole since
+    /// that could read an unbounded number of entries.
+    pub fn has_dynamic_children(&self) -> bool {
+        match self.children() {
+            Children::None => false,
+            Children::Named(children) => children
+                .iter()
+                .any(|child| child.desc.has_dynamic_children()),
+            Children::Dynamic(_) => true,
+        }
+    }
+
+    /// Returns the path of named children leading to the first descendant of
+    /// the given type, e.g. to find an app within the plugins wrapping it.
+    pub fn path_to_type(&self, type_id: TypeId) -> Option<Vec<String>> {
+        if self.type_id == type_id {
+            return Some(vec![]);
+        }
+
+        match self.children() {
+            Children::Named(children) => children.iter().find_map(|child| {
+                let mut path = child.desc.path_to_type(type_id)?;
+                path.insert(0, child.name.clone());
+                Some(path)
+            }),
+            _ => None,
+        }
+    }
+
+    fn node_json(&self, store: &Store, bytes: &[u8], page: &Page) -> Result<Value> {
+        match self.children() {
+            Children::Dynamic(child) => {
+                let (entries, next) = self.entries(child, store, page)?;
+                Ok(json!({
+                    "type": self.type_name,
+                    "entries": entries,
+                    "next": next.map(hex::encode),
+                }))
+            }
+            Children::Named(children) => Ok(json!({
+                "type": self.type_name,
+                "children": children.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+                "value": self.render(store, &mut &bytes[..])?,
+            })),
+            Children::None => Ok(json!({
+                "type": self.type_name,
+                "value": self.render(store, &mut &bytes[..])?,
+            })),
+        }
+    }
+
+    /// Converts a value to JSON, consuming its encoding from `bytes`. Types
+    /// with dynamic descendants are rendered field by field, with dynamic
+    /// children as `null`.
+    fn render(&self, store: &Store, bytes: &mut &[u8]) -> Result<Value> {
+        if !self.has_dynamic_children() {
+            if let Some(to_json) = self.to_json {
+                let start = *bytes;
+                if let Some(value) = to_json(store.clone(), bytes)? {
+                    return Ok(value);
+                }
+                *bytes = start;
+            }
+        }
+
+        match self.children() {
+            Children::Named(children) => {
+                self.skip_header(bytes)?;
+                let mut object = serde_json::Map::new();
+                for child in children {
+                    let value = child.desc.render(&child.store_key.apply(store), bytes)?;
+                    object.insert(child.name.clone(), value);
+                }
+                Ok(Value::Object(object))
+            }
+            _ => {
+                self.load_bytes(store.clone(), bytes)?;
+                Ok(Value::Null)
+            }
+        }
+    }
+
+    fn entries(
+        &self,
+        child: &DynamicChild,
+        store: &Store,
+        page: &Page,
+    ) -> Result<(Vec<Value>, Option<Vec<u8>>)> {
+        let limit = page.limit.min(MAX_PAGE_LIMIT) as usize;
+        let mut start = page.after.clone().map(increment_bytes).unwrap_or_default();
+        let mut entries = vec![];
+        let mut last_key = None;
+
+        while entries.len() < limit {
+            let (key, value) = match store.get_next_inclusive(&start)? {
+                Some(kv) => kv,
+                None => break,
+            };
+
+            // skip past keys nested under the entry, e.g. the entries of a
+            // map inside a map
+            let key_len = child.key_desc().encoding_bytes_subslice(&key)?.len();
+            let entry_key = key[..key_len].to_vec();
+            let value = if key_len == key.len() {
+                value
+            } else {
+                store
+                    .get(&entry_key)?
+                    .ok_or_else(|| Error::Query("Missing value for entry".into()))?
+            };
+
+            let mut key_bytes = entry_key.as_slice();
+            let key_json = match child.key_desc().to_json {
+                Some(to_json) => to_json(Store::default(), &mut key_bytes)?,
+                None => None,
+            };
+            let value_store = store.sub(&entry_key);
+            entries.push(json!({
+                "key": key_json,
+                "key_hex": hex::encode(&entry_key),
+                "value": child.value_desc().render(&value_store, &mut value.as_slice())?,
+            }));
+
+            start = increment_bytes(entry_key.clone());
+            last_key = Some(entry_key);
+        }
+
+        let next = if entries.len() == limit {
+            last_key
+        } else {
+            None
+        };
+
+        Ok((entries, next))
+    }
+
+    /// Consumes the header encoded before the named children of types
+    /// deriving `State`, i.e. their version byte.
+    fn skip_header(&self, bytes: &mut &[u8]) -> Result<()> {
+        match &self.meta {
+            Some(meta) if !compat_mode() => meta.load_bytes(Store::default(), bytes),
+            _ => Ok(()),
+        }
+    }
+
+    fn load_bytes(&self, store: Store, bytes: &mut &[u8]) -> Result<()> {
+        let load = self
+            .load
+            .ok_or_else(|| Error::Query(format!("Cannot load {}", self.type_name)))?;
+        load(store, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::Map;
+    use crate::describe::Describe;
+    use crate::orga;
+    use crate::state::State;
+    use crate::store::{BackingStore, MapStore, Shared, Write};
+
+    #[orga]
+    pub struct Account {
+        pub balance: u64,
+        pub nonce: u32,
+    }
+
+    #[orga]
+    pub struct Bank {
+        pub height: u64,
+        pub accounts: Map<u32, Account>,
+        pub nested: Map<u8, Map<u8, u16>>,
+        pub fee: u64,
+    }
+
+    fn bank_store() -> Result<(Store, Vec<u8>)> {
+        let mut store = Store::new(BackingStore::MapStore(Shared::new(MapStore::new())));
+
+        let mut bank = Bank {
+            height: 7,
+            fee: 3,
+            ..Default::default()
+        };
+        bank.attach(store.clone())?;
+        for i in 1..=5 {
+            bank.accounts.insert(
+                i,
+                Account {
+                    balance: i as u64 * 100,
+                    nonce: i,
+                },
+            )?;
+        }
+        bank.nested.insert(1, Map::new())?;
+        bank.nested.get_mut(1)?.unwrap().insert(2, 3)?;
+        bank.nested.insert(4, Map::new())?;
+
+        let mut bytes = vec![];
+        bank.flush(&mut bytes)?;
+        store.put(vec![], bytes.clone())?;
+
+        Ok((store, bytes))
+    }
+
+    fn browse(path: &[&str], page: &Page) -> Result<Value> {
+        let (store, bytes) = bank_store()?;
+        let path: Vec<_> = path.iter().map(|s| s.to_string()).collect();
+        Bank::describe().browse(store, &bytes, &path, page)
+    }
+
+    #[test]
+    fn named_children() -> Result<()> {
+        let root = browse(&[], &Page::default())?;
+        assert_eq!(
+            root["children"],
+            json!(["height", "accounts", "nested", "fee"])
+        );
+        assert_eq!(root["value"]["height"], 7);
+        assert_eq!(root["value"]["accounts"], Value::Null);
+        assert_eq!(root["value"]["fee"], 3);
+
+        assert_eq!(browse(&["fee"], &Page::default())?["value"], 3);
+        assert_eq!(
+            browse(&["accounts", "2"], &Page::default())?["value"],
+            json!({ "balance": 200, "nonce": 2 })
+        );
+        assert_eq!(
+            browse(&["accounts", "0x00000003", "balance"], &Page::default())?["value"],
+            300
+        );
+        assert_eq!(browse(&["nested", "1", "2"], &Page::default())?["value"], 3);
+
+        assert!(browse(&["missing"], &Page::default()).is_err());
+        assert!(browse(&["accounts", "9"], &Page::default()).is_err());
+        assert!(browse(&["accounts", "x"], &Page::default()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn paginate() -> Result<()> {
+        let page = Page {
+            after: None,
+            limit: 2,
+        };
+        let first = browse(&["accounts"], &page)?;
+        assert_eq!(first["entries"][0]["key"], 1);
+        assert_eq!(first["entries"][1]["key_hex"], "00000002");
+        assert_eq!(first["entries"][1]["value"]["balance"], 200);
+        assert_eq!(first["next"], "00000002");
+
+        let page = Page {
+            after: Some(vec![0, 0, 0, 4]),
+            limit: 2,
+        };
+        let last = browse(&["accounts"], &page)?;
+        assert_eq!(last["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(last["entries"][0]["key"], 5);
+        assert_eq!(last["next"], Value::Null);
+
+        // nested entries are skipped when listing the outer map
+        let nested = browse(&["nested"], &Page::default())?;
+        let keys: Vec<_> = nested["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["key"].clone())
+            .collect();
+        assert_eq!(keys, vec![json!(1), json!(4)]);
+
+        Ok(())
+    }
+}
@@ -2,8 +2,8 @@ use crate::state::State;
 use std::any::{type_name, TypeId};
 
 use super::{
-    ApplyQueryBytesFn, Children, Describe, Descriptor, DynamicChild, Inspect, KeyOp, LoadFn,
-    NamedChild,
+    ApplyQueryBytesFn, Children, Describe, Descriptor, DynamicChild, FromJsonFn, Inspect, KeyOp,
+    LoadFn, MaybeFromJson, MaybeToJson, NamedChild, ToJsonFn, ToJsonWrapper,
 };
 
 pub struct Builder {
@@ -11,6 +11,8 @@ pub struct Builder {
     type_name: String,
     state_version: u32,
     load: LoadFn,
+    to_json: ToJsonFn,
+    from_json: FromJsonFn,
     children: Option<Children>,
     meta: Option<Box<Descriptor>>,
 }
@@ -20,11 +22,13 @@ impl Builder {
         Builder {
             type_id: TypeId::of::<T>(),
             type_name: type_name::<T>().to_string(),
-            state_version: 0, // TODO
+            state_version: 0,
             load: |store, bytes| {
                 T::load(store, bytes)?;
                 Ok(())
             },
+            to_json: |store, bytes| ToJsonWrapper(T::load(store, bytes)?).maybe_to_json(),
+            from_json: <T as MaybeFromJson>::maybe_from_json,
             // meta: Some(Box::new(<u8 as Describe>::describe())),
             meta: None,
             children: None,
@@ -62,6 +66,13 @@ impl Builder {
         }
     }
 
+    pub fn state_version(self, version: u32) -> Self {
+        Builder {
+            state_version: version,
+            ..self
+        }
+    }
+
     pub fn meta<T: Describe>(self) -> Self {
         Builder {
             meta: Some(Box::new(T::describe())),
@@ -93,6 +104,8 @@ impl Builder {
             type_name: self.type_name,
             state_version: self.state_version,
             load: Some(self.load),
+            to_json: Some(self.to_json),
+            from_json: Some(self.from_json),
             children: self.children.unwrap_or_default(),
             meta: self.meta,
         }
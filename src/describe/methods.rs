@@ -0,0 +1,468 @@
+//! Runtime metadata about the call and query methods of a type.
+//!
+//! The `#[orga]` macro implements these traits for every type it is applied
+//! to, so tools such as the [CLI](crate::cli) can discover the methods and
+//! fields reachable from an app's root type, parse their arguments from
+//! strings, and read state by path.
+//!
+//! Only fields and methods known at compile time are described. Dynamic
+//! children, e.g. the entries of a `Map`, are reached through query methods
+//! such as `get`.
+
+use super::{MaybeToJson, ToJsonWrapper};
+use crate::collections::Ref;
+use crate::{Error, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Encodes the string arguments of a method as its `MethodCall` variant.
+pub type EncodeArgsFn = fn(&[String]) -> Result<Vec<u8>>;
+
+/// The methods and fields of a type which can be called or queried.
+#[derive(Clone, Debug, Default)]
+pub struct MethodTree {
+    pub fields: Vec<FieldDescriptor>,
+    pub methods: Vec<MethodDescriptor>,
+}
+
+/// A named field along with its own methods.
+#[derive(Clone, Debug)]
+pub struct FieldDescriptor {
+    pub name: String,
+    /// The bytes selecting the field in encoded calls and queries, i.e. its
+    /// appended store key.
+    pub key: Vec<u8>,
+    pub tree: MethodTree,
+}
+
+#[derive(Clone, Debug)]
+pub struct MethodDescriptor {
+    pub name: String,
+    /// The variant index of the method in its `MethodCall` or `MethodQuery`
+    /// enum.
+    pub index: u8,
+    pub args: Vec<ArgDescriptor>,
+    /// Set for call methods.
+    pub encode: Option<EncodeArgsFn>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArgDescriptor {
+    pub name: String,
+    pub type_name: String,
+}
+
+impl MethodTree {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.methods.is_empty()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&MethodDescriptor> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    /// Encodes a call to the method at the end of the given field path, in the
+    /// same format as the type's `Call` encoding.
+    pub fn encode_call(&self, path: &[String], args: &[String]) -> Result<Vec<u8>> {
+        let (name, fields) = path
+            .split_last()
+            .ok_or_else(|| Error::Call("Missing method name".into()))?;
+
+        let mut bytes = vec![];
+        let mut tree = self;
+        for field in fields {
+            let field = tree
+                .field(field)
+                .ok_or_else(|| Error::Call(format!("Unknown field '{}'", field)))?;
+            bytes.extend_from_slice(&field.key);
+            tree = &field.tree;
+        }
+
+        let encode = tree
+            .method(name)
+            .and_then(|method| method.encode)
+            .ok_or_else(|| Error::Call(format!("Unknown call method '{}'", name)))?;
+        let mut method_bytes = encode(args)?;
+        // method calls are distinguished from field calls by this offset, see
+        // `call::Item`
+        method_bytes[0] += crate::call::PREFIX_OFFSET;
+        bytes.extend(method_bytes);
+
+        Ok(bytes)
+    }
+}
+
+impl ArgDescriptor {
+    pub fn new(name: &str, type_name: &str) -> Self {
+        Self {
+            name: name.trim_start_matches('_').to_string(),
+            type_name: type_name.replace(' ', ""),
+        }
+    }
+}
+
+/// Describes the call methods and call fields of a type.
+pub trait DescribeCalls {
+    fn describe_calls() -> MethodTree;
+}
+
+impl<T> DescribeCalls for T {
+    fn describe_calls() -> MethodTree {
+        MethodTree {
+            fields: T::call_fields(),
+            methods: T::call_methods(),
+        }
+    }
+}
+
+/// Describes the query methods and public fields of a type.
+pub trait DescribeQueries {
+    fn describe_queries() -> MethodTree;
+}
+
+impl<T> DescribeQueries for T {
+    fn describe_queries() -> MethodTree {
+        MethodTree {
+            fields: T::query_fields(),
+            methods: T::query_methods(),
+        }
+    }
+}
+
+/// Implemented by `#[orga]` for types with `#[call]` methods.
+pub trait CallMethods {
+    fn call_methods() -> Vec<MethodDescriptor>;
+}
+
+impl<T> CallMethods for T {
+    default fn call_methods() -> Vec<MethodDescriptor> {
+        vec![]
+    }
+}
+
+/// Implemented by `#[orga]` for structs with `#[call]` fields.
+pub trait CallFields {
+    fn call_fields() -> Vec<FieldDescriptor>;
+}
+
+impl<T> CallFields for T {
+    default fn call_fields() -> Vec<FieldDescriptor> {
+        vec![]
+    }
+}
+
+/// Implemented by `#[orga]` for types with `#[query]` methods.
+pub trait QueryMethods {
+    fn query_methods() -> Vec<MethodDescriptor>;
+
+    /// Calls the named query method, parsing its arguments from strings.
+    /// Returns `None` if there is no such method.
+    fn query_method(&self, name: &str, args: &[String]) -> Option<Result<Value>>;
+}
+
+impl<T> QueryMethods for T {
+    default fn query_methods() -> Vec<MethodDescriptor> {
+        vec![]
+    }
+
+    default fn query_method(&self, _name: &str, _args: &[String]) -> Option<Result<Value>> {
+        None
+    }
+}
+
+/// Implemented by `#[orga]` for structs with public fields.
+pub trait ReadFields {
+    fn query_fields() -> Vec<FieldDescriptor>;
+
+    /// Reads the given path starting from the named field. Returns `None` if
+    /// there is no such field.
+    fn read_field(&self, name: &str, rest: &[String]) -> Option<Result<Value>>;
+}
+
+impl<T> ReadFields for T {
+    default fn query_fields() -> Vec<FieldDescriptor> {
+        vec![]
+    }
+
+    default fn read_field(&self, _name: &str, _rest: &[String]) -> Option<Result<Value>> {
+        None
+    }
+}
+
+/// Reads a value by path, e.g. `["accounts", "balance", "<address>"]`.
+///
+/// Each segment names a public field, or a query method followed by its
+/// arguments. An empty path returns the value itself as JSON.
+pub trait ReadPath {
+    fn read_path(&self, path: &[String]) -> Result<Value>;
+}
+
+impl<T> ReadPath for T {
+    fn read_path(&self, path: &[String]) -> Result<Value> {
+        let (name, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return to_json(self),
+        };
+
+        if let Some(res) = self.read_field(name, rest) {
+            return res;
+        }
+        if let Some(res) = self.query_method(name, rest) {
+            return res;
+        }
+
+        Err(Error::Query(format!(
+            "Unknown field or query method '{}'",
+            name
+        )))
+    }
+}
+
+/// Converts the return value of a query method to JSON. Errors are returned
+/// rather than serialized, so that values missing from a partial store can be
+/// fetched.
+pub trait QueryOutput {
+    fn into_json(self) -> Result<Value>;
+}
+
+impl<T> QueryOutput for T {
+    default fn into_json(self) -> Result<Value> {
+        to_json(&self)
+    }
+}
+
+impl<T> QueryOutput for Result<T> {
+    fn into_json(self) -> Result<Value> {
+        self?.into_json()
+    }
+}
+
+impl<T> QueryOutput for Option<T> {
+    fn into_json(self) -> Result<Value> {
+        match self {
+            Some(value) => value.into_json(),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+impl<'a, V> QueryOutput for Ref<'a, V> {
+    fn into_json(self) -> Result<Value> {
+        to_json(&*self)
+    }
+}
+
+fn to_json<T>(value: &T) -> Result<Value> {
+    ToJsonWrapper(value)
+        .maybe_to_json()?
+        .ok_or_else(|| Error::Query("Value cannot be converted to JSON".into()))
+}
+
+trait ParseArg: Sized {
+    fn parse_arg(arg: &str) -> Result<Self>;
+}
+
+impl<T> ParseArg for T {
+    default fn parse_arg(_arg: &str) -> Result<Self> {
+        Err(Error::Call(format!(
+            "Cannot parse argument of type {}",
+            std::any::type_name::<T>()
+        )))
+    }
+}
+
+impl<T: DeserializeOwned> ParseArg for T {
+    fn parse_arg(arg: &str) -> Result<Self> {
+        // unquoted strings are accepted for types which deserialize from
+        // strings, e.g. addresses
+        serde_json::from_str(arg)
+            .or_else(|_| serde_json::from_value(Value::String(arg.to_string())))
+            .map_err(|err| Error::Call(format!("Invalid argument '{}': {}", arg, err)))
+    }
+}
+
+/// Parses a method argument from a JSON string.
+pub fn parse_arg<T>(arg: &str) -> Result<T> {
+    T::parse_arg(arg)
+}
+
+pub fn check_arg_count(method: &str, args: &[String], expected: usize) -> Result<()> {
+    if args.len() != expected {
+        return Err(Error::Call(format!(
+            "Method '{}' takes {} arguments, got {}",
+            method,
+            expected,
+            args.len()
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call::Call;
+    use crate::coins::{Address, Amount, Coin, Symbol};
+    use crate::collections::Map;
+    use crate::encoding::{Decode, Encode};
+    use crate::orga;
+
+    #[orga]
+    pub struct Counter {
+        pub count: u64,
+        #[call]
+        pub inner: Inner,
+    }
+
+    #[orga]
+    impl Counter {
+        #[call]
+        pub fn increment(&mut self, by: u64) -> Result<()> {
+            self.count += by;
+            Ok(())
+        }
+
+        #[query]
+        pub fn count_plus(&self, n: u64) -> Result<u64> {
+            Ok(self.count + n)
+        }
+    }
+
+    #[orga]
+    pub struct Inner {
+        pub owner: Address,
+    }
+
+    #[orga]
+    impl Inner {
+        #[call]
+        pub fn noop(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        #[call]
+        pub fn set_owner(&mut self, owner: Address, _fee: Amount) -> Result<()> {
+            self.owner = owner;
+            Ok(())
+        }
+    }
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    pub struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+        const NAME: &'static str = "SIMP";
+    }
+
+    #[orga]
+    pub struct Ledger<S: Symbol> {
+        pub entries: Map<Address, Coin<S>>,
+    }
+
+    #[orga]
+    impl<S: Symbol> Ledger<S>
+    where
+        S: Clone,
+    {
+        #[call]
+        pub fn clear(&mut self, owner: Address) -> Result<()> {
+            self.entries.remove(owner)?;
+            Ok(())
+        }
+    }
+
+    fn strings(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn describe() {
+        let calls = Counter::describe_calls();
+        assert_eq!(calls.methods[0].name, "increment");
+        assert_eq!(calls.methods[0].args, vec![ArgDescriptor::new("by", "u64")]);
+        // fields are selected by their store key, not their position among
+        // the call fields
+        let inner = calls.field("inner").unwrap();
+        assert_eq!(inner.key, vec![1]);
+        assert_eq!(inner.tree.method("set_owner").unwrap().index, 1);
+        assert_eq!(
+            inner.tree.method("set_owner").unwrap().args,
+            vec![
+                ArgDescriptor::new("owner", "Address"),
+                ArgDescriptor::new("fee", "Amount")
+            ]
+        );
+
+        let queries = Counter::describe_queries();
+        assert_eq!(queries.methods[0].name, "count_plus");
+        assert!(queries.field("count").is_some());
+        assert!(queries
+            .field("inner")
+            .unwrap()
+            .tree
+            .field("owner")
+            .is_some());
+    }
+
+    #[test]
+    fn encode_call() -> Result<()> {
+        let calls = Counter::describe_calls();
+
+        let bytes = calls.encode_call(&strings(&["increment"]), &strings(&["5"]))?;
+        let call = <Counter as Call>::Call::decode(bytes.as_slice())?;
+        let mut counter = Counter::default();
+        counter.call(call)?;
+        assert_eq!(counter.count, 5);
+
+        let owner = Address::from_pubkey([2; 33]);
+        let bytes = calls.encode_call(
+            &strings(&["inner", "set_owner"]),
+            &[owner.to_string(), "100".to_string()],
+        )?;
+        let call = <Counter as Call>::Call::decode(bytes.as_slice())?;
+        assert_eq!(call.encode()?, bytes);
+        counter.call(call)?;
+        assert_eq!(counter.inner.owner, owner);
+
+        assert!(calls
+            .encode_call(&strings(&["increment"]), &strings(&["x"]))
+            .is_err());
+        assert!(calls.encode_call(&strings(&["increment"]), &[]).is_err());
+        assert!(calls.encode_call(&strings(&["missing"]), &[]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn generic_where_clause() -> Result<()> {
+        let calls = Ledger::<Simp>::describe_calls();
+        let owner = Address::from_pubkey([2; 33]);
+        let bytes = calls.encode_call(&strings(&["clear"]), &[owner.to_string()])?;
+        let call = <Ledger<Simp> as Call>::Call::decode(bytes.as_slice())?;
+        assert_eq!(call.encode()?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_path() -> Result<()> {
+        let counter = Counter {
+            count: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(counter.read_path(&strings(&["count"]))?, 3);
+        assert_eq!(counter.read_path(&strings(&["count_plus", "2"]))?, 5);
+        assert!(counter.read_path(&strings(&["count_plus"])).is_err());
+        assert!(counter.read_path(&strings(&["missing"])).is_err());
+
+        Ok(())
+    }
+}
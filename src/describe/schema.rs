@@ -0,0 +1,751 @@
+//! A stable, serializable description of an app's state layout and of the
+//! encodings of its calls and queries.
+//!
+//! A [Schema] is built from the [Descriptor] of the app's root type and the
+//! [MethodTree]s implemented by `#[orga]`. It contains every type reachable
+//! from the root, keyed by type name, with the fields, store keys and version
+//! of each struct, and the field and method indexes used to encode calls and
+//! queries.
+//!
+//! Schemas are meant to be checked in alongside clients: comparing a stored
+//! schema to the current one with [Schema::breaking_changes] catches layout
+//! changes, and bindings can be generated from it as [JSON
+//! Schema](Schema::json_schema) or [TypeScript](Schema::typescript).
+
+use super::methods::{ArgDescriptor, DescribeCalls, DescribeQueries, MethodTree};
+use super::{Children, Describe, Descriptor, KeyOp};
+use crate::store::Store;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+pub mod typescript;
+
+/// The version of the schema format, incremented on incompatible changes to
+/// its structure.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schema {
+    pub schema_version: u32,
+    /// The type name of the app's root type.
+    pub root: String,
+    pub types: BTreeMap<String, TypeSchema>,
+    pub calls: MethodsSchema,
+    pub queries: MethodsSchema,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TypeSchema {
+    /// A type stored as its `ed` encoding, without children in the store.
+    Value,
+    /// A type with named fields, encoded in order after an optional version
+    /// byte.
+    Struct {
+        version: u32,
+        /// Whether the fields are preceded by the version byte.
+        header: bool,
+        fields: Vec<FieldSchema>,
+    },
+    /// A collection with entries stored under their encoded keys, e.g. a
+    /// `Map`.
+    Dynamic {
+        key: String,
+        value: String,
+        /// Whether the collection writes any bytes into its parent's
+        /// encoding.
+        encoded: bool,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub store_key: StoreKey,
+}
+
+/// The hex-encoded store key of a field, relative to its parent or absolute.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKey {
+    Append(String),
+    Absolute(String),
+}
+
+/// The call or query methods of a type and of its fields. Encodings start
+/// with the key of each field in the path, followed by the index of the
+/// method plus the call or query prefix offset (`0x40` or `0x80`) and its
+/// arguments.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MethodsSchema {
+    pub fields: Vec<FieldMethods>,
+    pub methods: Vec<MethodSchema>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldMethods {
+    pub name: String,
+    /// The appended store key of the field.
+    pub key: Vec<u8>,
+    #[serde(flatten)]
+    pub inner: MethodsSchema,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MethodSchema {
+    pub name: String,
+    pub index: u8,
+    pub args: Vec<ArgDescriptor>,
+}
+
+impl Schema {
+    pub fn of<T: Describe>() -> Self {
+        Self::from_parts(&T::describe(), &T::describe_calls(), &T::describe_queries())
+    }
+
+    pub fn from_parts(desc: &Descriptor, calls: &MethodTree, queries: &MethodTree) -> Self {
+        let mut types = BTreeMap::new();
+        add_type(&mut types, desc);
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            root: desc.type_name.clone(),
+            types,
+            calls: calls.into(),
+            queries: queries.into(),
+        }
+    }
+
+    /// Lists the changes from this schema to `newer` which would break
+    /// clients built against this one, i.e. changed encodings or store
+    /// layouts and removed or renumbered methods. Added types, methods and
+    /// fields of calls and queries are not breaking.
+    pub fn breaking_changes(&self, newer: &Schema) -> Vec<String> {
+        let mut changes = vec![];
+
+        if self.root != newer.root {
+            changes.push(format!(
+                "Root type changed from {} to {}",
+                self.root, newer.root
+            ));
+        }
+
+        for (name, old) in &self.types {
+            if let Some(new) = newer.types.get(name) {
+                compare_types(name, old, new, &mut changes);
+            }
+        }
+
+        compare_methods("call", "", &self.calls, &newer.calls, &mut changes);
+        compare_methods("query", "", &self.queries, &newer.queries, &mut changes);
+
+        changes
+    }
+
+    /// Generates a JSON Schema for the JSON representation of the root type,
+    /// as returned by queries. Fields stored under their own keys accept any
+    /// value.
+    pub fn json_schema(&self) -> Value {
+        let idents = self.idents();
+        let defs: serde_json::Map<_, _> = self
+            .types
+            .iter()
+            .filter_map(|(name, ty)| match ty {
+                TypeSchema::Struct { fields, .. } => {
+                    let properties: serde_json::Map<_, _> = fields
+                        .iter()
+                        .map(|field| {
+                            let schema = if self.is_stored_separately(&field.type_name) {
+                                json!({})
+                            } else {
+                                self.json_type(&Ty::parse(&field.type_name), &idents)
+                            };
+                            (field.name.clone(), schema)
+                        })
+                        .collect();
+                    let def = json!({
+                        "type": "object",
+                        "properties": properties,
+                    });
+                    Some((idents[name].clone(), def))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut schema = self.json_type(&Ty::parse(&self.root), &idents);
+        schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+        schema["$defs"] = Value::Object(defs);
+        schema
+    }
+
+    /// Generates TypeScript types and `ed`-compatible encoders and decoders
+    /// for the types and methods in the schema, see [typescript].
+    pub fn typescript(&self) -> String {
+        typescript::generate(self)
+    }
+
+    fn json_type(&self, ty: &Ty, idents: &BTreeMap<String, String>) -> Value {
+        if let Some((_, json_type)) = JSON_OVERRIDES.iter().find(|(name, _)| *name == ty.full()) {
+            return json!({ "type": json_type });
+        }
+        if let Some(name) = self.lookup(ty) {
+            if let TypeSchema::Struct { .. } = self.types[name] {
+                return json!({ "$ref": format!("#/$defs/{}", idents[name]) });
+            }
+        }
+
+        match ty {
+            Ty::Tuple(items) if items.is_empty() => json!({ "type": "null" }),
+            Ty::Tuple(items) => json!({
+                "type": "array",
+                "prefixItems": items.iter().map(|item| self.json_type(item, idents)).collect::<Vec<_>>(),
+                "items": false,
+            }),
+            Ty::Array(item, len) => json!({
+                "type": "array",
+                "items": self.json_type(item, idents),
+                "minItems": len,
+                "maxItems": len,
+            }),
+            Ty::Path { name, args, .. } => match (name.as_str(), args.as_slice()) {
+                ("bool", _) => json!({ "type": "boolean" }),
+                ("Vec", [item]) => json!({
+                    "type": "array",
+                    "items": self.json_type(item, idents),
+                }),
+                ("Option", [item]) => json!({
+                    "anyOf": [self.json_type(item, idents), { "type": "null" }],
+                }),
+                (name, _) if INTEGERS.iter().any(|(int, _, _)| *int == name) => {
+                    json!({ "type": "integer" })
+                }
+                _ => json!({}),
+            },
+        }
+    }
+
+    /// Returns the name of the type in the schema matching the parsed type,
+    /// either by its full name or, e.g. for method arguments, by its
+    /// unqualified name if that is unique.
+    pub(crate) fn lookup(&self, ty: &Ty) -> Option<&String> {
+        if let Some((name, _)) = self.types.get_key_value(ty.full()) {
+            return Some(name);
+        }
+
+        let short = short_name(ty.full());
+        let mut matches = self.types.keys().filter(|name| short_name(name) == short);
+        match (matches.next(), matches.next()) {
+            (Some(name), None) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns true for collections which store their entries under their own
+    /// keys and write nothing into their parent's encoding.
+    pub(crate) fn is_stored_separately(&self, type_name: &str) -> bool {
+        matches!(
+            self.types.get(type_name),
+            Some(TypeSchema::Dynamic { encoded: false, .. })
+        )
+    }
+
+    /// Assigns a unique identifier to each struct and collection type, e.g.
+    /// `MapU32Account` for `orga::collections::Map<u32, app::Account>`.
+    pub(crate) fn idents(&self) -> BTreeMap<String, String> {
+        let mut idents = BTreeMap::new();
+        let mut taken = BTreeMap::new();
+
+        for (name, ty) in &self.types {
+            if matches!(ty, TypeSchema::Value) {
+                continue;
+            }
+
+            let base = Ty::parse(name).ident();
+            let count = taken.entry(base.clone()).or_insert(0);
+            *count += 1;
+            let ident = if *count == 1 {
+                base
+            } else {
+                format!("{}{}", base, count)
+            };
+            idents.insert(name.clone(), ident);
+        }
+
+        idents
+    }
+}
+
+/// Types with custom JSON representations, which are not reflected by their
+/// fields.
+const JSON_OVERRIDES: &[(&str, &str)] = &[
+    ("orga::coins::Address", "string"),
+    ("orga::coins::amount::Amount", "integer"),
+];
+
+/// Integer type names with their size in bytes and signedness.
+pub(crate) const INTEGERS: &[(&str, usize, bool)] = &[
+    ("u8", 1, false),
+    ("u16", 2, false),
+    ("u32", 4, false),
+    ("u64", 8, false),
+    ("u128", 16, false),
+    ("i8", 1, true),
+    ("i16", 2, true),
+    ("i32", 4, true),
+    ("i64", 8, true),
+    ("i128", 16, true),
+];
+
+fn add_type(types: &mut BTreeMap<String, TypeSchema>, desc: &Descriptor) {
+    if types.contains_key(&desc.type_name) {
+        return;
+    }
+
+    let ty = match desc.children() {
+        Children::None => TypeSchema::Value,
+        Children::Named(children) => TypeSchema::Struct {
+            version: desc.state_version,
+            header: desc.meta.is_some(),
+            fields: children
+                .iter()
+                .map(|child| FieldSchema {
+                    name: child.name.clone(),
+                    type_name: child.desc.type_name.clone(),
+                    store_key: match &child.store_key {
+                        KeyOp::Append(key) => StoreKey::Append(hex::encode(key)),
+                        KeyOp::Absolute(key) => StoreKey::Absolute(hex::encode(key)),
+                    },
+                })
+                .collect(),
+        },
+        Children::Dynamic(child) => TypeSchema::Dynamic {
+            key: child.key_desc().type_name.clone(),
+            value: child.value_desc().type_name.clone(),
+            // collections which load from no bytes keep all their data under
+            // their own keys
+            encoded: desc
+                .load
+                .map_or(true, |load| load(Store::default(), &mut &[][..]).is_err()),
+        },
+    };
+    types.insert(desc.type_name.clone(), ty);
+
+    match desc.children() {
+        Children::None => {}
+        Children::Named(children) => {
+            for child in children {
+                add_type(types, &child.desc);
+            }
+        }
+        Children::Dynamic(child) => {
+            add_type(types, child.key_desc());
+            add_type(types, child.value_desc());
+        }
+    }
+}
+
+impl From<&MethodTree> for MethodsSchema {
+    fn from(tree: &MethodTree) -> Self {
+        Self {
+            fields: tree
+                .fields
+                .iter()
+                .map(|field| FieldMethods {
+                    name: field.name.clone(),
+                    key: field.key.clone(),
+                    inner: (&field.tree).into(),
+                })
+                .collect(),
+            methods: tree
+                .methods
+                .iter()
+                .map(|method| MethodSchema {
+                    name: method.name.clone(),
+                    index: method.index,
+                    args: method.args.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn compare_types(name: &str, old: &TypeSchema, new: &TypeSchema, changes: &mut Vec<String>) {
+    use TypeSchema::*;
+
+    match (old, new) {
+        (Value, Value) => {}
+        (
+            Struct {
+                version: old_version,
+                header: old_header,
+                fields: old_fields,
+            },
+            Struct {
+                version: new_version,
+                header: new_header,
+                fields: new_fields,
+            },
+        ) => {
+            if old_version != new_version {
+                changes.push(format!(
+                    "{}: version changed from {} to {}",
+                    name, old_version, new_version
+                ));
+            }
+            if old_header != new_header {
+                changes.push(format!("{}: version byte added or removed", name));
+            }
+
+            for i in 0..old_fields.len().max(new_fields.len()) {
+                match (old_fields.get(i), new_fields.get(i)) {
+                    (Some(old), None) => {
+                        changes.push(format!("{}: field `{}` removed", name, old.name))
+                    }
+                    (None, Some(new)) => {
+                        changes.push(format!("{}: field `{}` added", name, new.name))
+                    }
+                    (Some(old), Some(new)) if old != new => changes.push(format!(
+                        "{}: field {} changed from `{}: {}` to `{}: {}`{}",
+                        name,
+                        i,
+                        old.name,
+                        old.type_name,
+                        new.name,
+                        new.type_name,
+                        if old.store_key != new.store_key {
+                            " with a different store key"
+                        } else {
+                            ""
+                        }
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        (old, new) if old != new => {
+            changes.push(format!("{}: changed from {:?} to {:?}", name, old, new))
+        }
+        _ => {}
+    }
+}
+
+fn compare_methods(
+    kind: &str,
+    path: &str,
+    old: &MethodsSchema,
+    new: &MethodsSchema,
+    changes: &mut Vec<String>,
+) {
+    let qualify = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+
+    for old_method in &old.methods {
+        let method_path = qualify(&old_method.name);
+        match new.methods.iter().find(|m| m.name == old_method.name) {
+            None => changes.push(format!("{} method `{}` removed", kind, method_path)),
+            Some(new_method) if new_method.index != old_method.index => changes.push(format!(
+                "{} method `{}` index changed from {} to {}",
+                kind, method_path, old_method.index, new_method.index
+            )),
+            Some(new_method) if new_method.args != old_method.args => changes.push(format!(
+                "{} method `{}` arguments changed",
+                kind, method_path
+            )),
+            _ => {}
+        }
+    }
+
+    for old_field in &old.fields {
+        let field_path = qualify(&old_field.name);
+        match new.fields.iter().find(|f| f.name == old_field.name) {
+            None => changes.push(format!("{} field `{}` removed", kind, field_path)),
+            Some(new_field) => {
+                if new_field.key != old_field.key {
+                    changes.push(format!(
+                        "{} field `{}` key changed from {} to {}",
+                        kind,
+                        field_path,
+                        hex::encode(&old_field.key),
+                        hex::encode(&new_field.key)
+                    ));
+                }
+                compare_methods(
+                    kind,
+                    &field_path,
+                    &old_field.inner,
+                    &new_field.inner,
+                    changes,
+                );
+            }
+        }
+    }
+}
+
+/// A type name as returned by [std::any::type_name], parsed into its
+/// structure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Ty {
+    Path {
+        full: String,
+        /// The last path segment, without generic arguments.
+        name: String,
+        args: Vec<Ty>,
+    },
+    Array(Box<Ty>, usize),
+    Tuple(Vec<Ty>),
+}
+
+impl Ty {
+    pub(crate) fn parse(type_name: &str) -> Self {
+        let s = type_name.trim();
+
+        if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            return Ty::Tuple(split_top_level(inner, ',').map(Ty::parse).collect());
+        }
+
+        if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            let mut parts: Vec<_> = split_top_level(inner, ';').collect();
+            let len = match parts.len() {
+                2 => parts.pop().unwrap().trim().parse().unwrap_or_default(),
+                _ => 0,
+            };
+            return Ty::Array(Box::new(Ty::parse(parts[0])), len);
+        }
+
+        let (path, args) = match (s.find('<'), s.ends_with('>')) {
+            (Some(start), true) => (
+                &s[..start],
+                split_top_level(&s[start + 1..s.len() - 1], ',')
+                    .map(Ty::parse)
+                    .collect(),
+            ),
+            _ => (s, vec![]),
+        };
+
+        Ty::Path {
+            full: s.to_string(),
+            name: path.rsplit("::").next().unwrap_or(path).to_string(),
+            args,
+        }
+    }
+
+    pub(crate) fn full(&self) -> &str {
+        match self {
+            Ty::Path { full, .. } => full,
+            _ => "",
+        }
+    }
+
+    /// Returns a PascalCase identifier for the type.
+    fn ident(&self) -> String {
+        let ident = match self {
+            Ty::Path { name, args, .. } => {
+                let mut ident = name.clone();
+                for arg in args {
+                    ident.push_str(&arg.ident());
+                }
+                ident
+            }
+            Ty::Array(item, len) => format!("{}Array{}", item.ident(), len),
+            Ty::Tuple(items) => items
+                .iter()
+                .fold("Tuple".to_string(), |ident, item| ident + &item.ident()),
+        };
+
+        let mut chars = ident.chars().filter(|c| c.is_ascii_alphanumeric());
+        match chars.next() {
+            Some(first) => first.to_ascii_uppercase().to_string() + &chars.collect::<String>(),
+            None => "Unit".to_string(),
+        }
+    }
+}
+
+/// Splits by the separator, ignoring separators nested in brackets.
+fn split_top_level(s: &str, sep: char) -> impl Iterator<Item = &str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            c if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+
+    parts.into_iter().filter(|part| !part.trim().is_empty())
+}
+
+/// Strips module paths and whitespace from a type name, e.g.
+/// `Map<u32,Account>` for `orga::collections::Map<u32, app::Account>`.
+pub(crate) fn short_name(type_name: &str) -> String {
+    let mut out = String::new();
+    let mut segment = String::new();
+    let mut chars = type_name.chars().filter(|c| !c.is_whitespace()).peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            segment.clear();
+        } else if c.is_alphanumeric() || c == '_' {
+            segment.push(c);
+        } else {
+            out.push_str(&segment);
+            segment.clear();
+            out.push(c);
+        }
+    }
+    out.push_str(&segment);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coins::{Address, Amount};
+    use crate::collections::Map;
+    use crate::orga;
+    use crate::Result;
+
+    #[orga]
+    pub struct Account {
+        pub balance: Amount,
+        pub nonce: u64,
+    }
+
+    #[orga]
+    pub struct Bank {
+        pub height: u64,
+        #[call]
+        pub accounts: Map<Address, Account>,
+        pub flags: (bool, Option<u32>),
+    }
+
+    #[orga]
+    impl Bank {
+        #[call]
+        pub fn transfer(&mut self, _to: Address, _amount: Amount) -> Result<()> {
+            Ok(())
+        }
+
+        #[query]
+        pub fn balance(&self, _address: Address) -> Result<Amount> {
+            Ok(0.into())
+        }
+    }
+
+    fn bank_name() -> String {
+        std::any::type_name::<Bank>().to_string()
+    }
+
+    #[test]
+    fn schema() {
+        let schema = Schema::of::<Bank>();
+        assert_eq!(schema.root, bank_name());
+
+        let (header, fields) = match &schema.types[&bank_name()] {
+            TypeSchema::Struct { header, fields, .. } => (*header, fields),
+            other => panic!("Unexpected type {:?}", other),
+        };
+        assert!(header);
+        let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["height", "accounts", "flags"]);
+        assert_eq!(fields[1].store_key, StoreKey::Append("01".to_string()));
+
+        let accounts = &schema.types[&fields[1].type_name];
+        assert_eq!(
+            accounts,
+            &TypeSchema::Dynamic {
+                key: std::any::type_name::<Address>().to_string(),
+                value: std::any::type_name::<Account>().to_string(),
+                encoded: false,
+            }
+        );
+        assert_eq!(schema.types["u64"], TypeSchema::Value);
+
+        assert_eq!(schema.calls.methods[0].name, "transfer");
+        assert_eq!(schema.calls.fields[0].name, "accounts");
+        assert_eq!(schema.calls.fields[0].key, vec![1]);
+        assert_eq!(schema.queries.methods[0].name, "balance");
+
+        let json = serde_json::to_value(&schema).unwrap();
+        let roundtrip: Schema = serde_json::from_value(json).unwrap();
+        assert_eq!(roundtrip, schema);
+    }
+
+    #[test]
+    fn breaking_changes() {
+        let old = Schema::of::<Bank>();
+        assert!(old.breaking_changes(&old).is_empty());
+
+        let mut new = old.clone();
+        if let Some(TypeSchema::Struct { fields, .. }) = new.types.get_mut(&bank_name()) {
+            fields.swap(0, 2);
+        }
+        new.calls.methods[0].index = 1;
+        new.queries.methods.clear();
+
+        let changes = old.breaking_changes(&new);
+        assert_eq!(changes.len(), 4);
+        assert!(changes[0].contains("field 0 changed from `height: u64`"));
+        assert!(changes[2].contains("call method `transfer` index changed from 0 to 1"));
+        assert!(changes[3].contains("query method `balance` removed"));
+    }
+
+    #[test]
+    fn json_schema() {
+        let schema = Schema::of::<Bank>();
+        let json_schema = schema.json_schema();
+        assert_eq!(json_schema["$ref"], "#/$defs/Bank");
+
+        let bank = &json_schema["$defs"]["Bank"]["properties"];
+        assert_eq!(bank["height"], json!({ "type": "integer" }));
+        assert_eq!(bank["accounts"], json!({}));
+        assert_eq!(
+            bank["flags"]["prefixItems"][0],
+            json!({ "type": "boolean" })
+        );
+        assert_eq!(
+            json_schema["$defs"]["Account"]["properties"]["balance"],
+            json!({ "type": "integer" })
+        );
+    }
+
+    #[test]
+    fn parse_types() {
+        let ty = Ty::parse("orga::collections::map::Map<u32, (u8, [u8; 32])>");
+        assert_eq!(ty.ident(), "MapU32TupleU8U8Array32");
+        match ty {
+            Ty::Path { name, args, .. } => {
+                assert_eq!(name, "Map");
+                assert_eq!(
+                    args[1],
+                    Ty::Tuple(vec![
+                        Ty::parse("u8"),
+                        Ty::Array(Box::new(Ty::parse("u8")), 32)
+                    ])
+                );
+            }
+            _ => panic!("Expected a path"),
+        }
+
+        assert_eq!(
+            short_name("orga::collections::map::Map<u32, app::Account>"),
+            "Map<u32,Account>"
+        );
+    }
+}
@@ -0,0 +1,498 @@
+//! TypeScript bindings generated from a [Schema].
+//!
+//! The output is a single self-contained module with:
+//!
+//! - an interface and a `Codec` for each struct type, reading and writing the
+//!   same bytes as its `ed` encoding, including the version byte,
+//! - codecs for primitives, `Option`, `Vec`, arrays and tuples,
+//! - `calls` and `queries` objects mirroring the app's call and query methods,
+//!   with a function per method returning its encoded bytes.
+//!
+//! Fields stored under their own keys (e.g. maps) are left out of the
+//! interfaces since they are not part of their parent's encoding. Types whose
+//! encoding is not known from the schema get codecs which throw, and method
+//! arguments of such types are passed as already-encoded bytes.
+
+use super::{MethodsSchema, Schema, Ty, TypeSchema, INTEGERS};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const PRELUDE: &str = r#"export class Writer {
+  private chunks: Uint8Array[] = [];
+
+  write(bytes: Uint8Array): void {
+    this.chunks.push(bytes);
+  }
+
+  finish(): Uint8Array {
+    const length = this.chunks.reduce((n, chunk) => n + chunk.length, 0);
+    const out = new Uint8Array(length);
+    let offset = 0;
+    for (const chunk of this.chunks) {
+      out.set(chunk, offset);
+      offset += chunk.length;
+    }
+    return out;
+  }
+}
+
+export class Reader {
+  constructor(private bytes: Uint8Array, private offset = 0) {}
+
+  read(length: number): Uint8Array {
+    if (this.offset + length > this.bytes.length) {
+      throw new Error("Unexpected end of input");
+    }
+    const out = this.bytes.subarray(this.offset, this.offset + length);
+    this.offset += length;
+    return out;
+  }
+
+  get remaining(): number {
+    return this.bytes.length - this.offset;
+  }
+}
+
+export interface Codec<T> {
+  write(w: Writer, value: T): void;
+  read(r: Reader): T;
+}
+
+export function encode<T>(codec: Codec<T>, value: T): Uint8Array {
+  const w = new Writer();
+  codec.write(w, value);
+  return w.finish();
+}
+
+export function decode<T>(codec: Codec<T>, bytes: Uint8Array): T {
+  const r = new Reader(bytes);
+  const value = codec.read(r);
+  if (r.remaining !== 0) {
+    throw new Error("Unexpected trailing bytes");
+  }
+  return value;
+}
+
+function bigInt(size: number, signed: boolean): Codec<bigint> {
+  return {
+    write(w, value) {
+      const out = new Uint8Array(size);
+      let v = BigInt.asUintN(size * 8, value);
+      for (let i = size - 1; i >= 0; i--) {
+        out[i] = Number(v & 0xffn);
+        v >>= 8n;
+      }
+      w.write(out);
+    },
+    read(r) {
+      let v = 0n;
+      for (const byte of r.read(size)) {
+        v = (v << 8n) | BigInt(byte);
+      }
+      return signed ? BigInt.asIntN(size * 8, v) : v;
+    },
+  };
+}
+
+function int(size: number, signed: boolean): Codec<number> {
+  const codec = bigInt(size, signed);
+  return {
+    write: (w, value) => codec.write(w, BigInt(value)),
+    read: (r) => Number(codec.read(r)),
+  };
+}
+
+export const u8 = int(1, false);
+export const u16 = int(2, false);
+export const u32 = int(4, false);
+export const u64 = bigInt(8, false);
+export const u128 = bigInt(16, false);
+export const i8 = int(1, true);
+export const i16 = int(2, true);
+export const i32 = int(4, true);
+export const i64 = bigInt(8, true);
+export const i128 = bigInt(16, true);
+
+export const bool: Codec<boolean> = {
+  write: (w, value) => w.write(new Uint8Array([value ? 1 : 0])),
+  read(r) {
+    const byte = r.read(1)[0];
+    if (byte > 1) {
+      throw new Error("Invalid bool");
+    }
+    return byte === 1;
+  },
+};
+
+export const unit: Codec<null> = {
+  write: () => {},
+  read: () => null,
+};
+
+/** Bytes of a fixed length, e.g. `[u8; 32]`. */
+export function bytes(length: number): Codec<Uint8Array> {
+  return {
+    write(w, value) {
+      if (value.length !== length) {
+        throw new Error(`Expected ${length} bytes`);
+      }
+      w.write(value);
+    },
+    read: (r) => r.read(length).slice(),
+  };
+}
+
+/** The remaining bytes of the input, e.g. `Vec<u8>`. */
+export const rest: Codec<Uint8Array> = {
+  write: (w, value) => w.write(value),
+  read: (r) => r.read(r.remaining).slice(),
+};
+
+export function array<T>(codec: Codec<T>, length: number): Codec<T[]> {
+  return {
+    write(w, value) {
+      if (value.length !== length) {
+        throw new Error(`Expected ${length} items`);
+      }
+      value.forEach((item) => codec.write(w, item));
+    },
+    read: (r) => Array.from({ length }, () => codec.read(r)),
+  };
+}
+
+/** Items until the end of the input, as `ed` encodes `Vec`. */
+export function vec<T>(codec: Codec<T>): Codec<T[]> {
+  return {
+    write: (w, value) => value.forEach((item) => codec.write(w, item)),
+    read(r) {
+      const items = [];
+      while (r.remaining > 0) {
+        items.push(codec.read(r));
+      }
+      return items;
+    },
+  };
+}
+
+export function option<T>(codec: Codec<T>): Codec<T | null> {
+  return {
+    write(w, value) {
+      bool.write(w, value !== null);
+      if (value !== null) {
+        codec.write(w, value);
+      }
+    },
+    read: (r) => (bool.read(r) ? codec.read(r) : null),
+  };
+}
+
+export function tuple<T extends unknown[]>(...codecs: Codec<any>[]): Codec<T> {
+  return {
+    write: (w, value) => codecs.forEach((codec, i) => codec.write(w, value[i])),
+    read: (r) => codecs.map((codec) => codec.read(r)) as T,
+  };
+}
+
+/** Fields are passed lazily so codecs can reference types declared later. */
+function struct<T>(version: number | null, fields: () => [string, Codec<any>][]): Codec<T> {
+  return {
+    write(w, value: any) {
+      if (version !== null) {
+        u8.write(w, version);
+      }
+      for (const [name, codec] of fields()) {
+        codec.write(w, value[name]);
+      }
+    },
+    read(r) {
+      if (version !== null && u8.read(r) !== version) {
+        throw new Error("Unexpected version");
+      }
+      const value: any = {};
+      for (const [name, codec] of fields()) {
+        value[name] = codec.read(r);
+      }
+      return value;
+    },
+  };
+}
+
+function unsupported(name: string): Codec<never> {
+  const fail = (): never => {
+    throw new Error(`Encoding of ${name} is not known`);
+  };
+  return { write: fail, read: fail };
+}
+
+/**
+ * Encodes a call or query of the method at the given path of field keys,
+ * offsetting the method index by the call or query prefix.
+ */
+function method(
+  prefix: number,
+  path: number[],
+  index: number,
+  args: [Codec<any>, unknown][],
+): Uint8Array {
+  const w = new Writer();
+  w.write(new Uint8Array([...path, index + prefix]));
+  for (const [codec, value] of args) {
+    codec.write(w, value);
+  }
+  return w.finish();
+}
+"#;
+
+/// Generates the TypeScript module for the schema.
+pub fn generate(schema: &Schema) -> String {
+    let idents = schema.idents();
+    let mut out = format!(
+        "// Generated from the schema of `{}`. Do not edit.\n\n{}",
+        schema.root, PRELUDE
+    );
+    for (helper, prefix) in [
+        ("callMethod", crate::call::PREFIX_OFFSET),
+        ("queryMethod", crate::query::PREFIX_OFFSET),
+    ] {
+        write!(
+            out,
+            "\nconst {} = (path: number[], index: number, args: [Codec<any>, unknown][]) =>\n  method({:#04x}, path, index, args);\n",
+            helper, prefix
+        )
+        .unwrap();
+    }
+
+    for (name, ty) in &schema.types {
+        let (version, fields) = match ty {
+            TypeSchema::Struct {
+                version,
+                header,
+                fields,
+            } => (header.then_some(*version), fields),
+            _ => continue,
+        };
+        let ident = &idents[name];
+
+        let mut members = String::new();
+        let mut codecs = String::new();
+        for field in fields {
+            if schema.is_stored_separately(&field.type_name) {
+                continue;
+            }
+            let (ts_type, codec) = resolve(schema, &idents, &Ty::parse(&field.type_name));
+            writeln!(members, "  {:?}: {};", field.name, ts_type).unwrap();
+            writeln!(codecs, "  [{:?}, {}],", field.name, codec).unwrap();
+        }
+
+        let version = version.map_or("null".to_string(), |v| v.to_string());
+        write!(
+            out,
+            "\n/** `{}` */\nexport interface {} {{\n{}}}\n\nexport const {}: Codec<{}> = struct({}, () => [\n{}]);\n",
+            name, ident, members, ident, ident, version, codecs
+        )
+        .unwrap();
+    }
+
+    write!(
+        out,
+        "\nexport const calls = {};\n\nexport const queries = {};\n",
+        methods(schema, &idents, &schema.calls, "callMethod", &[], 0),
+        methods(schema, &idents, &schema.queries, "queryMethod", &[], 0),
+    )
+    .unwrap();
+
+    out
+}
+
+/// Returns the TypeScript type and codec expression for a type.
+fn resolve(schema: &Schema, idents: &BTreeMap<String, String>, ty: &Ty) -> (String, String) {
+    if let Some(name) = schema.lookup(ty) {
+        match &schema.types[name] {
+            TypeSchema::Struct { .. } => return (idents[name].clone(), idents[name].clone()),
+            TypeSchema::Dynamic { encoded: false, .. } => {
+                return ("null".to_string(), "unit".to_string())
+            }
+            _ => {}
+        }
+    }
+
+    let is_u8 = |ty: &Ty| matches!(ty, Ty::Path { name, .. } if name == "u8");
+    let list = |ts_type: String| {
+        if ts_type.contains(' ') {
+            format!("({})[]", ts_type)
+        } else {
+            format!("{}[]", ts_type)
+        }
+    };
+
+    match ty {
+        Ty::Tuple(items) if items.is_empty() => ("null".to_string(), "unit".to_string()),
+        Ty::Tuple(items) => {
+            let (types, codecs): (Vec<_>, Vec<_>) = items
+                .iter()
+                .map(|item| resolve(schema, idents, item))
+                .unzip();
+            (
+                format!("[{}]", types.join(", ")),
+                format!("tuple({})", codecs.join(", ")),
+            )
+        }
+        Ty::Array(item, len) if is_u8(item) => {
+            ("Uint8Array".to_string(), format!("bytes({})", len))
+        }
+        Ty::Array(item, len) => {
+            let (ts_type, codec) = resolve(schema, idents, item);
+            (list(ts_type), format!("array({}, {})", codec, len))
+        }
+        Ty::Path { name, args, full } => match (name.as_str(), args.as_slice()) {
+            ("Vec", [item]) if is_u8(item) => ("Uint8Array".to_string(), "rest".to_string()),
+            ("Vec", [item]) => {
+                let (ts_type, codec) = resolve(schema, idents, item);
+                (list(ts_type), format!("vec({})", codec))
+            }
+            ("Option", [item]) => {
+                let (ts_type, codec) = resolve(schema, idents, item);
+                (format!("{} | null", ts_type), format!("option({})", codec))
+            }
+            ("bool", _) => ("boolean".to_string(), "bool".to_string()),
+            ("PhantomData", _) => ("null".to_string(), "unit".to_string()),
+            (name, _) => match INTEGERS.iter().find(|(int, _, _)| *int == name) {
+                Some((_, size, _)) if *size <= 4 => ("number".to_string(), name.to_string()),
+                Some(_) => ("bigint".to_string(), name.to_string()),
+                None => ("unknown".to_string(), format!("unsupported({:?})", full)),
+            },
+        },
+    }
+}
+
+/// Generates the object literal for the methods of a type and its fields.
+fn methods(
+    schema: &Schema,
+    idents: &BTreeMap<String, String>,
+    tree: &MethodsSchema,
+    helper: &str,
+    path: &[u8],
+    depth: usize,
+) -> String {
+    let indent = "  ".repeat(depth + 1);
+    let mut out = "{\n".to_string();
+
+    for method in &tree.methods {
+        let mut params = vec![];
+        let mut args = vec![];
+        for arg in &method.args {
+            let (ts_type, codec) = match resolve(schema, idents, &Ty::parse(&arg.type_name)) {
+                (_, codec) if codec.starts_with("unsupported") => (
+                    format!("Uint8Array /* encoded {} */", arg.type_name),
+                    "rest".to_string(),
+                ),
+                resolved => resolved,
+            };
+            params.push(format!("{}: {}", arg.name, ts_type));
+            args.push(format!("[{}, {}]", codec, arg.name));
+        }
+
+        writeln!(
+            out,
+            "{}{:?}: ({}) => {}({:?}, {}, [{}]),",
+            indent,
+            method.name,
+            params.join(", "),
+            helper,
+            path,
+            method.index,
+            args.join(", ")
+        )
+        .unwrap();
+    }
+
+    for field in &tree.fields {
+        if field.inner.fields.is_empty() && field.inner.methods.is_empty() {
+            continue;
+        }
+        let path = [path, &field.key].concat();
+        writeln!(
+            out,
+            "{}{:?}: {},",
+            indent,
+            field.name,
+            methods(schema, idents, &field.inner, helper, &path, depth + 1)
+        )
+        .unwrap();
+    }
+
+    out + &"  ".repeat(depth) + "}"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::Bank;
+    use super::*;
+    use crate::encoding::Encode;
+    use crate::orga;
+    use crate::query::{self, Query};
+    use crate::Result;
+
+    #[orga]
+    pub struct Pool {
+        total: u64,
+        pub stats: Stats,
+    }
+
+    #[orga]
+    pub struct Stats {
+        pub count: u32,
+    }
+
+    #[orga]
+    impl Stats {
+        #[query]
+        pub fn count_plus(&self, n: u32) -> Result<u32> {
+            Ok(self.count + n)
+        }
+    }
+
+    #[test]
+    fn generate() {
+        let ts = Schema::of::<Bank>().typescript();
+
+        assert!(ts.contains("export interface Bank {\n  \"height\": bigint;\n  \"flags\": [boolean, number | null];\n}"));
+        assert!(ts.contains("export const Bank: Codec<Bank> = struct(0, () => [\n  [\"height\", u64],\n  [\"flags\", tuple(bool, option(u32))],\n]);"));
+        assert!(ts.contains("\"balance\": Amount;"));
+        assert!(ts.contains("\"transfer\": (to: Address, amount: Amount) => callMethod([], 0, [[Address, to], [Amount, amount]]),"));
+        assert!(ts.contains(
+            "\"balance\": (address: Address) => queryMethod([], 0, [[Address, address]]),"
+        ));
+    }
+
+    #[test]
+    fn nested_query() -> Result<()> {
+        let schema = Schema::of::<Pool>();
+        let ts = schema.typescript();
+        assert!(ts.contains("method(0x40, path, index, args)"));
+        assert!(ts.contains("method(0x80, path, index, args)"));
+
+        // the private `total` field comes first, so `stats` is selected by
+        // its store key rather than its position among the public fields
+        let stats = &schema.queries.fields[0];
+        assert_eq!(stats.key, vec![1]);
+        let count_plus = &stats.inner.methods[0];
+        assert!(ts.contains(&format!(
+            "\"count_plus\": (n: number) => queryMethod({:?}, {}, [[u32, n]]),",
+            stats.key, count_plus.index
+        )));
+
+        let expected: <Pool as Query>::Query = query::Item::Field(PoolFieldQuery::Stats(
+            query::Item::Method(StatsMethodQuery::CountPlus(5, vec![])),
+        ));
+        let bytes = [
+            stats.key.clone(),
+            vec![count_plus.index + query::PREFIX_OFFSET],
+            5u32.encode()?,
+        ]
+        .concat();
+        assert_eq!(expected.encode()?, bytes);
+
+        Ok(())
+    }
+}
@@ -17,6 +17,8 @@ pub enum Error {
     Client(String),
     #[error("Coins Error: {0}")]
     Coins(String),
+    #[error("Config Error: {0}")]
+    Config(String),
     #[error(transparent)]
     Dalek(#[from] ed25519_dalek::ed25519::Error),
     #[error(transparent)]
@@ -27,6 +29,10 @@ pub enum Error {
     Downcast(String),
     #[error(transparent)]
     Ed(#[from] ed::Error),
+    #[error("Event Error: {0}")]
+    Event(String),
+    #[error("Genesis Error: {0}")]
+    Genesis(String),
     #[error("Ibc Error: {0}")]
     Ibc(String),
     #[cfg(feature = "ibc")]
@@ -36,6 +42,8 @@ pub enum Error {
     InvalidID,
     #[error(transparent)]
     IO(#[from] std::io::Error),
+    #[error("Keyring Error: {0}")]
+    Keyring(String),
     #[cfg(feature = "merk-verify")]
     #[error(transparent)]
     Merk(#[from] merk::Error),
@@ -49,6 +57,9 @@ pub enum Error {
     ParseInt(#[from] std::num::ParseIntError),
     #[error("Poison Error: {0}")]
     Poison(String),
+    #[cfg(feature = "metrics")]
+    #[error(transparent)]
+    Prometheus(#[from] prometheus::Error),
     #[error("Tendermint Error: {0}")]
     Tendermint(String),
     #[cfg(feature = "abci")]
@@ -0,0 +1,208 @@
+//! Typed application events.
+//!
+//! Types implementing [Event] (usually with `#[derive(Event)]`) can be emitted
+//! from calls with [emit], which converts them into ABCI events with one
+//! attribute per field. Clients can decode them back from a transaction's
+//! [Receipt](crate::client::receipt::Receipt).
+//!
+//! ```ignore
+//! #[derive(Event)]
+//! #[event(kind = "transfer")]
+//! pub struct TransferEvent {
+//!     #[event(index)]
+//!     pub from: Address,
+//!     #[event(index)]
+//!     pub to: Address,
+//!     pub amount: Amount,
+//! }
+//! ```
+//!
+//! The event kind defaults to the snake-cased name of the type. Fields marked
+//! with `#[event(index)]` are indexed by the node, so transactions can be
+//! searched by their value.
+
+use crate::coins::{Address, Amount, Decimal};
+use crate::context::Context;
+use crate::plugins::Events;
+use crate::{Error, Result};
+use std::str::FromStr;
+use tendermint_proto::v0_34::abci::{Event as AbciEvent, EventAttribute};
+
+pub use orga_macros::Event;
+
+/// A typed event which can be converted to and from an ABCI event.
+pub trait Event: Sized {
+    /// The ABCI event type.
+    const KIND: &'static str;
+
+    fn attributes(&self) -> Vec<Attribute>;
+
+    fn from_attributes(attributes: &[(String, String)]) -> Result<Self>;
+
+    fn to_abci(&self) -> AbciEvent {
+        AbciEvent {
+            r#type: Self::KIND.to_string(),
+            attributes: self
+                .attributes()
+                .into_iter()
+                .map(|attr| EventAttribute {
+                    key: attr.key.into(),
+                    value: attr.value.into(),
+                    index: attr.index,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// An attribute of an [Event], with its value encoded as a string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    pub key: &'static str,
+    pub value: String,
+    pub index: bool,
+}
+
+/// Adds the event to the events of the current call, if any. Events emitted
+/// by a call which fails are discarded along with its state changes.
+pub fn emit<E: Event>(event: &E) {
+    if let Some(events) = Context::resolve::<Events>() {
+        events.add(event.to_abci());
+    }
+}
+
+/// Returns the value of the attribute with the given key, or an error if it is
+/// missing.
+pub fn find_attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Result<&'a str> {
+    attributes
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .ok_or_else(|| Error::Event(format!("Missing attribute {}", key)))
+}
+
+/// A value which can be used as an [Event] attribute.
+pub trait EventValue: Sized {
+    fn to_event_value(&self) -> String;
+
+    fn from_event_value(value: &str) -> Result<Self>;
+}
+
+macro_rules! from_str_value {
+    ($($type:ty),*) => {
+        $(
+            impl EventValue for $type {
+                fn to_event_value(&self) -> String {
+                    self.to_string()
+                }
+
+                fn from_event_value(value: &str) -> Result<Self> {
+                    <$type>::from_str(value).map_err(|err| {
+                        Error::Event(format!(
+                            "Invalid {} value {:?}: {}",
+                            stringify!($type),
+                            value,
+                            err
+                        ))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+from_str_value!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, bool, String, Address, Decimal);
+
+impl EventValue for Amount {
+    fn to_event_value(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_event_value(value: &str) -> Result<Self> {
+        u64::from_event_value(value).map(Amount::new)
+    }
+}
+
+/// Byte strings are encoded as hex.
+impl EventValue for Vec<u8> {
+    fn to_event_value(&self) -> String {
+        hex::encode(self)
+    }
+
+    fn from_event_value(value: &str) -> Result<Self> {
+        hex::decode(value).map_err(|err| Error::Event(format!("Invalid hex value: {}", err)))
+    }
+}
+
+/// `None` is encoded as an empty string.
+impl<T: EventValue> EventValue for Option<T> {
+    fn to_event_value(&self) -> String {
+        self.as_ref()
+            .map(EventValue::to_event_value)
+            .unwrap_or_default()
+    }
+
+    fn from_event_value(value: &str) -> Result<Self> {
+        if value.is_empty() {
+            Ok(None)
+        } else {
+            T::from_event_value(value).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::receipt;
+
+    #[derive(Event, Debug, PartialEq)]
+    struct Swap {
+        #[event(index)]
+        trader: Address,
+        amount: Amount,
+        price: Decimal,
+        memo: Option<String>,
+        hash: Vec<u8>,
+    }
+
+    #[derive(Event, Debug, PartialEq)]
+    #[event(kind = "custom")]
+    struct Other {
+        value: u64,
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let swap = Swap {
+            trader: Address::from_pubkey([2; 33]),
+            amount: 123.into(),
+            price: "1.5".parse()?,
+            memo: None,
+            hash: vec![0xab, 0xcd],
+        };
+
+        let abci = swap.to_abci();
+        assert_eq!(abci.r#type, "swap");
+        assert!(abci.attributes[0].index);
+        assert!(!abci.attributes[1].index);
+
+        let event: receipt::Event = abci.into();
+        assert_eq!(event.attribute("hash"), Some("abcd"));
+        assert_eq!(event.attribute("memo"), Some(""));
+        assert_eq!(event.decode::<Swap>()?, Some(swap));
+        assert_eq!(event.decode::<Other>()?, None);
+
+        let other: receipt::Event = Other { value: 1 }.to_abci().into();
+        assert_eq!(other.kind, "custom");
+        assert_eq!(other.decode::<Other>()?, Some(Other { value: 1 }));
+
+        let invalid = receipt::Event {
+            kind: "custom".into(),
+            attributes: vec![("value".into(), "x".into())],
+        };
+        assert!(invalid.decode::<Other>().is_err());
+
+        Ok(())
+    }
+}
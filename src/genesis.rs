@@ -0,0 +1,117 @@
+//! Declarative genesis initialization.
+//!
+//! Modules implementing [Genesis] read their initial state from a section of
+//! the `app_state` in genesis.json, keyed by the name the app gives them. An
+//! app's `init_chain` then only needs to route each section to its module:
+//!
+//! ```ignore
+//! impl InitChain for App {
+//!     fn init_chain(&mut self, ctx: &InitChainCtx) -> Result<()> {
+//!         self.accounts.init_genesis(ctx, "accounts")?;
+//!         self.staking.init_genesis(ctx, "staking")
+//!     }
+//! }
+//! ```
+//!
+//! with a genesis such as:
+//!
+//! ```json
+//! "app_state": {
+//!   "accounts": {
+//!     "balances": [{ "address": "nomic1...", "amount": 100000000 }]
+//!   },
+//!   "staking": { "max_validators": 100, "gentxs": [] }
+//! }
+//! ```
+//!
+//! Sections are validated before they are applied, and the same validation
+//! can be run ahead of a launch with [validate_section].
+
+use crate::plugins::InitChainCtx;
+use crate::{Error, Result};
+use serde::de::DeserializeOwned;
+
+/// A module which can be initialized from a section of the genesis
+/// `app_state`.
+pub trait Genesis {
+    /// The section of the `app_state` read by this module.
+    type Config: DeserializeOwned;
+
+    /// Checks the config for the chain with the given ID, without modifying
+    /// any state.
+    fn validate_genesis(_config: &Self::Config, _chain_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writes the config into the module's state. The config has already been
+    /// validated.
+    fn apply_genesis(&mut self, config: Self::Config) -> Result<()>;
+
+    /// Validates and applies the `key` section of the genesis `app_state`, if
+    /// it is present.
+    fn init_genesis(&mut self, ctx: &InitChainCtx, key: &str) -> Result<()> {
+        let config = match section::<Self::Config>(&ctx.app_state_bytes, key)? {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+
+        Self::validate_genesis(&config, &ctx.chain_id)?;
+        self.apply_genesis(config)
+    }
+}
+
+/// Deserializes the `key` section of the given `app_state`. Returns `None` if
+/// the `app_state` is empty or does not contain the section.
+pub fn section<T: DeserializeOwned>(app_state_bytes: &[u8], key: &str) -> Result<Option<T>> {
+    if app_state_bytes.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+
+    let mut app_state: serde_json::Value = serde_json::from_slice(app_state_bytes)
+        .map_err(|err| Error::Genesis(format!("Invalid app_state: {}", err)))?;
+
+    match app_state.get_mut(key).map(serde_json::Value::take) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value)
+            .map(Some)
+            .map_err(|err| Error::Genesis(format!("Invalid {} genesis: {}", key, err))),
+    }
+}
+
+/// Runs the validation of [Genesis::init_genesis] on the `key` section of the
+/// given `app_state`, e.g. to check a genesis file before launching a network.
+pub fn validate_section<T: Genesis>(
+    app_state_bytes: &[u8],
+    key: &str,
+    chain_id: &str,
+) -> Result<()> {
+    match section::<T::Config>(app_state_bytes, key)? {
+        Some(config) => T::validate_genesis(&config, chain_id),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Foo {
+        bar: u32,
+    }
+
+    #[test]
+    fn sections() -> Result<()> {
+        let app_state = br#"{"foo":{"bar":1},"baz":null}"#;
+        assert_eq!(section::<Foo>(app_state, "foo")?, Some(Foo { bar: 1 }));
+        assert_eq!(section::<Foo>(app_state, "baz")?, None);
+        assert_eq!(section::<Foo>(app_state, "missing")?, None);
+        assert_eq!(section::<Foo>(b"", "foo")?, None);
+
+        assert!(section::<Foo>(br#"{"foo":{"bar":"x"}}"#, "foo").is_err());
+        assert!(section::<Foo>(b"not json", "foo").is_err());
+
+        Ok(())
+    }
+}
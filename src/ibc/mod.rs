@@ -30,8 +30,9 @@ use ibc_proto::protobuf::Protobuf;
 use ibc_rs::applications::transfer::msgs::transfer::MsgTransfer;
 use ibc_rs::clients::ics07_tendermint::client_type;
 use ibc_rs::core::ics02_client::msgs::ClientMsg;
+use ibc_rs::core::ics04_channel::msgs::PacketMsg;
 use ibc_rs::core::MsgEnvelope;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::coins::Address;
 use crate::collections::{Deque, Map};
@@ -40,6 +41,8 @@ use crate::describe::{Describe, Descriptor};
 use crate::encoding::{
     Adapter, ByteTerminatedString, Decode, Encode, EofTerminatedString, FixedString,
 };
+use crate::events::emit;
+use crate::genesis::Genesis;
 use crate::migrate::{Migrate, MigrateInto};
 use crate::plugins::Signer;
 use crate::query::Query;
@@ -51,7 +54,7 @@ use ibc::core::timestamp::Timestamp as IbcTimestamp;
 
 mod impls;
 pub mod transfer;
-use transfer::{Transfer, TransferInfo};
+use transfer::{IbcTransferEvent, Transfer, TransferGenesis, TransferInfo};
 #[cfg(feature = "abci")]
 mod service;
 #[cfg(feature = "abci")]
@@ -158,20 +161,38 @@ impl Ibc {
             ));
         }
 
+        let event = IbcTransferEvent {
+            direction: "send".into(),
+            channel: message.chan_id_on_a.to_string(),
+            sender: message.packet_data.sender.to_string(),
+            receiver: message.packet_data.receiver.to_string(),
+            denom: message.packet_data.token.denom.to_string(),
+            amount: message.packet_data.token.amount.to_string(),
+        };
         self.deliver_message(IbcMessage::Ics20(message))?;
+        emit(&event);
 
         Ok(())
     }
 
     pub fn deliver_message(&mut self, message: IbcMessage) -> crate::Result<Option<TransferInfo>> {
         let mut maybe_client_update = None;
+        let mut recv_channel = None;
+        // drop any transfer left over from a packet which failed to process
+        self.transfer_mut().incoming_transfer_mut().take();
 
         use IbcMessage::*;
 
         match message {
             Ics26(msg) => {
-                if let MsgEnvelope::Client(ClientMsg::UpdateClient(msg)) = &msg {
-                    maybe_client_update = Some(msg.clone());
+                match &msg {
+                    MsgEnvelope::Client(ClientMsg::UpdateClient(msg)) => {
+                        maybe_client_update = Some(msg.clone());
+                    }
+                    MsgEnvelope::Packet(PacketMsg::Recv(msg)) => {
+                        recv_channel = Some(msg.packet.chan_id_on_b.clone());
+                    }
+                    _ => {}
                 }
                 dispatch(&mut self.ctx, &mut self.router, msg)
                     .map_err(|e| Error::Ibc(e.to_string()))?
@@ -197,7 +218,14 @@ impl Ibc {
             }
         }
 
-        Ok(self.transfer_mut().incoming_transfer_mut().take())
+        // the receive event is only emitted once the packet has been
+        // processed and its successful acknowledgement written
+        let incoming_transfer = self.transfer_mut().incoming_transfer_mut().take();
+        if let (Some(transfer), Some(channel)) = (&incoming_transfer, &recv_channel) {
+            emit(&IbcTransferEvent::received(channel, transfer));
+        }
+
+        Ok(incoming_transfer)
     }
 
     fn signer(&mut self) -> crate::Result<Address> {
@@ -216,6 +244,26 @@ impl Ibc {
     }
 }
 
+/// The genesis config of [Ibc]. Clients, connections and channels are not
+/// part of the genesis, and are created by relayers after launch.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IbcGenesis {
+    #[serde(default)]
+    pub transfer: TransferGenesis,
+}
+
+impl Genesis for Ibc {
+    type Config = IbcGenesis;
+
+    fn validate_genesis(config: &IbcGenesis, chain_id: &str) -> crate::Result<()> {
+        Transfer::validate_genesis(&config.transfer, chain_id)
+    }
+
+    fn apply_genesis(&mut self, config: IbcGenesis) -> crate::Result<()> {
+        self.transfer_mut().apply_genesis(config.transfer)
+    }
+}
+
 impl std::fmt::Debug for IbcContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("IbcContext").finish()
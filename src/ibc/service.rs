@@ -1,4 +1,6 @@
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use ibc::core::ics24_host::identifier::{ClientId, ConnectionId, PortId};
 use ibc::core::ics24_host::{identifier::ChannelId, path::ChannelEndPath};
@@ -22,6 +24,8 @@ use ibc_proto::cosmos::bank::v1beta1::{
     QuerySpendableBalancesResponse, QuerySupplyOfRequest, QuerySupplyOfResponse,
     QueryTotalSupplyRequest, QueryTotalSupplyResponse,
 };
+use ibc_proto::cosmos::base::abci::v1beta1::TxResponse;
+use ibc_proto::cosmos::base::query::v1beta1::PageResponse;
 use ibc_proto::cosmos::base::v1beta1::Coin;
 use ibc_proto::ibc::core::connection::v1::{
     QueryConnectionParamsRequest, QueryConnectionParamsResponse,
@@ -102,6 +106,9 @@ use prost::Message;
 use tendermint_proto::p2p::DefaultNodeInfo;
 use tonic::{Request, Response, Status};
 
+use crate::abci::config::NodeConfig;
+use crate::abci::indexer::{EventQuery, IndexedTx, Page, TxIndex};
+use crate::abci::Node;
 use crate::client::Client;
 
 use super::{IbcContext, PortChannel};
@@ -878,7 +885,28 @@ impl HealthService for AppHealthService {
     }
 }
 
-pub struct AppTxService {}
+pub struct AppTxService {
+    tx_index: Option<Arc<TxIndex>>,
+}
+
+impl AppTxService {
+    fn tx_index(&self) -> Result<&TxIndex, Status> {
+        self.tx_index
+            .as_deref()
+            .ok_or_else(|| Status::unimplemented("Transaction index is not enabled"))
+    }
+}
+
+fn tx_response(tx: IndexedTx) -> TxResponse {
+    TxResponse {
+        height: tx.height as i64,
+        txhash: hex::encode_upper(&tx.hash),
+        code: tx.code,
+        raw_log: tx.log,
+        info: tx.call.unwrap_or_default(),
+        ..Default::default()
+    }
+}
 
 #[tonic::async_trait]
 impl TxService for AppTxService {
@@ -894,9 +922,20 @@ impl TxService for AppTxService {
 
     async fn get_tx(
         &self,
-        _request: Request<GetTxRequest>,
+        request: Request<GetTxRequest>,
     ) -> Result<Response<GetTxResponse>, Status> {
-        unimplemented!()
+        let hash = hex::decode(&request.get_ref().hash)
+            .map_err(|_| Status::invalid_argument("Invalid transaction hash"))?;
+        let tx = self
+            .tx_index()?
+            .tx(&hash)
+            .map_err(|err| Status::internal(err.to_string()))?
+            .ok_or_else(|| Status::not_found("Transaction not found"))?;
+
+        Ok(Response::new(GetTxResponse {
+            tx: None,
+            tx_response: Some(tx_response(tx)),
+        }))
     }
 
     async fn broadcast_tx(
@@ -908,9 +947,36 @@ impl TxService for AppTxService {
 
     async fn get_txs_event(
         &self,
-        _request: Request<GetTxsEventRequest>,
+        request: Request<GetTxsEventRequest>,
     ) -> Result<Response<GetTxsEventResponse>, Status> {
-        unimplemented!()
+        let request = request.into_inner();
+        let queries = request
+            .events
+            .iter()
+            .map(|event| event.parse())
+            .collect::<crate::Result<Vec<EventQuery>>>()
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let mut page = Page::default();
+        if let Some(pagination) = request.pagination {
+            page.offset = pagination.offset;
+            if pagination.limit > 0 {
+                page.limit = pagination.limit;
+            }
+        }
+
+        let res = self
+            .tx_index()?
+            .txs_by_events(&queries, page)
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+
+        Ok(Response::new(GetTxsEventResponse {
+            tx_responses: res.txs.into_iter().map(tx_response).collect(),
+            pagination: Some(PageResponse {
+                next_key: vec![],
+                total: res.total,
+            }),
+            ..Default::default()
+        }))
     }
 
     async fn get_block_with_txs(
@@ -921,10 +987,51 @@ impl TxService for AppTxService {
     }
 }
 
+#[derive(Default)]
 pub struct GrpcOpts {
     pub host: String,
     pub port: u16,
     pub chain_id: String,
+    /// The path of the node's transaction index, see
+    /// [`Node::tx_index_path`](crate::abci::Node::tx_index_path), used to serve
+    /// `GetTx` and `GetTxsEvent`.
+    pub tx_index: Option<PathBuf>,
+}
+
+impl GrpcOpts {
+    pub fn new(host: String, port: u16, chain_id: String) -> Self {
+        Self {
+            host,
+            port,
+            chain_id,
+            tx_index: None,
+        }
+    }
+
+    /// The options for the gRPC server configured in the `[grpc]` section of
+    /// the node's `orga.toml`, serving transaction queries if the node keeps
+    /// a transaction index.
+    pub fn from_config<P: AsRef<Path>>(home: P, config: &NodeConfig, chain_id: String) -> Self {
+        let opts = Self::new(
+            config.grpc.addr.ip().to_string(),
+            config.grpc.addr.port(),
+            chain_id,
+        );
+        if config.storage.tx_index {
+            return opts.tx_index(Node::<()>::tx_index_path(home));
+        }
+
+        opts
+    }
+
+    /// Serves transaction queries from the node's transaction index at the
+    /// given path.
+    #[must_use]
+    pub fn tx_index<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.tx_index = Some(path.into());
+
+        self
+    }
 }
 
 pub async fn start_grpc<C: Client<IbcContext> + 'static>(client: fn() -> C, opts: &GrpcOpts) {
@@ -944,7 +1051,12 @@ pub async fn start_grpc<C: Client<IbcContext> + 'static>(client: fn() -> C, opts
         revision_number,
     });
     let health_service = HealthServer::new(AppHealthService {});
-    let tx_service = TxServer::new(AppTxService {});
+    let tx_index = opts.tx_index.as_ref().map(|path| {
+        let index = TxIndex::open_reader(path, path.with_extension("grpc"))
+            .expect("Failed to open transaction index");
+        Arc::new(index)
+    });
+    let tx_service = TxServer::new(AppTxService { tx_index });
     Server::builder()
         .add_service(health_service)
         .add_service(tx_service)
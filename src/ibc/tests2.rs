@@ -157,6 +157,7 @@ async fn start_grpc_server() {
                 &GrpcOpts {
                     host: "127.0.0.1".to_string(),
                     port: 9001,
+                    ..Default::default()
                 },
             )
             .await
@@ -288,6 +289,7 @@ async fn ibc_app() -> Result<()> {
                 &GrpcOpts {
                     host: "127.0.0.1".to_string(),
                     port: 9001,
+                    ..Default::default()
                 },
             )
             .await;
@@ -3,6 +3,8 @@ use crate::{
     collections::Map,
     describe::{Builder, Describe},
     encoding::LengthVec,
+    events::Event,
+    genesis::Genesis,
     orga,
     state::State,
 };
@@ -33,6 +35,8 @@ use ibc::{
     },
     Signer,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 const ACCOUNT_PREFIX: &str = "nomic"; // TODO: configurable prefix
 impl From<TokenTransferError> for crate::Error {
     fn from(err: TokenTransferError) -> Self {
@@ -135,6 +139,56 @@ impl TokenTransferValidationContext for Transfer {
 
 type Denom = LengthVec<u8, u8>;
 
+/// The genesis config of [Transfer], e.g. to carry over IBC token balances
+/// when relaunching a network. Balances are minted, so vouchers should match
+/// tokens escrowed on their source chains.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TransferGenesis {
+    #[serde(default)]
+    pub balances: Vec<TransferBalance>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransferBalance {
+    /// The full denom, including its trace, e.g. `transfer/channel-0/uatom`.
+    pub denom: String,
+    pub address: Address,
+    pub amount: Amount,
+}
+
+impl Genesis for Transfer {
+    type Config = TransferGenesis;
+
+    fn validate_genesis(config: &TransferGenesis, _chain_id: &str) -> crate::Result<()> {
+        let mut seen = HashSet::new();
+        for balance in config.balances.iter() {
+            let _: PrefixedDenom = balance.denom.parse().map_err(|_| {
+                crate::Error::Genesis(format!("Invalid IBC denom {}", balance.denom))
+            })?;
+            let _: Denom = balance.denom.as_str().try_into()?;
+            if !seen.insert((balance.denom.as_str(), balance.address)) {
+                return Err(crate::Error::Genesis(format!(
+                    "Duplicate {} balance for {}",
+                    balance.denom, balance.address
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_genesis(&mut self, config: TransferGenesis) -> crate::Result<()> {
+        for balance in config.balances {
+            let denom: Denom = balance.denom.as_str().try_into()?;
+            let mut denom_balances = self.accounts.entry(denom)?.or_default()?;
+            let mut account_balance = denom_balances.entry(balance.address)?.or_default()?;
+            *account_balance = (*account_balance + balance.amount).result()?;
+        }
+
+        Ok(())
+    }
+}
+
 impl TryFrom<PrefixedDenom> for Denom {
     type Error = crate::Error;
 
@@ -442,6 +496,37 @@ pub struct TransferInfo {
     pub memo: String,
 }
 
+/// Emitted for outgoing ICS-20 transfers sent with
+/// [Ibc::raw_transfer](super::Ibc::raw_transfer) and for incoming transfers of
+/// tokens native to this chain. `direction` is `"send"` or `"receive"`, and
+/// `channel` is the channel on this chain.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+#[event(kind = "ibc_transfer")]
+pub struct IbcTransferEvent {
+    pub direction: String,
+    #[event(index)]
+    pub channel: String,
+    #[event(index)]
+    pub sender: String,
+    #[event(index)]
+    pub receiver: String,
+    pub denom: String,
+    pub amount: String,
+}
+
+impl IbcTransferEvent {
+    pub(crate) fn received(channel: &ChannelId, transfer: &TransferInfo) -> Self {
+        Self {
+            direction: "receive".into(),
+            channel: channel.to_string(),
+            sender: transfer.sender.clone(),
+            receiver: transfer.receiver.clone(),
+            denom: transfer.denom.to_string(),
+            amount: transfer.amount.to_string(),
+        }
+    }
+}
+
 impl Describe for TransferInfo {
     fn describe() -> orga::describe::Descriptor {
         Builder::new::<()>().build()
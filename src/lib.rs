@@ -25,6 +25,10 @@ pub mod call;
 
 pub mod client;
 
+/// Command-line interface generated from an app's call and query methods.
+#[cfg(feature = "cli")]
+pub mod cli;
+
 /// Data structures which implement the [`state::State`](state/trait.State.html)
 /// trait.
 pub mod collections;
@@ -37,12 +41,21 @@ pub mod describe;
 /// crate.
 pub mod encoding;
 
+pub mod events;
+
+pub mod genesis;
+
 /// Integration with [merk](https://docs.rs/merk) (gated by `merk` feature).
 #[cfg(feature = "merk-verify")]
 pub mod merk;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
 pub mod query;
 
+pub mod scheduler;
+
 /// High-level abstractions for state data.
 pub mod state;
 
@@ -20,3 +20,14 @@ pub use proofbuilder::ProofBuilder;
 pub use proofstore::ProofStore;
 #[cfg(feature = "merk-full")]
 pub use store::MerkStore;
+
+/// Computes the app hash reported to Tendermint for the given Merk root hash.
+pub fn calc_app_hash(merk_root: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha512_256};
+
+    let mut hasher = Sha512_256::new();
+    hasher.update(b"ibc");
+    hasher.update(merk_root);
+
+    hasher.finalize().to_vec()
+}
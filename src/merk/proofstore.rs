@@ -5,6 +5,17 @@ use std::ops::Bound;
 
 pub struct ProofStore(pub ProofMap);
 
+impl ProofStore {
+    /// Verifies a merk proof against the given root hash, returning a store
+    /// containing the proven entries.
+    pub fn verify(root_hash: [u8; 32], proof_bytes: &[u8]) -> Result<Store> {
+        let map = merk::proofs::query::verify(proof_bytes, root_hash)?;
+        let store = Shared::new(ProofStore(map));
+
+        Ok(Store::new(BackingStore::ProofMap(store)))
+    }
+}
+
 impl Read for ProofStore {
     fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let maybe_value = self.0.get(key).map_err(|err| {
@@ -1,8 +1,9 @@
 use crate::store::Read;
 use crate::Result;
 use merk::{Hash, Merk};
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use tendermint_proto::v0_34::abci::{RequestLoadSnapshotChunk, Snapshot as AbciSnapshot};
@@ -32,6 +33,10 @@ impl Snapshot {
         })
     }
 
+    pub fn root_hash(&self) -> Hash {
+        self.hash
+    }
+
     fn chunk(&self, index: usize) -> Result<Vec<u8>> {
         let checkpoint = self.checkpoint.borrow();
         // TODO: refactor ChunkProducer in Merk to not retain reference to db,
@@ -60,6 +65,11 @@ impl Read for Snapshot {
     }
 }
 
+/// Selects the heights at which snapshots are created and how long they are
+/// kept. In configuration files, filters are written as tables tagged with
+/// their `type`, e.g. `{ type = "interval", interval = 100, limit = 50 }`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum SnapshotFilter {
     Interval {
         interval: u64,
@@ -67,6 +77,7 @@ pub enum SnapshotFilter {
     },
     SpecificHeight {
         height: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         keep_until: Option<u64>,
     },
 }
@@ -76,6 +87,11 @@ impl SnapshotFilter {
         SnapshotFilter::Interval { interval, limit }
     }
 
+    /// Keeps a snapshot of each of the last `count` heights.
+    pub fn recent(count: u64) -> Self {
+        SnapshotFilter::interval(1, count)
+    }
+
     pub fn specific_height(height: u64, keep_until: Option<u64>) -> Self {
         SnapshotFilter::SpecificHeight { height, keep_until }
     }
@@ -99,6 +115,132 @@ impl SnapshotFilter {
     }
 }
 
+/// The maximum number of history checkpoints kept open at once. Others are
+/// opened on demand, evicting the least recently used.
+pub const MAX_OPEN_CHECKPOINTS: usize = 8;
+
+/// On-disk checkpoints of past heights, retained for historical queries.
+///
+/// Unlike [Snapshots], checkpoints are not opened when loaded: each is opened
+/// the first time it is read, and at most [MAX_OPEN_CHECKPOINTS] stay open.
+#[derive(Default)]
+pub struct History {
+    heights: BTreeSet<u64>,
+    open: RefCell<VecDeque<(u64, Snapshot)>>,
+    filters: Vec<SnapshotFilter>,
+    path: PathBuf,
+}
+
+impl History {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            std::fs::create_dir(path)?;
+        }
+
+        let mut heights = BTreeSet::new();
+        for entry in path.read_dir()? {
+            let entry = entry?;
+            let height_str = entry.file_name();
+            let height: u64 = height_str.to_str().unwrap_or_default().parse()?;
+            heights.insert(height);
+        }
+
+        Ok(Self {
+            heights,
+            open: RefCell::default(),
+            filters: vec![],
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn with_filters(mut self, filters: Vec<SnapshotFilter>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Returns the checkpoint at the given height, opening it if it is not
+    /// already open.
+    pub fn get(&self, height: u64) -> Result<Option<Snapshot>> {
+        if !self.heights.contains(&height) {
+            return Ok(None);
+        }
+
+        let mut open = self.open.borrow_mut();
+        if let Some(index) = open.iter().position(|(h, _)| *h == height) {
+            let entry = open.remove(index).unwrap();
+            let snapshot = entry.1.clone();
+            open.push_back(entry);
+            return Ok(Some(snapshot));
+        }
+
+        // TODO: open read-only
+        let snapshot = Snapshot::new(Merk::open(self.path(height))?)?;
+        Self::insert_open(&mut open, height, snapshot.clone());
+
+        Ok(Some(snapshot))
+    }
+
+    /// The number of checkpoints currently open.
+    pub fn open_count(&self) -> usize {
+        self.open.borrow().len()
+    }
+
+    pub fn heights(&self) -> impl Iterator<Item = u64> + '_ {
+        self.heights.iter().copied()
+    }
+
+    pub fn should_create(&self, height: u64) -> bool {
+        height > 0 && self.filters.iter().any(|f| f.should_create(height))
+    }
+
+    pub fn should_keep(&self, ss_height: u64, cur_height: u64) -> bool {
+        self.filters
+            .iter()
+            .any(|f| f.should_keep(ss_height, cur_height))
+    }
+
+    pub fn create(&mut self, height: u64, checkpoint: Merk) -> Result<()> {
+        if self.heights.insert(height) {
+            let snapshot = Snapshot::new(checkpoint)?;
+            Self::insert_open(self.open.get_mut(), height, snapshot);
+        }
+
+        self.maybe_prune(height)
+    }
+
+    pub fn maybe_prune(&mut self, cur_height: u64) -> Result<()> {
+        let remove_heights = self
+            .heights
+            .iter()
+            .copied()
+            .filter(|ss_height| !self.should_keep(*ss_height, cur_height))
+            .collect::<Vec<_>>();
+
+        for ss_height in remove_heights {
+            self.heights.remove(&ss_height);
+            self.open.get_mut().retain(|(h, _)| *h != ss_height);
+
+            let path = self.path(ss_height);
+            if path.exists() {
+                std::fs::remove_dir_all(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn path(&self, height: u64) -> PathBuf {
+        self.path.join(height.to_string())
+    }
+
+    fn insert_open(open: &mut VecDeque<(u64, Snapshot)>, height: u64, snapshot: Snapshot) {
+        open.push_back((height, snapshot));
+        while open.len() > MAX_OPEN_CHECKPOINTS {
+            open.pop_front();
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Snapshots {
     snapshots: BTreeMap<u64, Snapshot>,
@@ -229,3 +371,26 @@ impl Snapshots {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_recent() {
+        let filter = SnapshotFilter::recent(3);
+        assert!(filter.should_create(7));
+        assert!(filter.should_keep(8, 10));
+        assert!(!filter.should_keep(7, 10));
+    }
+
+    #[test]
+    fn filter_interval() {
+        let filter = SnapshotFilter::interval(10, 2);
+        assert!(filter.should_create(20));
+        assert!(!filter.should_create(25));
+        assert!(filter.should_keep(20, 39));
+        assert!(!filter.should_keep(20, 40));
+        assert!(!filter.should_keep(25, 26));
+    }
+}
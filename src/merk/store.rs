@@ -7,12 +7,15 @@ use std::path::{Path, PathBuf};
 use std::{collections::BTreeMap, convert::TryInto};
 use tendermint_proto::v0_34::abci::{self, *};
 
-use super::snapshot;
+use super::{calc_app_hash, snapshot};
 type Map = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
 
 pub const SNAPSHOT_INTERVAL: u64 = 1000;
 pub const FIRST_SNAPSHOT_HEIGHT: u64 = 2;
 
+/// The default number of recent heights kept in memory for queries.
+pub const DEFAULT_MEM_SNAPSHOT_LIMIT: usize = 20;
+
 /// A [`store::Store`] implementation backed by a [`merk`](https://docs.rs/merk)
 /// Merkle key/value store.
 pub struct MerkStore {
@@ -20,9 +23,11 @@ pub struct MerkStore {
     home: PathBuf,
     map: Option<Map>,
     snapshots: snapshot::Snapshots,
+    history: snapshot::History,
     restorer: Option<Restorer>,
     target_snapshot: Option<Snapshot>,
     mem_snapshots: BTreeMap<u64, StaticSnapshot>,
+    mem_snapshot_limit: usize,
 }
 
 impl MerkStore {
@@ -41,10 +46,12 @@ impl MerkStore {
             map: Some(Map::new()),
             merk: Some(merk),
             snapshots: Self::load_snapshots(home.join("snapshots")),
+            history: snapshot::History::default(),
             home,
             target_snapshot: None,
             restorer: None,
             mem_snapshots: BTreeMap::new(),
+            mem_snapshot_limit: DEFAULT_MEM_SNAPSHOT_LIMIT,
         }
     }
 
@@ -59,23 +66,30 @@ impl MerkStore {
             map: Some(Default::default()),
             merk: Some(merk),
             snapshots: snapshot::Snapshots::default(),
+            history: snapshot::History::default(),
             home,
             target_snapshot: None,
             restorer: None,
             mem_snapshots: BTreeMap::new(),
+            mem_snapshot_limit: DEFAULT_MEM_SNAPSHOT_LIMIT,
         }
     }
 
     fn load_snapshots<P: AsRef<Path>>(path: P) -> snapshot::Snapshots {
         snapshot::Snapshots::load(path.as_ref())
             .expect("Failed to load snapshots")
-            // TODO: make configurable
-            .with_filters(vec![
-                #[cfg(feature = "state-sync")]
-                snapshot::SnapshotFilter::specific_height(2, None),
-                #[cfg(feature = "state-sync")]
-                snapshot::SnapshotFilter::interval(1000, 4),
-            ])
+            .with_filters(Self::default_snapshot_filters())
+    }
+
+    /// The filters selecting the heights of state sync snapshots, unless
+    /// overridden with [MerkStore::with_snapshot_filters].
+    pub fn default_snapshot_filters() -> Vec<snapshot::SnapshotFilter> {
+        vec![
+            #[cfg(feature = "state-sync")]
+            snapshot::SnapshotFilter::specific_height(2, None),
+            #[cfg(feature = "state-sync")]
+            snapshot::SnapshotFilter::interval(1000, 4),
+        ]
     }
 
     pub fn init_from(
@@ -106,6 +120,49 @@ impl MerkStore {
         Ok(merk_store)
     }
 
+    /// Retains on-disk checkpoints of past heights selected by the given
+    /// filters, so that state can be read and proven at those heights after
+    /// they have left the in-memory snapshots. Checkpoints are created on
+    /// commit, pruned once no filter keeps them, and only opened when queried
+    /// (see [snapshot::MAX_OPEN_CHECKPOINTS]).
+    ///
+    /// Each checkpoint is a full RocksDB directory, so prefer sparse filters:
+    /// e.g. `SnapshotFilter::interval(100, 50)` keeps every 100th height for
+    /// the last 5000 blocks, while `SnapshotFilter::recent(20)` keeps each of
+    /// the last 20 heights.
+    pub fn with_history(mut self, filters: Vec<snapshot::SnapshotFilter>) -> Result<Self> {
+        let height = self.height()?;
+        self.history =
+            snapshot::History::load(self.path("history").as_path())?.with_filters(filters);
+        self.history.maybe_prune(height)?;
+
+        Ok(self)
+    }
+
+    /// Sets the filters selecting the heights at which state sync snapshots
+    /// are created and how long they are kept.
+    #[must_use]
+    pub fn with_snapshot_filters(mut self, filters: Vec<snapshot::SnapshotFilter>) -> Self {
+        self.snapshots = std::mem::take(&mut self.snapshots).with_filters(filters);
+
+        self
+    }
+
+    /// Sets the number of recent heights kept in memory, which can be queried
+    /// without an on-disk checkpoint. Defaults to
+    /// [DEFAULT_MEM_SNAPSHOT_LIMIT].
+    #[must_use]
+    pub fn mem_snapshot_limit(mut self, limit: usize) -> Self {
+        self.mem_snapshot_limit = limit.max(1);
+
+        self
+    }
+
+    /// The on-disk checkpoints retained for historical queries.
+    pub fn history(&self) -> &snapshot::History {
+        &self.history
+    }
+
     fn path<T: ToString>(&self, name: T) -> PathBuf {
         self.home.join(name.to_string())
     }
@@ -129,6 +186,13 @@ impl MerkStore {
             .apply(batch.as_ref(), aux_batch.as_ref())?)
     }
 
+    /// Applies the pending writes to the tree along with the block height,
+    /// so the root hash reflects the block before it is committed.
+    pub fn apply_block(&mut self, height: u64) -> Result<()> {
+        let metadata = vec![(b"height".to_vec(), Some(height.to_be_bytes().to_vec()))];
+        self.write(metadata)
+    }
+
     pub fn merk(&self) -> &Merk {
         self.merk.as_ref().unwrap()
     }
@@ -157,19 +221,34 @@ fn to_batch<I: IntoIterator<Item = (Vec<u8>, Option<Vec<u8>>)>>(i: I) -> Vec<Bat
 impl Read for MerkStore {
     /// Gets a value from the underlying `Merk` store.
     fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        match self.map.as_ref().unwrap().get(key) {
-            Some(Some(value)) => Ok(Some(value.clone())),
-            Some(None) => Ok(None),
-            None => Ok(self.merk.as_ref().unwrap().get(key)?),
-        }
+        let value = match self.map.as_ref().unwrap().get(key) {
+            Some(Some(value)) => Some(value.clone()),
+            Some(None) => None,
+            None => self.merk.as_ref().unwrap().get(key)?,
+        };
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_read(value.as_ref().map_or(0, Vec::len));
+
+        Ok(value)
     }
 
     fn get_next(&self, start: &[u8]) -> Result<Option<KV>> {
-        get_next(self.merk().raw_iter(), start)
+        let entry = get_next(self.merk().raw_iter(), start)?;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_read(entry.as_ref().map_or(0, |(k, v)| k.len() + v.len()));
+
+        Ok(entry)
     }
 
     fn get_prev(&self, end: Option<&[u8]>) -> Result<Option<KV>> {
-        get_prev(self.merk().raw_iter(), end)
+        let entry = get_prev(self.merk().raw_iter(), end)?;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_read(entry.as_ref().map_or(0, |(k, v)| k.len() + v.len()));
+
+        Ok(entry)
     }
 }
 
@@ -240,27 +319,23 @@ pub(crate) fn get_prev(
 impl Write for MerkStore {
     /// Writes a value to the underlying `Merk` store.
     fn put(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_write(key.len() + value.len());
+
         self.map.as_mut().unwrap().insert(key, Some(value));
         Ok(())
     }
 
     /// Deletes a value from the underlying `Merk` store.
     fn delete(&mut self, key: &[u8]) -> Result<()> {
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_write(key.len());
+
         self.map.as_mut().unwrap().insert(key.to_vec(), None);
         Ok(())
     }
 }
 
-fn calc_app_hash(merk_root: &[u8]) -> Vec<u8> {
-    use sha2::{Digest, Sha512_256};
-
-    let mut hasher = Sha512_256::new();
-    hasher.update(b"ibc");
-    hasher.update(merk_root);
-
-    hasher.finalize().to_vec()
-}
-
 impl ABCIStore for MerkStore {
     fn height(&self) -> Result<u64> {
         let maybe_bytes = self.merk().get_aux(b"height")?;
@@ -277,13 +352,16 @@ impl ABCIStore for MerkStore {
     }
 
     fn commit(&mut self, header: tendermint_proto::v0_34::types::Header) -> Result<()> {
-        let height = header.height as u64;
-        let height_bytes = height.to_be_bytes();
-
-        let metadata = vec![(b"height".to_vec(), Some(height_bytes.to_vec()))];
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::merk_commit_timer();
 
-        self.write(metadata)?;
-        self.merk.as_mut().unwrap().flush()?;
+        let height = header.height as u64;
+        self.apply_block(height)?;
+        {
+            #[cfg(feature = "metrics")]
+            let _timer = crate::metrics::merk_flush_timer();
+            self.merk.as_mut().unwrap().flush()?;
+        }
 
         let recent = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -294,16 +372,27 @@ impl ABCIStore for MerkStore {
 
         #[cfg(feature = "state-sync")]
         if recent && self.snapshots.should_create(height) {
+            #[cfg(feature = "metrics")]
+            let _timer = crate::metrics::snapshot_timer("state_sync");
             let path = self.snapshots.path(height);
             let checkpoint = self.merk().checkpoint(path)?;
             self.snapshots.create(height, checkpoint)?;
         }
 
+        if self.history.should_create(height) {
+            #[cfg(feature = "metrics")]
+            let _timer = crate::metrics::snapshot_timer("history");
+            let path = self.history.path(height);
+            let checkpoint = self.merk().checkpoint(path)?;
+            self.history.create(height, checkpoint)?;
+        } else {
+            self.history.maybe_prune(height)?;
+        }
+
         let snapshot = self.merk().snapshot()?.staticize();
         self.mem_snapshots.insert(height, snapshot);
 
-        // TODO: parameterize
-        while self.mem_snapshots.len() > 20 {
+        while self.mem_snapshots.len() > self.mem_snapshot_limit {
             let ss = self.mem_snapshots.pop_first().unwrap();
             let db = self.merk().db();
             unsafe { ss.1.drop(db) };
@@ -398,3 +487,76 @@ fn read_u64(bytes: &[u8]) -> u64 {
     array.copy_from_slice(bytes);
     u64::from_be_bytes(array)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::snapshot::SnapshotFilter;
+    use super::*;
+    use tempdir::TempDir;
+    use tendermint_proto::google::protobuf::Timestamp;
+    use tendermint_proto::v0_34::types::Header;
+
+    fn commit(store: &mut MerkStore, height: i64, value: u8) {
+        store.put(vec![1], vec![value]).unwrap();
+        store
+            .commit(Header {
+                height,
+                time: Some(Timestamp::default()),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn history_retention() {
+        let home = TempDir::new("merk-history").unwrap();
+        let mut store = MerkStore::new(home.path())
+            .with_history(vec![SnapshotFilter::recent(2)])
+            .unwrap();
+
+        for height in 1..=4 {
+            commit(&mut store, height, height as u8 * 10);
+        }
+
+        let heights: Vec<_> = store.history().heights().collect();
+        assert_eq!(heights, vec![3, 4]);
+        assert_eq!(
+            store.history().get(3).unwrap().unwrap().get(&[1]).unwrap(),
+            Some(vec![30])
+        );
+        assert!(!home.path().join("history/2").exists());
+
+        drop(store);
+        let store = MerkStore::new(home.path())
+            .with_history(vec![SnapshotFilter::recent(2)])
+            .unwrap();
+        assert_eq!(store.history().open_count(), 0);
+        assert_eq!(
+            store.history().get(4).unwrap().unwrap().get(&[1]).unwrap(),
+            Some(vec![40])
+        );
+        assert_eq!(store.history().open_count(), 1);
+        assert!(store.history().get(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn history_open_limit() {
+        let home = TempDir::new("merk-history-limit").unwrap();
+        let count = snapshot::MAX_OPEN_CHECKPOINTS as u64 + 4;
+        let mut store = MerkStore::new(home.path())
+            .with_history(vec![SnapshotFilter::recent(count)])
+            .unwrap();
+
+        for height in 1..=count {
+            commit(&mut store, height as i64, height as u8);
+        }
+        assert_eq!(store.history().heights().count(), count as usize);
+        assert_eq!(store.history().open_count(), snapshot::MAX_OPEN_CHECKPOINTS);
+
+        assert_eq!(
+            store.history().get(1).unwrap().unwrap().get(&[1]).unwrap(),
+            Some(vec![1])
+        );
+        assert_eq!(store.history().open_count(), snapshot::MAX_OPEN_CHECKPOINTS);
+    }
+}
@@ -0,0 +1,364 @@
+//! Prometheus metrics for nodes (gated by the `metrics` feature).
+//!
+//! The ABCI state machine, node and store record their metrics into a global
+//! registry, which is served in the Prometheus text format at `/metrics` when
+//! `metrics.enabled` is set in the node's `orga.toml`. The built-in metrics
+//! are:
+//!
+//! - `orga_abci_phase_seconds{phase}`: time spent handling each ABCI request
+//!   type, e.g. `begin_block`, `deliver_tx` or `check_tx`
+//! - `orga_block_seconds`: time from `BeginBlock` to the end of `Commit`
+//! - `orga_txs_total{phase, code}`: `DeliverTx` and `CheckTx` results by code
+//! - `orga_query_seconds{path}`: ABCI query latency, where `path` is `store`,
+//!   `custom` or `other` (see [query_timer])
+//! - `orga_store_reads_total`, `orga_store_read_bytes_total`,
+//!   `orga_store_writes_total`, `orga_store_write_bytes_total`: backing store
+//!   operations
+//! - `orga_merk_commit_seconds`, `orga_merk_flush_seconds`: time spent
+//!   committing blocks to Merk, and writing them to disk
+//! - `orga_snapshot_seconds{kind}`: time spent creating state sync and
+//!   history checkpoints
+//! - `orga_height`, `orga_app_hash{hash}`: the last committed block
+//!
+//! While the server is running, a [Metrics] handle is available in the
+//! context, so apps can register their own counters and gauges:
+//!
+//! ```ignore
+//! if let Some(metrics) = Context::resolve::<Metrics>() {
+//!     metrics.counter("swaps_total", "Number of executed swaps")?.inc();
+//! }
+//! ```
+
+use crate::context::Context;
+use crate::{Error, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramTimer, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+
+const NAMESPACE: &str = "orga";
+
+/// Histogram buckets for block phases, from 1ms to 30s.
+const PHASE_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+static METRICS: LazyLock<Metrics> =
+    LazyLock::new(|| Metrics::new().expect("Failed to register metrics"));
+
+/// The metrics registry, with the built-in node metrics. Clones share the
+/// same underlying registry.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    phase_seconds: HistogramVec,
+    block_seconds: Histogram,
+    block_start: Arc<Mutex<Option<Instant>>>,
+    txs: IntCounterVec,
+    query_seconds: HistogramVec,
+    store_reads: IntCounter,
+    store_read_bytes: IntCounter,
+    store_writes: IntCounter,
+    store_write_bytes: IntCounter,
+    merk_commit_seconds: Histogram,
+    merk_flush_seconds: Histogram,
+    snapshot_seconds: HistogramVec,
+    height: IntGauge,
+    app_hash: IntGaugeVec,
+    counters: Arc<Mutex<HashMap<String, IntCounter>>>,
+    gauges: Arc<Mutex<HashMap<String, Gauge>>>,
+}
+
+impl Metrics {
+    fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)?;
+
+        let histogram = |name: &str, help: &str| -> Result<Histogram> {
+            let histogram =
+                Histogram::with_opts(HistogramOpts::new(name, help).buckets(PHASE_BUCKETS.into()))?;
+            registry.register(Box::new(histogram.clone()))?;
+            Ok(histogram)
+        };
+        let histogram_vec = |name: &str, help: &str, label: &str| -> Result<HistogramVec> {
+            let histogram = HistogramVec::new(
+                HistogramOpts::new(name, help).buckets(PHASE_BUCKETS.into()),
+                &[label],
+            )?;
+            registry.register(Box::new(histogram.clone()))?;
+            Ok(histogram)
+        };
+        let counter = |name: &str, help: &str| -> Result<IntCounter> {
+            let counter = IntCounter::new(name, help)?;
+            registry.register(Box::new(counter.clone()))?;
+            Ok(counter)
+        };
+
+        let txs = IntCounterVec::new(
+            Opts::new(
+                "txs_total",
+                "Transactions handled, by phase and result code",
+            ),
+            &["phase", "code"],
+        )?;
+        registry.register(Box::new(txs.clone()))?;
+        let height = IntGauge::new("height", "The height of the last committed block")?;
+        registry.register(Box::new(height.clone()))?;
+        let app_hash = IntGaugeVec::new(
+            Opts::new("app_hash", "The app hash of the last committed block"),
+            &["hash"],
+        )?;
+        registry.register(Box::new(app_hash.clone()))?;
+
+        Ok(Self {
+            phase_seconds: histogram_vec(
+                "abci_phase_seconds",
+                "Time spent handling ABCI requests",
+                "phase",
+            )?,
+            block_seconds: histogram("block_seconds", "Time from BeginBlock to the end of Commit")?,
+            block_start: Default::default(),
+            txs,
+            query_seconds: histogram_vec("query_seconds", "ABCI query latency", "path")?,
+            store_reads: counter("store_reads_total", "Reads from the backing store")?,
+            store_read_bytes: counter(
+                "store_read_bytes_total",
+                "Bytes read from the backing store",
+            )?,
+            store_writes: counter("store_writes_total", "Writes to the backing store")?,
+            store_write_bytes: counter(
+                "store_write_bytes_total",
+                "Bytes written to the backing store",
+            )?,
+            merk_commit_seconds: histogram(
+                "merk_commit_seconds",
+                "Time spent committing blocks to Merk",
+            )?,
+            merk_flush_seconds: histogram(
+                "merk_flush_seconds",
+                "Time spent writing committed blocks to disk",
+            )?,
+            snapshot_seconds: histogram_vec(
+                "snapshot_seconds",
+                "Time spent creating snapshots",
+                "kind",
+            )?,
+            height,
+            app_hash,
+            counters: Default::default(),
+            gauges: Default::default(),
+            registry,
+        })
+    }
+
+    /// The global registry.
+    pub fn global() -> &'static Metrics {
+        &METRICS
+    }
+
+    /// Returns the app-defined counter with the given name, registering it on
+    /// first use. Names are prefixed with `orga_`.
+    pub fn counter(&self, name: &str, help: &str) -> Result<IntCounter> {
+        let mut counters = self.counters.lock().unwrap();
+        if let Some(counter) = counters.get(name) {
+            return Ok(counter.clone());
+        }
+
+        let counter = IntCounter::new(name, help)?;
+        self.registry.register(Box::new(counter.clone()))?;
+        counters.insert(name.to_string(), counter.clone());
+
+        Ok(counter)
+    }
+
+    /// Returns the app-defined gauge with the given name, registering it on
+    /// first use. Names are prefixed with `orga_`.
+    pub fn gauge(&self, name: &str, help: &str) -> Result<Gauge> {
+        let mut gauges = self.gauges.lock().unwrap();
+        if let Some(gauge) = gauges.get(name) {
+            return Ok(gauge.clone());
+        }
+
+        let gauge = Gauge::new(name, help)?;
+        self.registry.register(Box::new(gauge.clone()))?;
+        gauges.insert(name.to_string(), gauge.clone());
+
+        Ok(gauge)
+    }
+
+    /// Encodes all metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String> {
+        let mut buf = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+
+        String::from_utf8(buf).map_err(|err| Error::App(err.to_string()))
+    }
+}
+
+/// Starts timing an ABCI request of the given phase, observed when the
+/// returned timer is dropped.
+pub fn phase_timer(phase: &str) -> HistogramTimer {
+    METRICS
+        .phase_seconds
+        .with_label_values(&[phase])
+        .start_timer()
+}
+
+pub fn begin_block() {
+    *METRICS.block_start.lock().unwrap() = Some(Instant::now());
+}
+
+/// Records the result of a `DeliverTx` or `CheckTx` request.
+pub fn record_tx(phase: &str, code: u32) {
+    METRICS
+        .txs
+        .with_label_values(&[phase, code.to_string().as_str()])
+        .inc();
+}
+
+/// Records a committed block, completing the block timer started by
+/// [begin_block].
+pub fn record_commit(height: u64, app_hash: &[u8]) {
+    if let Some(start) = METRICS.block_start.lock().unwrap().take() {
+        METRICS.block_seconds.observe(start.elapsed().as_secs_f64());
+    }
+
+    METRICS.height.set(height as i64);
+    METRICS.app_hash.reset();
+    METRICS
+        .app_hash
+        .with_label_values(&[hex::encode(app_hash).as_str()])
+        .set(1);
+}
+
+/// Starts timing an ABCI query with the given path. Paths are reduced to a
+/// fixed set of labels so clients can't create unbounded series: `store` for
+/// state queries (an empty or `store/...` path), `custom` for `custom/...`
+/// paths, and `other` for anything else.
+pub fn query_timer(path: &str) -> HistogramTimer {
+    METRICS
+        .query_seconds
+        .with_label_values(&[query_label(path)])
+        .start_timer()
+}
+
+fn query_label(path: &str) -> &'static str {
+    let path = path.trim_start_matches('/');
+    if path.is_empty() || path.starts_with("store/") {
+        "store"
+    } else if path.starts_with("custom/") {
+        "custom"
+    } else {
+        "other"
+    }
+}
+
+pub fn record_read(bytes: usize) {
+    METRICS.store_reads.inc();
+    METRICS.store_read_bytes.inc_by(bytes as u64);
+}
+
+pub fn record_write(bytes: usize) {
+    METRICS.store_writes.inc();
+    METRICS.store_write_bytes.inc_by(bytes as u64);
+}
+
+pub fn merk_commit_timer() -> HistogramTimer {
+    METRICS.merk_commit_seconds.start_timer()
+}
+
+pub fn merk_flush_timer() -> HistogramTimer {
+    METRICS.merk_flush_seconds.start_timer()
+}
+
+/// Starts timing the creation of a snapshot, where `kind` is `state_sync` or
+/// `history`.
+pub fn snapshot_timer(kind: &str) -> HistogramTimer {
+    METRICS
+        .snapshot_seconds
+        .with_label_values(&[kind])
+        .start_timer()
+}
+
+/// Serves `GET /metrics` on the given address until the server fails, and
+/// makes the [Metrics] handle available in the context.
+pub async fn serve(addr: SocketAddr) -> Result<()> {
+    Context::add(Metrics::global().clone());
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|req| async { Ok::<_, Infallible>(handle(req)) }))
+    });
+
+    Server::try_bind(&addr)
+        .map_err(|err| Error::App(format!("Failed to bind metrics server: {}", err)))?
+        .serve(make_service)
+        .await
+        .map_err(|err| Error::App(format!("Metrics server failed: {}", err)))
+}
+
+fn handle(req: Request<Body>) -> Response<Body> {
+    let status = |status: StatusCode, body: String| {
+        Response::builder()
+            .status(status)
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        return status(StatusCode::NOT_FOUND, "Not found".into());
+    }
+
+    match Metrics::global().encode() {
+        Ok(body) => Response::builder()
+            .header("Content-Type", TextEncoder::new().format_type())
+            .body(Body::from(body))
+            .unwrap(),
+        Err(err) => status(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode() -> Result<()> {
+        record_tx("test", 0);
+        record_tx("test", 1);
+        record_tx("test", 1);
+        drop(query_timer(""));
+        drop(query_timer("/store/ibc/key"));
+        drop(query_timer("/anything/goes"));
+
+        let metrics = Metrics::global();
+        metrics.counter("test_swaps_total", "Swaps")?.inc_by(2);
+        metrics.counter("test_swaps_total", "Swaps")?.inc();
+        metrics.gauge("test_price", "Price")?.set(1.5);
+        assert!(metrics.counter("test_price", "Price").is_err());
+
+        let text = metrics.encode()?;
+        assert!(text.contains("orga_txs_total{code=\"1\",phase=\"test\"} 2"));
+        assert!(text.contains("orga_query_seconds_count{path=\"store\"}"));
+        assert!(text.contains("orga_query_seconds_count{path=\"other\"}"));
+        assert!(!text.contains("anything"));
+        assert!(text.contains("orga_test_swaps_total 3"));
+        assert!(text.contains("orga_test_price 1.5"));
+        assert!(text.contains("# TYPE orga_abci_phase_seconds histogram"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_labels() {
+        assert_eq!(query_label(""), "store");
+        assert_eq!(query_label("store/ibc/key"), "store");
+        assert_eq!(query_label("/custom/bank/balance"), "custom");
+        assert_eq!(query_label("/state/0102"), "other");
+    }
+}
@@ -1,4 +1,11 @@
-use crate::abci::{prost::Adapter, AbciQuery, App};
+use crate::abci::consensus::{
+    CommitInfo, ExtendedCommitInfo, RequestExtendVote, RequestPrepareProposal,
+    RequestProcessProposal, RequestVerifyVoteExtension,
+};
+use crate::abci::{
+    prost::Adapter, AbciQuery, App, ExtendVote, PrepareProposal, ProcessProposal,
+    VerifyVoteExtension,
+};
 use crate::call::Call;
 use crate::collections::{Entry, EntryMap, Map};
 use crate::context::Context;
@@ -181,6 +188,95 @@ impl From<RequestEndBlock> for EndBlockCtx {
     }
 }
 
+pub struct PrepareProposalCtx {
+    pub height: u64,
+    pub time: Option<Timestamp>,
+    pub max_tx_bytes: i64,
+    /// The candidate transactions from the mempool, which may be reordered,
+    /// removed, or added to. Transactions past `max_tx_bytes` are dropped.
+    pub txs: Vec<Vec<u8>>,
+    /// The previous block's votes, including their vote extensions.
+    pub local_last_commit: Option<ExtendedCommitInfo>,
+    pub misbehavior: Vec<Evidence>,
+    pub proposer_address: Vec<u8>,
+}
+
+impl From<RequestPrepareProposal> for PrepareProposalCtx {
+    fn from(req: RequestPrepareProposal) -> Self {
+        PrepareProposalCtx {
+            height: req.height as u64,
+            time: req.time,
+            max_tx_bytes: req.max_tx_bytes,
+            txs: req.txs.into_iter().map(|tx| tx.to_vec()).collect(),
+            local_last_commit: req.local_last_commit,
+            misbehavior: req.misbehavior,
+            proposer_address: req.proposer_address.to_vec(),
+        }
+    }
+}
+
+pub struct ProcessProposalCtx {
+    pub height: u64,
+    pub time: Option<Timestamp>,
+    pub hash: Vec<u8>,
+    pub txs: Vec<Vec<u8>>,
+    pub proposed_last_commit: Option<CommitInfo>,
+    pub misbehavior: Vec<Evidence>,
+    pub proposer_address: Vec<u8>,
+}
+
+impl From<RequestProcessProposal> for ProcessProposalCtx {
+    fn from(req: RequestProcessProposal) -> Self {
+        ProcessProposalCtx {
+            height: req.height as u64,
+            time: req.time,
+            hash: req.hash.to_vec(),
+            txs: req.txs.into_iter().map(|tx| tx.to_vec()).collect(),
+            proposed_last_commit: req.proposed_last_commit,
+            misbehavior: req.misbehavior,
+            proposer_address: req.proposer_address.to_vec(),
+        }
+    }
+}
+
+pub struct ExtendVoteCtx {
+    pub height: u64,
+    pub time: Option<Timestamp>,
+    pub hash: Vec<u8>,
+    pub txs: Vec<Vec<u8>>,
+    pub proposer_address: Vec<u8>,
+}
+
+impl From<RequestExtendVote> for ExtendVoteCtx {
+    fn from(req: RequestExtendVote) -> Self {
+        ExtendVoteCtx {
+            height: req.height as u64,
+            time: req.time,
+            hash: req.hash.to_vec(),
+            txs: req.txs.into_iter().map(|tx| tx.to_vec()).collect(),
+            proposer_address: req.proposer_address.to_vec(),
+        }
+    }
+}
+
+pub struct VerifyVoteExtensionCtx {
+    pub height: u64,
+    pub hash: Vec<u8>,
+    pub validator_address: Vec<u8>,
+    pub vote_extension: Vec<u8>,
+}
+
+impl From<RequestVerifyVoteExtension> for VerifyVoteExtensionCtx {
+    fn from(req: RequestVerifyVoteExtension) -> Self {
+        VerifyVoteExtensionCtx {
+            height: req.height as u64,
+            hash: req.hash.to_vec(),
+            validator_address: req.validator_address.to_vec(),
+            vote_extension: req.vote_extension.to_vec(),
+        }
+    }
+}
+
 type OperatorMap = Map<[u8; 20], [u8; 32]>;
 
 pub struct Validators {
@@ -474,6 +570,52 @@ impl Drop for ContextRemover {
 }
 
 impl<T: App> ABCIPlugin<T> {
+    /// Runs a consensus hook on the inner app with the validator set and block
+    /// time available in the context. Validator updates are discarded, since
+    /// these hooks can't change state.
+    fn run_hook<R>(
+        &mut self,
+        time: Option<&Timestamp>,
+        op: impl FnOnce(&mut T) -> Result<R>,
+    ) -> Result<R> {
+        let _context_remover = ContextRemover;
+        Context::add(Validators::new(
+            self.current_vp.clone(),
+            self.cons_key_by_op_addr.clone(),
+        ));
+        if let Some(timestamp) = time.or(self.time.as_ref()) {
+            Context::add(Time {
+                seconds: timestamp.seconds,
+                nanos: timestamp.nanos,
+            });
+        }
+
+        op(&mut self.inner)
+    }
+
+    /// The current validator set, as updates setting each validator's voting
+    /// power. Used to start a chain from an imported state.
+    pub(crate) fn validator_set(&self) -> Result<Vec<ValidatorUpdate>> {
+        let mut res = vec![];
+        for entry in self
+            .current_vp
+            .borrow()
+            .as_ref()
+            .ok_or_else(|| Error::App("Validator set not available".to_string()))?
+            .iter()?
+        {
+            let entry = entry?;
+            res.push(ValidatorUpdate {
+                pub_key: Some(PublicKey {
+                    sum: Some(Sum::Ed25519(entry.pubkey.to_vec())),
+                }),
+                power: entry.power as i64,
+            });
+        }
+
+        Ok(res)
+    }
+
     fn build_updates(&mut self) -> Result<()> {
         let mut update_keys = vec![];
         let mut update_map = HashMap::new();
@@ -552,7 +694,8 @@ impl<T: State> State for ABCIPlugin<T> {
 impl<T: State + Describe> Describe for ABCIPlugin<T> {
     fn describe() -> crate::describe::Descriptor {
         crate::describe::Builder::new::<Self>()
-            .meta::<T>()
+            // the version byte written by `flush`, as in `QueryPlugin`
+            .meta::<u8>()
             .named_child::<T>("inner", &[0])
             // TODO: other fields
             .build()
@@ -567,3 +710,28 @@ where
         self.inner.abci_query(req)
     }
 }
+
+impl<T: App> PrepareProposal for ABCIPlugin<T> {
+    fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+        let time = ctx.time.clone();
+        self.run_hook(time.as_ref(), |inner| inner.prepare_proposal(ctx))
+    }
+}
+
+impl<T: App> ProcessProposal for ABCIPlugin<T> {
+    fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+        self.run_hook(ctx.time.as_ref(), |inner| inner.process_proposal(ctx))
+    }
+}
+
+impl<T: App> ExtendVote for ABCIPlugin<T> {
+    fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+        self.run_hook(ctx.time.as_ref(), |inner| inner.extend_vote(ctx))
+    }
+}
+
+impl<T: App> VerifyVoteExtension for ABCIPlugin<T> {
+    fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+        self.run_hook(None, |inner| inner.verify_vote_extension(ctx))
+    }
+}
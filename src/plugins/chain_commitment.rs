@@ -120,9 +120,15 @@ impl<T: Migrate> MigrateFrom<ChainCommitmentPluginV0<T>> for ChainCommitmentPlug
 // Query types for now.
 #[cfg(feature = "abci")]
 mod abci {
-    use super::super::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+    use super::super::{
+        BeginBlockCtx, EndBlockCtx, ExtendVoteCtx, InitChainCtx, PrepareProposalCtx,
+        ProcessProposalCtx, VerifyVoteExtensionCtx,
+    };
     use super::*;
-    use crate::abci::{BeginBlock, EndBlock, InitChain};
+    use crate::abci::{
+        BeginBlock, EndBlock, ExtendVote, InitChain, PrepareProposal, ProcessProposal,
+        VerifyVoteExtension,
+    };
     use crate::state::State;
 
     impl<T> BeginBlock for ChainCommitmentPlugin<T>
@@ -159,6 +165,42 @@ mod abci {
         }
     }
 
+    impl<T> PrepareProposal for ChainCommitmentPlugin<T>
+    where
+        T: PrepareProposal + State,
+    {
+        fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+            self.inner.prepare_proposal(ctx)
+        }
+    }
+
+    impl<T> ProcessProposal for ChainCommitmentPlugin<T>
+    where
+        T: ProcessProposal + State,
+    {
+        fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+            self.inner.process_proposal(ctx)
+        }
+    }
+
+    impl<T> ExtendVote for ChainCommitmentPlugin<T>
+    where
+        T: ExtendVote + State,
+    {
+        fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+            self.inner.extend_vote(ctx)
+        }
+    }
+
+    impl<T> VerifyVoteExtension for ChainCommitmentPlugin<T>
+    where
+        T: VerifyVoteExtension + State,
+    {
+        fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+            self.inner.verify_vote_extension(ctx)
+        }
+    }
+
     impl<T> crate::abci::AbciQuery for ChainCommitmentPlugin<T>
     where
         T: crate::abci::AbciQuery + State,
@@ -78,9 +78,15 @@ impl<S, T> DerefMut for FeePlugin<S, T> {
 // TODO: Remove dependency on ABCI for this otherwise-pure plugin.
 #[cfg(feature = "abci")]
 mod abci {
-    use super::super::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+    use super::super::{
+        BeginBlockCtx, EndBlockCtx, ExtendVoteCtx, InitChainCtx, PrepareProposalCtx,
+        ProcessProposalCtx, VerifyVoteExtensionCtx,
+    };
     use super::*;
-    use crate::abci::{BeginBlock, EndBlock, InitChain};
+    use crate::abci::{
+        BeginBlock, EndBlock, ExtendVote, InitChain, PrepareProposal, ProcessProposal,
+        VerifyVoteExtension,
+    };
 
     impl<S, T> BeginBlock for FeePlugin<S, T>
     where
@@ -112,6 +118,46 @@ mod abci {
         }
     }
 
+    impl<S, T> PrepareProposal for FeePlugin<S, T>
+    where
+        S: Symbol,
+        T: PrepareProposal + State,
+    {
+        fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+            self.inner.prepare_proposal(ctx)
+        }
+    }
+
+    impl<S, T> ProcessProposal for FeePlugin<S, T>
+    where
+        S: Symbol,
+        T: ProcessProposal + State,
+    {
+        fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+            self.inner.process_proposal(ctx)
+        }
+    }
+
+    impl<S, T> ExtendVote for FeePlugin<S, T>
+    where
+        S: Symbol,
+        T: ExtendVote + State,
+    {
+        fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+            self.inner.extend_vote(ctx)
+        }
+    }
+
+    impl<S, T> VerifyVoteExtension for FeePlugin<S, T>
+    where
+        S: Symbol,
+        T: VerifyVoteExtension + State,
+    {
+        fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+            self.inner.verify_vote_extension(ctx)
+        }
+    }
+
     impl<S, T> crate::abci::AbciQuery for FeePlugin<S, T>
     where
         S: Symbol,
@@ -112,9 +112,15 @@ where
 // TODO: Remove dependency on ABCI for this otherwise-pure plugin.
 #[cfg(feature = "abci")]
 mod abci {
-    use super::super::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+    use super::super::{
+        BeginBlockCtx, EndBlockCtx, ExtendVoteCtx, InitChainCtx, PrepareProposalCtx,
+        ProcessProposalCtx, VerifyVoteExtensionCtx,
+    };
     use super::*;
-    use crate::abci::{BeginBlock, EndBlock, InitChain};
+    use crate::abci::{
+        BeginBlock, EndBlock, ExtendVote, InitChain, PrepareProposal, ProcessProposal,
+        VerifyVoteExtension,
+    };
 
     impl<T> BeginBlock for NoncePlugin<T>
     where
@@ -143,6 +149,42 @@ mod abci {
         }
     }
 
+    impl<T> PrepareProposal for NoncePlugin<T>
+    where
+        T: PrepareProposal + State,
+    {
+        fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+            self.inner.prepare_proposal(ctx)
+        }
+    }
+
+    impl<T> ProcessProposal for NoncePlugin<T>
+    where
+        T: ProcessProposal + State,
+    {
+        fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+            self.inner.process_proposal(ctx)
+        }
+    }
+
+    impl<T> ExtendVote for NoncePlugin<T>
+    where
+        T: ExtendVote + State,
+    {
+        fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+            self.inner.extend_vote(ctx)
+        }
+    }
+
+    impl<T> VerifyVoteExtension for NoncePlugin<T>
+    where
+        T: VerifyVoteExtension + State,
+    {
+        fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+            self.inner.verify_vote_extension(ctx)
+        }
+    }
+
     impl<T> crate::abci::AbciQuery for NoncePlugin<T>
     where
         T: crate::abci::AbciQuery + State + Call,
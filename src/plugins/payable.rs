@@ -172,7 +172,10 @@ where
 mod abci {
     use super::super::*;
     use super::*;
-    use crate::abci::{BeginBlock, EndBlock, InitChain};
+    use crate::abci::{
+        BeginBlock, EndBlock, ExtendVote, InitChain, PrepareProposal, ProcessProposal,
+        VerifyVoteExtension,
+    };
 
     impl<T> BeginBlock for PayablePlugin<T>
     where
@@ -201,6 +204,42 @@ mod abci {
         }
     }
 
+    impl<T> PrepareProposal for PayablePlugin<T>
+    where
+        T: PrepareProposal + State,
+    {
+        fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+            self.inner.prepare_proposal(ctx)
+        }
+    }
+
+    impl<T> ProcessProposal for PayablePlugin<T>
+    where
+        T: ProcessProposal + State,
+    {
+        fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+            self.inner.process_proposal(ctx)
+        }
+    }
+
+    impl<T> ExtendVote for PayablePlugin<T>
+    where
+        T: ExtendVote + State,
+    {
+        fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+            self.inner.extend_vote(ctx)
+        }
+    }
+
+    impl<T> VerifyVoteExtension for PayablePlugin<T>
+    where
+        T: VerifyVoteExtension + State,
+    {
+        fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+            self.inner.verify_vote_extension(ctx)
+        }
+    }
+
     impl<T> crate::abci::AbciQuery for PayablePlugin<T>
     where
         T: crate::abci::AbciQuery + State + Call,
@@ -108,9 +108,15 @@ impl<T: State + Describe> Describe for QueryPlugin<T> {
 mod abci {
     use std::ops::{Deref, DerefMut};
 
-    use super::super::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+    use super::super::{
+        BeginBlockCtx, EndBlockCtx, ExtendVoteCtx, InitChainCtx, PrepareProposalCtx,
+        ProcessProposalCtx, VerifyVoteExtensionCtx,
+    };
     use super::*;
-    use crate::abci::{BeginBlock, EndBlock, InitChain};
+    use crate::abci::{
+        BeginBlock, EndBlock, ExtendVote, InitChain, PrepareProposal, ProcessProposal,
+        VerifyVoteExtension,
+    };
     use crate::state::State;
 
     impl<T> BeginBlock for QueryPlugin<T>
@@ -140,6 +146,45 @@ mod abci {
         }
     }
 
+    impl<T> PrepareProposal for QueryPlugin<T>
+    where
+        T: PrepareProposal + State,
+    {
+        fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+            self.inner.borrow_mut().deref_mut().prepare_proposal(ctx)
+        }
+    }
+
+    impl<T> ProcessProposal for QueryPlugin<T>
+    where
+        T: ProcessProposal + State,
+    {
+        fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+            self.inner.borrow_mut().deref_mut().process_proposal(ctx)
+        }
+    }
+
+    impl<T> ExtendVote for QueryPlugin<T>
+    where
+        T: ExtendVote + State,
+    {
+        fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+            self.inner.borrow_mut().deref_mut().extend_vote(ctx)
+        }
+    }
+
+    impl<T> VerifyVoteExtension for QueryPlugin<T>
+    where
+        T: VerifyVoteExtension + State,
+    {
+        fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+            self.inner
+                .borrow_mut()
+                .deref_mut()
+                .verify_vote_extension(ctx)
+        }
+    }
+
     impl<T> crate::abci::AbciQuery for QueryPlugin<T>
     where
         T: crate::abci::AbciQuery + State + Call,
@@ -367,7 +367,10 @@ impl<S: 'static, T: State> MigrateFrom<SdkCompatPluginV0<S, T>> for SdkCompatPlu
 mod abci {
     use super::super::*;
     use super::*;
-    use crate::abci::{BeginBlock, EndBlock, InitChain};
+    use crate::abci::{
+        BeginBlock, EndBlock, ExtendVote, InitChain, PrepareProposal, ProcessProposal,
+        VerifyVoteExtension,
+    };
 
     impl<S, T: State> BeginBlock for SdkCompatPlugin<S, T>
     where
@@ -396,6 +399,42 @@ mod abci {
         }
     }
 
+    impl<S, T: State> PrepareProposal for SdkCompatPlugin<S, T>
+    where
+        T: PrepareProposal + State,
+    {
+        fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+            self.inner.prepare_proposal(ctx)
+        }
+    }
+
+    impl<S, T: State> ProcessProposal for SdkCompatPlugin<S, T>
+    where
+        T: ProcessProposal + State,
+    {
+        fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+            self.inner.process_proposal(ctx)
+        }
+    }
+
+    impl<S, T: State> ExtendVote for SdkCompatPlugin<S, T>
+    where
+        T: ExtendVote + State,
+    {
+        fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+            self.inner.extend_vote(ctx)
+        }
+    }
+
+    impl<S, T: State> VerifyVoteExtension for SdkCompatPlugin<S, T>
+    where
+        T: VerifyVoteExtension + State,
+    {
+        fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+            self.inner.verify_vote_extension(ctx)
+        }
+    }
+
     impl<S, T> crate::abci::AbciQuery for SdkCompatPlugin<S, T>
     where
         T: crate::abci::AbciQuery + State + CallTrait,
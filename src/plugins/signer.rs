@@ -398,12 +398,7 @@ pub mod keplr {
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(feature = "abci")]
 pub fn load_privkey() -> Result<SecretKey> {
-    use std::path::PathBuf;
-
-    let home = match std::env::var("NOMIC_HOME_DIR") {
-        Ok(home) => Some(PathBuf::from(home)),
-        Err(_) => home::home_dir(),
-    };
+    let home = crate::abci::home_dir();
 
     let orga_home = home.expect("No home directory set").join(".orga-wallet");
 
@@ -446,9 +441,15 @@ pub fn load_keypair() -> Result<KeyPair> {
 // distinction between Contexts and normal State / Call / Query types for now.
 #[cfg(feature = "abci")]
 mod abci {
-    use super::super::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+    use super::super::{
+        BeginBlockCtx, EndBlockCtx, ExtendVoteCtx, InitChainCtx, PrepareProposalCtx,
+        ProcessProposalCtx, VerifyVoteExtensionCtx,
+    };
     use super::*;
-    use crate::abci::{BeginBlock, EndBlock, InitChain};
+    use crate::abci::{
+        BeginBlock, EndBlock, ExtendVote, InitChain, PrepareProposal, ProcessProposal,
+        VerifyVoteExtension,
+    };
 
     impl<T> BeginBlock for SignerPlugin<T>
     where
@@ -477,6 +478,42 @@ mod abci {
         }
     }
 
+    impl<T> PrepareProposal for SignerPlugin<T>
+    where
+        T: PrepareProposal + State,
+    {
+        fn prepare_proposal(&mut self, ctx: &mut PrepareProposalCtx) -> Result<()> {
+            self.inner.prepare_proposal(ctx)
+        }
+    }
+
+    impl<T> ProcessProposal for SignerPlugin<T>
+    where
+        T: ProcessProposal + State,
+    {
+        fn process_proposal(&mut self, ctx: &ProcessProposalCtx) -> Result<()> {
+            self.inner.process_proposal(ctx)
+        }
+    }
+
+    impl<T> ExtendVote for SignerPlugin<T>
+    where
+        T: ExtendVote + State,
+    {
+        fn extend_vote(&mut self, ctx: &ExtendVoteCtx) -> Result<Vec<u8>> {
+            self.inner.extend_vote(ctx)
+        }
+    }
+
+    impl<T> VerifyVoteExtension for SignerPlugin<T>
+    where
+        T: VerifyVoteExtension + State,
+    {
+        fn verify_vote_extension(&mut self, ctx: &VerifyVoteExtensionCtx) -> Result<()> {
+            self.inner.verify_vote_extension(ctx)
+        }
+    }
+
     impl<T> crate::abci::AbciQuery for SignerPlugin<T>
     where
         T: crate::abci::AbciQuery + State + Call,
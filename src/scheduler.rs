@@ -0,0 +1,321 @@
+//! Deferred execution of tasks at a future block height or time.
+//!
+//! A [Scheduler] is a state field which holds tasks until they become due.
+//! Types which own a scheduler implement [Scheduled] and call
+//! [Scheduled::run_scheduled] from their `BeginBlock` or `EndBlock`
+//! implementation to execute the tasks which have matured:
+//!
+//! ```ignore
+//! impl BeginBlock for MyModule {
+//!     fn begin_block(&mut self, ctx: &BeginBlockCtx) -> Result<()> {
+//!         self.run_scheduled(Phase::BeginBlock, ctx.height)
+//!     }
+//! }
+//! ```
+//!
+//! Tasks can be any [State] type. To defer call payloads, use
+//! `Adapter<<Self as Call>::Call>` as the task type and dispatch it to
+//! `Call::call` in [Scheduled::run_task].
+
+use crate::collections::priority_queue::Handle;
+use crate::collections::PriorityQueue;
+use crate::context::Context;
+use crate::encoding::{Decode, Encode};
+use crate::orga;
+use crate::plugins::Time;
+use crate::state::State;
+use crate::Result;
+
+/// The ABCI phase in which a scheduled task will run.
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    BeginBlock,
+    EndBlock,
+}
+
+/// The condition after which a scheduled task becomes due.
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// Due once the block height is greater than or equal to the given height.
+    Height(u64),
+    /// Due once the block time is greater than or equal to the given time, in
+    /// seconds since the Unix epoch.
+    Time(i64),
+}
+
+/// Identifies a scheduled task so that it can be cancelled.
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskHandle {
+    pub phase: Phase,
+    pub trigger: Trigger,
+    pub handle: Handle,
+}
+
+#[orga(skip(Default))]
+pub struct Scheduler<T: State> {
+    begin_block_by_height: PriorityQueue<u64, T>,
+    begin_block_by_time: PriorityQueue<u64, T>,
+    end_block_by_height: PriorityQueue<u64, T>,
+    end_block_by_time: PriorityQueue<u64, T>,
+}
+
+impl<T: State> Default for Scheduler<T> {
+    fn default() -> Self {
+        Self {
+            begin_block_by_height: PriorityQueue::default(),
+            begin_block_by_time: PriorityQueue::default(),
+            end_block_by_height: PriorityQueue::default(),
+            end_block_by_time: PriorityQueue::default(),
+        }
+    }
+}
+
+impl<T: State> Scheduler<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules a task to run in the given phase once `trigger` is reached.
+    pub fn schedule(&mut self, phase: Phase, trigger: Trigger, task: T) -> Result<TaskHandle> {
+        let handle = match trigger {
+            Trigger::Height(height) => self.height_queue(phase).push(height, task)?,
+            Trigger::Time(seconds) => self.time_queue(phase).push(time_key(seconds), task)?,
+        };
+
+        Ok(TaskHandle {
+            phase,
+            trigger,
+            handle,
+        })
+    }
+
+    /// Schedules a task to run in `BeginBlock` once the chain reaches the given
+    /// height.
+    pub fn schedule_at_height(&mut self, height: u64, task: T) -> Result<TaskHandle> {
+        self.schedule(Phase::BeginBlock, Trigger::Height(height), task)
+    }
+
+    /// Schedules a task to run in `BeginBlock` once the block time reaches the
+    /// given number of seconds since the Unix epoch.
+    pub fn schedule_at_time(&mut self, seconds: i64, task: T) -> Result<TaskHandle> {
+        self.schedule(Phase::BeginBlock, Trigger::Time(seconds), task)
+    }
+
+    /// Removes a scheduled task, returning it if it had not yet run.
+    pub fn cancel(&mut self, task: TaskHandle) -> Result<Option<T>> {
+        let removed = match task.trigger {
+            Trigger::Height(_) => self.height_queue(task.phase).remove(task.handle)?,
+            Trigger::Time(_) => self.time_queue(task.phase).remove(task.handle)?,
+        };
+
+        Ok(removed.map(|(_, task)| task.into_inner()))
+    }
+
+    /// Returns the number of tasks waiting to run.
+    pub fn len(&self) -> u64 {
+        self.begin_block_by_height.len()
+            + self.begin_block_by_time.len()
+            + self.end_block_by_height.len()
+            + self.end_block_by_time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes and returns the tasks for the given phase which are due at the
+    /// given height and time. Height-triggered tasks are returned before
+    /// time-triggered tasks, each in trigger order.
+    pub fn take_due(&mut self, phase: Phase, height: u64, seconds: Option<i64>) -> Result<Vec<T>> {
+        let mut due: Vec<T> = self
+            .height_queue(phase)
+            .pop_until(&height)?
+            .into_iter()
+            .map(|(_, task)| task.into_inner())
+            .collect();
+
+        if let Some(seconds) = seconds {
+            due.extend(
+                self.time_queue(phase)
+                    .pop_until(&time_key(seconds))?
+                    .into_iter()
+                    .map(|(_, task)| task.into_inner()),
+            );
+        }
+
+        Ok(due)
+    }
+
+    fn height_queue(&mut self, phase: Phase) -> &mut PriorityQueue<u64, T> {
+        match phase {
+            Phase::BeginBlock => &mut self.begin_block_by_height,
+            Phase::EndBlock => &mut self.end_block_by_height,
+        }
+    }
+
+    fn time_queue(&mut self, phase: Phase) -> &mut PriorityQueue<u64, T> {
+        match phase {
+            Phase::BeginBlock => &mut self.begin_block_by_time,
+            Phase::EndBlock => &mut self.end_block_by_time,
+        }
+    }
+}
+
+/// The key of a time trigger in its queue. Queue keys are ordered by their
+/// encoding, which only matches numeric order for unsigned integers, so times
+/// before the Unix epoch are clamped to it.
+fn time_key(seconds: i64) -> u64 {
+    seconds.max(0) as u64
+}
+
+/// Implemented by state types which own a [Scheduler] and know how to run its
+/// tasks.
+pub trait Scheduled: State {
+    type Task: State;
+
+    fn scheduler(&mut self) -> &mut Scheduler<Self::Task>;
+
+    fn run_task(&mut self, task: Self::Task) -> Result<()>;
+
+    /// Runs all tasks for the given phase which are due at `height` and at the
+    /// current block time (from the [Time] context, if available).
+    ///
+    /// A task which errors aborts the remaining tasks and the error is
+    /// returned, so the block phase fails as it would for any other error.
+    fn run_scheduled(&mut self, phase: Phase, height: u64) -> Result<()> {
+        let seconds = Context::resolve::<Time>().map(|time| time.seconds);
+        let due = self.scheduler().take_due(phase, height, seconds)?;
+        for task in due {
+            self.run_task(task)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{MapStore, Shared, Store};
+
+    #[orga]
+    pub struct Counter {
+        scheduler: Scheduler<u32>,
+        total: u32,
+    }
+
+    impl Scheduled for Counter {
+        type Task = u32;
+
+        fn scheduler(&mut self) -> &mut Scheduler<u32> {
+            &mut self.scheduler
+        }
+
+        fn run_task(&mut self, task: u32) -> Result<()> {
+            self.total += task;
+            Ok(())
+        }
+    }
+
+    fn setup() -> Counter {
+        let store = Store::new(Shared::new(MapStore::new()).into());
+        let mut counter = Counter::default();
+        counter.attach(store).unwrap();
+        counter
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn run_by_height() -> Result<()> {
+        Context::remove::<Time>();
+        let mut counter = setup();
+        counter.scheduler.schedule_at_height(5, 1)?;
+        counter.scheduler.schedule_at_height(3, 10)?;
+        counter
+            .scheduler
+            .schedule(Phase::EndBlock, Trigger::Height(3), 100)?;
+
+        counter.run_scheduled(Phase::BeginBlock, 2)?;
+        assert_eq!(counter.total, 0);
+
+        counter.run_scheduled(Phase::BeginBlock, 3)?;
+        assert_eq!(counter.total, 10);
+
+        counter.run_scheduled(Phase::EndBlock, 3)?;
+        assert_eq!(counter.total, 110);
+
+        counter.run_scheduled(Phase::BeginBlock, 10)?;
+        assert_eq!(counter.total, 111);
+        assert!(counter.scheduler.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn run_by_time_and_cancel() -> Result<()> {
+        let mut counter = setup();
+        counter.scheduler.schedule_at_time(100, 1)?;
+        let cancelled = counter.scheduler.schedule_at_time(100, 10)?;
+        counter.scheduler.schedule_at_time(200, 100)?;
+
+        assert_eq!(counter.scheduler.cancel(cancelled)?, Some(10));
+        assert_eq!(counter.scheduler.cancel(cancelled)?, None);
+
+        Context::add(Time::from_seconds(150));
+        counter.run_scheduled(Phase::BeginBlock, 1)?;
+        assert_eq!(counter.total, 1);
+
+        Context::add(Time::from_seconds(200));
+        counter.run_scheduled(Phase::BeginBlock, 2)?;
+        assert_eq!(counter.total, 101);
+
+        Context::remove::<Time>();
+        Ok(())
+    }
+
+    #[orga]
+    pub struct Log {
+        scheduler: Scheduler<u32>,
+        ran: Vec<u32>,
+    }
+
+    impl Scheduled for Log {
+        type Task = u32;
+
+        fn scheduler(&mut self) -> &mut Scheduler<u32> {
+            &mut self.scheduler
+        }
+
+        fn run_task(&mut self, task: u32) -> Result<()> {
+            self.ran.push(task);
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn tie_order() -> Result<()> {
+        let store = Store::new(Shared::new(MapStore::new()).into());
+        let mut log = Log::default();
+        log.attach(store)?;
+
+        // Earliest trigger first, and tasks with equal triggers in the order
+        // they were scheduled
+        log.scheduler.schedule_at_time(300, 1)?;
+        log.scheduler.schedule_at_time(100, 2)?;
+        log.scheduler.schedule_at_time(300, 3)?;
+        log.scheduler.schedule_at_time(100, 4)?;
+        log.scheduler.schedule_at_time(-5, 5)?;
+        log.scheduler.schedule_at_height(7, 6)?;
+        log.scheduler.schedule_at_height(7, 7)?;
+        log.scheduler.schedule_at_height(2, 8)?;
+
+        Context::add(Time::from_seconds(1000));
+        log.run_scheduled(Phase::BeginBlock, 10)?;
+        assert_eq!(log.ran, vec![8, 6, 7, 5, 2, 4, 1, 3]);
+
+        Context::remove::<Time>();
+        Ok(())
+    }
+}
@@ -6,23 +6,32 @@ use crate::merk::snapshot::Snapshot;
 use crate::merk::ProofStore;
 #[cfg(feature = "merk-full")]
 use crate::merk::{merk::HASH_LENGTH, MerkStore, ProofBuilder};
-#[cfg(feature = "merk-full")]
 use crate::store::BufStore;
-use crate::store::ReadWrite;
 use crate::store::{Empty, MapStore, PartialMapStore, Read, Shared, Write, KV};
+use crate::store::{Error as StoreError, ReadOnlyStore, ReadWrite};
 use crate::{Error, Result};
 #[cfg(feature = "merk-full")]
 use ics23::CommitmentProof;
+use std::any::Any;
 
 #[cfg(feature = "merk-full")]
 type WrappedMerkStore = Shared<BufStore<Shared<BufStore<Shared<MerkStore>>>>>;
 
+/// The backing store type used by `Store` by default.
+///
+/// Besides the built-in stores, applications can supply their own backend
+/// through the `Other` variant (for any type implementing `Read` and `Write`)
+/// or the `OtherReadOnly` variant (for any type implementing `Read`), see
+/// [BackingStore::other] and [BackingStore::read_only]. Writes to read-only
+/// backends return [StoreError::ReadOnly].
 #[derive(Clone)]
 pub enum BackingStore {
     MapStore(Shared<MapStore>),
     PartialMapStore(Shared<PartialMapStore>),
     Null(Empty),
     Other(Shared<Box<dyn ReadWrite>>),
+    OtherReadOnly(Shared<Box<dyn ReadOnlyStore>>),
+    Layered(Shared<BufStore<BackingStore>>),
 
     #[cfg(feature = "merk-full")]
     WrappedMerk(WrappedMerkStore),
@@ -55,6 +64,8 @@ impl Read for BackingStore {
             BackingStore::PartialMapStore(ref store) => store.get(key),
             BackingStore::Null(ref null) => null.get(key),
             BackingStore::Other(ref store) => store.borrow().get(key),
+            BackingStore::OtherReadOnly(ref store) => store.borrow().get(key),
+            BackingStore::Layered(ref store) => store.get(key),
 
             #[cfg(feature = "merk-full")]
             BackingStore::WrappedMerk(ref store) => store.get(key),
@@ -81,6 +92,8 @@ impl Read for BackingStore {
             BackingStore::PartialMapStore(ref store) => store.get_next(key),
             BackingStore::Null(ref null) => null.get_next(key),
             BackingStore::Other(ref store) => store.borrow().get_next(key),
+            BackingStore::OtherReadOnly(ref store) => store.borrow().get_next(key),
+            BackingStore::Layered(ref store) => store.get_next(key),
 
             #[cfg(feature = "merk-full")]
             BackingStore::WrappedMerk(ref store) => store.get_next(key),
@@ -107,6 +120,8 @@ impl Read for BackingStore {
             BackingStore::PartialMapStore(ref store) => store.get_prev(key),
             BackingStore::Null(ref null) => null.get_prev(key),
             BackingStore::Other(ref store) => store.borrow().get_prev(key),
+            BackingStore::OtherReadOnly(ref store) => store.borrow().get_prev(key),
+            BackingStore::Layered(ref store) => store.get_prev(key),
 
             #[cfg(feature = "merk-full")]
             BackingStore::WrappedMerk(ref store) => store.get_prev(key),
@@ -132,85 +147,97 @@ impl Write for BackingStore {
     fn put(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
         match self {
             BackingStore::MapStore(ref mut store) => store.put(key, value),
-            BackingStore::PartialMapStore(_) => {
-                panic!("put() is not implemented for PartialMapStore")
-            }
+            BackingStore::PartialMapStore(_) => read_only_err("PartialMapStore"),
             BackingStore::Null(ref mut store) => store.put(key, value),
             BackingStore::Other(ref mut store) => store.borrow_mut().put(key, value),
+            BackingStore::OtherReadOnly(_) => read_only_err("OtherReadOnly"),
+            BackingStore::Layered(ref mut store) => store.put(key, value),
 
             #[cfg(feature = "merk-full")]
             BackingStore::WrappedMerk(ref mut store) => store.put(key, value),
             #[cfg(feature = "merk-full")]
             BackingStore::Merk(ref mut store) => store.put(key, value),
             #[cfg(feature = "merk-full")]
-            BackingStore::Snapshot(_) => {
-                panic!("put() is not implemented for Snapshot")
-            }
+            BackingStore::Snapshot(_) => read_only_err("Snapshot"),
             #[cfg(feature = "merk-full")]
-            BackingStore::MemSnapshot(_) => {
-                panic!("put() is not implemented for MemSnapshot")
-            }
+            BackingStore::MemSnapshot(_) => read_only_err("MemSnapshot"),
             #[cfg(feature = "merk-full")]
-            BackingStore::ProofBuilder(_) => {
-                panic!("put() is not implemented for ProofBuilder")
-            }
+            BackingStore::ProofBuilder(_) => read_only_err("ProofBuilder"),
             #[cfg(feature = "merk-full")]
-            BackingStore::ProofBuilderSnapshot(_) => {
-                panic!("put() is not implemented for ProofBuilderSnapshot")
-            }
+            BackingStore::ProofBuilderSnapshot(_) => read_only_err("ProofBuilderSnapshot"),
             #[cfg(feature = "merk-full")]
-            BackingStore::ProofBuilderMemSnapshot(_) => {
-                panic!("put() is not implemented for ProofBuilderMemSnapshot")
-            }
+            BackingStore::ProofBuilderMemSnapshot(_) => read_only_err("ProofBuilderMemSnapshot"),
             #[cfg(feature = "merk-verify")]
-            BackingStore::ProofMap(_) => {
-                panic!("put() is not implemented for ProofMap")
-            }
+            BackingStore::ProofMap(_) => read_only_err("ProofMap"),
         }
     }
 
     fn delete(&mut self, key: &[u8]) -> Result<()> {
         match self {
             BackingStore::MapStore(ref mut store) => store.delete(key),
-            BackingStore::PartialMapStore(_) => {
-                panic!("delete() is not implemented for PartialMapStore")
-            }
+            BackingStore::PartialMapStore(_) => read_only_err("PartialMapStore"),
             BackingStore::Null(ref mut store) => store.delete(key),
             BackingStore::Other(ref mut store) => store.borrow_mut().delete(key),
+            BackingStore::OtherReadOnly(_) => read_only_err("OtherReadOnly"),
+            BackingStore::Layered(ref mut store) => store.delete(key),
 
             #[cfg(feature = "merk-full")]
             BackingStore::WrappedMerk(ref mut store) => store.delete(key),
             #[cfg(feature = "merk-full")]
             BackingStore::Merk(ref mut store) => store.delete(key),
             #[cfg(feature = "merk-full")]
-            BackingStore::ProofBuilder(_) => {
-                panic!("delete() is not implemented for ProofBuilder")
-            }
+            BackingStore::ProofBuilder(_) => read_only_err("ProofBuilder"),
             #[cfg(feature = "merk-full")]
-            BackingStore::ProofBuilderSnapshot(_) => {
-                panic!("delete() is not implemented for ProofBuilderSnapshot")
-            }
+            BackingStore::ProofBuilderSnapshot(_) => read_only_err("ProofBuilderSnapshot"),
             #[cfg(feature = "merk-full")]
-            BackingStore::ProofBuilderMemSnapshot(_) => {
-                panic!("delete() is not implemented for ProofBuilderMemSnapshot")
-            }
+            BackingStore::ProofBuilderMemSnapshot(_) => read_only_err("ProofBuilderMemSnapshot"),
             #[cfg(feature = "merk-full")]
-            BackingStore::Snapshot(_) => {
-                panic!("delete() is not implemented for Snapshot")
-            }
+            BackingStore::Snapshot(_) => read_only_err("Snapshot"),
             #[cfg(feature = "merk-full")]
-            BackingStore::MemSnapshot(_) => {
-                panic!("delete() is not implemented for MemSnapshot")
-            }
+            BackingStore::MemSnapshot(_) => read_only_err("MemSnapshot"),
             #[cfg(feature = "merk-verify")]
-            BackingStore::ProofMap(_) => {
-                panic!("delete() is not implemented for ProofMap")
-            }
+            BackingStore::ProofMap(_) => read_only_err("ProofMap"),
         }
     }
 }
 
+fn read_only_err(name: &str) -> Result<()> {
+    Err(StoreError::ReadOnly(name.to_string()).into())
+}
+
 impl BackingStore {
+    /// Creates a backing store from an application-supplied read/write store.
+    pub fn other<T: ReadWrite>(store: T) -> Self {
+        BackingStore::Other(Shared::new(Box::new(store)))
+    }
+
+    /// Creates a backing store from an application-supplied store which only
+    /// supports reads. Writes will return [StoreError::ReadOnly].
+    pub fn read_only<T: ReadOnlyStore>(store: T) -> Self {
+        BackingStore::OtherReadOnly(Shared::new(Box::new(store)))
+    }
+
+    /// Consumes the backing store and returns the application-supplied store
+    /// of type `T` given to [BackingStore::other] or [BackingStore::read_only].
+    ///
+    /// Errors if the backing store is not a custom store of type `T`, or if it
+    /// is still shared by other references.
+    pub fn downcast_other<T: 'static>(self) -> Result<T> {
+        let any: Box<dyn Any> = match self {
+            BackingStore::Other(store) => try_unwrap(store)?.into_any(),
+            BackingStore::OtherReadOnly(store) => try_unwrap(store)?.into_any(),
+            _ => {
+                return Err(Error::Downcast(
+                    "Backing store is not a custom store".into(),
+                ))
+            }
+        };
+
+        any.downcast::<T>()
+            .map(|store| *store)
+            .map_err(|_| Error::Downcast("Custom store has a different type".into()))
+    }
+
     #[cfg(feature = "merk-full")]
     pub fn into_proof_builder(self) -> Result<ProofBuilder<MerkStore>> {
         match self {
@@ -279,7 +306,7 @@ impl BackingStore {
         match self {
             BackingStore::Other(store) => Ok(store),
             _ => Err(Error::Downcast(
-                "Failed to downcast backing store to map store".into(),
+                "Failed to downcast backing store to other store".into(),
             )),
         }
     }
@@ -315,6 +342,12 @@ impl BackingStore {
     }
 }
 
+fn try_unwrap<T>(store: Shared<T>) -> Result<T> {
+    store
+        .try_into_inner()
+        .map_err(|_| Error::Downcast("Backing store is still shared".into()))
+}
+
 #[cfg(feature = "merk-full")]
 impl From<WrappedMerkStore> for BackingStore {
     fn from(store: WrappedMerkStore) -> BackingStore {
@@ -335,3 +368,92 @@ impl From<Shared<MapStore>> for BackingStore {
         BackingStore::MapStore(store)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct FixedStore(BTreeMap<Vec<u8>, Vec<u8>>);
+
+    impl Read for FixedStore {
+        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            Ok(self.0.get(key).cloned())
+        }
+
+        fn get_next(&self, key: &[u8]) -> Result<Option<KV>> {
+            use std::ops::Bound::{Excluded, Unbounded};
+            Ok(self
+                .0
+                .range::<[u8], _>((Excluded(key), Unbounded))
+                .next()
+                .map(|(k, v)| (k.clone(), v.clone())))
+        }
+
+        fn get_prev(&self, key: Option<&[u8]>) -> Result<Option<KV>> {
+            use std::ops::Bound::{Excluded, Unbounded};
+            let end = key.map_or(Unbounded, Excluded);
+            Ok(self
+                .0
+                .range::<[u8], _>((Unbounded, end))
+                .next_back()
+                .map(|(k, v)| (k.clone(), v.clone())))
+        }
+    }
+
+    #[test]
+    fn read_only_backend() -> Result<()> {
+        let mut backend = FixedStore::default();
+        backend.0.insert(vec![1], vec![10]);
+        backend.0.insert(vec![2], vec![20]);
+
+        let mut store = Store::with_read_only_backend(backend);
+        assert_eq!(store.get(&[1])?, Some(vec![10]));
+        assert_eq!(store.get_next(&[1])?, Some((vec![2], vec![20])));
+
+        assert!(matches!(
+            store.put(vec![3], vec![30]),
+            Err(Error::StoreErr(StoreError::ReadOnly(_)))
+        ));
+        assert!(matches!(
+            store.delete(&[1]),
+            Err(Error::StoreErr(StoreError::ReadOnly(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn custom_backend_downcast() -> Result<()> {
+        let mut store = Store::with_backend(MapStore::new());
+        store.put(vec![1], vec![10])?;
+
+        let backend = store
+            .into_backing_store()
+            .into_inner()
+            .downcast_other::<MapStore>()?;
+        assert_eq!(backend.get(&[1])?, Some(vec![10]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn downcast_wrong_type() {
+        let backing = BackingStore::other(MapStore::new());
+        assert!(backing.downcast_other::<FixedStore>().is_err());
+
+        let backing = BackingStore::MapStore(Shared::new(MapStore::new()));
+        assert!(backing.downcast_other::<MapStore>().is_err());
+    }
+
+    #[test]
+    fn builtin_read_only_errors() {
+        let mut store = BackingStore::PartialMapStore(Shared::new(PartialMapStore::new()));
+        assert!(matches!(
+            store.put(vec![1], vec![1]),
+            Err(Error::StoreErr(StoreError::ReadOnly(_)))
+        ));
+    }
+}
@@ -1,4 +1,7 @@
 use std::cell::{Ref, RefCell};
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
 
 use crate::Result;
 
@@ -49,3 +52,184 @@ impl<T: Write> Write for ReadLog<T> {
         self.inner.delete(key)
     }
 }
+
+/// A single key's change, with the value it held before and after. A `None`
+/// value means the key was absent (for `old`) or deleted (for `new`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub key: Vec<u8>,
+    pub old: Option<Vec<u8>>,
+    pub new: Option<Vec<u8>>,
+}
+
+/// Wraps a store and records the writes made through it, along with the value
+/// each written key held before its first write.
+///
+/// Writing a key several times produces a single [Change] from the original
+/// value to the final one, and writes which leave a key with its original
+/// value are omitted from [WriteLog::changes].
+pub struct WriteLog<T> {
+    inner: T,
+    writes: BTreeMap<Vec<u8>, (Option<Vec<u8>>, Option<Vec<u8>>)>,
+}
+
+impl<T> WriteLog<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the recorded changes in ascending key order.
+    pub fn changes(&self) -> Vec<Change> {
+        self.writes
+            .iter()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(key, (old, new))| Change {
+                key: key.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            })
+            .collect()
+    }
+
+    /// Clears the recorded changes, returning them in ascending key order.
+    pub fn take_changes(&mut self) -> Vec<Change> {
+        let changes = self.changes();
+        self.writes.clear();
+        changes
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read> WriteLog<T> {
+    fn record(&mut self, key: &[u8], new: Option<Vec<u8>>) -> Result<()> {
+        if let Some((_, value)) = self.writes.get_mut(key) {
+            *value = new;
+            return Ok(());
+        }
+
+        let old = self.inner.get(key)?;
+        self.writes.insert(key.to_vec(), (old, new));
+        Ok(())
+    }
+}
+
+impl<T: Read> Read for WriteLog<T> {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.inner.get(key)
+    }
+
+    fn get_next(&self, key: &[u8]) -> Result<Option<KV>> {
+        self.inner.get_next(key)
+    }
+
+    fn get_prev(&self, key: Option<&[u8]>) -> Result<Option<KV>> {
+        self.inner.get_prev(key)
+    }
+}
+
+impl<T: Read + Write> Write for WriteLog<T> {
+    fn put(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+        self.record(key.as_slice(), Some(value.clone()))?;
+        self.inner.put(key, value)
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<()> {
+        self.record(key, None)?;
+        self.inner.delete(key)
+    }
+}
+
+/// Merges `next` into `changes`, keeping the earliest old value and the latest
+/// new value for each key. Keys which end up unchanged are removed.
+pub fn merge_changes(changes: Vec<Change>, next: Vec<Change>) -> Vec<Change> {
+    let mut merged: BTreeMap<Vec<u8>, (Option<Vec<u8>>, Option<Vec<u8>>)> = changes
+        .into_iter()
+        .map(|change| (change.key, (change.old, change.new)))
+        .collect();
+
+    for change in next {
+        merged
+            .entry(change.key)
+            .and_modify(|(_, new)| *new = change.new.clone())
+            .or_insert((change.old, change.new));
+    }
+
+    merged
+        .into_iter()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(key, (old, new))| Change { key, old, new })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MapStore;
+
+    #[test]
+    fn write_log_changes() -> Result<()> {
+        let mut base = MapStore::new();
+        base.put(vec![1], vec![10])?;
+        base.put(vec![2], vec![20])?;
+
+        let mut log = WriteLog::new(base);
+        log.put(vec![1], vec![11])?;
+        log.put(vec![1], vec![12])?;
+        log.delete(&[2])?;
+        log.put(vec![3], vec![30])?;
+        log.put(vec![4], vec![40])?;
+        log.delete(&[4])?;
+
+        assert_eq!(
+            log.changes(),
+            vec![
+                Change {
+                    key: vec![1],
+                    old: Some(vec![10]),
+                    new: Some(vec![12]),
+                },
+                Change {
+                    key: vec![2],
+                    old: Some(vec![20]),
+                    new: None,
+                },
+                Change {
+                    key: vec![3],
+                    old: None,
+                    new: Some(vec![30]),
+                },
+            ]
+        );
+        assert_eq!(log.get(&[1])?, Some(vec![12]));
+
+        log.take_changes();
+        assert!(log.changes().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge() {
+        let change = |key: u8, old: Option<u8>, new: Option<u8>| Change {
+            key: vec![key],
+            old: old.map(|v| vec![v]),
+            new: new.map(|v| vec![v]),
+        };
+
+        let merged = merge_changes(
+            vec![change(1, None, Some(1)), change(2, Some(2), Some(3))],
+            vec![change(1, Some(1), Some(4)), change(2, Some(3), Some(2))],
+        );
+        assert_eq!(merged, vec![change(1, None, Some(4))]);
+    }
+}
@@ -33,6 +33,8 @@ pub enum Error {
     GetNextUnknown(Vec<u8>),
     #[error("Tried to read unknown store data before key {0:?}")]
     GetPrevUnknown(Option<Vec<u8>>),
+    #[error("Tried to write to read-only store {0}")]
+    ReadOnly(String),
 }
 
 /// A key/value entry - the first element is the key and the second element is
@@ -161,3 +163,15 @@ impl<T: Read + Write + 'static> ReadWrite for T {
         self
     }
 }
+
+/// A store which only supports reads, usable as a custom backing store through
+/// `BackingStore::read_only`.
+pub trait ReadOnlyStore: Read + Any + 'static {
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Read + 'static> ReadOnlyStore for T {
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
@@ -26,11 +26,11 @@ impl Read for Empty {
 
 impl Write for Empty {
     fn put(&mut self, _key: Vec<u8>, _value: Vec<u8>) -> Result<()> {
-        unimplemented!()
+        Err(OrgaError::StoreErr(Error::ReadOnly("Empty".into())))
     }
 
     fn delete(&mut self, _key: &[u8]) -> Result<()> {
-        unimplemented!()
+        Err(OrgaError::StoreErr(Error::ReadOnly("Empty".into())))
     }
 }
 
@@ -58,13 +58,11 @@ impl Read for Unknown {
 
 impl Write for Unknown {
     fn put(&mut self, _key: Vec<u8>, _value: Vec<u8>) -> Result<()> {
-        // TODO: WriteUnknown error
-        unimplemented!()
+        Err(OrgaError::StoreErr(Error::ReadOnly("Unknown".into())))
     }
 
     fn delete(&mut self, _key: &[u8]) -> Result<()> {
-        // TODO: WriteUnknown error
-        unimplemented!()
+        Err(OrgaError::StoreErr(Error::ReadOnly("Unknown".into())))
     }
 }
 
@@ -88,4 +86,13 @@ mod tests {
         let store = Empty;
         assert_eq!(store.get_prev(Some(&[1])).unwrap(), None)
     }
+
+    #[test]
+    fn put_errors() {
+        let mut store = Empty;
+        assert!(matches!(
+            store.put(vec![1], vec![1]),
+            Err(OrgaError::StoreErr(Error::ReadOnly(_)))
+        ));
+    }
 }
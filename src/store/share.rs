@@ -33,6 +33,14 @@ impl<T> Shared<T> {
         }
     }
 
+    /// Returns the inner store if this is the only reference to it, otherwise
+    /// returns `self` back as the error value.
+    pub fn try_into_inner(self) -> std::result::Result<T, Self> {
+        Rc::try_unwrap(self.0)
+            .map(RefCell::into_inner)
+            .map_err(Shared)
+    }
+
     pub fn borrow_mut(&mut self) -> RefMut<T> {
         self.0.borrow_mut()
     }
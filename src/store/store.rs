@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::ops::{Bound, RangeBounds};
 
-use super::{BackingStore, Iter, Read, Shared, Write, KV};
+use super::{BackingStore, BufStore, Iter, Read, ReadOnlyStore, ReadWrite, Shared, Write, KV};
 use crate::describe::Describe;
 use crate::encoding::{Decode, Encode, LengthVec, Terminated};
 use crate::migrate::Migrate;
@@ -9,12 +9,13 @@ use crate::query::FieldQuery;
 use crate::state::State;
 use crate::{orga, Error, Result};
 
-// TODO: figure out how to let users set DefaultBackingStore, similar to setting
-// the global allocator in the standard library
-
 /// The default backing store used as the type parameter given to `Store`. This
 /// is used to prevent generic parameters bubbling up to the application level
 /// for state types when they often all use the same backing store.
+///
+/// Applications which need a different backend can wrap it with
+/// `BackingStore::other` or `BackingStore::read_only` rather than changing
+/// this type.
 pub type DefaultBackingStore = BackingStore;
 
 /// Wraps a "backing store" (an implementation of `Read` and possibly `Write`),
@@ -42,6 +43,17 @@ impl Store {
         Self::new(BackingStore::MapStore(Shared::new(MapStore::new())))
     }
 
+    /// Creates a `Store` backed by an application-supplied read/write store.
+    pub fn with_backend<T: ReadWrite>(backend: T) -> Self {
+        Self::new(BackingStore::other(backend))
+    }
+
+    /// Creates a `Store` backed by an application-supplied store which only
+    /// supports reads.
+    pub fn with_read_only_backend<T: ReadOnlyStore>(backend: T) -> Self {
+        Self::new(BackingStore::read_only(backend))
+    }
+
     pub fn with_partial_map_store() -> Self {
         use super::PartialMapStore;
         Self::new(BackingStore::PartialMapStore(Shared::new(
@@ -55,6 +67,137 @@ impl Store {
             self.delete(&k)
         })
     }
+
+    /// Creates a savepoint by wrapping the backing store in a new in-memory
+    /// write buffer. Writes made through any clone of this store (including
+    /// substores) after the savepoint is created can then be discarded with
+    /// [Store::rollback_to] or kept with [Store::release].
+    ///
+    /// Savepoints only cover writes which reach the store. State types which
+    /// retain changes in memory until they are flushed (e.g. `Map`) must be
+    /// flushed before rolling back, or loaded from the store after the
+    /// savepoint is created, for their changes to be covered. Use
+    /// [Store::transact] to have this done for a state value.
+    pub fn savepoint(&self) -> Savepoint {
+        let mut shared = self.store.clone();
+        let mut backing = shared.borrow_mut();
+        let depth = layer_depth(&backing);
+        let parent = std::mem::take(&mut *backing);
+        *backing = BackingStore::Layered(Shared::new(BufStore::wrap(parent)));
+
+        Savepoint { depth }
+    }
+
+    /// Discards all writes made since the given savepoint was created,
+    /// including those of any savepoints nested inside it.
+    pub fn rollback_to(&self, savepoint: Savepoint) -> Result<()> {
+        while self.depth() > savepoint.depth {
+            self.pop_layer()?;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the writes made since the given savepoint was created, applying
+    /// them to the enclosing savepoint (or the underlying backing store if
+    /// there is none).
+    pub fn release(&self, savepoint: Savepoint) -> Result<()> {
+        while self.depth() > savepoint.depth {
+            self.pop_layer()?.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs `op` inside a savepoint, keeping its writes if it returns `Ok` and
+    /// discarding them if it returns `Err`.
+    ///
+    /// Transactions may be nested, in which case the writes of an inner
+    /// transaction are only persisted once all enclosing transactions succeed.
+    pub fn transaction<T, F>(&self, op: F) -> Result<T>
+    where
+        F: FnOnce(&Store) -> Result<T>,
+    {
+        let savepoint = self.savepoint();
+        match op(self) {
+            Ok(value) => {
+                self.release(savepoint)?;
+                Ok(value)
+            }
+            Err(err) => {
+                self.rollback_to(savepoint)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Runs `op` on `state` like [Store::transaction], also discarding the
+    /// changes `op` made to `state` in memory if it returns `Err`. `state` must
+    /// be attached to this store.
+    ///
+    /// Changes `state` held in memory before the call are flushed to the store
+    /// first, so they are kept either way.
+    pub fn transact<T, U, F>(&self, state: &mut T, op: F) -> Result<U>
+    where
+        T: State + Default,
+        F: FnOnce(&mut T) -> Result<U>,
+    {
+        let mut bytes = vec![];
+        std::mem::take(state).flush(&mut bytes)?;
+        *state = T::load(self.clone(), &mut bytes.as_slice())?;
+
+        let savepoint = self.savepoint();
+        match op(state) {
+            Ok(value) => {
+                self.release(savepoint)?;
+                Ok(value)
+            }
+            Err(err) => {
+                self.rollback_to(savepoint)?;
+                *state = T::load(self.clone(), &mut bytes.as_slice())?;
+                Err(err)
+            }
+        }
+    }
+
+    fn depth(&self) -> usize {
+        layer_depth(&self.store.borrow())
+    }
+
+    /// Removes the outermost savepoint layer, restoring its parent as the
+    /// backing store and returning the layer so its buffered writes can be
+    /// flushed to the parent or dropped.
+    fn pop_layer(&self) -> Result<BufStore<BackingStore>> {
+        let mut shared = self.store.clone();
+        let mut backing = shared.borrow_mut();
+        let layer = match std::mem::take(&mut *backing) {
+            BackingStore::Layered(layer) => layer,
+            other => {
+                *backing = other;
+                return Err(Error::Store("No savepoint to roll back".into()));
+            }
+        };
+
+        let layer = layer.into_inner();
+        *backing = layer.store().clone();
+
+        Ok(layer)
+    }
+}
+
+/// A marker for a point in a store's write history, created by
+/// [Store::savepoint].
+#[must_use]
+#[derive(Debug)]
+pub struct Savepoint {
+    depth: usize,
+}
+
+fn layer_depth(store: &BackingStore) -> usize {
+    match store {
+        BackingStore::Layered(layer) => 1 + layer_depth(layer.borrow().store()),
+        _ => 0,
+    }
 }
 
 impl Migrate for Store {}
@@ -353,4 +496,137 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn savepoint_rollback() -> Result<()> {
+        let mut store = Store::with_map_store();
+        store.put(vec![1], vec![1])?;
+
+        let savepoint = store.savepoint();
+        let mut sub = store.sub(&[2]);
+        sub.put(vec![3], vec![3])?;
+        store.delete(&[1])?;
+        assert!(store.get(&[1])?.is_none());
+        assert_eq!(store.get(&[2, 3])?, Some(vec![3]));
+
+        store.rollback_to(savepoint)?;
+        assert_eq!(store.get(&[1])?, Some(vec![1]));
+        assert!(store.get(&[2, 3])?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn savepoint_release() -> Result<()> {
+        let mut store = Store::with_map_store();
+
+        let savepoint = store.savepoint();
+        store.put(vec![1], vec![1])?;
+        store.release(savepoint)?;
+
+        let backing = store.into_backing_store().into_inner().into_map_store()?;
+        assert_eq!(backing.borrow().get(&[1])?, Some(vec![1]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn nested_transactions() -> Result<()> {
+        let store = Store::with_map_store();
+
+        let res: Result<()> = store.transaction(|store| {
+            store.clone().put(vec![1], vec![1])?;
+
+            store.transaction(|store| store.clone().put(vec![2], vec![2]))?;
+
+            let inner: Result<()> = store.transaction(|store| {
+                store.clone().put(vec![3], vec![3])?;
+                Err(Error::App("inner failure".into()))
+            });
+            assert!(inner.is_err());
+            assert!(store.get(&[3])?.is_none());
+            assert_eq!(store.get(&[2])?, Some(vec![2]));
+
+            Ok(())
+        });
+        res?;
+
+        assert_eq!(store.get(&[1])?, Some(vec![1]));
+        assert_eq!(store.get(&[2])?, Some(vec![2]));
+        assert!(store.get(&[3])?.is_none());
+
+        let res: Result<()> = store.transaction(|store| {
+            store.clone().put(vec![4], vec![4])?;
+            store.transaction(|store| store.clone().put(vec![5], vec![5]))?;
+            Err(Error::App("outer failure".into()))
+        });
+        assert!(res.is_err());
+        assert!(store.get(&[4])?.is_none());
+        assert!(store.get(&[5])?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn batch_of_state_calls() -> Result<()> {
+        use crate::collections::Map;
+
+        let store = Store::with_map_store();
+        let amounts = [5u64, 0, 7];
+
+        let results: Vec<bool> = amounts
+            .iter()
+            .enumerate()
+            .map(|(i, amount)| {
+                store
+                    .transaction(|store| {
+                        let mut map: Map<u8, u64> = Map::load(store.clone(), &mut &[][..])?;
+                        map.insert(i as u8, *amount)?;
+                        map.flush(&mut vec![])?;
+                        if *amount == 0 {
+                            return Err(Error::App("amount must be positive".into()));
+                        }
+                        Ok(())
+                    })
+                    .is_ok()
+            })
+            .collect();
+        assert_eq!(results, vec![true, false, true]);
+
+        let map: Map<u8, u64> = Map::load(store, &mut &[][..])?;
+        assert_eq!(*map.get(0)?.unwrap(), 5);
+        assert!(map.get(1)?.is_none());
+        assert_eq!(*map.get(2)?.unwrap(), 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn transact_unflushed_state() -> Result<()> {
+        use crate::collections::Map;
+
+        let store = Store::with_map_store();
+        let mut map: Map<u8, u64> = Map::load(store.clone(), &mut &[][..])?;
+        map.insert(0, 1)?;
+
+        // Changes buffered in memory by the failed op are discarded, while
+        // those made before it are kept
+        let res: Result<()> = store.transact(&mut map, |map| {
+            map.insert(1, 2)?;
+            *map.get_mut(0)?.unwrap() = 10;
+            Err(Error::App("failure".into()))
+        });
+        assert!(res.is_err());
+        assert_eq!(*map.get(0)?.unwrap(), 1);
+        assert!(map.get(1)?.is_none());
+
+        store.transact(&mut map, |map| map.insert(1, 2))?;
+        map.flush(&mut vec![])?;
+
+        let map: Map<u8, u64> = Map::load(store, &mut &[][..])?;
+        assert_eq!(*map.get(0)?.unwrap(), 1);
+        assert_eq!(*map.get(1)?.unwrap(), 2);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,543 @@
+//! Locating or installing the Tendermint binary.
+//!
+//! The binary comes from one of several sources, configured in the
+//! `[tendermint]` section of the node's `orga.toml`:
+//!
+//! ```toml
+//! [tendermint]
+//! # "download" (the default), "archive", "path" or "system"
+//! source = "archive"
+//! version = "0.34.26"
+//! path = "/opt/vendor/tendermint_0.34.26_linux_amd64.tar.gz"
+//! sha256 = "70415c1d20f48e4c19d8317ec7befd924681bb2d144ad8fded429041b80b3f79"
+//! ```
+//!
+//! - `download` fetches the release archive of `version` for the current
+//!   platform (or from `url`) and checks it against `sha256`, which defaults
+//!   to a pinned hash for known releases.
+//! - `archive` extracts the binary from a local `.tar.gz` archive at `path`,
+//!   checking it against `sha256`, which is required. This works without
+//!   network access.
+//! - `path` runs the binary at `path` as is.
+//! - `system` looks up `path` (by default `tendermint`, then `cometbft`) in
+//!   `$PATH`.
+//!
+//! Downloaded and extracted binaries are installed in the Tendermint home
+//! directory, and reused while they exist. Before it is run, the binary's
+//! version is checked for compatibility with the ABCI protocol spoken by the
+//! node (see [ABCI_COMPATIBLE_VERSION]).
+
+use crate::{Error, Result};
+use flate2::read::GzDecoder;
+use is_executable::IsExecutable;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tar::Archive;
+
+/// The Tendermint version installed by default.
+pub const DEFAULT_VERSION: &str = "0.34.26";
+
+/// The Tendermint release series whose ABCI protocol the node implements.
+/// CometBFT releases in the same series are also compatible.
+pub const ABCI_COMPATIBLE_VERSION: &str = "0.34";
+
+/// The names of the binary in release archives, and in `$PATH`.
+const BINARY_NAMES: &[&str] = &["tendermint", "cometbft"];
+
+/// Hashes of the release archives of known versions, by platform.
+const PINNED_HASHES: &[(&str, &str, &str)] = &[
+    (
+        "0.34.26",
+        "darwin_amd64",
+        "39dfde6ccc2c8b4cb699d1f3788b97da16cc8495156c39c82e94fa3834187909",
+    ),
+    (
+        "0.34.26",
+        "linux_amd64",
+        "70415c1d20f48e4c19d8317ec7befd924681bb2d144ad8fded429041b80b3f79",
+    ),
+    (
+        "0.34.26",
+        "linux_arm64",
+        "b0c9b5fae8a7dc53d84d62867204927ef37b1f91be5617f33a8f7fe378dfc5b9",
+    ),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinarySource {
+    #[default]
+    Download,
+    Archive,
+    Path,
+    System,
+}
+
+/// Where the Tendermint binary comes from, see the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BinaryConfig {
+    pub source: BinarySource,
+    /// The version to download or extract. Binaries from a path are checked
+    /// for compatibility, but may be of any patch version.
+    pub version: String,
+    /// The archive for the `archive` source, the binary for the `path`
+    /// source, or the name to look up for the `system` source.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// The URL of the archive for the `download` source, if not the release
+    /// archive of `version`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The hex-encoded SHA-256 hash of the archive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Whether to check the binary's version before running it.
+    pub check_version: bool,
+}
+
+impl Default for BinaryConfig {
+    fn default() -> Self {
+        Self {
+            source: BinarySource::Download,
+            version: DEFAULT_VERSION.to_string(),
+            path: None,
+            url: None,
+            sha256: None,
+            check_version: true,
+        }
+    }
+}
+
+impl BinaryConfig {
+    /// Uses the binary at the given path.
+    pub fn path<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            source: BinarySource::Path,
+            path: Some(path.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Looks up the binary in `$PATH`.
+    pub fn system() -> Self {
+        Self {
+            source: BinarySource::System,
+            ..Default::default()
+        }
+    }
+
+    /// Extracts the binary of the given version from a local archive with
+    /// the given hex-encoded SHA-256 hash.
+    pub fn archive<P: Into<PathBuf>>(path: P, version: &str, sha256: &str) -> Self {
+        Self {
+            source: BinarySource::Archive,
+            version: version.to_string(),
+            path: Some(path.into()),
+            sha256: Some(sha256.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let requires_path = matches!(self.source, BinarySource::Archive | BinarySource::Path);
+        if requires_path && self.path.is_none() {
+            return Err(Error::Config(format!(
+                "tendermint.path is required for the {:?} source",
+                self.source
+            )));
+        }
+        if self.source == BinarySource::Archive && self.sha256.is_none() {
+            return Err(Error::Config(
+                "tendermint.sha256 is required for the archive source".into(),
+            ));
+        }
+        if let Some(sha256) = &self.sha256 {
+            parse_hash(sha256)?;
+        }
+
+        Ok(())
+    }
+
+    /// The path the binary is installed at in the given Tendermint home, for
+    /// the `download` and `archive` sources.
+    pub fn install_path(&self, home: &Path) -> PathBuf {
+        home.join(format!("tendermint-v{}", self.version))
+    }
+
+    /// Returns the path of the binary, installing it in the given Tendermint
+    /// home if needed, and checks its version.
+    pub async fn resolve(&self, home: &Path) -> Result<PathBuf> {
+        self.validate()?;
+
+        let bin = match self.source {
+            BinarySource::Path => {
+                let path = self.path.clone().unwrap();
+                if !path.is_executable() {
+                    return Err(Error::Tendermint(format!(
+                        "{} is not an executable",
+                        path.display()
+                    )));
+                }
+                path
+            }
+            BinarySource::System => {
+                let path = std::env::var_os("PATH").unwrap_or_default();
+                let names = match &self.path {
+                    Some(name) => vec![name.as_os_str()],
+                    None => BINARY_NAMES.iter().map(OsStr::new).collect(),
+                };
+                names
+                    .into_iter()
+                    .find_map(|name| find_in_path(name, &path))
+                    .ok_or_else(|| {
+                        Error::Tendermint("Tendermint binary not found in PATH".into())
+                    })?
+            }
+            BinarySource::Archive | BinarySource::Download => {
+                let bin = self.install_path(home);
+                if bin.is_executable() {
+                    debug!("Tendermint already installed");
+                } else {
+                    let archive = self.read_archive().await?;
+                    info!("Installing Tendermint to {}", home.display());
+                    install(&archive, &bin)?;
+                }
+                bin
+            }
+        };
+
+        if self.check_version {
+            let version = binary_version(&bin)?;
+            check_compatible(&version)?;
+            info!("Using Tendermint {} at {}", version, bin.display());
+        }
+
+        Ok(bin)
+    }
+
+    /// Reads the archive, and verifies its hash.
+    async fn read_archive(&self) -> Result<Vec<u8>> {
+        let (bytes, sha256) = match self.source {
+            BinarySource::Archive => (
+                fs::read(self.path.as_ref().unwrap())?,
+                self.sha256.clone().unwrap(),
+            ),
+            _ => {
+                let platform = match self.sha256 {
+                    Some(_) => platform()?,
+                    None => release_platform(&self.version, &platform()?).to_string(),
+                };
+                let url = self
+                    .url
+                    .clone()
+                    .unwrap_or_else(|| release_url(&self.version, &platform));
+                let sha256 = self
+                    .sha256
+                    .clone()
+                    .or_else(|| pinned_hash(&self.version, &platform).map(str::to_string))
+                    .ok_or_else(|| {
+                        Error::Tendermint(format!(
+                            "No known hash for Tendermint {} on {}, set tendermint.sha256",
+                            self.version, platform
+                        ))
+                    })?;
+
+                info!("Downloading Tendermint from {}", url);
+                let bytes = reqwest::get(&url)
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(|err| Error::Tendermint(format!("Failed to download: {}", err)))?
+                    .bytes()
+                    .await
+                    .map_err(|err| Error::Tendermint(format!("Failed to download: {}", err)))?;
+                (bytes.to_vec(), sha256)
+            }
+        };
+
+        verify_hash(&bytes, &sha256)?;
+
+        Ok(bytes)
+    }
+}
+
+/// The platform name used in release archives, e.g. `linux_amd64`.
+pub fn platform() -> Result<String> {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        "linux" => "linux",
+        os => return Err(Error::Tendermint(format!("Unsupported OS {}", os))),
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => {
+            return Err(Error::Tendermint(format!(
+                "Unsupported architecture {}",
+                arch
+            )))
+        }
+    };
+
+    Ok(format!("{}_{}", os, arch))
+}
+
+pub fn release_url(version: &str, platform: &str) -> String {
+    format!(
+        "https://github.com/informalsystems/tendermint/releases/download/v{0}/tendermint_{0}_{1}.tar.gz",
+        version, platform
+    )
+}
+
+/// The platform whose pinned release archive is downloaded on `platform`.
+/// Apple silicon Macs use the `darwin_amd64` build, which runs under Rosetta
+/// 2, for versions without a pinned `darwin_arm64` hash.
+fn release_platform<'a>(version: &str, platform: &'a str) -> &'a str {
+    if platform == "darwin_arm64" && pinned_hash(version, platform).is_none() {
+        info!("No pinned Tendermint hash for darwin_arm64, using the darwin_amd64 build");
+        return "darwin_amd64";
+    }
+
+    platform
+}
+
+fn pinned_hash(version: &str, platform: &str) -> Option<&'static str> {
+    PINNED_HASHES
+        .iter()
+        .find(|(v, p, _)| *v == version && *p == platform)
+        .map(|(_, _, hash)| *hash)
+}
+
+fn parse_hash(sha256: &str) -> Result<[u8; 32]> {
+    hex::decode(sha256)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::Config("tendermint.sha256 must be a hex-encoded SHA-256 hash".into()))
+}
+
+fn verify_hash(bytes: &[u8], sha256: &str) -> Result<()> {
+    let expected = parse_hash(sha256)?;
+    let digest = Sha256::digest(bytes);
+    if digest.as_slice() != expected {
+        return Err(Error::Tendermint(format!(
+            "Tendermint archive has hash {}, expected {}",
+            hex::encode(digest),
+            sha256
+        )));
+    }
+    info!("Confirmed correct Tendermint archive hash");
+
+    Ok(())
+}
+
+/// Extracts the binary from a `.tar.gz` archive to `dest`.
+fn install(archive: &[u8], dest: &Path) -> Result<()> {
+    let mut archive = Archive::new(GzDecoder::new(archive));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        let is_binary = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .map_or(false, |name| BINARY_NAMES.contains(&name));
+        if !is_binary {
+            continue;
+        }
+
+        let mut bytes = vec![];
+        entry.read_to_end(&mut bytes)?;
+
+        // Write to a temporary file first so an interrupted install is not
+        // mistaken for a complete one
+        let mut tmp = dest.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, bytes)?;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))?;
+        fs::rename(&tmp, dest)?;
+
+        return Ok(());
+    }
+
+    Err(Error::Tendermint(
+        "Archive does not contain a Tendermint binary".into(),
+    ))
+}
+
+/// Finds an executable with the given name in a `$PATH`-style list of
+/// directories.
+fn find_in_path(name: &OsStr, path: &OsStr) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_executable())
+}
+
+/// Runs `<bin> version`, returning the version without a leading `v`.
+pub fn binary_version(bin: &Path) -> Result<String> {
+    let output = Command::new(bin).arg("version").output()?;
+    if !output.status.success() {
+        return Err(Error::Tendermint(format!(
+            "{} version exited with {}",
+            bin.display(),
+            output.status
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.lines().next().unwrap_or_default().trim();
+
+    Ok(version.trim_start_matches('v').to_string())
+}
+
+/// Checks that the given Tendermint version is in the
+/// [ABCI_COMPATIBLE_VERSION] series.
+pub fn check_compatible(version: &str) -> Result<()> {
+    let series = version.splitn(3, '.').take(2).collect::<Vec<_>>().join(".");
+    if series != ABCI_COMPATIBLE_VERSION {
+        return Err(Error::Tendermint(format!(
+            "Tendermint {} is not compatible with this node, which requires {}.x",
+            version, ABCI_COMPATIBLE_VERSION
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use tempdir::TempDir;
+
+    fn write_script(path: &Path, version: &str) -> Result<()> {
+        fs::write(path, format!("#!/bin/sh\necho v{}\n", version))?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+
+        Ok(())
+    }
+
+    fn build_archive(version: &str) -> Result<Vec<u8>> {
+        let script = format!("#!/bin/sh\necho {}\n", version);
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (name, data) in [("README.md", "docs"), ("tendermint", script.as_str())] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, name, data.as_bytes())?;
+        }
+
+        Ok(builder.into_inner()?.finish()?)
+    }
+
+    #[test]
+    fn compatibility() {
+        assert!(check_compatible("0.34.26").is_ok());
+        assert!(check_compatible("0.34.29").is_ok());
+        assert!(check_compatible("0.37.2").is_err());
+        assert!(check_compatible("0.3").is_err());
+        assert!(check_compatible("").is_err());
+    }
+
+    #[test]
+    fn pinned() {
+        assert!(pinned_hash(DEFAULT_VERSION, "linux_amd64").is_some());
+        assert_eq!(
+            release_platform(DEFAULT_VERSION, "darwin_arm64"),
+            "darwin_amd64"
+        );
+        assert!(pinned_hash(
+            DEFAULT_VERSION,
+            release_platform(DEFAULT_VERSION, "darwin_arm64")
+        )
+        .is_some());
+        assert_eq!(
+            release_platform(DEFAULT_VERSION, "linux_arm64"),
+            "linux_arm64"
+        );
+        assert!(release_url("0.34.26", "darwin_arm64")
+            .ends_with("tendermint_0.34.26_darwin_arm64.tar.gz"));
+    }
+
+    #[test]
+    fn validate() {
+        assert!(BinaryConfig::default().validate().is_ok());
+        assert!(BinaryConfig::system().validate().is_ok());
+        assert!(BinaryConfig {
+            source: BinarySource::Path,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(BinaryConfig {
+            source: BinarySource::Archive,
+            path: Some("tm.tar.gz".into()),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(BinaryConfig::archive("tm.tar.gz", "0.34.26", "abcd")
+            .validate()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn path_and_system() -> Result<()> {
+        let dir = TempDir::new("orga-tm-bin")?;
+        let bin = dir.path().join("tendermint");
+        write_script(&bin, "0.34.26")?;
+
+        assert_eq!(BinaryConfig::path(&bin).resolve(dir.path()).await?, bin);
+        assert_eq!(binary_version(&bin)?, "0.34.26");
+
+        let path =
+            std::env::join_paths([dir.path().join("missing"), dir.path().to_path_buf()]).unwrap();
+        assert_eq!(
+            find_in_path(OsStr::new("tendermint"), &path),
+            Some(bin.clone())
+        );
+        assert_eq!(find_in_path(OsStr::new("cometbft"), &path), None);
+
+        write_script(&bin, "0.37.0")?;
+        assert!(BinaryConfig::path(&bin).resolve(dir.path()).await.is_err());
+        let unchecked = BinaryConfig {
+            check_version: false,
+            ..BinaryConfig::path(&bin)
+        };
+        assert_eq!(unchecked.resolve(dir.path()).await?, bin);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn archive() -> Result<()> {
+        let dir = TempDir::new("orga-tm-archive")?;
+        let archive_path = dir.path().join("tendermint.tar.gz");
+        let archive = build_archive("0.34.26")?;
+        fs::write(&archive_path, &archive)?;
+        let sha256 = hex::encode(Sha256::digest(&archive));
+
+        let home = dir.path().join("home");
+        fs::create_dir(&home)?;
+        let wrong_hash = hex::encode([0; 32]);
+        let config = BinaryConfig::archive(&archive_path, "0.34.26", &wrong_hash);
+        assert!(config.resolve(&home).await.is_err());
+        assert!(!config.install_path(&home).exists());
+
+        let config = BinaryConfig::archive(&archive_path, "0.34.26", &sha256);
+        let bin = config.resolve(&home).await?;
+        assert_eq!(bin, home.join("tendermint-v0.34.26"));
+        assert!(bin.is_executable());
+
+        // Installed binaries are reused without reading the archive
+        fs::remove_file(&archive_path)?;
+        assert_eq!(config.resolve(&home).await?, bin);
+
+        Ok(())
+    }
+}
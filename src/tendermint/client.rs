@@ -1,22 +1,33 @@
+use super::light_client::{LightClient, TrustOptions};
 use crate::{
     abci::App,
     call::Call,
-    client::{sync::Transport as SyncTransport, Transport},
+    client::{
+        receipt::{Event, Receipt},
+        sync::Transport as SyncTransport,
+        BroadcastMode, Transport,
+    },
     encoding::Encode,
-    merk::ProofStore,
+    merk::{calc_app_hash, ProofStore},
     plugins::{ABCICall, ABCIPlugin},
     query::Query,
     state::State,
-    store::{BackingStore, Shared, Store},
+    store::Store,
     Error, Result,
 };
 use futures_lite::future::block_on;
+use std::time::{Duration, Instant};
+use tendermint::hash::Algorithm;
 use tendermint_rpc::{self as tm, Client as _};
 use tokio::sync::Mutex;
 
+const INCLUSION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 pub struct HttpClient {
     client: tm::HttpClient,
     height: Mutex<Option<u32>>,
+    light_client: Option<LightClient>,
+    broadcast_mode: BroadcastMode,
 }
 
 impl HttpClient {
@@ -24,6 +35,8 @@ impl HttpClient {
         Ok(Self {
             client: tm::HttpClient::new(url)?,
             height: Mutex::new(None),
+            light_client: None,
+            broadcast_mode: BroadcastMode::default(),
         })
     }
 
@@ -31,34 +44,136 @@ impl HttpClient {
         Ok(Self {
             client: tm::HttpClient::new(url)?,
             height: Mutex::new(Some(height)),
+            light_client: None,
+            broadcast_mode: BroadcastMode::default(),
         })
     }
+
+    /// Verifies query proofs against app hashes from headers checked by a
+    /// light client rooted at the given trusted header, rather than trusting
+    /// the root hash returned by the RPC node.
+    #[must_use]
+    pub fn trust(mut self, options: TrustOptions) -> Self {
+        self.light_client = Some(LightClient::new(self.client.clone(), options));
+        self
+    }
+
+    /// Sets how calls are broadcast. Defaults to [BroadcastMode::Commit].
+    #[must_use]
+    pub fn broadcast_mode(mut self, mode: BroadcastMode) -> Self {
+        self.broadcast_mode = mode;
+        self
+    }
 }
 
-impl<T: App + Call + Query + State + Default> Transport<ABCIPlugin<T>> for HttpClient {
-    async fn call(&self, call: <ABCIPlugin<T> as Call>::Call) -> Result<()> {
-        // TODO: shouldn't need to deal with ABCIPlugin at this level
-        let call = match call {
-            ABCICall::DeliverTx(call) => call,
-            _ => return Err(Error::Client("Unexpected call type".into())),
-        };
-        let call_bytes = call.encode()?;
-        let res = self.client.broadcast_tx_commit(call_bytes).await?;
+pub(crate) fn convert_events(events: Vec<tendermint::abci::Event>) -> Vec<Event> {
+    events
+        .into_iter()
+        .map(|event| Event {
+            kind: event.kind,
+            attributes: event
+                .attributes
+                .into_iter()
+                .map(|attr| (attr.key, attr.value))
+                .collect(),
+        })
+        .collect()
+}
 
-        if let tendermint::abci::Code::Err(code) = res.check_tx.code {
-            let msg = format!("code {}: {}", code, res.check_tx.log);
-            return Err(Error::Call(msg));
+impl HttpClient {
+    /// Broadcasts an encoded transaction according to the broadcast mode.
+    pub(crate) async fn broadcast(&self, call_bytes: Vec<u8>) -> Result<Receipt> {
+        match self.broadcast_mode {
+            BroadcastMode::Async => {
+                let res = self.client.broadcast_tx_async(call_bytes).await?;
+                Ok(Receipt::pending(res.hash.as_bytes().to_vec()))
+            }
+            BroadcastMode::Sync => {
+                let res = self.client.broadcast_tx_sync(call_bytes).await?;
+                if let tendermint::abci::Code::Err(code) = res.code {
+                    let msg = format!("code {}: {}", code, res.log);
+                    return Err(Error::Call(msg));
+                }
+
+                Ok(Receipt {
+                    log: res.log,
+                    ..Receipt::pending(res.hash.as_bytes().to_vec())
+                })
+            }
+            BroadcastMode::Commit => {
+                let res = self.client.broadcast_tx_commit(call_bytes).await?;
+                if let tendermint::abci::Code::Err(code) = res.check_tx.code {
+                    let msg = format!("code {}: {}", code, res.check_tx.log);
+                    return Err(Error::Call(msg));
+                }
+
+                Ok(Receipt {
+                    hash: res.hash.as_bytes().to_vec(),
+                    height: Some(res.height.value()),
+                    code: res.deliver_tx.code.value(),
+                    log: res.deliver_tx.log,
+                    events: convert_events(res.deliver_tx.events),
+                })
+            }
         }
+    }
 
-        Ok(())
+    /// Polls for the result of the transaction with the given hash.
+    pub(crate) async fn tx_receipt(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        let tm_hash = tendermint::Hash::from_bytes(Algorithm::Sha256, hash)
+            .map_err(|e| Error::Tendermint(e.to_string()))?;
+        let start = Instant::now();
+
+        loop {
+            match self.client.tx(tm_hash, false).await {
+                Ok(res) => {
+                    return Ok(Receipt {
+                        hash: hash.to_vec(),
+                        height: Some(res.height.value()),
+                        code: res.tx_result.code.value(),
+                        log: res.tx_result.log,
+                        events: convert_events(res.tx_result.events),
+                    })
+                }
+                // the node returns an error until the transaction is indexed
+                Err(err) if start.elapsed() >= timeout => {
+                    return Err(Error::Client(format!(
+                        "Timed out waiting for inclusion of tx {}: {}",
+                        hex::encode_upper(hash),
+                        err
+                    )))
+                }
+                Err(_) => tokio::time::sleep(INCLUSION_POLL_INTERVAL).await,
+            }
+        }
     }
 
-    async fn query(&self, query: T::Query) -> Result<Store> {
-        let query_bytes = query.encode()?;
-        let maybe_height = self.height.lock().await.map(Into::into);
+    /// Runs an encoded query at the given height, or the latest height if
+    /// `None`, returning the verified proof store and the height it was
+    /// evaluated at.
+    pub(crate) async fn query_at(
+        &self,
+        query_bytes: Vec<u8>,
+        height: Option<u32>,
+    ) -> Result<(Store, u32)> {
+        let (root_hash, proof_bytes, height) = self.query_proof(query_bytes, height).await?;
+        let store = proof_store(root_hash, &proof_bytes)?;
+
+        Ok((store, height))
+    }
+
+    /// Runs an encoded query at the given height, or the latest height if
+    /// `None`, returning the root hash and merk proof of the result along
+    /// with the height it was evaluated at. If a light client is configured,
+    /// the root hash is checked against a verified header.
+    pub(crate) async fn query_proof(
+        &self,
+        query_bytes: Vec<u8>,
+        height: Option<u32>,
+    ) -> Result<([u8; 32], Vec<u8>, u32)> {
         let res = self
             .client
-            .abci_query(None, query_bytes, maybe_height, true)
+            .abci_query(None, query_bytes, height.map(Into::into), true)
             .await?;
 
         if let tendermint::abci::Code::Err(code) = res.code {
@@ -66,10 +181,9 @@ impl<T: App + Call + Query + State + Default> Transport<ABCIPlugin<T>> for HttpC
             return Err(Error::Query(msg));
         }
 
-        self.height.lock().await.replace(res.height.value() as u32);
-
-        // TODO: we shouldn't need to include the root hash in the result, it
-        // should come from a trusted source
+        // TODO: we shouldn't need to include the root hash in the result. It
+        // is only trusted if a light client is configured to check it against
+        // the app hash of a verified header.
         let root_hash = match res.value[0..32].try_into() {
             Ok(inner) => inner,
             _ => {
@@ -78,22 +192,70 @@ impl<T: App + Call + Query + State + Default> Transport<ABCIPlugin<T>> for HttpC
                 ));
             }
         };
-        let proof_bytes = &res.value[32..];
+        let proof_bytes = res.value[32..].to_vec();
+
+        if let Some(light_client) = &self.light_client {
+            // the app hash for the state at height H is included in header H+1
+            let app_hash = calc_app_hash(&root_hash);
+            light_client
+                .verify_app_hash(res.height.value() + 1, app_hash.as_slice())
+                .await?;
+        }
+
+        Ok((root_hash, proof_bytes, res.height.value() as u32))
+    }
+
+    /// Returns the latest block height of the node, and whether it is still
+    /// catching up to the rest of the network.
+    pub(crate) async fn latest_height(&self) -> Result<(u64, bool)> {
+        let status = self.client.status().await?;
+        Ok((
+            status.sync_info.latest_block_height.value(),
+            status.sync_info.catching_up,
+        ))
+    }
+}
+
+/// Verifies a merk proof against the given root hash, returning a store
+/// containing the proven entries.
+pub(crate) fn proof_store(root_hash: [u8; 32], proof_bytes: &[u8]) -> Result<Store> {
+    ProofStore::verify(root_hash, proof_bytes)
+}
+
+impl<T: App + Call + Query + State + Default> Transport<ABCIPlugin<T>> for HttpClient {
+    async fn call(&self, call: <ABCIPlugin<T> as Call>::Call) -> Result<Receipt> {
+        // TODO: shouldn't need to deal with ABCIPlugin at this level
+        let call = match call {
+            ABCICall::DeliverTx(call) => call,
+            _ => return Err(Error::Client("Unexpected call type".into())),
+        };
+        self.broadcast(call.encode()?).await
+    }
 
-        let map = merk::proofs::query::verify(proof_bytes, root_hash)?;
+    async fn wait_for_inclusion(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        self.tx_receipt(hash, timeout).await
+    }
 
-        let store: Shared<ProofStore> = Shared::new(ProofStore(map));
-        let store = Store::new(BackingStore::ProofMap(store));
+    async fn query(&self, query: T::Query) -> Result<Store> {
+        let maybe_height = *self.height.lock().await;
+        let (store, height) = self.query_at(query.encode()?, maybe_height).await?;
+        self.height.lock().await.replace(height);
 
         Ok(store)
     }
 }
 
 impl<T: App + Call + Query + State + Default> SyncTransport<ABCIPlugin<T>> for HttpClient {
-    fn call_sync(&self, call: <ABCIPlugin<T> as Call>::Call) -> Result<()> {
+    fn call_sync(&self, call: <ABCIPlugin<T> as Call>::Call) -> Result<Receipt> {
         block_on(Transport::<ABCIPlugin<T>>::call(self, call))
     }
 
+    fn wait_for_inclusion_sync(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        block_on(Transport::<ABCIPlugin<T>>::wait_for_inclusion(
+            self, hash, timeout,
+        ))
+    }
+
     fn query_sync(&self, query: T::Query) -> Result<Store> {
         block_on(Transport::<ABCIPlugin<T>>::query(self, query))
     }
@@ -107,6 +269,7 @@ mod tests {
         coins::{Accounts, Symbol},
         collections::Map,
         context::Context,
+        genesis::Genesis,
         plugins::{ChainId, ConvertSdkTx, DefaultPlugins, PaidCall},
     };
 
@@ -142,9 +305,8 @@ mod tests {
     }
 
     impl InitChain for App {
-        fn init_chain(&mut self, _ctx: &crate::plugins::InitChainCtx) -> Result<()> {
-            self.accounts
-                .deposit(DerivedKey::address_for(b"alice").unwrap(), 100_000.into())
+        fn init_chain(&mut self, ctx: &crate::plugins::InitChainCtx) -> Result<()> {
+            self.accounts.init_genesis(ctx, "accounts")
         }
     }
 
@@ -168,7 +330,22 @@ mod tests {
                     timeout_commit: None,
                 },
             );
-            node.await.run().await.unwrap();
+            let node = node.await;
+
+            let genesis_path = home.path().join("tendermint/config/genesis.json");
+            let mut genesis: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(&genesis_path).unwrap()).unwrap();
+            genesis["app_state"] = serde_json::json!({
+                "accounts": {
+                    "balances": [{
+                        "address": DerivedKey::address_for(b"alice").unwrap().to_string(),
+                        "amount": 100_000,
+                    }],
+                },
+            });
+            std::fs::write(&genesis_path, serde_json::to_vec_pretty(&genesis).unwrap()).unwrap();
+
+            node.run().await.unwrap();
             home.close().unwrap();
         });
 
@@ -214,15 +391,16 @@ mod tests {
             .unwrap();
         assert_eq!(res.value, 100_000);
 
-        client
+        let receipt = client
             .call(
                 |app| build_call!(app.accounts.take_as_funding(50_000.into())),
                 |app| build_call!(app.increment_foo()),
             )
             .await
             .unwrap();
+        assert!(receipt.is_ok());
 
-        let old_height = 2; // TODO: get from call response
+        let old_height = receipt.height.unwrap() as u32 - 1;
         let client = HttpClient::with_height("http://localhost:26657", old_height).unwrap();
         let client =
             AppClient::<App, App, _, FooCoin, _>::new(client, DerivedKey::new(b"alice").unwrap());
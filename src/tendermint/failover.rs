@@ -0,0 +1,420 @@
+//! A transport which spreads requests across several RPC nodes.
+
+use super::client::HttpClient;
+use crate::{
+    abci::App,
+    call::Call,
+    client::{receipt::Receipt, sync::Transport as SyncTransport, Transport},
+    encoding::Encode,
+    plugins::{ABCICall, ABCIPlugin},
+    query::Query,
+    state::State,
+    store::Store,
+    Error, Result,
+};
+use futures_lite::future::block_on;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex as SyncMutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// The health of an endpoint, as last observed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EndpointStatus {
+    pub url: String,
+    /// The latest block height reported by the node.
+    pub height: Option<u64>,
+    /// The latency of the last successful request.
+    pub latency: Option<Duration>,
+    pub catching_up: bool,
+    /// The error from the last failed request, if it failed more recently
+    /// than the cooldown period.
+    pub last_error: Option<String>,
+}
+
+struct Endpoint {
+    url: String,
+    client: HttpClient,
+    health: SyncMutex<Health>,
+}
+
+#[derive(Default)]
+struct Health {
+    height: Option<u64>,
+    latency: Option<Duration>,
+    catching_up: bool,
+    failed_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+/// A [Transport] over several Tendermint RPC endpoints.
+///
+/// Requests go to the healthiest endpoint: one which has not failed within the
+/// cooldown period, is not lagging behind the highest known block, and has the
+/// lowest latency. If a request fails because the node could not be reached,
+/// it is retried on the next endpoint, while errors returned by the app are
+/// returned immediately. Queries may optionally be spread across all healthy
+/// endpoints.
+///
+/// Endpoint heights are refreshed with [FailoverClient::check_health] before a
+/// request once the health interval has passed since the last check.
+///
+/// As with [HttpClient], every query made through the same client is evaluated
+/// at the height of the first one, even when they are served by different
+/// endpoints, so a logical request sees a consistent state.
+pub struct FailoverClient {
+    endpoints: Vec<Endpoint>,
+    height: Mutex<Option<u32>>,
+    load_balance: bool,
+    max_lag: u64,
+    cooldown: Duration,
+    health_interval: Duration,
+    last_check: SyncMutex<Option<Instant>>,
+    next: AtomicUsize,
+}
+
+impl FailoverClient {
+    pub fn new<S: AsRef<str>>(urls: &[S]) -> Result<Self> {
+        let mut client = Self {
+            endpoints: vec![],
+            height: Mutex::new(None),
+            load_balance: false,
+            max_lag: 2,
+            cooldown: Duration::from_secs(30),
+            health_interval: Duration::from_secs(10),
+            last_check: SyncMutex::new(None),
+            next: AtomicUsize::new(0),
+        };
+        for url in urls {
+            client = client.endpoint(url.as_ref(), HttpClient::new(url.as_ref())?);
+        }
+
+        Ok(client)
+    }
+
+    pub fn with_height<S: AsRef<str>>(urls: &[S], height: u32) -> Result<Self> {
+        let mut client = Self::new(urls)?;
+        client.height = Mutex::new(Some(height));
+        Ok(client)
+    }
+
+    /// Adds an endpoint with a preconfigured client, e.g. one using a light
+    /// client or a different broadcast mode.
+    #[must_use]
+    pub fn endpoint(mut self, url: impl Into<String>, client: HttpClient) -> Self {
+        self.endpoints.push(Endpoint {
+            url: url.into(),
+            client,
+            health: SyncMutex::new(Health::default()),
+        });
+        self
+    }
+
+    /// Spreads queries across all healthy endpoints rather than sending them
+    /// to the healthiest one.
+    #[must_use]
+    pub fn load_balance(mut self, enabled: bool) -> Self {
+        self.load_balance = enabled;
+        self
+    }
+
+    /// Sets how many blocks an endpoint may lag behind the highest known
+    /// height before it is considered unhealthy. Defaults to 2.
+    #[must_use]
+    pub fn max_lag(mut self, blocks: u64) -> Self {
+        self.max_lag = blocks;
+        self
+    }
+
+    /// Sets how long an endpoint is avoided after a failed request. Defaults
+    /// to 30 seconds.
+    #[must_use]
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Sets how often endpoint heights are refreshed before a request.
+    /// Defaults to 10 seconds.
+    #[must_use]
+    pub fn health_interval(mut self, interval: Duration) -> Self {
+        self.health_interval = interval;
+        self
+    }
+
+    /// Fetches the status of every endpoint, updating their height and
+    /// latency.
+    pub async fn check_health(&self) -> Vec<EndpointStatus> {
+        *self.last_check.lock().unwrap() = Some(Instant::now());
+
+        for i in 0..self.endpoints.len() {
+            let start = Instant::now();
+            match self.endpoints[i].client.latest_height().await {
+                Ok((height, catching_up)) => {
+                    self.record_success(i, start.elapsed());
+                    let mut health = self.endpoints[i].health.lock().unwrap();
+                    health.height = Some(height);
+                    health.catching_up = catching_up;
+                }
+                Err(err) => self.record_failure(i, &err),
+            }
+        }
+
+        self.status()
+    }
+
+    /// Checks the health of the endpoints if it has not been checked within
+    /// the health interval.
+    async fn refresh_health(&self) {
+        let stale = {
+            let mut last_check = self.last_check.lock().unwrap();
+            let stale =
+                last_check.map_or(true, |checked| checked.elapsed() >= self.health_interval);
+            if stale {
+                // claim the refresh so concurrent requests don't repeat it
+                *last_check = Some(Instant::now());
+            }
+            stale
+        };
+
+        if stale {
+            self.check_health().await;
+        }
+    }
+
+    /// Returns the last observed health of every endpoint.
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let now = Instant::now();
+        self.endpoints
+            .iter()
+            .map(|endpoint| {
+                let health = endpoint.health.lock().unwrap();
+                EndpointStatus {
+                    url: endpoint.url.clone(),
+                    height: health.height,
+                    latency: health.latency,
+                    catching_up: health.catching_up,
+                    last_error: if health.cooling_down(now, self.cooldown) {
+                        health.last_error.clone()
+                    } else {
+                        None
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Returns endpoint indexes in the order they should be tried.
+    fn order(&self, spread: bool) -> Vec<usize> {
+        let now = Instant::now();
+        let health: Vec<_> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let health = endpoint.health.lock().unwrap();
+                (
+                    health.cooling_down(now, self.cooldown),
+                    health.height,
+                    health.catching_up,
+                    health.latency,
+                )
+            })
+            .collect();
+        let best_height = health.iter().filter_map(|h| h.1).max().unwrap_or(0);
+
+        let rank = |i: usize| {
+            let (cooling_down, height, catching_up, latency) = health[i];
+            let lagging = catching_up || height.map_or(false, |h| h + self.max_lag < best_height);
+            let unhealthy = cooling_down || lagging;
+            (unhealthy, latency.unwrap_or(Duration::MAX))
+        };
+
+        let mut order: Vec<_> = (0..self.endpoints.len()).collect();
+        order.sort_by_key(|&i| rank(i));
+
+        if spread {
+            let healthy = order.iter().take_while(|&&i| !rank(i).0).count();
+            if healthy > 1 {
+                let offset = self.next.fetch_add(1, Ordering::Relaxed) % healthy;
+                order[..healthy].rotate_left(offset);
+            }
+        }
+
+        order
+    }
+
+    fn record_success(&self, i: usize, latency: Duration) {
+        let mut health = self.endpoints[i].health.lock().unwrap();
+        health.latency = Some(latency);
+        health.failed_at = None;
+    }
+
+    fn record_failure(&self, i: usize, err: &Error) {
+        log::debug!("RPC endpoint {} failed: {}", self.endpoints[i].url, err);
+        let mut health = self.endpoints[i].health.lock().unwrap();
+        health.failed_at = Some(Instant::now());
+        health.last_error = Some(err.to_string());
+    }
+}
+
+impl Health {
+    fn cooling_down(&self, now: Instant, cooldown: Duration) -> bool {
+        self.failed_at
+            .map_or(false, |failed_at| now.duration_since(failed_at) < cooldown)
+    }
+}
+
+fn no_endpoints() -> Error {
+    Error::Client("No RPC endpoints configured".into())
+}
+
+/// Whether a request failed because the node could not be reached, rather
+/// than being rejected by the app, in which case another endpoint is tried.
+fn is_transport_error(err: &Error) -> bool {
+    matches!(err, Error::TendermintRPC(_))
+}
+
+impl<T: App + Call + Query + State + Default> Transport<ABCIPlugin<T>> for FailoverClient {
+    /// Broadcasts to the healthiest endpoint. Only failures to reach a node are
+    /// retried on another endpoint, so a rejected transaction is not
+    /// resubmitted.
+    async fn call(&self, call: <ABCIPlugin<T> as Call>::Call) -> Result<Receipt> {
+        let call = match call {
+            ABCICall::DeliverTx(call) => call,
+            _ => return Err(Error::Client("Unexpected call type".into())),
+        };
+        let call_bytes = call.encode()?;
+        self.refresh_health().await;
+
+        let mut last_err = None;
+        for i in self.order(false) {
+            let start = Instant::now();
+            match self.endpoints[i].client.broadcast(call_bytes.clone()).await {
+                Ok(receipt) => {
+                    self.record_success(i, start.elapsed());
+                    return Ok(receipt);
+                }
+                Err(err) if is_transport_error(&err) => {
+                    self.record_failure(i, &err);
+                    last_err = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(no_endpoints))
+    }
+
+    async fn wait_for_inclusion(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        let i = *self.order(false).first().ok_or_else(no_endpoints)?;
+        self.endpoints[i].client.tx_receipt(hash, timeout).await
+    }
+
+    async fn query(&self, query: T::Query) -> Result<Store> {
+        let query_bytes = query.encode()?;
+        self.refresh_health().await;
+
+        let mut last_err = None;
+        for i in self.order(self.load_balance) {
+            let maybe_height = *self.height.lock().await;
+            let start = Instant::now();
+            match self.endpoints[i]
+                .client
+                .query_at(query_bytes.clone(), maybe_height)
+                .await
+            {
+                Ok((store, height)) => {
+                    self.record_success(i, start.elapsed());
+                    self.height.lock().await.replace(height);
+                    return Ok(store);
+                }
+                Err(err) if is_transport_error(&err) => {
+                    self.record_failure(i, &err);
+                    last_err = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(no_endpoints))
+    }
+}
+
+impl<T: App + Call + Query + State + Default> SyncTransport<ABCIPlugin<T>> for FailoverClient {
+    fn call_sync(&self, call: <ABCIPlugin<T> as Call>::Call) -> Result<Receipt> {
+        block_on(Transport::<ABCIPlugin<T>>::call(self, call))
+    }
+
+    fn wait_for_inclusion_sync(&self, hash: &[u8], timeout: Duration) -> Result<Receipt> {
+        block_on(Transport::<ABCIPlugin<T>>::wait_for_inclusion(
+            self, hash, timeout,
+        ))
+    }
+
+    fn query_sync(&self, query: T::Query) -> Result<Store> {
+        block_on(Transport::<ABCIPlugin<T>>::query(self, query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> FailoverClient {
+        FailoverClient::new(&["http://a:26657", "http://b:26657", "http://c:26657"]).unwrap()
+    }
+
+    fn set_health(client: &FailoverClient, i: usize, height: u64, latency_ms: u64) {
+        let mut health = client.endpoints[i].health.lock().unwrap();
+        health.height = Some(height);
+        health.latency = Some(Duration::from_millis(latency_ms));
+    }
+
+    #[test]
+    fn order_by_health() {
+        let client = client();
+        set_health(&client, 0, 100, 50);
+        set_health(&client, 1, 100, 10);
+        set_health(&client, 2, 90, 1);
+        assert_eq!(client.order(false), vec![1, 0, 2]);
+
+        client.record_failure(1, &Error::Client("unreachable".into()));
+        assert_eq!(client.order(false), vec![0, 2, 1]);
+        assert_eq!(
+            client.status()[1].last_error.as_deref(),
+            Some("Client Error: unreachable")
+        );
+
+        client.record_success(1, Duration::from_millis(10));
+        assert_eq!(client.order(false), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn transport_errors() {
+        let unreachable = tendermint_rpc::Error::client_internal("connection refused".into());
+        assert!(is_transport_error(&unreachable.into()));
+        assert!(!is_transport_error(&Error::Query("invalid query".into())));
+        assert!(!is_transport_error(&Error::Call("code 1: failed".into())));
+    }
+
+    #[test]
+    fn health_refresh() {
+        let client = client().health_interval(Duration::from_secs(60));
+        *client.last_check.lock().unwrap() = Some(Instant::now());
+        // a recent check is not repeated, so no request is made
+        block_on(client.refresh_health());
+        assert!(client.status().iter().all(|s| s.last_error.is_none()));
+    }
+
+    #[test]
+    fn spread_queries() {
+        let client = client();
+        set_health(&client, 0, 100, 10);
+        set_health(&client, 1, 100, 20);
+        set_health(&client, 2, 50, 1);
+
+        let firsts: Vec<_> = (0..4).map(|_| client.order(true)[0]).collect();
+        assert_eq!(firsts, vec![0, 1, 0, 1]);
+        assert_eq!(client.order(true)[2], 2);
+    }
+}
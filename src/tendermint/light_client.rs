@@ -0,0 +1,366 @@
+//! Light-client verification of Tendermint headers.
+//!
+//! A [LightClient] starts from a header which is trusted by height and hash
+//! (e.g. taken from a block explorer or shipped with a wallet), and verifies
+//! later headers by checking validator signatures, either skipping ahead via
+//! bisection or walking one height at a time. Earlier headers are verified by
+//! following the `last_block_id` hash chain back from a trusted header.
+//!
+//! Verified headers provide trusted app hashes, which [`HttpClient`] uses to
+//! check query proofs instead of trusting the root hash sent by the RPC node.
+//!
+//! [`HttpClient`]: super::client::HttpClient
+
+use crate::{Error, Result};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tendermint::block::{Header, Height};
+use tendermint::validator::Set as ValidatorSet;
+use tendermint::{Hash, Time};
+use tendermint_light_client_verifier::options::Options;
+use tendermint_light_client_verifier::types::{LightBlock, PeerId, TrustThreshold};
+use tendermint_light_client_verifier::{ProdVerifier, Verdict, Verifier};
+use tendermint_rpc::{self as tm, Client as _, Paging};
+use tokio::sync::Mutex;
+
+/// How many times to poll for a header which has not been produced yet.
+const MAX_WAIT_ATTEMPTS: u32 = 60;
+const WAIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How headers after the trusted header are verified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationMode {
+    /// Jump directly to the target header when enough of the trusted
+    /// validator set signed it, bisecting otherwise.
+    Skipping,
+    /// Verify every header between the trusted header and the target.
+    Sequential,
+}
+
+/// The root of trust for a [LightClient].
+#[derive(Clone, Debug)]
+pub struct TrustOptions {
+    pub height: u64,
+    pub hash: Hash,
+    pub trusting_period: Duration,
+    pub trust_threshold: TrustThreshold,
+    pub clock_drift: Duration,
+    pub mode: VerificationMode,
+}
+
+impl TrustOptions {
+    /// Trusts the header with the given height and hash, using skipping
+    /// verification, a trusting period of two weeks and a trust threshold of
+    /// one third.
+    pub fn new(height: u64, hash: Hash) -> Self {
+        Self {
+            height,
+            hash,
+            trusting_period: Duration::from_secs(14 * 24 * 60 * 60),
+            trust_threshold: TrustThreshold::ONE_THIRD,
+            clock_drift: Duration::from_secs(5),
+            mode: VerificationMode::Skipping,
+        }
+    }
+
+    /// Trusts the header with the given height and hex-encoded hash.
+    pub fn from_hex(height: u64, hash: &str) -> Result<Self> {
+        let hash = Hash::from_hex_upper(tendermint::hash::Algorithm::Sha256, hash)
+            .map_err(|e| Error::Tendermint(e.to_string()))?;
+        Ok(Self::new(height, hash))
+    }
+
+    #[must_use]
+    pub fn trusting_period(mut self, trusting_period: Duration) -> Self {
+        self.trusting_period = trusting_period;
+        self
+    }
+
+    #[must_use]
+    pub fn trust_threshold(mut self, trust_threshold: TrustThreshold) -> Self {
+        self.trust_threshold = trust_threshold;
+        self
+    }
+
+    #[must_use]
+    pub fn mode(mut self, mode: VerificationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+/// Tracks headers verified from a trusted root, fetching headers, commits and
+/// validator sets from a Tendermint RPC node.
+pub struct LightClient {
+    rpc: tm::HttpClient,
+    verifier: ProdVerifier,
+    trust: TrustOptions,
+    trusted: Mutex<BTreeMap<u64, LightBlock>>,
+}
+
+impl LightClient {
+    /// Constructs a light client for the given RPC node. The trusted header is
+    /// fetched and checked against `trust.hash` on first use.
+    pub fn new(rpc: tm::HttpClient, trust: TrustOptions) -> Self {
+        Self {
+            rpc,
+            verifier: ProdVerifier::default(),
+            trust,
+            trusted: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn options(&self) -> Options {
+        Options {
+            trust_threshold: self.trust.trust_threshold,
+            trusting_period: self.trust.trusting_period,
+            clock_drift: self.trust.clock_drift,
+        }
+    }
+
+    /// Returns the header at the given height once it has been verified,
+    /// waiting for it to be produced if the chain has not yet reached it.
+    pub async fn verify_height(&self, height: u64) -> Result<Header> {
+        let mut trusted = self.trusted.lock().await;
+
+        if trusted.is_empty() {
+            let root = self.fetch(self.trust.height).await?;
+            if root.signed_header.header.hash() != self.trust.hash {
+                return Err(Error::Tendermint(format!(
+                    "Header at trusted height {} does not match trusted hash",
+                    self.trust.height
+                )));
+            }
+            trusted.insert(self.trust.height, root);
+        }
+
+        if let Some(block) = trusted.get(&height) {
+            return Ok(block.signed_header.header.clone());
+        }
+
+        let block = match trusted.range(..height).next_back() {
+            Some((_, below)) => {
+                let below = below.clone();
+                self.wait_for_height(height).await?;
+                self.verify_forward(&mut trusted, below, height).await?
+            }
+            None => {
+                let (_, above) = trusted.range(height..).next().unwrap();
+                let above = above.clone();
+                self.verify_backward(&mut trusted, above, height).await?
+            }
+        };
+
+        Ok(block.signed_header.header)
+    }
+
+    /// Verifies the header at the given height and checks that it commits to
+    /// the given app hash.
+    pub async fn verify_app_hash(&self, height: u64, app_hash: &[u8]) -> Result<()> {
+        let header = self.verify_height(height).await?;
+        if header.app_hash.as_bytes() != app_hash {
+            return Err(Error::Tendermint(format!(
+                "App hash does not match verified header at height {}",
+                height
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn verify_forward(
+        &self,
+        trusted: &mut BTreeMap<u64, LightBlock>,
+        from: LightBlock,
+        target: u64,
+    ) -> Result<LightBlock> {
+        let options = self.options();
+        let mut current = from;
+        let mut pivot = self.next_pivot(current.height().value(), target);
+
+        loop {
+            let untrusted = self.fetch(pivot).await?;
+            let verdict = self.verifier.verify_update_header(
+                untrusted.as_untrusted_state(),
+                current.as_trusted_state(),
+                &options,
+                now()?,
+            );
+
+            match verdict {
+                Verdict::Success => {
+                    trusted.insert(pivot, untrusted.clone());
+                    current = untrusted;
+                    if pivot == target {
+                        return Ok(current);
+                    }
+                    pivot = self.next_pivot(pivot, target);
+                }
+                Verdict::NotEnoughTrust(_) => {
+                    let height = current.height().value();
+                    let midpoint = height + (pivot - height) / 2;
+                    if midpoint == height {
+                        return Err(Error::Tendermint(format!(
+                            "Not enough trust to verify header at height {}",
+                            pivot
+                        )));
+                    }
+                    pivot = midpoint;
+                }
+                Verdict::Invalid(detail) => {
+                    return Err(Error::Tendermint(format!(
+                        "Invalid header at height {}: {}",
+                        pivot, detail
+                    )));
+                }
+            }
+        }
+    }
+
+    async fn verify_backward(
+        &self,
+        trusted: &mut BTreeMap<u64, LightBlock>,
+        from: LightBlock,
+        target: u64,
+    ) -> Result<LightBlock> {
+        let mut current = from;
+
+        while current.height().value() > target {
+            let height = current.height().value() - 1;
+            let prev = self.fetch(height).await?;
+
+            let expected = current.signed_header.header.last_block_id.map(|id| id.hash);
+            if expected != Some(prev.signed_header.header.hash()) {
+                return Err(Error::Tendermint(format!(
+                    "Header at height {} does not match hash chain",
+                    height
+                )));
+            }
+
+            trusted.insert(height, prev.clone());
+            current = prev;
+        }
+
+        Ok(current)
+    }
+
+    fn next_pivot(&self, height: u64, target: u64) -> u64 {
+        match self.trust.mode {
+            VerificationMode::Skipping => target,
+            VerificationMode::Sequential => height + 1,
+        }
+    }
+
+    async fn wait_for_height(&self, height: u64) -> Result<()> {
+        for _ in 0..MAX_WAIT_ATTEMPTS {
+            let latest = self.rpc.latest_commit().await?;
+            if latest.signed_header.header.height.value() >= height {
+                return Ok(());
+            }
+            tokio::time::sleep(WAIT_INTERVAL).await;
+        }
+
+        Err(Error::Tendermint(format!(
+            "Timed out waiting for header at height {}",
+            height
+        )))
+    }
+
+    async fn fetch(&self, height: u64) -> Result<LightBlock> {
+        let signed_header = self.rpc.commit(to_height(height)?).await?.signed_header;
+        let validators = self.validator_set(height).await?;
+        let next_validators = self.validator_set(height + 1).await?;
+
+        let block = LightBlock::new(
+            signed_header,
+            validators,
+            next_validators,
+            PeerId::new([0; 20]),
+        );
+        check_validators(&block)?;
+
+        Ok(block)
+    }
+
+    async fn validator_set(&self, height: u64) -> Result<ValidatorSet> {
+        let res = self.rpc.validators(to_height(height)?, Paging::All).await?;
+        Ok(ValidatorSet::new(res.validators, None))
+    }
+}
+
+/// Checks that the validator sets fetched alongside a header are the ones it
+/// commits to. The verifier only checks signatures against the sets it is
+/// given, so without this an RPC node could pair a header with a set of its
+/// choosing.
+fn check_validators(block: &LightBlock) -> Result<()> {
+    let header = &block.signed_header.header;
+    let height = header.height.value();
+    check_validator_hash(
+        height,
+        "validators",
+        header.validators_hash,
+        &block.validators,
+    )?;
+    check_validator_hash(
+        height,
+        "next validators",
+        header.next_validators_hash,
+        &block.next_validators,
+    )
+}
+
+fn check_validator_hash(
+    height: u64,
+    kind: &str,
+    expected: Hash,
+    validators: &ValidatorSet,
+) -> Result<()> {
+    if validators.hash() != expected {
+        return Err(Error::Tendermint(format!(
+            "Header at height {} does not match fetched {} set",
+            height, kind
+        )));
+    }
+
+    Ok(())
+}
+
+fn to_height(height: u64) -> Result<Height> {
+    Height::try_from(height).map_err(|e| Error::Tendermint(e.to_string()))
+}
+
+fn now() -> Result<Time> {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::Tendermint(e.to_string()))?;
+    Time::from_unix_timestamp(since_epoch.as_secs() as i64, since_epoch.subsec_nanos())
+        .map_err(|e| Error::Tendermint(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tendermint::validator::Info;
+    use tendermint::{vote, PublicKey};
+
+    fn validator_set(key_hex: &str) -> ValidatorSet {
+        let key = hex::decode(key_hex).unwrap();
+        let info = Info::new(
+            PublicKey::from_raw_ed25519(&key).unwrap(),
+            vote::Power::from(10u32),
+        );
+        ValidatorSet::new(vec![info], None)
+    }
+
+    #[test]
+    fn swapped_validator_set() {
+        let honest =
+            validator_set("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        let swapped =
+            validator_set("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c");
+
+        check_validator_hash(5, "validators", honest.hash(), &honest).unwrap();
+        assert!(check_validator_hash(5, "validators", honest.hash(), &swapped).is_err());
+        assert!(check_validator_hash(5, "next validators", swapped.hash(), &honest).is_err());
+    }
+}
@@ -1,54 +1,22 @@
+pub mod binary;
 pub mod client;
+pub mod failover;
+pub mod light_client;
+pub mod subscribe;
 
 use crate::error::{Error, Result};
-use flate2::read::GzDecoder;
-use hex_literal::hex;
-use is_executable::IsExecutable;
-use log::{debug, info};
+use binary::BinaryConfig;
 use nom::bytes::complete::take_until;
 use nom::multi::{many0, many1};
 use nom::sequence::separated_pair;
-use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::{prelude::*, BufReader};
-use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::str::FromStr;
 use std::sync::mpsc::{self, Receiver, Sender};
-use tar::Archive;
 use toml_edit::{value, Document};
 
-#[cfg(target_os = "macos")]
-static TENDERMINT_BINARY_URL: &str = "https://github.com/informalsystems/tendermint/releases/download/v0.34.26/tendermint_0.34.26_darwin_amd64.tar.gz";
-#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-static TENDERMINT_BINARY_URL: &str = "https://github.com/informalsystems/tendermint/releases/download/v0.34.26/tendermint_0.34.26_linux_amd64.tar.gz";
-#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
-static TENDERMINT_BINARY_URL: &str = "https://github.com/informalsystems/tendermint/releases/download/v0.34.26/tendermint_0.34.26_linux_arm64.tar.gz";
-
-#[cfg(target_os = "macos")]
-static TENDERMINT_ZIP_HASH: [u8; 32] =
-    hex!("39dfde6ccc2c8b4cb699d1f3788b97da16cc8495156c39c82e94fa3834187909");
-#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-static TENDERMINT_ZIP_HASH: [u8; 32] =
-    hex!("70415c1d20f48e4c19d8317ec7befd924681bb2d144ad8fded429041b80b3f79");
-#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
-static TENDERMINT_ZIP_HASH: [u8; 32] =
-    hex!("b0c9b5fae8a7dc53d84d62867204927ef37b1f91be5617f33a8f7fe378dfc5b9");
-
-const TENDERMINT_BINARY_NAME: &str = "tendermint-v0.34.26";
-
-fn verify_hash(tendermint_bytes: &[u8]) {
-    let mut hasher = Sha256::new();
-    hasher.update(tendermint_bytes);
-    let digest = hasher.finalize();
-    let bytes = digest.as_slice();
-    assert_eq!(
-        bytes, TENDERMINT_ZIP_HASH,
-        "Tendermint binary zip did not match expected hash"
-    );
-    info!("Confirmed correct Tendermint zip hash");
-}
 pub struct Child {
     child: std::process::Child,
     sender: Sender<Option<()>>,
@@ -69,7 +37,10 @@ impl Child {
 
 #[derive(Debug)]
 pub struct Tendermint {
-    command: std::process::Command,
+    args: Vec<String>,
+    stdout: Option<Stdio>,
+    stderr: Option<Stdio>,
+    binary: BinaryConfig,
     home: PathBuf,
     genesis_bytes: Option<Vec<u8>>,
     config_contents: Option<toml_edit::Document>,
@@ -81,16 +52,18 @@ impl Tendermint {
     /// from Rust programs
     ///
     /// Passed home_path generates and enclosing directory which will, in
-    /// addition to housing the downloaded tendermint binary, will serve
+    /// addition to housing the installed tendermint binary, will serve
     /// as the tendermint --home argument
     pub fn new<T: Into<PathBuf> + Clone>(home_path: T) -> Tendermint {
         let path: PathBuf = home_path.clone().into();
         if !path.exists() {
             fs::create_dir(path.clone()).expect("Failed to create Tendermint home directory");
         }
-        let tm_bin_path = path.join(TENDERMINT_BINARY_NAME);
         let tendermint = Tendermint {
-            command: Command::new(tm_bin_path.to_str().unwrap()),
+            args: vec![],
+            stdout: None,
+            stderr: None,
+            binary: BinaryConfig::default(),
             home: home_path.clone().into(),
             genesis_bytes: None,
             config_contents: None,
@@ -99,59 +72,50 @@ impl Tendermint {
         tendermint.home(home_path.into())
     }
 
-    async fn install(&self) {
-        let tendermint_path = self.home.join(TENDERMINT_BINARY_NAME);
+    /// Builds the command running the given subcommand, installing the
+    /// Tendermint binary first if needed.
+    async fn command(&mut self, subcommand: &str) -> Command {
+        let bin = self
+            .binary
+            .resolve(&self.home)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to install Tendermint: {}", err));
 
-        if tendermint_path.is_executable() {
-            debug!("Tendermint already installed");
-            return;
+        let mut command = Command::new(bin);
+        command.args(&self.args).arg(subcommand);
+        if let Some(stdout) = self.stdout.take() {
+            command.stdout(stdout);
         }
-
-        info!("Installing Tendermint to {}", self.home.to_str().unwrap());
-        let buf = reqwest::get(TENDERMINT_BINARY_URL)
-            .await
-            .expect("Failed to download Tendermint zip file from GitHub")
-            .bytes()
-            .await
-            .expect("Failed to read bytes from Tendermint zip file")
-            .to_vec();
-
-        verify_hash(&buf);
-
-        let cursor = std::io::Cursor::new(buf);
-        let tar = GzDecoder::new(cursor);
-        let mut archive = Archive::new(tar);
-
-        for item in archive.entries().unwrap() {
-            if item.as_ref().unwrap().path().unwrap().to_str().unwrap() == "tendermint" {
-                let mut tendermint_bytes = vec![];
-                item.unwrap().read_to_end(&mut tendermint_bytes).unwrap();
-
-                let mut f = fs::File::create(tendermint_path)
-                    .expect("Could not create Tendermint binary on file system");
-                f.write_all(tendermint_bytes.as_slice())
-                    .expect("Failed to write Tendermint binary to file system");
-
-                let mut perms = f.metadata().unwrap().permissions();
-                perms.set_mode(0o755);
-                f.set_permissions(perms)
-                    .expect("Failed to set Tendermint binary permissions");
-                break;
-            }
+        if let Some(stderr) = self.stderr.take() {
+            command.stderr(stderr);
         }
+
+        command
+    }
+
+    fn arg<S: AsRef<str>>(&mut self, arg: S) {
+        self.args.push(arg.as_ref().to_string());
+    }
+
+    /// Sets where the Tendermint binary comes from, see [`binary`](mod@binary). Defaults to
+    /// downloading the pinned release.
+    #[must_use]
+    pub fn binary(mut self, binary: BinaryConfig) -> Self {
+        self.binary = binary;
+        self
     }
 
     pub fn flags(mut self, flags: Vec<String>) -> Self {
         for flag in flags {
-            self.command.arg(flag.trim());
+            self.arg(flag.trim());
         }
         self
     }
 
     fn home(mut self, home_path: PathBuf) -> Self {
         let new_home = home_path.to_str().unwrap();
-        self.command.arg("--home");
-        self.command.arg(new_home);
+        self.arg("--home");
+        self.arg(new_home);
         self
     }
 
@@ -164,8 +128,8 @@ impl Tendermint {
     ///     unsafe_reset_all
     #[must_use]
     pub fn log_level(mut self, level: &str) -> Self {
-        self.command.arg("--log_level");
-        self.command.arg(level);
+        self.arg("--log_level");
+        self.arg(level);
         self
     }
 
@@ -178,7 +142,7 @@ impl Tendermint {
     ///     unsafe_reset_all
     #[must_use]
     pub fn trace(mut self) -> Self {
-        self.command.arg("--trace");
+        self.arg("--trace");
         self
     }
 
@@ -189,8 +153,8 @@ impl Tendermint {
     ///     start
     #[must_use]
     pub fn moniker(mut self, moniker: &str) -> Self {
-        self.command.arg("--moniker");
-        self.command.arg(moniker);
+        self.arg("--moniker");
+        self.arg(moniker);
         self
     }
 
@@ -206,8 +170,8 @@ impl Tendermint {
     /// terminating methods will cause the tendermint process to fail
     #[must_use]
     pub fn p2p_laddr(mut self, addr: &str) -> Self {
-        self.command.arg("--p2p.laddr");
-        self.command.arg(addr);
+        self.arg("--p2p.laddr");
+        self.arg(addr);
         self
     }
 
@@ -222,10 +186,10 @@ impl Tendermint {
     /// terminating methods will cause the tendermint process to fail
     #[must_use]
     pub fn p2p_persistent_peers(mut self, peers: Vec<String>) -> Self {
-        self.command.arg("--p2p.persistent_peers");
+        self.arg("--p2p.persistent_peers");
         let mut arg: String = "".to_string();
         peers.iter().for_each(|x| arg += x);
-        self.command.arg(&arg);
+        self.arg(&arg);
         self
     }
 
@@ -241,8 +205,8 @@ impl Tendermint {
     /// terminating methods will cause the tendermint process to fail
     #[must_use]
     pub fn rpc_laddr(mut self, addr: &str) -> Self {
-        self.command.arg("--rpc.laddr");
-        self.command.arg(addr);
+        self.arg("--rpc.laddr");
+        self.arg(addr);
         self
     }
 
@@ -259,8 +223,8 @@ impl Tendermint {
     /// methods will cause the tendermint process to fail
     #[must_use]
     pub fn proxy_app(mut self, addr: &str) -> Self {
-        self.command.arg("--proxy_app");
-        self.command.arg(addr);
+        self.arg("--proxy_app");
+        self.arg(addr);
         self
     }
 
@@ -293,7 +257,7 @@ impl Tendermint {
     /// ```
     #[must_use]
     pub fn stdout<T: Into<Stdio>>(mut self, cfg: T) -> Self {
-        self.command.stdout(cfg);
+        self.stdout = Some(cfg.into());
         self
     }
 
@@ -326,7 +290,7 @@ impl Tendermint {
     /// ```
     #[must_use]
     pub fn stderr<T: Into<Stdio>>(mut self, cfg: T) -> Self {
-        self.command.stderr(cfg);
+        self.stderr = Some(cfg.into());
         self
     }
 
@@ -339,7 +303,7 @@ impl Tendermint {
     /// terminating methods will cause the tendermint process to fail
     #[must_use]
     pub fn keep_addr_book(mut self) -> Self {
-        self.command.arg("--keep_addr_book");
+        self.arg("--keep_addr_book");
         self
     }
 
@@ -525,14 +489,13 @@ impl Tendermint {
     /// Note: This will locally install the Tendermint binary if it is
     /// not already contained in the Tendermint home directory
     pub async fn start(mut self) -> Child {
-        self.install().await;
+        let mut command = self.command("start").await;
         self.mutate_configuration();
-        self.command.arg("start");
         if !self.show_logs {
-            self.command.stdout(Stdio::piped());
+            command.stdout(Stdio::piped());
         }
 
-        let mut child = self.command.spawn().unwrap();
+        let mut child = command.spawn().unwrap();
 
         let (tx, rx): (Sender<Option<()>>, Receiver<Option<()>>) = mpsc::channel();
         if !self.show_logs {
@@ -597,9 +560,7 @@ impl Tendermint {
     /// not already contained in the Tendermint home directory
     #[must_use]
     pub async fn init(mut self) -> Self {
-        self.install().await;
-        self.command.arg("init");
-        let mut child = self.command.spawn().unwrap();
+        let mut child = self.command("init").await.spawn().unwrap();
         child.wait().unwrap();
         self.mutate_configuration();
 
@@ -611,9 +572,7 @@ impl Tendermint {
     /// Note: This will locally install the Tendermint binary if it is
     /// not already contained in the Tendermint home directory
     pub async fn unsafe_reset_all(mut self) {
-        self.install().await;
-        self.command.arg("unsafe_reset_all");
-        let mut child = self.command.spawn().unwrap();
+        let mut child = self.command("unsafe_reset_all").await.spawn().unwrap();
         child.wait().unwrap();
     }
 }
@@ -708,7 +667,7 @@ mod tests {
         let expected: HashSet<String> = HashSet::from([
             "config".to_string(),
             "data".to_string(),
-            TENDERMINT_BINARY_NAME.to_string(),
+            format!("tendermint-v{}", binary::DEFAULT_VERSION),
         ]);
 
         assert_eq!(file_set, expected);
@@ -0,0 +1,116 @@
+//! A native [EventSource] backed by Tendermint's websocket RPC.
+
+use super::client::convert_events;
+use crate::client::subscribe::{BlockEvent, BlockEvents, EventSource};
+use crate::{Error, Result};
+use futures_lite::StreamExt;
+use tendermint::block::Height;
+use tendermint_rpc::client::CompatMode;
+use tendermint_rpc::event::EventData;
+use tendermint_rpc::query::EventType;
+use tendermint_rpc::{self as tm, Client as _, SubscriptionClient as _};
+use tokio::task::JoinHandle;
+
+/// Listens for new blocks over a websocket connection to a Tendermint node,
+/// e.g. `ws://localhost:26657/websocket`.
+pub struct WebSocketSource {
+    url: String,
+    conn: Option<Connection>,
+}
+
+struct Connection {
+    client: tm::WebSocketClient,
+    blocks: tm::Subscription,
+    driver: JoinHandle<()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.driver.abort();
+    }
+}
+
+impl WebSocketSource {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            conn: None,
+        }
+    }
+
+    fn conn(&mut self) -> Result<&mut Connection> {
+        self.conn
+            .as_mut()
+            .ok_or_else(|| Error::Client("WebSocket disconnected".into()))
+    }
+}
+
+impl EventSource for WebSocketSource {
+    async fn connect(&mut self) -> Result<()> {
+        self.conn = None;
+
+        let url: tm::WebSocketClientUrl = self.url.as_str().try_into()?;
+        let (client, driver) = tm::WebSocketClient::builder(url)
+            .compat_mode(CompatMode::V0_34)
+            .build()
+            .await?;
+        let driver = tokio::spawn(async move {
+            if let Err(err) = driver.run().await {
+                log::debug!("WebSocket driver stopped: {}", err);
+            }
+        });
+        let blocks = client.subscribe(EventType::NewBlock.into()).await?;
+
+        self.conn = Some(Connection {
+            client,
+            blocks,
+            driver,
+        });
+
+        Ok(())
+    }
+
+    async fn next_height(&mut self) -> Result<u64> {
+        loop {
+            let event = match self.conn()?.blocks.next().await {
+                Some(event) => event,
+                None => {
+                    self.conn = None;
+                    return Err(Error::Client("WebSocket disconnected".into()));
+                }
+            };
+
+            if let EventData::NewBlock {
+                block: Some(block), ..
+            } = event?.data
+            {
+                return Ok(block.header.height.value());
+            }
+        }
+    }
+
+    async fn block_events(&mut self, height: u64) -> Result<BlockEvents> {
+        let tm_height = Height::try_from(height).map_err(|e| Error::Tendermint(e.to_string()))?;
+        let res = self.conn()?.client.block_results(tm_height).await?;
+
+        let mut events = vec![];
+        let mut push = |tx_index, tm_events| {
+            events.extend(
+                convert_events(tm_events)
+                    .into_iter()
+                    .map(|event| BlockEvent { tx_index, event }),
+            )
+        };
+
+        push(None, res.begin_block_events.unwrap_or_default());
+        for (i, tx) in res.txs_results.unwrap_or_default().into_iter().enumerate() {
+            push(Some(i as u32), tx.events);
+        }
+        push(None, res.end_block_events.unwrap_or_default());
+
+        Ok(BlockEvents {
+            height: res.height.value(),
+            events,
+        })
+    }
+}
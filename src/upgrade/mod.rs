@@ -2,11 +2,14 @@ use crate::coins::{Address, Amount, Decimal};
 use crate::collections::Map;
 use crate::context::GetContext;
 use crate::encoding::LengthVec;
+use crate::events::{emit, Event};
+use crate::genesis::Genesis;
 use crate::migrate::MigrateFrom;
 use crate::orga;
 use crate::plugins::{Signer, Time, ValidatorEntry, Validators};
 use crate::prelude::{Read, Store};
 use crate::{Error as OrgaError, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
 
@@ -17,7 +20,16 @@ pub enum Error {
     #[error(
         "Node is running version {expected:?}, but the network has upgraded to version {actual:?}"
     )]
-    Version { expected: Version, actual: Version },
+    Version {
+        expected: Version,
+        actual: Version,
+        /// The manifest announced for the network version, if any.
+        manifest: Option<Manifest>,
+    },
+    #[error("Upgrade Binary Error: {0}")]
+    Binary(String),
+    #[error("Upgrade Manifest Error: {0}")]
+    Manifest(String),
 }
 
 type PubKey = [u8; 32];
@@ -30,7 +42,51 @@ pub struct Signal {
     pub time: i64,
 }
 
-#[orga(skip(Default), version = 1)]
+/// Announces where the binaries for a version can be downloaded. `url` points
+/// to a JSON manifest listing a binary and its hash for each platform, and
+/// `sha256` is the hash of that manifest, so a node can verify the binaries
+/// it downloads against what was signaled on chain. See
+/// [`supervisor`](crate::abci::supervisor) for the manifest format.
+#[orga]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub url: LengthVec<u16, u8>,
+    pub sha256: [u8; 32],
+}
+
+impl Manifest {
+    pub fn new(url: &str, sha256: [u8; 32]) -> Result<Self> {
+        let url: LengthVec<u16, u8> = url
+            .as_bytes()
+            .to_vec()
+            .try_into()
+            .map_err(|_| OrgaError::App("Manifest URL is too long".into()))?;
+
+        Ok(Self { url, sha256 })
+    }
+
+    /// The URL of the manifest.
+    pub fn url(&self) -> Result<&str> {
+        let url = std::str::from_utf8(&self.url)
+            .map_err(|_| OrgaError::App("Manifest URL must be UTF-8".into()))?;
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(OrgaError::App("Manifest URL must be an HTTP(S) URL".into()));
+        }
+
+        Ok(url)
+    }
+}
+
+/// Emitted by [Upgrade::signal]. The version is encoded as hex.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+#[event(kind = "upgrade_signal")]
+pub struct SignalEvent {
+    #[event(index)]
+    pub validator: Address,
+    pub version: Vec<u8>,
+}
+
+#[orga(skip(Default), version = 2)]
 pub struct Upgrade {
     pub signals: Map<PubKey, Signal>,
     pub threshold: Decimal,
@@ -39,6 +95,14 @@ pub struct Upgrade {
     #[state(absolute_prefix(b"/version"))]
     // TODO: use Value/Box instead of Map<(), _>
     pub current_version: Map<(), Version>,
+    /// The manifest announced with each validator's current signal, see
+    /// [Upgrade::signal_with_manifest].
+    #[orga(version(V2))]
+    pub signal_manifests: Map<PubKey, Manifest>,
+    /// The manifests which reached the threshold when their version was
+    /// activated, by version.
+    #[orga(version(V2))]
+    pub manifests: Map<Version, Manifest>,
 }
 
 impl Default for Upgrade {
@@ -53,6 +117,8 @@ impl Default for Upgrade {
             activation_delay_seconds: 60 * 60 * 24,
             rate_limit_seconds: 60,
             current_version,
+            signal_manifests: Default::default(),
+            manifests: Default::default(),
         }
     }
 }
@@ -63,15 +129,46 @@ impl MigrateFrom<UpgradeV0> for UpgradeV1 {
     }
 }
 
+impl MigrateFrom<UpgradeV1> for UpgradeV2 {
+    fn migrate_from(prev: UpgradeV1) -> Result<Self> {
+        Ok(Self {
+            signals: prev.signals,
+            threshold: prev.threshold,
+            activation_delay_seconds: prev.activation_delay_seconds,
+            rate_limit_seconds: prev.rate_limit_seconds,
+            current_version: prev.current_version,
+            signal_manifests: Default::default(),
+            manifests: Default::default(),
+        })
+    }
+}
+
 #[orga]
 impl Upgrade {
     #[call]
     pub fn signal(&mut self, version: Version) -> Result<()> {
         crate::plugins::disable_fee();
+        self.record_signal(version, None)
+    }
+
+    /// Signals like [Upgrade::signal], also announcing the manifest of the
+    /// binaries for the version. Validators may announce different manifests;
+    /// a manifest is only passed on to nodes once validators announcing it
+    /// for the activated version hold more than the threshold of voting
+    /// power. Signaling the same version again replaces the announced
+    /// manifest without restarting the activation delay.
+    #[call]
+    pub fn signal_with_manifest(&mut self, version: Version, manifest: Manifest) -> Result<()> {
+        crate::plugins::disable_fee();
+        manifest.url()?;
+        self.record_signal(version, Some(manifest))
+    }
+
+    fn record_signal(&mut self, version: Version, manifest: Option<Manifest>) -> Result<()> {
         let cons_key = self.signer_cons_key()?;
         let now = self.current_seconds()?;
 
-        let signal = Signal { version, time: now };
+        let mut signal = Signal { version, time: now };
         if let Some(prev_signal) = self.signals.get(cons_key)? {
             let soonest = prev_signal.time + self.rate_limit_seconds;
             if signal.time < soonest {
@@ -81,13 +178,30 @@ impl Upgrade {
                 )));
             }
             if signal.version == prev_signal.version {
-                return Err(OrgaError::App(format!(
-                    "Version {:?} has already been signaled",
-                    signal.version
-                )));
+                let prev_manifest = self
+                    .signal_manifests
+                    .get(cons_key)?
+                    .map(|manifest| manifest.clone());
+                if prev_manifest == manifest {
+                    return Err(OrgaError::App(format!(
+                        "Version {:?} has already been signaled",
+                        signal.version
+                    )));
+                }
+                signal.time = prev_signal.time;
             }
         }
 
+        emit(&SignalEvent {
+            validator: self.signer()?,
+            version: signal.version.to_vec(),
+        });
+        match manifest {
+            Some(manifest) => self.signal_manifests.insert(cons_key, manifest)?,
+            None => {
+                self.signal_manifests.remove(cons_key)?;
+            }
+        }
         self.signals.insert(cons_key, signal)
     }
 
@@ -95,9 +209,14 @@ impl Upgrade {
         let bin_version = bin_version.clone();
         let net_version = self.current_version.get(())?.unwrap().clone();
         if bin_version != net_version {
+            let manifest = self
+                .manifests
+                .get(net_version.clone())?
+                .map(|manifest| manifest.clone());
             return Err(Error::Version {
                 expected: net_version,
                 actual: bin_version,
+                manifest,
             }
             .into());
         }
@@ -105,18 +224,27 @@ impl Upgrade {
         if !upgrade_authorized {
             return Ok(());
         }
-        if let Some(new_version) = self.upgrade_ready()? {
+        if let Some((new_version, manifest)) = self.upgrade_ready()? {
+            match manifest {
+                Some(manifest) => self.manifests.insert(new_version.clone(), manifest)?,
+                None => {
+                    self.manifests.remove(new_version.clone())?;
+                }
+            }
             self.current_version.insert((), new_version)?;
         }
 
         Ok(())
     }
 
-    fn upgrade_ready(&mut self) -> Result<Option<Version>> {
+    /// Returns the version which reached the threshold, if any, along with
+    /// the manifest announced for it by validators above the threshold.
+    fn upgrade_ready(&mut self) -> Result<Option<(Version, Option<Manifest>)>> {
         let now = self.current_seconds()?;
         let latest_counted_time = now - self.activation_delay_seconds;
         let mut total_vp = 0;
         let mut signal_vps = HashMap::new();
+        let mut manifest_vps: Vec<(Version, Manifest, u64)> = vec![];
         for validator in self.current_validators()? {
             total_vp += validator.power;
             if let Some(signal) = self.signals.get(validator.pubkey)? {
@@ -127,15 +255,38 @@ impl Upgrade {
                     && validator.power > 0
                 {
                     *signal_vps.entry(signal.version.clone()).or_default() += validator.power;
+
+                    if let Some(manifest) = self.signal_manifests.get(validator.pubkey)? {
+                        match manifest_vps
+                            .iter_mut()
+                            .find(|(v, m, _)| *v == signal.version && *m == *manifest)
+                        {
+                            Some((_, _, vp)) => *vp += validator.power,
+                            None => manifest_vps.push((
+                                signal.version.clone(),
+                                manifest.clone(),
+                                validator.power,
+                            )),
+                        }
+                    }
                 }
             }
         }
         let vp_threshold = (self.threshold * Amount::new(total_vp))?;
 
-        Ok(signal_vps
+        let version = match signal_vps
             .into_iter()
             .find(|(_, vp)| Amount::new(*vp) > vp_threshold)
-            .map(|(version, _)| version))
+        {
+            Some((version, _)) => version,
+            None => return Ok(None),
+        };
+        let manifest = manifest_vps
+            .into_iter()
+            .find(|(v, _, vp)| *v == version && Amount::new(*vp) > vp_threshold)
+            .map(|(_, manifest, _)| manifest);
+
+        Ok(Some((version, manifest)))
     }
 
     fn current_seconds(&mut self) -> Result<i64> {
@@ -172,6 +323,79 @@ impl Upgrade {
     }
 }
 
+/// The genesis config of [Upgrade]. Omitted fields keep their defaults.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UpgradeGenesis {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activation_delay_seconds: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_seconds: Option<i64>,
+    /// The hex-encoded network version to start at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_version: Option<String>,
+}
+
+impl UpgradeGenesis {
+    fn version(&self) -> Result<Option<Version>> {
+        self.current_version
+            .as_ref()
+            .map(|version| {
+                let bytes = hex::decode(version)
+                    .map_err(|_| OrgaError::Genesis("Version must be hex-encoded".into()))?;
+                if bytes.is_empty() {
+                    return Err(OrgaError::Genesis("Version must not be empty".into()));
+                }
+                bytes
+                    .try_into()
+                    .map_err(|_| OrgaError::Genesis("Version is too long".into()))
+            })
+            .transpose()
+    }
+}
+
+impl Genesis for Upgrade {
+    type Config = UpgradeGenesis;
+
+    fn validate_genesis(config: &UpgradeGenesis, _chain_id: &str) -> Result<()> {
+        if let Some(threshold) = config.threshold {
+            if threshold <= Decimal::zero() || threshold > Decimal::one() {
+                return Err(OrgaError::Genesis(
+                    "Upgrade threshold must be greater than 0 and at most 1".into(),
+                ));
+            }
+        }
+        if config.activation_delay_seconds.unwrap_or_default() < 0
+            || config.rate_limit_seconds.unwrap_or_default() < 0
+        {
+            return Err(OrgaError::Genesis(
+                "Upgrade delays must not be negative".into(),
+            ));
+        }
+        config.version()?;
+
+        Ok(())
+    }
+
+    fn apply_genesis(&mut self, config: UpgradeGenesis) -> Result<()> {
+        if let Some(version) = config.version()? {
+            self.current_version.insert((), version)?;
+        }
+        if let Some(threshold) = config.threshold {
+            self.threshold = threshold;
+        }
+        if let Some(delay) = config.activation_delay_seconds {
+            self.activation_delay_seconds = delay;
+        }
+        if let Some(rate_limit) = config.rate_limit_seconds {
+            self.rate_limit_seconds = rate_limit;
+        }
+
+        Ok(())
+    }
+}
+
 pub fn load_version(store: Store) -> Result<Option<Vec<u8>>> {
     let store = unsafe { store.with_prefix(vec![]) };
     store.get(VERSION_KEY)
@@ -241,7 +465,7 @@ mod tests {
         assert!(upgrade.step(&next_version, true).is_err());
         assert_eq!(&*upgrade.current_version.get(())?.unwrap(), &version);
         set_time(12);
-        assert!(upgrade.upgrade_ready()?.unwrap() == next_version);
+        assert!(upgrade.upgrade_ready()?.unwrap().0 == next_version);
         assert_eq!(&*upgrade.current_version.get(())?.unwrap(), &version);
         upgrade.step(&version, false)?;
         assert_eq!(&*upgrade.current_version.get(())?.unwrap(), &version);
@@ -252,4 +476,87 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    #[serial]
+    fn manifests() -> Result<()> {
+        setup_validators();
+        set_time(0);
+        let version: Version = vec![0].try_into().unwrap();
+        let next_version: Version = vec![1].try_into().unwrap();
+        let mut upgrade = Upgrade {
+            activation_delay_seconds: 4,
+            rate_limit_seconds: 5,
+            ..Default::default()
+        };
+
+        let manifest = Manifest::new("https://example.com/v1.json", [1; 32])?;
+        let other = Manifest::new("https://example.com/other.json", [2; 32])?;
+        set_signer([0; 20]);
+        assert!(upgrade
+            .signal_with_manifest(next_version.clone(), Manifest::new("file:///v1", [1; 32])?)
+            .is_err());
+        upgrade.signal_with_manifest(next_version.clone(), manifest.clone())?;
+        set_signer([1; 20]);
+        upgrade.signal_with_manifest(next_version.clone(), other)?;
+        set_signer([2; 20]);
+        upgrade.signal(next_version.clone())?;
+
+        // The version has enough voting power, but no manifest does
+        set_time(5);
+        assert_eq!(upgrade.upgrade_ready()?, Some((next_version.clone(), None)));
+
+        // Announcing a manifest for the signaled version keeps the signal time
+        set_time(6);
+        upgrade.signal_with_manifest(next_version.clone(), manifest.clone())?;
+        assert!(upgrade
+            .signal_with_manifest(next_version.clone(), manifest.clone())
+            .is_err());
+        assert_eq!(
+            upgrade.upgrade_ready()?,
+            Some((next_version.clone(), Some(manifest.clone())))
+        );
+
+        upgrade.step(&version, true)?;
+        assert_eq!(&*upgrade.current_version.get(())?.unwrap(), &next_version);
+        match upgrade.step(&version, true) {
+            Err(OrgaError::Upgrade(Error::Version {
+                manifest: Some(announced),
+                ..
+            })) => assert_eq!(announced, manifest),
+            _ => panic!("Expected version error with manifest"),
+        }
+        assert_eq!(manifest.url()?, "https://example.com/v1.json");
+
+        Ok(())
+    }
+
+    #[test]
+    fn genesis() -> Result<()> {
+        let config: UpgradeGenesis = serde_json::from_value(serde_json::json!({
+            "activation_delay_seconds": 30,
+            "current_version": "0102",
+        }))?;
+        Upgrade::validate_genesis(&config, "test")?;
+
+        let mut upgrade = Upgrade::default();
+        upgrade.apply_genesis(config)?;
+        assert_eq!(upgrade.activation_delay_seconds, 30);
+        assert_eq!(upgrade.rate_limit_seconds, 60);
+        let version: Version = vec![1, 2].try_into().unwrap();
+        assert_eq!(&*upgrade.current_version.get(())?.unwrap(), &version);
+
+        let invalid = UpgradeGenesis {
+            current_version: Some("xyz".into()),
+            ..Default::default()
+        };
+        assert!(Upgrade::validate_genesis(&invalid, "test").is_err());
+        let invalid = UpgradeGenesis {
+            threshold: Some(Decimal::zero()),
+            ..Default::default()
+        };
+        assert!(Upgrade::validate_genesis(&invalid, "test").is_err());
+
+        Ok(())
+    }
 }